@@ -0,0 +1,26 @@
+'use strict'
+
+const { Readable } = require('node:stream')
+const native = require('./road-network-json.node')
+
+/**
+ * Converts a GML file in-process. Returns an object-mode stream of GeoJSON
+ * Feature objects; the stream's `summary` promise resolves with the counts.
+ */
+function convert(path, options) {
+  const stream = new Readable({ objectMode: true, read() {} })
+  stream.summary = native
+    .convert(path, options, (err, feature) => {
+      if (err) stream.destroy(err)
+      else stream.push(feature === null ? null : JSON.parse(feature))
+    })
+    .catch((err) => {
+      stream.destroy(err)
+      throw err
+    })
+  // Errors surface on the stream; don't also report them as unhandled
+  stream.summary.catch(() => {})
+  return stream
+}
+
+module.exports = { convert }
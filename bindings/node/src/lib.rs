@@ -0,0 +1,103 @@
+//! Node.js bindings. `convert` parses a GML file on a libuv worker thread and
+//! calls back with each feature as it is read; `index.js` wraps that in a stream.
+//!
+//! The returned promise can settle before queued callbacks have run, so the end
+//! of the features is marked by a final `null` through the same queue.
+
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Env, JsFunction, Task};
+use napi_derive::napi;
+use road_network_json::crs::Crs;
+use road_network_json::dataset::DATASETS;
+use road_network_json::gml::{self, ParseOptions};
+use std::path::Path;
+
+#[napi(object)]
+pub struct ConvertOptions {
+    /// `"wgs84"` (default) or `"hk80"`.
+    pub crs: Option<String>,
+    /// `[minX, minY, maxX, maxY]` in the output CRS.
+    pub bbox: Option<Vec<f64>>,
+    /// Stop after this many features.
+    pub limit: Option<u32>,
+    /// The ID attribute, for files not named after a known dataset.
+    pub id_field: Option<String>,
+}
+
+#[napi(object)]
+pub struct ConvertSummary {
+    pub features: u32,
+    pub skipped: u32,
+}
+
+pub struct ConvertTask {
+    path: String,
+    id_field: String,
+    options: ParseOptions,
+    on_feature: ThreadsafeFunction<Option<String>, ErrorStrategy::CalleeHandled>,
+}
+
+impl Task for ConvertTask {
+    type Output = ConvertSummary;
+    type JsValue = ConvertSummary;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let summary = gml::parse_gml_file(&self.path, &self.id_field, &self.options, |_, feature| {
+            let json = serde_json::to_string(&feature)?;
+            self.on_feature.call(Ok(Some(json)), ThreadsafeFunctionCallMode::Blocking);
+            Ok(())
+        })
+        .map_err(|e| Error::from_reason(format!("{:#}", e)))?;
+        self.on_feature.call(Ok(None), ThreadsafeFunctionCallMode::Blocking);
+        Ok(ConvertSummary {
+            features: summary.features as u32,
+            skipped: summary.skipped as u32,
+        })
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+/// Converts the GML file at `path`, calling `onFeature(null, json)` with each
+/// GeoJSON Feature and `onFeature(null, null)` after the last. Resolves with the
+/// counts once the file is read.
+#[napi(ts_args_type = "path: string, options: ConvertOptions | undefined, onFeature: (err: Error | null, feature: string | null) => void")]
+pub fn convert(path: String, options: Option<ConvertOptions>, on_feature: JsFunction) -> Result<AsyncTask<ConvertTask>> {
+    let options = options.unwrap_or(ConvertOptions {
+        crs: None,
+        bbox: None,
+        limit: None,
+        id_field: None,
+    });
+    let file_name = Path::new(&path).file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    let id_field = match (options.id_field, DATASETS.iter().find(|dataset| dataset.file_name == file_name)) {
+        (Some(id_field), _) => id_field,
+        (None, Some(dataset)) => dataset.id_field.to_string(),
+        (None, None) => return Err(Error::from_reason(format!("Unknown dataset {}; pass idField", file_name))),
+    };
+    let target_crs = match options.crs.as_deref() {
+        None | Some("wgs84") => Crs::Wgs84,
+        Some("hk80") => Crs::Hk80,
+        Some(other) => return Err(Error::from_reason(format!("Unknown CRS {}; use wgs84 or hk80", other))),
+    };
+    let bbox = match options.bbox.as_deref() {
+        None => None,
+        Some(&[min_x, min_y, max_x, max_y]) => Some([min_x, min_y, max_x, max_y]),
+        Some(_) => return Err(Error::from_reason("bbox needs four numbers")),
+    };
+    let on_feature = on_feature.create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))?;
+    Ok(AsyncTask::new(ConvertTask {
+        path,
+        id_field,
+        options: ParseOptions {
+            target_crs,
+            bbox,
+            limit: options.limit.map(|limit| limit as usize),
+            ..ParseOptions::default()
+        },
+        on_feature,
+    }))
+}
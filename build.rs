@@ -0,0 +1,22 @@
+fn main() {
+    // The gRPC service is described in Rust rather than compiled from
+    // `proto/road_network.proto`, so building it needs no `protoc`
+    #[cfg(feature = "grpc")]
+    {
+        let convert_stream = tonic_build::manual::Method::builder()
+            .name("convert_stream")
+            .route_name("ConvertStream")
+            .input_type("crate::grpc::GmlChunk")
+            .output_type("crate::grpc::Feature")
+            .codec_path("tonic::codec::ProstCodec")
+            .client_streaming()
+            .server_streaming()
+            .build();
+        let service = tonic_build::manual::Service::builder()
+            .name("Converter")
+            .package("road_network")
+            .method(convert_stream)
+            .build();
+        tonic_build::manual::Builder::new().build_client(false).compile(&[service]);
+    }
+}
@@ -0,0 +1,42 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<core:CityModel xmlns:core="http://www.opengis.net/citygml/2.0" xmlns:gen="http://www.opengis.net/citygml/generics/2.0" xmlns:gml="http://www.opengis.net/gml">
+<gml:boundedBy><gml:Envelope srsName="EPSG:2326" srsDimension="2"><gml:lowerCorner>833500 815500</gml:lowerCorner><gml:upperCorner>836800 819200</gml:upperCorner></gml:Envelope></gml:boundedBy>
+<core:cityObjectMember>
+<gen:GenericCityObject gml:id="CL_1">
+<core:creationDate>2019-04-01</core:creationDate>
+<gen:intAttribute name="ROUTE_ID"><gen:value>1001</gen:value></gen:intAttribute>
+<gen:stringAttribute name="STREET_ENAME"><gen:value>NATHAN ROAD</gen:value></gen:stringAttribute>
+<gen:stringAttribute name="STREET_CNAME"><gen:value>彌敦道</gen:value></gen:stringAttribute>
+<gen:intAttribute name="TRAVEL_DIRECTION"><gen:value>1</gen:value></gen:intAttribute>
+<gen:doubleAttribute name="SHAPE_LENGTH"><gen:value>141.42</gen:value></gen:doubleAttribute>
+<gen:lod0Network><gml:MultiCurve srsName="EPSG:2326" srsDimension="3"><gml:curveMember><gml:LineString><gml:posList>836600 819000 5.2 836650 819050 5.8 836700 819100 7.1</gml:posList></gml:LineString></gml:curveMember></gml:MultiCurve></gen:lod0Network>
+</gen:GenericCityObject>
+</core:cityObjectMember>
+<core:cityObjectMember>
+<gen:GenericCityObject gml:id="CL_2">
+<gen:intAttribute name="ROUTE_ID"><gen:value>1002</gen:value></gen:intAttribute>
+<gen:stringAttribute name="STREET_ENAME"><gen:value>DES VOEUX ROAD CENTRAL</gen:value></gen:stringAttribute>
+<gen:stringAttribute name="STREET_CNAME"><gen:value>德輔道中</gen:value></gen:stringAttribute>
+<gen:intAttribute name="TRAVEL_DIRECTION"><gen:value>2</gen:value></gen:intAttribute>
+<gen:dateAttribute name="LAST_UPDATE"><gen:value>15/03/2023</gen:value></gen:dateAttribute>
+<gen:lod0Network><gml:MultiCurve srsName="urn:ogc:def:crs:EPSG::2326" srsDimension="2"><gml:curveMember><gml:LineString><gml:posList>815600 833600 815600 833600 815650 833700 815700 833800</gml:posList></gml:LineString></gml:curveMember></gml:MultiCurve></gen:lod0Network>
+</gen:GenericCityObject>
+</core:cityObjectMember>
+<core:cityObjectMember>
+<gen:GenericCityObject gml:id="CL_3">
+<gen:intAttribute name="ROUTE_ID"><gen:value>1003</gen:value></gen:intAttribute>
+<gen:stringAttribute name="STREET_ENAME"><gen:value>QUEEN&apos;S ROAD EAST</gen:value></gen:stringAttribute>
+<gen:intAttribute name="TRAVEL_DIRECTION"><gen:value>3</gen:value></gen:intAttribute>
+<gen:doubleAttribute name="SHAPE_LENGTH"><gen:value>1,234.5 m</gen:value></gen:doubleAttribute>
+<gen:lod0Network><gml:MultiCurve srsName="EPSG:2326"><gml:curveMember><gml:LineString><gml:coordinates>835000,815000 835100,815050</gml:coordinates></gml:LineString></gml:curveMember></gml:MultiCurve></gen:lod0Network>
+</gen:GenericCityObject>
+</core:cityObjectMember>
+<core:cityObjectMember>
+<gen:GenericCityObject gml:id="CL_4">
+<gen:intAttribute name="ROUTE_ID"><gen:value>1004</gen:value></gen:intAttribute>
+<gen:stringAttribute name="STREET_ENAME"><gen:value>HENNESSY ROAD</gen:value></gen:stringAttribute>
+<gen:intAttribute name="TRAVEL_DIRECTION"><gen:value>1</gen:value></gen:intAttribute>
+<gen:lod0Network><gml:MultiCurve srsName="EPSG:2326"><gml:curveMember><gml:LineString><gml:pos>836000 815500</gml:pos><gml:pos>836040 815530</gml:pos><gml:pos>836080 815560</gml:pos></gml:LineString></gml:curveMember></gml:MultiCurve></gen:lod0Network>
+</gen:GenericCityObject>
+</core:cityObjectMember>
+</core:CityModel>
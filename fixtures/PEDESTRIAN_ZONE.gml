@@ -0,0 +1,16 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<core:CityModel xmlns:core="http://www.opengis.net/citygml/2.0" xmlns:gen="http://www.opengis.net/citygml/generics/2.0" xmlns:gml="http://www.opengis.net/gml">
+<core:cityObjectMember>
+<gen:GenericCityObject gml:id="PZ_1">
+<gen:stringAttribute name="PED_ZONE_ID"><gen:value>PZ0001</gen:value></gen:stringAttribute>
+<gen:stringAttribute name="STREET_ENAME"><gen:value>SAI YEUNG CHOI STREET SOUTH</gen:value></gen:stringAttribute>
+<gen:lod1Geometry><gml:MultiSurface srsName="EPSG:2326" srsDimension="2"><gml:surfaceMember><gml:Polygon><gml:exterior><gml:LinearRing><gml:posList>836000 819000 836100 819000 836100 819100 836000 819100 836000 819000</gml:posList></gml:LinearRing></gml:exterior><gml:interior><gml:LinearRing><gml:posList>836040 819040 836060 819040 836060 819060 836040 819060 836040 819040</gml:posList></gml:LinearRing></gml:interior></gml:Polygon></gml:surfaceMember></gml:MultiSurface></gen:lod1Geometry>
+</gen:GenericCityObject>
+</core:cityObjectMember>
+<core:cityObjectMember>
+<gen:GenericCityObject gml:id="PZ_2">
+<gen:stringAttribute name="PED_ZONE_ID"><gen:value>PZ0002</gen:value></gen:stringAttribute>
+<gen:lod1Geometry><gml:MultiSurface srsName="EPSG:2326" srsDimension="2"><gml:surfaceMember><gml:Polygon><gml:exterior><gml:LinearRing><gml:posList>833000 816000 833050 816000 833050 816050 833000 816050 833000 816000</gml:posList></gml:LinearRing></gml:exterior></gml:Polygon></gml:surfaceMember><gml:surfaceMember><gml:Polygon><gml:exterior><gml:LinearRing><gml:posList>833100 816000 833150 816000 833150 816050 833100 816000</gml:posList></gml:LinearRing></gml:exterior></gml:Polygon></gml:surfaceMember></gml:MultiSurface></gen:lod1Geometry>
+</gen:GenericCityObject>
+</core:cityObjectMember>
+</core:CityModel>
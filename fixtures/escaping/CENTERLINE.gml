@@ -0,0 +1,33 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<core:CityModel xmlns:core="http://www.opengis.net/citygml/2.0" xmlns:gen="http://www.opengis.net/citygml/generics/2.0" xmlns:gml="http://www.opengis.net/gml">
+<core:cityObjectMember>
+<gen:GenericCityObject gml:id="CL_1">
+<gen:intAttribute name="ROUTE_ID"><gen:value>2001</gen:value></gen:intAttribute>
+<gen:stringAttribute name="STREET_ENAME"><gen:value>NATHAN ROAD &amp; JORDAN ROAD</gen:value></gen:stringAttribute>
+<gen:lod0Network><gml:LineString srsName="EPSG:2326"><gml:posList>836600 819000 836650 819050</gml:posList></gml:LineString></gen:lod0Network>
+</gen:GenericCityObject>
+</core:cityObjectMember>
+<core:cityObjectMember>
+<gen:GenericCityObject gml:id="CL_2">
+<gen:intAttribute name="ROUTE_ID"><gen:value>2002</gen:value></gen:intAttribute>
+<gen:stringAttribute name="STREET_ENAME"><gen:value>&lt;UNNAMED&gt; ROAD</gen:value></gen:stringAttribute>
+<gen:lod0Network><gml:LineString srsName="EPSG:2326"><gml:posList>836650 819050 836700 819100</gml:posList></gml:LineString></gen:lod0Network>
+</gen:GenericCityObject>
+</core:cityObjectMember>
+<core:cityObjectMember>
+<gen:GenericCityObject gml:id="CL_3">
+<gen:intAttribute name="ROUTE_ID"><gen:value>2003</gen:value></gen:intAttribute>
+<gen:stringAttribute name="STREET_ENAME"><gen:value>&quot;THE PEAK&quot; ROAD</gen:value></gen:stringAttribute>
+<gen:stringAttribute name="STREET_CNAME"><gen:value>&#x5F4C;&#25958;道</gen:value></gen:stringAttribute>
+<gen:stringAttribute name="REMARKS&amp;NOTES"><gen:value>a &lt; b &gt; c</gen:value></gen:stringAttribute>
+<gen:lod0Network><gml:LineString srsName="EPSG:2326"><gml:posList>836700 819100 836750 819150</gml:posList></gml:LineString></gen:lod0Network>
+</gen:GenericCityObject>
+</core:cityObjectMember>
+<core:cityObjectMember>
+<gen:GenericCityObject gml:id="CL_4">
+<gen:intAttribute name="ROUTE_ID"><gen:value>2004</gen:value></gen:intAttribute>
+<gen:stringAttribute name="STREET_ENAME"><gen:value><![CDATA[SALISBURY ROAD & <TST>]]></gen:value></gen:stringAttribute>
+<gen:lod0Network><gml:LineString srsName="EPSG:2326"><gml:posList>836750 819150 836800 819200</gml:posList></gml:LineString></gen:lod0Network>
+</gen:GenericCityObject>
+</core:cityObjectMember>
+</core:CityModel>
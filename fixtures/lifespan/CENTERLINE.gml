@@ -0,0 +1,40 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<core:CityModel xmlns:core="http://www.opengis.net/citygml/2.0" xmlns:gen="http://www.opengis.net/citygml/generics/2.0" xmlns:gml="http://www.opengis.net/gml">
+<core:cityObjectMember>
+<gen:GenericCityObject gml:id="CL_1">
+<core:creationDate>2019-04-01</core:creationDate>
+<gen:intAttribute name="ROUTE_ID"><gen:value>3001</gen:value></gen:intAttribute>
+<gen:lod0Network><gml:LineString srsName="EPSG:2326"><gml:posList>836600 819000 836650 819050</gml:posList></gml:LineString></gen:lod0Network>
+</gen:GenericCityObject>
+</core:cityObjectMember>
+<core:cityObjectMember>
+<gen:GenericCityObject gml:id="CL_2">
+<core:creationDate>2019-04-01Z</core:creationDate>
+<gen:intAttribute name="ROUTE_ID"><gen:value>3002</gen:value></gen:intAttribute>
+<gen:lod0Network><gml:LineString srsName="EPSG:2326"><gml:posList>836650 819000 836700 819050</gml:posList></gml:LineString></gen:lod0Network>
+</gen:GenericCityObject>
+</core:cityObjectMember>
+<core:cityObjectMember>
+<gen:GenericCityObject gml:id="CL_3">
+<core:creationDate>2019-04-01+08:00</core:creationDate>
+<gen:intAttribute name="ROUTE_ID"><gen:value>3003</gen:value></gen:intAttribute>
+<gen:lod0Network><gml:LineString srsName="EPSG:2326"><gml:posList>836700 819000 836750 819050</gml:posList></gml:LineString></gen:lod0Network>
+</gen:GenericCityObject>
+</core:cityObjectMember>
+<core:cityObjectMember>
+<gen:GenericCityObject gml:id="CL_4">
+<core:creationDate>2019-04-01-08:00</core:creationDate>
+<core:terminationDate>2024-06-30-05:00</core:terminationDate>
+<gen:intAttribute name="ROUTE_ID"><gen:value>3004</gen:value></gen:intAttribute>
+<gen:lod0Network><gml:LineString srsName="EPSG:2326"><gml:posList>836750 819000 836800 819050</gml:posList></gml:LineString></gen:lod0Network>
+</gen:GenericCityObject>
+</core:cityObjectMember>
+<core:cityObjectMember>
+<gen:GenericCityObject gml:id="CL_5">
+<core:creationDate>2019-04-01T08:30:00-08:00</core:creationDate>
+<core:terminationDate>2024-06-30T23:00:00+08:00</core:terminationDate>
+<gen:intAttribute name="ROUTE_ID"><gen:value>3005</gen:value></gen:intAttribute>
+<gen:lod0Network><gml:LineString srsName="EPSG:2326"><gml:posList>836800 819000 836850 819050</gml:posList></gml:LineString></gen:lod0Network>
+</gen:GenericCityObject>
+</core:cityObjectMember>
+</core:CityModel>
@@ -0,0 +1,36 @@
+/* C interface to road-network-json, built with `cargo build --release --features ffi`
+ * (libroad_network_json.so / .dylib / .dll). */
+#ifndef ROAD_NETWORK_JSON_H
+#define ROAD_NETWORK_JSON_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct RnjFeatures RnjFeatures;
+
+/* Converts a CENTERLINE.gml or PEDESTRIAN_ZONE.gml document to GeoJSON Features
+ * in WGS84. Returns NULL on failure; see rnj_last_error(). */
+RnjFeatures *rnj_convert(const uint8_t *data, size_t len);
+
+/* The number of features converted. */
+size_t rnj_features_count(const RnjFeatures *features);
+
+/* The next feature as a GeoJSON Feature string, or NULL after the last one. The
+ * string stays valid until the next call on the same handle. */
+const char *rnj_features_next(RnjFeatures *features);
+
+/* Frees a result of rnj_convert(). NULL is ignored. */
+void rnj_features_free(RnjFeatures *features);
+
+/* The message of the last failure on this thread, or NULL. */
+const char *rnj_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
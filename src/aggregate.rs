@@ -0,0 +1,133 @@
+//! Road length and pedestrian zone area summed per aggregation polygon.
+//!
+//! Centerlines are cut exactly at polygon boundaries, so a road crossing two
+//! areas counts towards each by the length inside it. Pedestrian zones are
+//! counted whole towards the area containing their point-on-surface, which is
+//! plenty for zones much smaller than the areas.
+
+use crate::districts::{self, Polygon};
+use crate::geometry;
+use rstar::primitives::{GeomWithData, Line};
+use rstar::{AABB, RTree};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+type BoundaryEdge = GeomWithData<Line<[f64; 2]>, ()>;
+
+#[derive(Default)]
+struct Totals {
+    road_length_m: f64,
+    by_type: BTreeMap<String, f64>,
+    zone_area_m2: f64,
+    zone_count: i64,
+}
+
+struct Area {
+    polygons: Vec<Polygon>,
+    bbox: [f64; 4],
+    totals: Totals,
+}
+
+pub struct Aggregation {
+    areas: Vec<Area>,
+    /// Every ring edge of every area, for finding where roads cross boundaries.
+    boundaries: RTree<BoundaryEdge>,
+}
+
+impl Aggregation {
+    /// Prepares one area per feature of a GeoJSON FeatureCollection; features
+    /// without a polygon geometry collect nothing.
+    pub fn new(features: &[Value]) -> Self {
+        let areas: Vec<Area> = features
+            .iter()
+            .map(|feature| {
+                let polygons = districts::polygons(&feature["geometry"]);
+                Area {
+                    bbox: districts::bbox(&polygons),
+                    polygons,
+                    totals: Totals::default(),
+                }
+            })
+            .collect();
+        let edges = areas
+            .iter()
+            .flat_map(|area| area.polygons.iter().flatten())
+            .flat_map(|ring| ring.windows(2).map(|w| GeomWithData::new(Line::new(w[0], w[1]), ())))
+            .collect();
+        Aggregation {
+            areas,
+            boundaries: RTree::bulk_load(edges),
+        }
+    }
+
+    /// Adds the length of a `[lon, lat]` centerline inside each area, under
+    /// `road_type` as well when given.
+    pub fn add_road(&mut self, coords: &[Vec<f64>], road_type: Option<&str>) {
+        for w in coords.windows(2) {
+            let (a, b) = (&w[0], &w[1]);
+            let envelope = AABB::from_corners([a[0], a[1]], [b[0], b[1]]);
+            let mut cuts = vec![0.0, 1.0];
+            for edge in self.boundaries.locate_in_envelope_intersecting(&envelope) {
+                let (c, d) = (edge.geom().from, edge.geom().to);
+                cuts.extend(geometry::segment_crossing(a, b, &c, &d));
+            }
+            cuts.sort_by(|x, y| x.total_cmp(y));
+            cuts.dedup();
+
+            let at = |t: f64| vec![a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t];
+            for piece in cuts.windows(2) {
+                let middle = at((piece[0] + piece[1]) / 2.0);
+                let length = geometry::haversine_m(&at(piece[0]), &at(piece[1]));
+                for area in &mut self.areas {
+                    if districts::contains(&area.polygons, &area.bbox, &middle) {
+                        area.totals.road_length_m += length;
+                        if let Some(road_type) = road_type {
+                            *area.totals.by_type.entry(road_type.to_string()).or_default() += length;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Adds a pedestrian zone, given as polygons of rings (exterior first), to
+    /// the area(s) containing its largest member. Holes don't count towards its
+    /// area.
+    pub fn add_zone(&mut self, polygons: &[&[Vec<Vec<f64>>]]) {
+        let exterior_area = |rings: &&[Vec<Vec<f64>>]| rings.first().map_or(0.0, |ring| geometry::ring_area_m2(ring));
+        let Some(largest) = polygons.iter().max_by(|a, b| exterior_area(a).total_cmp(&exterior_area(b))) else {
+            return;
+        };
+        let Some(inside) = geometry::polygon_point_on_surface(largest) else {
+            return;
+        };
+        let area_m2: f64 = polygons
+            .iter()
+            .flat_map(|rings| rings.iter().enumerate())
+            .map(|(i, ring)| if i == 0 { geometry::ring_area_m2(ring) } else { -geometry::ring_area_m2(ring) })
+            .sum();
+        for area in &mut self.areas {
+            if districts::contains(&area.polygons, &area.bbox, &inside) {
+                area.totals.zone_area_m2 += area_m2;
+                area.totals.zone_count += 1;
+            }
+        }
+    }
+
+    /// Adds the totals to each feature's properties, in the order given to [`new`](Self::new).
+    pub fn enrich(&self, features: &mut [Value]) {
+        for (feature, area) in features.iter_mut().zip(&self.areas) {
+            if !feature["properties"].is_object() {
+                feature["properties"] = Value::Object(Map::new());
+            }
+            let properties = &mut feature["properties"];
+            let totals = &area.totals;
+            properties["road_length_m"] = totals.road_length_m.into();
+            for (road_type, length) in &totals.by_type {
+                properties[format!("road_length_m_{}", road_type)] = (*length).into();
+            }
+            properties["pedestrian_zone_area_m2"] = totals.zone_area_m2.into();
+            properties["pedestrian_zone_count"] = totals.zone_count.into();
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Attribute types set in the config (`[input.attributes]`), overriding the type
+//! implied by the `gen:*Attribute` element each value is written in.
+
+use crate::geojson::PropertyValue;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttributeType {
+    String,
+    Int,
+    Float,
+    /// An ISO 8601 date string, with the time of day when the source has one.
+    Date,
+}
+
+impl AttributeType {
+    /// The type's name as written in the config.
+    pub fn name(self) -> &'static str {
+        match self {
+            AttributeType::String => "string",
+            AttributeType::Int => "int",
+            AttributeType::Float => "float",
+            AttributeType::Date => "date",
+        }
+    }
+
+    /// Reads an attribute's text as this type, or `None` when it doesn't parse.
+    /// Numbers must be plain Rust literals; see [`AttributeType::parse_with`].
+    pub fn parse(self, text: &str) -> Option<PropertyValue> {
+        self.parse_with(text, &NumberFormat::strict())
+    }
+
+    /// Like [`AttributeType::parse`], reading ints and floats written as
+    /// `numbers` describes.
+    pub fn parse_with(self, text: &str, numbers: &NumberFormat) -> Option<PropertyValue> {
+        match self {
+            AttributeType::String => Some(PropertyValue::String(text.to_string())),
+            AttributeType::Int => {
+                let text = numbers.normalize(text)?;
+                match text.parse::<i64>() {
+                    Ok(value) => Some(PropertyValue::Int(value)),
+                    // Whole numbers written as doubles, e.g. `3.0`
+                    Err(_) => text
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|value| value.fract() == 0.0 && value.abs() < i64::MAX as f64)
+                        .map(|value| PropertyValue::Int(value as i64)),
+                }
+            }
+            AttributeType::Float => numbers.normalize(text)?.parse().ok().map(PropertyValue::Float),
+            AttributeType::Date => iso_date(text).map(PropertyValue::String),
+        }
+    }
+}
+
+/// How numbers are written in attribute values, set under `[input.numbers]`.
+/// By default thousands separators (`1,234.5`), surrounding whitespace and a
+/// trailing unit (`123.4 m`, `50km/h`, `12%`) are tolerated, e.g.
+///
+/// ```toml
+/// [input.numbers]
+/// thousands_separator = "."
+/// decimal_separator = ","
+/// strip_units = false
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NumberFormat {
+    /// Dropped wherever it appears; empty for none.
+    pub thousands_separator: String,
+    /// Read as the decimal point.
+    pub decimal_separator: String,
+    /// Drop anything after the last digit, such as a unit.
+    pub strip_units: bool,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        NumberFormat {
+            thousands_separator: ",".to_string(),
+            decimal_separator: ".".to_string(),
+            strip_units: true,
+        }
+    }
+}
+
+impl NumberFormat {
+    /// Numbers exactly as Rust writes them.
+    pub fn strict() -> Self {
+        NumberFormat {
+            thousands_separator: String::new(),
+            decimal_separator: ".".to_string(),
+            strip_units: false,
+        }
+    }
+
+    /// `text` rewritten as a plain Rust number literal, or `None` when it holds
+    /// no digits.
+    fn normalize(&self, text: &str) -> Option<String> {
+        let mut number = text.trim();
+        if self.strip_units {
+            number = number[..number.rfind(|c: char| c.is_ascii_digit())? + 1].trim_end();
+        }
+        let mut number = number.to_string();
+        if !self.thousands_separator.is_empty() {
+            number = number.replace(&self.thousands_separator, "");
+        }
+        if self.decimal_separator != "." {
+            number = number.replace(&self.decimal_separator, ".");
+        }
+        Some(number)
+    }
+}
+
+/// The type every value of an attribute written as `types` fits: int when all
+/// are ints, float when they mix ints and floats, date when all are dates, and
+/// string otherwise.
+pub fn common_type(types: &HashSet<AttributeType>) -> AttributeType {
+    if types.iter().all(|kind| *kind == AttributeType::Date) {
+        AttributeType::Date
+    } else if types.iter().all(|kind| *kind == AttributeType::Int) {
+        AttributeType::Int
+    } else if types.iter().all(|kind| matches!(kind, AttributeType::Int | AttributeType::Float)) {
+        AttributeType::Float
+    } else {
+        AttributeType::String
+    }
+}
+
+/// Normalises a date to `YYYY-MM-DD`, or `YYYY-MM-DDTHH:MM:SS` when followed by a
+/// time of day. Reads ISO dates, `YYYY/MM/DD`, `YYYYMMDD` and the `DD/MM/YYYY`
+/// common in Hong Kong data, with an optional `HH:MM[:SS]` after a space or `T`.
+fn iso_date(text: &str) -> Option<String> {
+    let (date, time) = match text.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time.trim())),
+        None => (text, None),
+    };
+    let parts: Vec<&str> = date.split(['-', '/']).collect();
+    let (year, month, day) = match parts[..] {
+        [year, month, day] if year.len() == 4 => (year, month, day),
+        [day, month, year] if year.len() == 4 => (year, month, day),
+        [compact] if compact.len() == 8 => (&compact[..4], &compact[4..6], &compact[6..]),
+        _ => return None,
+    };
+    let (year, month, day): (u32, u32, u32) = (year.parse().ok()?, month.parse().ok()?, day.parse().ok()?);
+    if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+    let date = format!("{:04}-{:02}-{:02}", year, month, day);
+    let Some(time) = time else {
+        return Some(date);
+    };
+    let parts: Vec<u32> = time.split(':').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+    let (hour, minute, second) = match parts[..] {
+        [hour, minute] => (hour, minute, 0),
+        [hour, minute, second] => (hour, minute, second),
+        _ => return None,
+    };
+    if hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    Some(format!("{}T{:02}:{:02}:{:02}", date, hour, minute, second))
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
@@ -0,0 +1,174 @@
+//! CityGML generics writer: the inverse of [`crate::gml`], turning GeoJSON
+//! features back into the `gen:GenericCityObject` layout of the source datasets.
+//!
+//! Coordinates are written in the HK80 grid (EPSG:2326) like the originals.
+//! Properties become `gen:stringAttribute`, `gen:intAttribute` or
+//! `gen:doubleAttribute` elements by type; lines are written as a
+//! `gml:MultiCurve` and zones as a `gml:MultiSurface` of one polygon per
+//! member, holes included.
+
+use crate::crs::{self, Crs};
+use crate::dataset::{Dataset, DatasetKind};
+use crate::geojson::{GeoJsonFeature, Geometry, PropertyValue};
+use crate::paths;
+use anyhow::{Context, Result};
+use quick_xml::escape::escape;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// A line in the HK80 grid with its dimension, as returned by [`to_grid`].
+type GridLine = (usize, Vec<Vec<f64>>);
+
+/// Writes `features`, whose coordinates are in `source`, as a CityGML file for
+/// `dataset`. Returns how many features were written; points and features
+/// without coordinates are skipped.
+pub fn write_city_model(path: &str, dataset: &Dataset, features: &[GeoJsonFeature], source: Crs) -> Result<usize> {
+    let mut objects = Vec::new();
+    let mut envelope = [f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY];
+    for feature in features {
+        // Parts are polygons' rings, exterior first, or a line's single curve
+        let parts: Vec<Vec<&Vec<Vec<f64>>>> = match &feature.geometry {
+            Geometry::Point(_) => continue,
+            Geometry::LineString(coords) => vec![vec![coords]],
+            Geometry::MultiLineString(lines) => lines.iter().map(|coords| vec![coords]).collect(),
+            geometry => geometry.polygons().into_iter().map(|rings| rings.iter().collect()).collect(),
+        };
+        let parts: Vec<Vec<GridLine>> = parts
+            .into_iter()
+            .map(|part| {
+                part.into_iter()
+                    .filter(|coords| coords.len() >= 2)
+                    .map(|coords| to_grid(coords, source))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|part| !part.is_empty())
+            .collect();
+        if parts.is_empty() {
+            continue;
+        }
+        for p in parts.iter().flatten().flat_map(|(_, coords)| coords) {
+            envelope = [envelope[0].min(p[0]), envelope[1].min(p[1]), envelope[2].max(p[0]), envelope[3].max(p[1])];
+        }
+        objects.push(city_object(dataset, objects.len() + 1, &feature.properties, &parts));
+    }
+
+    let file = File::create(paths::long_path(path)).with_context(|| format!("Failed to create {}", path))?;
+    let mut out = BufWriter::new(file);
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<core:CityModel xmlns:core="http://www.opengis.net/citygml/2.0" xmlns:gen="http://www.opengis.net/citygml/generics/2.0" xmlns:gml="http://www.opengis.net/gml">"#
+    )?;
+    if !objects.is_empty() {
+        writeln!(
+            out,
+            r#"<gml:boundedBy><gml:Envelope srsName="EPSG:2326" srsDimension="2"><gml:lowerCorner>{} {}</gml:lowerCorner><gml:upperCorner>{} {}</gml:upperCorner></gml:Envelope></gml:boundedBy>"#,
+            grid(envelope[0]),
+            grid(envelope[1]),
+            grid(envelope[2]),
+            grid(envelope[3])
+        )?;
+    }
+    for object in &objects {
+        out.write_all(object.as_bytes())?;
+    }
+    writeln!(out, "</core:CityModel>")?;
+    out.flush()?;
+    Ok(objects.len())
+}
+
+/// Projects a line into the HK80 grid, returning it with its dimension: 3 when
+/// every position has a height, otherwise 2.
+fn to_grid(coords: &[Vec<f64>], source: Crs) -> GridLine {
+    let dimension = if coords.iter().all(|p| p.len() >= 3) { 3 } else { 2 };
+    let values: Vec<f64> = coords.iter().flat_map(|p| p[..dimension].iter().copied()).collect();
+    (dimension, crs::project(&values, dimension, source.epsg(), Crs::Hk80))
+}
+
+/// Grid coordinates to the millimetre, without trailing zeros.
+fn grid(value: f64) -> f64 {
+    (value * 1000.0).round() / 1000.0
+}
+
+fn city_object(
+    dataset: &Dataset,
+    sequence: usize,
+    properties: &HashMap<String, PropertyValue>,
+    parts: &[Vec<GridLine>],
+) -> String {
+    let mut object = String::new();
+    object.push_str("<core:cityObjectMember>\n");
+    let _ = writeln!(object, r#"<gen:GenericCityObject gml:id="{}_{}">"#, dataset.gml_id_prefix, sequence);
+
+    // The ID attribute first, as in the source files, then the rest by name
+    let mut names: Vec<&String> = properties.keys().collect();
+    names.sort_by_key(|name| (name.as_str() != dataset.id_field, name.as_str()));
+    for name in names {
+        let (element, value) = match &properties[name] {
+            PropertyValue::String(s) => ("stringAttribute", escape(s.as_str()).into_owned()),
+            PropertyValue::Int(i) => ("intAttribute", i.to_string()),
+            PropertyValue::Float(v) => ("doubleAttribute", v.to_string()),
+            PropertyValue::Null => continue,
+        };
+        let _ = writeln!(
+            object,
+            r#"<gen:{element} name="{}"><gen:value>{}</gen:value></gen:{element}>"#,
+            escape(name.as_str()),
+            value
+        );
+    }
+
+    let pos_list = |dimension: usize, coords: &[Vec<f64>]| {
+        let values: Vec<String> = coords
+            .iter()
+            .flat_map(|p| p[..dimension].iter().map(|v| grid(*v).to_string()))
+            .collect();
+        values.join(" ")
+    };
+    let dimension = parts.iter().flatten().map(|(dimension, _)| *dimension).min().unwrap_or(2);
+    match dataset.kind {
+        DatasetKind::Line => {
+            let _ = write!(
+                object,
+                r#"<gen:lod0Network><gml:MultiCurve srsName="EPSG:2326" srsDimension="{}">"#,
+                dimension
+            );
+            for (_, coords) in parts.iter().flatten() {
+                let _ = write!(
+                    object,
+                    "<gml:curveMember><gml:LineString><gml:posList>{}</gml:posList></gml:LineString></gml:curveMember>",
+                    pos_list(dimension, coords)
+                );
+            }
+            object.push_str("</gml:MultiCurve></gen:lod0Network>\n");
+        }
+        DatasetKind::Zone => {
+            let _ = write!(
+                object,
+                r#"<gen:lod1Geometry><gml:MultiSurface srsName="EPSG:2326" srsDimension="{}">"#,
+                dimension
+            );
+            for rings in parts {
+                object.push_str("<gml:surfaceMember><gml:Polygon>");
+                for (i, (_, coords)) in rings.iter().enumerate() {
+                    let mut coords = coords.clone();
+                    if coords.first() != coords.last() {
+                        coords.push(coords[0].clone());
+                    }
+                    let boundary = if i == 0 { "exterior" } else { "interior" };
+                    let _ = write!(
+                        object,
+                        "<gml:{boundary}><gml:LinearRing><gml:posList>{}</gml:posList></gml:LinearRing></gml:{boundary}>",
+                        pos_list(dimension, &coords)
+                    );
+                }
+                object.push_str("</gml:Polygon></gml:surfaceMember>");
+            }
+            object.push_str("</gml:MultiSurface></gen:lod1Geometry>\n");
+        }
+    }
+    object.push_str("</gen:GenericCityObject>\n</core:cityObjectMember>\n");
+    object
+}
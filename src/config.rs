@@ -0,0 +1,551 @@
+//! Optional TOML configuration, loaded with `--config <path>`.
+//!
+//! Every section has defaults matching the Road Network (2nd Generation)
+//! datasets, so a config file only needs the keys it wants to override.
+
+use crate::attributes::{AttributeType, NumberFormat};
+use crate::dataset::{DATASETS, GENERIC_LINES};
+use crate::geojson::{GeoJsonFeature, PropertyValue, TemplatedFeature};
+use crate::gml::{GmlProfile, ParseOptions};
+use crate::ids::IdFormat;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub input: InputConfig,
+    pub graph: GraphConfig,
+    pub search: SearchConfig,
+    pub names: NamesConfig,
+    pub links: Vec<LinkConfig>,
+    /// Output shapes for `--format files`, by output layer.
+    pub templates: HashMap<String, TemplateConfig>,
+    /// Per-dataset settings, by output layer.
+    pub datasets: HashMap<String, DatasetConfig>,
+    /// Steps applied to every feature in order, before it is written.
+    pub transforms: Vec<TransformConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputConfig {
+    /// Force (`true`) or suppress (`false`) swapping each position's axes. When
+    /// unset, the order is detected from the geometry's `srsName`.
+    pub swap_axes: Option<bool>,
+    /// Drop vertices closer than this many metres to the previous one. Exactly
+    /// repeated vertices are dropped regardless.
+    pub min_segment_length: f64,
+    /// GML version whose coordinate elements are read: `"auto"` (the default),
+    /// `"2"`, `"3.1"` or `"3.2"`.
+    pub gml_profile: GmlProfile,
+    /// CityGML level of detail whose geometry properties (`lod0Network`,
+    /// `lod1Geometry`, ...) are read. When unset, each feature's lowest level
+    /// is read.
+    pub lod: Option<u8>,
+    /// Project each distinct vertex of a file once, as `--vertex-cache` does.
+    pub vertex_cache: bool,
+    /// Decimal places to snap projected positions to, as `--snap-decimals` does.
+    pub snap_decimals: Option<u32>,
+    /// Built-in dataset profile to apply, e.g. `"hk-rdnet"`. See [`crate::profile`].
+    pub profile: Option<String>,
+    /// Type to read each named attribute as, overriding the element it is
+    /// written in, e.g.
+    ///
+    /// ```toml
+    /// [input.attributes]
+    /// EXIT_NUM = "string"
+    /// LAST_UPDATE = "date"
+    /// ```
+    pub attributes: HashMap<String, AttributeType>,
+    /// Read only the features in use on this date, as `--as-of` does.
+    pub as_of: Option<String>,
+    /// Attribute holding the date each feature came into use, instead of the
+    /// CityGML `creationDate` (read as `creation_date`), e.g. `"LAST_UPDATE"`.
+    pub valid_from: Option<String>,
+    /// Attribute holding the date each feature went out of use, instead of the
+    /// CityGML `terminationDate` (read as `termination_date`).
+    pub valid_to: Option<String>,
+    /// How numbers in int and double attributes are written. See
+    /// [`NumberFormat`].
+    pub numbers: NumberFormat,
+    /// How feature IDs are normalized. See [`IdFormat`].
+    pub ids: IdFormat,
+}
+
+impl InputConfig {
+    pub fn parse_options(&self) -> ParseOptions {
+        let defaults = ParseOptions::default();
+        ParseOptions {
+            swap_axes: self.swap_axes,
+            min_segment_length: self.min_segment_length,
+            profile: self.gml_profile,
+            lod: self.lod,
+            vertex_cache: self.vertex_cache,
+            snap_decimals: self.snap_decimals,
+            attributes: self.attributes.clone(),
+            as_of: self.as_of.clone(),
+            valid_from: self.valid_from.clone().unwrap_or(defaults.valid_from.clone()),
+            valid_to: self.valid_to.clone().unwrap_or(defaults.valid_to.clone()),
+            numbers: self.numbers.clone(),
+            ids: self.ids.clone(),
+            ..defaults
+        }
+    }
+}
+
+/// Settings for one dataset, e.g.
+///
+/// ```toml
+/// [datasets.centerlines]
+/// required = ["ROUTE_ID", "STREET_ENAME"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatasetConfig {
+    /// Attributes every feature should have. Features lacking one, or with it
+    /// null or empty, are reported but still converted.
+    pub required: Vec<String>,
+}
+
+/// One step of the transform chain, e.g.
+///
+/// ```toml
+/// [[transforms]]
+/// type = "rename"
+/// from = "STREET_ENAME"
+/// to = "name"
+///
+/// [[transforms]]
+/// type = "set"
+/// attribute = "source"
+/// value = "TD"
+/// layers = ["centerlines"]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct TransformConfig {
+    /// Output layers the step applies to; all of them when empty.
+    #[serde(default)]
+    pub layers: Vec<String>,
+    #[serde(flatten)]
+    pub step: TransformStep,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransformStep {
+    /// Moves an attribute's value to another name.
+    Rename { from: String, to: String },
+    /// Removes the listed attributes.
+    Drop { attributes: Vec<String> },
+    /// Removes every attribute not listed.
+    Keep { attributes: Vec<String> },
+    /// Sets an attribute to the same value on every feature.
+    Set { attribute: String, value: PropertyValue },
+    /// Drops features whose attribute isn't one of `values`, compared as text.
+    Filter { attribute: String, values: Vec<String> },
+    /// Rounds every coordinate to this many decimal places.
+    RoundCoordinates { decimals: u32 },
+}
+
+/// An attribute of one layer that holds IDs of features in another, resolved by
+/// `--links`, e.g.
+///
+/// ```toml
+/// [[links]]
+/// from = "centerlines"
+/// attribute = "PED_ZONE_ID"
+/// to = "pedestrian_zones"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LinkConfig {
+    pub from: String,
+    pub attribute: String,
+    pub to: String,
+    /// Splits attributes that list several IDs.
+    #[serde(default = "default_link_separator")]
+    pub separator: String,
+}
+
+fn default_link_separator() -> String {
+    ",".to_string()
+}
+
+/// Reshapes a layer's features to match an existing API, e.g.
+///
+/// ```toml
+/// [templates.centerlines]
+/// members = { name = "STREET_ENAME" }
+/// properties = { id = "ROUTE_ID" }
+/// raw = "raw"
+/// ```
+///
+/// gives each centerline a top-level `name`, an `id` property and every source
+/// attribute nested under a `raw` property.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TemplateConfig {
+    /// Top-level Feature members, each copied from the named attribute.
+    pub members: BTreeMap<String, String>,
+    /// Properties, each copied from the named attribute. Without any, and
+    /// without `raw`, the attributes are kept as they are.
+    pub properties: BTreeMap<String, String>,
+    /// Property to nest every attribute under.
+    pub raw: Option<String>,
+}
+
+impl TemplateConfig {
+    /// The feature in this shape. Attributes a feature lacks come out as null,
+    /// so every feature has the same members.
+    pub fn apply<'a>(&'a self, feature: &'a GeoJsonFeature) -> TemplatedFeature<'a> {
+        let attribute = |name: &String| feature.properties.get(name).unwrap_or(&PropertyValue::Null);
+        let mut properties: BTreeMap<&str, Value> = self
+            .properties
+            .iter()
+            .map(|(name, source)| (name.as_str(), json!(attribute(source))))
+            .collect();
+        if let Some(raw) = &self.raw {
+            properties.insert(raw, json!(feature.properties));
+        } else if self.properties.is_empty() {
+            properties.extend(feature.properties.iter().map(|(name, value)| (name.as_str(), json!(value))));
+        }
+        TemplatedFeature {
+            feature_type: &feature.feature_type,
+            members: self.members.iter().map(|(name, source)| (name.as_str(), attribute(source))).collect(),
+            geometry: &feature.geometry,
+            properties,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    /// Attributes whose values are indexed for `search`.
+    pub name_attributes: Vec<String>,
+}
+
+impl SearchConfig {
+    /// The first non-empty name attribute, used wherever a feature needs a label.
+    pub fn name_of(&self, properties: &HashMap<String, PropertyValue>) -> Option<String> {
+        self.name_attributes
+            .iter()
+            .filter_map(|attribute| properties.get(attribute))
+            .map(|value| value.to_string())
+            .find(|name| !name.is_empty())
+    }
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            name_attributes: vec!["STREET_ENAME".to_string(), "STREET_CNAME".to_string()],
+        }
+    }
+}
+
+/// Languages that names can be kept in with `--lang`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    /// English names only.
+    En,
+    /// Chinese names only.
+    Zh,
+    /// Every name, as read.
+    Both,
+}
+
+/// Name attributes by language, e.g.
+///
+/// ```toml
+/// [names]
+/// lang = "zh"
+/// en = ["STREET_ENAME"]
+/// zh = ["STREET_CNAME"]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NamesConfig {
+    /// Language whose names are kept, as `--lang` sets.
+    pub lang: Option<Language>,
+    pub en: Vec<String>,
+    pub zh: Vec<String>,
+}
+
+impl Default for NamesConfig {
+    fn default() -> Self {
+        NamesConfig {
+            lang: None,
+            en: vec!["STREET_ENAME".to_string()],
+            zh: vec!["STREET_CNAME".to_string()],
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GraphConfig {
+    pub direction: DirectionConfig,
+    pub osm: OsmConfig,
+}
+
+/// How a segment may be travelled relative to its digitised vertex order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Both,
+    Forward,
+    Backward,
+    Closed,
+}
+
+/// Maps a centerline attribute onto a travel direction, e.g.
+///
+/// ```toml
+/// [graph.direction]
+/// attribute = "TRAVEL_DIRECTION"
+/// default = "both"
+///
+/// [graph.direction.values]
+/// "1" = "both"
+/// "2" = "forward"
+/// "3" = "backward"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DirectionConfig {
+    pub attribute: String,
+    /// Used when the attribute is missing or has an unmapped value.
+    pub default: Direction,
+    pub values: HashMap<String, Direction>,
+}
+
+impl Default for DirectionConfig {
+    fn default() -> Self {
+        DirectionConfig {
+            attribute: "TRAVEL_DIRECTION".to_string(),
+            default: Direction::Both,
+            values: HashMap::from([
+                ("1".to_string(), Direction::Both),
+                ("2".to_string(), Direction::Forward),
+                ("3".to_string(), Direction::Backward),
+            ]),
+        }
+    }
+}
+
+impl DirectionConfig {
+    pub fn resolve(&self, properties: &HashMap<String, PropertyValue>) -> Direction {
+        properties
+            .get(&self.attribute)
+            .and_then(|value| self.values.get(&value.to_string()))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// Tagging used by `graph --format osm`, e.g.
+///
+/// ```toml
+/// [graph.osm]
+/// name_attribute = "STREET_ENAME"
+/// highway_attribute = "ROAD_TYPE"
+/// default_highway = "unclassified"
+///
+/// [graph.osm.highway_values]
+/// "1" = "motorway"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OsmConfig {
+    pub name_attribute: String,
+    pub highway_attribute: Option<String>,
+    pub highway_values: HashMap<String, String>,
+    /// `highway=*` value for segments without a mapped attribute value. Routing
+    /// profiles ignore ways without a `highway` tag, so this must be routable.
+    pub default_highway: String,
+}
+
+impl Default for OsmConfig {
+    fn default() -> Self {
+        OsmConfig {
+            name_attribute: "STREET_ENAME".to_string(),
+            highway_attribute: None,
+            highway_values: HashMap::new(),
+            default_highway: "unclassified".to_string(),
+        }
+    }
+}
+
+impl OsmConfig {
+    pub fn highway(&self, properties: &HashMap<String, PropertyValue>) -> &str {
+        self.highway_attribute
+            .as_ref()
+            .and_then(|attribute| properties.get(attribute))
+            .and_then(|value| self.highway_values.get(&value.to_string()))
+            .unwrap_or(&self.default_highway)
+    }
+}
+
+impl Config {
+    /// Keeps only the names in [`NamesConfig::lang`]: the other language's name
+    /// attributes are dropped from every converted feature by a `drop` step at
+    /// the end of `[[transforms]]`, and are no longer used for search or labels.
+    pub fn apply_language(&mut self) {
+        let dropped = match self.names.lang {
+            None | Some(Language::Both) => return,
+            Some(Language::En) => self.names.zh.clone(),
+            Some(Language::Zh) => self.names.en.clone(),
+        };
+        let kept = if self.names.lang == Some(Language::En) { &self.names.en } else { &self.names.zh };
+        self.search.name_attributes.retain(|attribute| !dropped.contains(attribute));
+        if dropped.contains(&self.graph.osm.name_attribute)
+            && let Some(name) = kept.first()
+        {
+            self.graph.osm.name_attribute = name.clone();
+        }
+        self.transforms.push(TransformConfig {
+            layers: Vec::new(),
+            step: TransformStep::Drop { attributes: dropped },
+        });
+    }
+
+    /// Everything in the config that would make a run fail or quietly do
+    /// something other than intended, each as a message naming the key to fix.
+    /// Syntax errors and unknown keys are already refused by [`load`](Self::load).
+    pub fn problems(&self) -> Vec<String> {
+        let layers: Vec<&str> = DATASETS.iter().chain([&GENERIC_LINES]).map(|dataset| dataset.output_subdir).collect();
+        let known = |layer: &str| layers.contains(&layer);
+        let expected = layers.join(", ");
+        let mut problems = Vec::new();
+
+        let input = &self.input;
+        if input.min_segment_length < 0.0 || input.min_segment_length.is_nan() {
+            problems.push(format!("input.min_segment_length must be 0 or more, not {}", input.min_segment_length));
+        }
+        if let Some(lod) = input.lod.filter(|lod| *lod > 4) {
+            problems.push(format!("input.lod must be a CityGML level of detail from 0 to 4, not {}", lod));
+        }
+        if let Some(decimals) = input.snap_decimals.filter(|decimals| *decimals > 15) {
+            problems.push(format!("input.snap_decimals must be from 0 to 15, not {}", decimals));
+        }
+        if input.ids.pad_to == Some(0) {
+            problems.push("input.ids.pad_to = 0 pads nothing; remove it or give the ID width".to_string());
+        }
+        for (key, attribute) in [("valid_from", &input.valid_from), ("valid_to", &input.valid_to)] {
+            if attribute.as_deref().is_some_and(str::is_empty) {
+                problems.push(format!("input.{} is empty; remove it to use the CityGML lifespan dates", key));
+            }
+        }
+
+        for layer in self.datasets.keys().filter(|layer| !known(layer)) {
+            problems.push(format!("[datasets.{}] matches no layer; expected one of {}", layer, expected));
+        }
+        for layer in self.templates.keys() {
+            let base = layer.strip_suffix("_points").or_else(|| layer.strip_suffix("_endpoints")).unwrap_or(layer);
+            if !known(base) {
+                problems.push(format!("[templates.{}] matches no layer; expected one of {}", layer, expected));
+            }
+        }
+
+        for (i, link) in self.links.iter().enumerate() {
+            for layer in [&link.from, &link.to].into_iter().filter(|layer| !known(layer)) {
+                problems.push(format!("[[links]] #{}: `{}` is no layer; expected one of {}", i + 1, layer, expected));
+            }
+            if link.attribute.is_empty() {
+                problems.push(format!("[[links]] #{}: attribute is empty", i + 1));
+            }
+            if link.separator.is_empty() {
+                problems.push(format!("[[links]] #{}: separator is empty; remove it to split on commas", i + 1));
+            }
+        }
+
+        for (i, transform) in self.transforms.iter().enumerate() {
+            let step = format!("[[transforms]] #{}", i + 1);
+            for layer in transform.layers.iter().filter(|layer| !known(layer)) {
+                problems.push(format!("{}: `{}` in layers is no layer; expected one of {}", step, layer, expected));
+            }
+            match &transform.step {
+                TransformStep::Rename { from, to } if from == to => {
+                    problems.push(format!("{}: renames {} to itself", step, from));
+                }
+                TransformStep::Keep { attributes } if attributes.is_empty() => {
+                    problems.push(format!("{}: keep with no attributes removes them all; use drop instead", step));
+                }
+                TransformStep::Filter { attribute, values } if values.is_empty() => {
+                    problems.push(format!("{}: filter on {} with no values drops every feature", step, attribute));
+                }
+                _ => {}
+            }
+        }
+
+        if self.search.name_attributes.is_empty() {
+            problems.push("search.name_attributes is empty, so the search index will be empty".to_string());
+        }
+        let kept = match self.names.lang {
+            Some(Language::En) => Some(("en", &self.names.en)),
+            Some(Language::Zh) => Some(("zh", &self.names.zh)),
+            _ => None,
+        };
+        if let Some((lang, attributes)) = kept
+            && attributes.is_empty()
+        {
+            problems.push(format!("names.lang = \"{}\" keeps no names, as names.{} is empty", lang, lang));
+        }
+        if self.graph.direction.attribute.is_empty() {
+            problems.push("graph.direction.attribute is empty, so every segment gets the default direction".to_string());
+        }
+        if self.graph.osm.default_highway.is_empty() {
+            problems.push("graph.osm.default_highway is empty; routing profiles ignore ways without a highway tag".to_string());
+        }
+        problems
+    }
+
+    /// The attributes the config reads from source features, each with the key
+    /// naming it and the layer it must be in, or `None` when any layer will do.
+    pub fn source_attributes(&self) -> Vec<(String, Option<&str>, &str)> {
+        let mut attributes: Vec<(String, Option<&str>, &str)> = Vec::new();
+        for name in self.input.attributes.keys() {
+            attributes.push((format!("input.attributes.{}", name), None, name));
+        }
+        // The CityGML lifespan dates are read as properties of their own
+        let defaults = ParseOptions::default();
+        if let Some(name) = self.input.valid_from.as_ref().filter(|name| **name != defaults.valid_from) {
+            attributes.push(("input.valid_from".to_string(), None, name));
+        }
+        if let Some(name) = self.input.valid_to.as_ref().filter(|name| **name != defaults.valid_to) {
+            attributes.push(("input.valid_to".to_string(), None, name));
+        }
+        for (layer, dataset) in &self.datasets {
+            for name in &dataset.required {
+                attributes.push((format!("datasets.{}.required", layer), Some(layer), name));
+            }
+        }
+        for (i, link) in self.links.iter().enumerate() {
+            attributes.push((format!("[[links]] #{}", i + 1), Some(&link.from), &link.attribute));
+        }
+        let centerlines = Some(DATASETS[0].output_subdir);
+        attributes.push(("graph.direction.attribute".to_string(), centerlines, &self.graph.direction.attribute));
+        if let Some(name) = &self.graph.osm.highway_attribute {
+            attributes.push(("graph.osm.highway_attribute".to_string(), centerlines, name));
+        }
+        attributes.sort();
+        attributes
+    }
+
+    pub fn load(path: Option<&Path>) -> Result<Config> {
+        let Some(path) = path else {
+            return Ok(Config::default());
+        };
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config: {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("Invalid config: {}", path.display()))
+    }
+}
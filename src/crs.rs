@@ -0,0 +1,255 @@
+//! Coordinate reference systems the converter can emit.
+//!
+//! Source geometries are projected from the CRS named by their `srsName`
+//! (normally the Hong Kong 1980 Grid, EPSG:2326); output is WGS84 unless a
+//! consumer wants the grid coordinates.
+
+use crate::geometry;
+use clap::ValueEnum;
+use serde_json::{Value, json};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+const HK80_PROJ: &str = "+proj=tmerc +lat_0=22.31213333333334 +lon_0=114.1785555555556 +k=1 +x_0=836694.05 +y_0=819069.8 +ellps=intl +towgs84=-162.619,-276.959,-161.764,0.067753,-2.24365,-1.15883,-1.09425 +units=m +no_defs";
+const WGS84_PROJ: &str = "+proj=longlat +datum=WGS84 +no_defs";
+const HK1980_GEOGRAPHIC_PROJ: &str = "+proj=longlat +ellps=intl +towgs84=-162.619,-276.959,-161.764,0.067753,-2.24365,-1.15883,-1.09425 +no_defs";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Crs {
+    /// WGS84 longitude/latitude in degrees (EPSG:4326), as GeoJSON expects.
+    Wgs84,
+    /// Hong Kong 1980 Grid easting/northing in metres (EPSG:2326).
+    Hk80,
+}
+
+impl Crs {
+    pub fn epsg(self) -> u32 {
+        match self {
+            Crs::Wgs84 => 4326,
+            Crs::Hk80 => 2326,
+        }
+    }
+
+    /// Distance function for positions in this CRS (metres either way).
+    pub fn distance_fn(self) -> fn(&[f64], &[f64]) -> f64 {
+        match self {
+            Crs::Wgs84 => geometry::haversine_m,
+            Crs::Hk80 => geometry::planar_distance,
+        }
+    }
+
+    /// Ring area function for positions in this CRS (square metres either way).
+    pub fn ring_area_fn(self) -> fn(&[Vec<f64>]) -> f64 {
+        match self {
+            Crs::Wgs84 => geometry::ring_area_m2,
+            Crs::Hk80 => geometry::planar_ring_area,
+        }
+    }
+
+    /// Bearing function for positions in this CRS, in degrees from north.
+    pub fn bearing_fn(self) -> fn(&[f64], &[f64]) -> f64 {
+        match self {
+            Crs::Wgs84 => geometry::bearing_deg,
+            Crs::Hk80 => geometry::planar_bearing_deg,
+        }
+    }
+}
+
+/// How GeoJSON written in a CRS other than WGS84 names it, since RFC 7946
+/// readers otherwise take every position as longitude/latitude.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CrsAnnotation {
+    /// The `crs` member of the 2008 GeoJSON spec, naming the CRS by OGC URN.
+    Legacy,
+    /// A non-standard `x-crs` member holding `EPSG:<code>`.
+    XCrs,
+}
+
+impl CrsAnnotation {
+    /// The top-level member that labels a GeoJSON object as being in `crs`.
+    pub fn member(self, crs: Crs) -> (&'static str, Value) {
+        match self {
+            CrsAnnotation::Legacy => (
+                "crs",
+                json!({ "type": "name", "properties": { "name": format!("urn:ogc:def:crs:EPSG::{}", crs.epsg()) } }),
+            ),
+            CrsAnnotation::XCrs => ("x-crs", json!(format!("EPSG:{}", crs.epsg()))),
+        }
+    }
+}
+
+/// EPSG codes likely to appear in Hong Kong data whose official axis order puts
+/// northing (or latitude) first.
+const NORTHING_FIRST_EPSG: &[u32] = &[2326, 4326, 4490, 4611];
+
+/// EPSG code named by a `srsName` such as `EPSG:2326`,
+/// `urn:ogc:def:crs:EPSG::2326`, `http://www.opengis.net/def/crs/EPSG/0/2326` or
+/// `http://www.opengis.net/gml/srs/epsg.xml#2326`.
+pub fn epsg_code(srs_name: &str) -> Option<u32> {
+    if !srs_name.to_ascii_lowercase().contains("epsg") {
+        return None;
+    }
+    srs_name.rsplit([':', '/', '#']).next()?.parse().ok()
+}
+
+/// Whether positions written under `srs_name` list northing/latitude first.
+///
+/// Only the URN and `http://www.opengis.net/def/crs/EPSG/0/` forms promise the
+/// official EPSG axis order; the short `EPSG:2326` and old `epsg.xml#2326` forms
+/// are conventionally easting/longitude first, as most GIS software writes them.
+pub fn northing_first(srs_name: &str) -> bool {
+    let name = srs_name.to_ascii_lowercase();
+    let authoritative = name.starts_with("urn:ogc:def:crs:epsg:")
+        || name.starts_with("urn:x-ogc:def:crs:epsg:")
+        || name.contains("opengis.net/def/crs/epsg/");
+    authoritative && epsg_code(&name).is_some_and(|code| NORTHING_FIRST_EPSG.contains(&code))
+}
+
+/// proj4 definition for a source CRS the converter can read: the HK80 grid,
+/// HK1980 and WGS84 geographic, and the UTM zones covering Hong Kong.
+fn proj_string(epsg: u32) -> Option<&'static str> {
+    match epsg {
+        2326 => Some(HK80_PROJ),
+        4326 => Some(WGS84_PROJ),
+        4611 => Some(HK1980_GEOGRAPHIC_PROJ),
+        32649 => Some("+proj=utm +zone=49 +datum=WGS84 +units=m +no_defs"),
+        32650 => Some("+proj=utm +zone=50 +datum=WGS84 +units=m +no_defs"),
+        _ => None,
+    }
+}
+
+/// Whether positions in `epsg` can be projected by [`project`].
+pub fn is_supported(epsg: u32) -> bool {
+    proj_string(epsg).is_some()
+}
+
+/// A parsed source and target definition, with whether each is geographic.
+struct Transform {
+    from: proj4rs::Proj,
+    to: proj4rs::Proj,
+    from_degrees: bool,
+    to_degrees: bool,
+}
+
+/// Definitions parsed so far, by source and target EPSG code. `None` records a
+/// pair that couldn't be parsed.
+type TransformCache = HashMap<(u32, u32), Option<Rc<Transform>>>;
+
+thread_local! {
+    /// Cached so projecting each geometry doesn't parse the same proj strings again.
+    static TRANSFORMS: RefCell<TransformCache> = RefCell::new(HashMap::new());
+}
+
+fn transform(source: u32, target: u32) -> Option<Rc<Transform>> {
+    TRANSFORMS.with(|transforms| {
+        transforms
+            .borrow_mut()
+            .entry((source, target))
+            .or_insert_with(|| {
+                let from = proj4rs::Proj::from_proj_string(proj_string(source)?).ok()?;
+                let to = proj4rs::Proj::from_proj_string(proj_string(target)?).ok()?;
+                Some(Rc::new(Transform {
+                    from_degrees: from.is_latlong(),
+                    to_degrees: to.is_latlong(),
+                    from,
+                    to,
+                }))
+            })
+            .clone()
+    })
+}
+
+/// Converts a flat `x y [z] x y [z] ...` list of values in the `source` EPSG CRS,
+/// `dimension` values per position, into positions in `target`. Geographic values
+/// are in degrees, longitude first. Heights are carried over unchanged and any
+/// further ordinates are dropped. Points that fail to transform are dropped, as
+/// is everything when `source` is not [supported](is_supported).
+///
+/// When `source` already is `target`, as for HK80 Grid input written as HK80 or
+/// WGS84 input written as WGS84, the values are copied as they are without
+/// going through proj.
+pub fn project(values: &[f64], dimension: usize, source: u32, target: Crs) -> Vec<Vec<f64>> {
+    let positions = values.chunks_exact(dimension.max(2));
+    if source == target.epsg() {
+        return positions.map(|position| position[..position.len().min(3)].to_vec()).collect();
+    }
+
+    let Some(transform) = transform(source, target.epsg()) else {
+        return Vec::new();
+    };
+    positions
+        .filter_map(|position| {
+            let [x, y] = project_position(&transform, position)?;
+            let mut projected = vec![x, y];
+            projected.extend(position.get(2));
+            Some(projected)
+        })
+        .collect()
+}
+
+/// Projects the easting and northing, or longitude and latitude, of one position.
+fn project_position(transform: &Transform, position: &[f64]) -> Option<[f64; 2]> {
+    // proj4rs works in radians for geographic coordinates
+    let mut point = if transform.from_degrees {
+        (position[0].to_radians(), position[1].to_radians(), 0.0)
+    } else {
+        (position[0], position[1], 0.0)
+    };
+    proj4rs::transform::transform(&transform.from, &transform.to, &mut point).ok()?;
+    if transform.to_degrees {
+        Some([point.0.to_degrees(), point.1.to_degrees()])
+    } else {
+        Some([point.0, point.1])
+    }
+}
+
+/// Projected positions by source and target EPSG code and the exact bits of
+/// their first two ordinates, so that a vertex shared by several geometries,
+/// such as the common end of two road segments, is projected once. Every
+/// geometry then gets the very same projected values for it. Once `capacity`
+/// positions are held the cache starts again empty, which bounds its memory.
+pub struct VertexCache {
+    positions: HashMap<(u32, u32, u64, u64), Option<[f64; 2]>>,
+    capacity: usize,
+}
+
+impl VertexCache {
+    pub fn new(capacity: usize) -> Self {
+        VertexCache {
+            positions: HashMap::new(),
+            capacity,
+        }
+    }
+
+    /// Like [`project`], looking each position up before projecting it.
+    pub fn project(&mut self, values: &[f64], dimension: usize, source: u32, target: Crs) -> Vec<Vec<f64>> {
+        if source == target.epsg() {
+            return project(values, dimension, source, target);
+        }
+        let Some(transform) = transform(source, target.epsg()) else {
+            return Vec::new();
+        };
+        values
+            .chunks_exact(dimension.max(2))
+            .filter_map(|position| {
+                let key = (source, target.epsg(), position[0].to_bits(), position[1].to_bits());
+                let projected = match self.positions.get(&key) {
+                    Some(projected) => *projected,
+                    None => {
+                        if self.positions.len() >= self.capacity {
+                            self.positions.clear();
+                        }
+                        let projected = project_position(&transform, position);
+                        self.positions.insert(key, projected);
+                        projected
+                    }
+                };
+                let [x, y] = projected?;
+                let mut vertex = vec![x, y];
+                vertex.extend(position.get(2));
+                Some(vertex)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,84 @@
+//! The Road Network (2nd Generation) GML files this tool knows how to convert.
+
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatasetKind {
+    /// Linear features such as road centerlines.
+    Line,
+    /// Area features whose coordinates form a closed ring.
+    Zone,
+}
+
+pub struct Dataset {
+    pub file_name: &'static str,
+    pub id_field: &'static str,
+    pub output_subdir: &'static str,
+    pub kind: DatasetKind,
+    /// Prefix of the `gml:id`s written back by [`crate::citygml`].
+    pub gml_id_prefix: &'static str,
+}
+
+pub const DATASETS: &[Dataset] = &[
+    Dataset {
+        file_name: "CENTERLINE.gml",
+        id_field: "ROUTE_ID",
+        output_subdir: "centerlines",
+        kind: DatasetKind::Line,
+        gml_id_prefix: "CL",
+    },
+    Dataset {
+        file_name: "PEDESTRIAN_ZONE.gml",
+        id_field: "PED_ZONE_ID",
+        output_subdir: "pedestrian_zones",
+        kind: DatasetKind::Zone,
+        gml_id_prefix: "PZ",
+    },
+];
+
+impl Dataset {
+    /// The dataset's file in `data_dir`: `<file_name>`, or `<file_name>.gz` when
+    /// only a gzipped copy is there.
+    pub fn path_in(&self, data_dir: &str) -> String {
+        let path = format!("{}/{}", data_dir, self.file_name);
+        let gzipped = format!("{}.gz", path);
+        if !Path::new(&path).exists() && Path::new(&gzipped).exists() { gzipped } else { path }
+    }
+}
+
+/// Stand-ins for GML files that match no known dataset. Their features keep
+/// their `gml:id` as ID and all go to the `features` layer.
+pub const GENERIC_LINES: Dataset = Dataset {
+    file_name: "",
+    id_field: "",
+    output_subdir: "features",
+    kind: DatasetKind::Line,
+    gml_id_prefix: "OBJ",
+};
+
+pub const GENERIC_ZONES: Dataset = Dataset {
+    kind: DatasetKind::Zone,
+    ..GENERIC_LINES
+};
+
+/// The known dataset a GML document holds, told apart by the attribute its IDs
+/// are written in. `head` can be just the start of the file, as long as it
+/// reaches the first feature. Anything but a CityGML `CityModel` matches none.
+pub fn detect(head: &str) -> Option<&'static Dataset> {
+    if !head.contains(":CityModel") {
+        return None;
+    }
+    DATASETS
+        .iter()
+        .find(|dataset| head.contains(&format!("name=\"{}\"", dataset.id_field)))
+}
+
+/// The generic stand-in for a document [`detect`] doesn't recognise, by whether
+/// its first geometries are surfaces.
+pub fn generic(head: &str) -> &'static Dataset {
+    if head.contains(":Polygon") || head.contains(":Surface") || head.contains(":MultiSurface") {
+        &GENERIC_ZONES
+    } else {
+        &GENERIC_LINES
+    }
+}
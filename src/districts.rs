@@ -0,0 +1,113 @@
+//! Boundary polygons from GeoJSON, used to split output by district
+//! (`--split-by`), as the bounding boxes of `--clip-areas` and as `aggregate`
+//! areas.
+
+use crate::geometry;
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// A polygon as its rings, outer ring first.
+pub type Polygon = Vec<Vec<[f64; 2]>>;
+
+struct District {
+    name: String,
+    polygons: Vec<Polygon>,
+    bbox: [f64; 4],
+}
+
+pub struct Districts {
+    districts: Vec<District>,
+}
+
+fn ring(value: &Value) -> Option<Vec<[f64; 2]>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|position| Some([position.get(0)?.as_f64()?, position.get(1)?.as_f64()?]))
+        .collect()
+}
+
+fn polygon(value: &Value) -> Option<Polygon> {
+    value.as_array()?.iter().map(ring).collect()
+}
+
+/// The polygons of a GeoJSON Polygon or MultiPolygon geometry; empty for any
+/// other type.
+pub fn polygons(geometry: &Value) -> Vec<Polygon> {
+    let coordinates = &geometry["coordinates"];
+    match geometry["type"].as_str() {
+        Some("Polygon") => polygon(coordinates).into_iter().collect(),
+        Some("MultiPolygon") => coordinates
+            .as_array()
+            .map(|polygons| polygons.iter().filter_map(polygon).collect())
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// `[min_x, min_y, max_x, max_y]` of a set of polygons.
+pub fn bbox(polygons: &[Polygon]) -> [f64; 4] {
+    polygons.iter().flatten().flatten().fold(
+        [f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY],
+        |b, p| [b[0].min(p[0]), b[1].min(p[1]), b[2].max(p[0]), b[3].max(p[1])],
+    )
+}
+
+/// Whether `point` lies inside any of the polygons: within an outer ring and
+/// outside all of that polygon's holes.
+pub fn contains(polygons: &[Polygon], bbox: &[f64; 4], point: &[f64]) -> bool {
+    if point[0] < bbox[0] || point[0] > bbox[2] || point[1] < bbox[1] || point[1] > bbox[3] {
+        return false;
+    }
+    polygons.iter().any(|rings| {
+        let mut rings = rings.iter();
+        rings.next().is_some_and(|outer| geometry::point_in_ring(point, outer))
+            && rings.all(|hole| !geometry::point_in_ring(point, hole))
+    })
+}
+
+impl Districts {
+    /// Reads every Polygon and MultiPolygon feature of a GeoJSON FeatureCollection,
+    /// naming each by its `key` property.
+    pub fn load(path: &Path, key: &str) -> Result<Districts> {
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let collection: Value =
+            serde_json::from_str(&text).with_context(|| format!("Invalid GeoJSON: {}", path.display()))?;
+        let Some(features) = collection["features"].as_array() else {
+            bail!("{} is not a GeoJSON FeatureCollection", path.display());
+        };
+
+        let mut districts = Vec::new();
+        for feature in features {
+            let name = match &feature["properties"][key] {
+                Value::String(name) => name.clone(),
+                Value::Null => continue,
+                other => other.to_string(),
+            };
+            let polygons = polygons(&feature["geometry"]);
+            let bbox = bbox(&polygons);
+            if !polygons.is_empty() {
+                districts.push(District { name, polygons, bbox });
+            }
+        }
+        if districts.is_empty() {
+            bail!("{} has no polygons with a `{}` property", path.display(), key);
+        }
+        Ok(Districts { districts })
+    }
+
+    /// Each district's name and `[min_x, min_y, max_x, max_y]` bounding box.
+    pub fn bboxes(&self) -> impl Iterator<Item = (&str, &[f64; 4])> {
+        self.districts.iter().map(|d| (d.name.as_str(), &d.bbox))
+    }
+
+    /// Name of the first district containing `point`.
+    pub fn locate(&self, point: &[f64]) -> Option<&str> {
+        self.districts
+            .iter()
+            .find(|d| contains(&d.polygons, &d.bbox, point))
+            .map(|d| d.name.as_str())
+    }
+}
@@ -0,0 +1,70 @@
+//! Features with [`geo_types`] geometries, for use as a library.
+
+use crate::geojson::{self, GeoJsonFeature, PropertyValue};
+use crate::gml::{self, ParseOptions};
+use anyhow::Result;
+use geo_types::{Coord, LineString, MultiLineString, MultiPolygon, Point, Polygon};
+use std::collections::HashMap;
+
+/// One feature of a dataset, keyed by the ID attribute it was read with.
+#[derive(Debug, Clone)]
+pub struct Feature {
+    pub id: String,
+    /// Coordinates in [`ParseOptions::target_crs`], x (or longitude) first. Empty
+    /// when [`ParseOptions::geometry`] is off.
+    pub geometry: geo_types::Geometry<f64>,
+    pub properties: HashMap<String, PropertyValue>,
+}
+
+impl Feature {
+    pub fn new(id: String, feature: GeoJsonFeature) -> Self {
+        Feature {
+            id,
+            geometry: to_geo(&feature.geometry),
+            properties: feature.properties,
+        }
+    }
+}
+
+/// Reads every feature of a GML file into memory; see [`gml::parse_gml_file`]
+/// for streaming large files instead.
+pub fn read_features(path: &str, id_field: &str, options: &ParseOptions) -> Result<Vec<Feature>> {
+    let mut features = Vec::new();
+    gml::parse_gml_file(path, id_field, options, |id, feature| {
+        features.push(Feature::new(id, feature));
+        Ok(())
+    })?;
+    Ok(features)
+}
+
+/// Converts a GeoJSON geometry, dropping any z values.
+pub fn to_geo(geometry: &geojson::Geometry) -> geo_types::Geometry<f64> {
+    match geometry {
+        geojson::Geometry::Point(position) => Point(coord(position)).into(),
+        geojson::Geometry::LineString(line) => line_string(line).into(),
+        geojson::Geometry::MultiLineString(lines) => {
+            MultiLineString::new(lines.iter().map(|line| line_string(line)).collect()).into()
+        }
+        geojson::Geometry::Polygon(rings) => polygon(rings).into(),
+        geojson::Geometry::MultiPolygon(polygons) => {
+            MultiPolygon::new(polygons.iter().map(|rings| polygon(rings)).collect()).into()
+        }
+    }
+}
+
+fn coord(position: &[f64]) -> Coord<f64> {
+    Coord {
+        x: position[0],
+        y: position[1],
+    }
+}
+
+fn line_string(line: &[Vec<f64>]) -> LineString<f64> {
+    line.iter().map(|position| coord(position)).collect()
+}
+
+fn polygon(rings: &[Vec<Vec<f64>>]) -> Polygon<f64> {
+    let mut rings = rings.iter().map(|ring| line_string(ring));
+    let exterior = rings.next().unwrap_or_else(|| LineString::new(Vec::new()));
+    Polygon::new(exterior, rings.collect())
+}
@@ -0,0 +1,119 @@
+//! C ABI (`ffi` feature), for embedding the converter in C and C++ services.
+//! The declarations are in `include/road_network_json.h`.
+//!
+//! Strings returned by these functions are owned by the library and stay valid
+//! until the next call on the same handle (or, for [`rnj_last_error`], the next
+//! failing call on the same thread).
+
+use crate::gml;
+use std::cell::RefCell;
+use std::ffi::{CString, c_char};
+use std::ptr;
+
+/// The features of one converted document, handed out one at a time.
+pub struct RnjFeatures {
+    features: std::vec::IntoIter<CString>,
+    count: usize,
+    current: Option<CString>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Converts `len` bytes of a `CENTERLINE.gml` or `PEDESTRIAN_ZONE.gml` document
+/// to GeoJSON Features in WGS84. Returns null on failure; see [`rnj_last_error`].
+/// Free the result with [`rnj_features_free`].
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rnj_convert(data: *const u8, len: usize) -> *mut RnjFeatures {
+    if data.is_null() {
+        set_last_error("No GML given".to_string());
+        return ptr::null_mut();
+    }
+    // SAFETY: the caller promises `len` readable bytes at `data`
+    let bytes = unsafe { std::slice::from_raw_parts(data, len) };
+    let content = match std::str::from_utf8(bytes) {
+        Ok(content) => content,
+        Err(e) => {
+            set_last_error(format!("GML is not UTF-8: {}", e));
+            return ptr::null_mut();
+        }
+    };
+    let features = match gml::convert_document(content) {
+        Ok(features) => features,
+        Err(e) => {
+            set_last_error(format!("{:#}", e));
+            return ptr::null_mut();
+        }
+    };
+    let mut json = Vec::with_capacity(features.len());
+    for (_, feature) in &features {
+        match serde_json::to_string(feature) {
+            // JSON escapes control characters, so it never holds a NUL
+            Ok(text) => json.push(CString::new(text).unwrap_or_default()),
+            Err(e) => {
+                set_last_error(e.to_string());
+                return ptr::null_mut();
+            }
+        }
+    }
+    Box::into_raw(Box::new(RnjFeatures {
+        count: json.len(),
+        features: json.into_iter(),
+        current: None,
+    }))
+}
+
+/// The number of features converted.
+///
+/// # Safety
+///
+/// `features` must come from [`rnj_convert`] and not have been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rnj_features_count(features: *const RnjFeatures) -> usize {
+    // SAFETY: the caller promises a live handle
+    unsafe { features.as_ref() }.map_or(0, |features| features.count)
+}
+
+/// The next feature as a GeoJSON Feature string, or null after the last one.
+///
+/// # Safety
+///
+/// `features` must come from [`rnj_convert`] and not have been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rnj_features_next(features: *mut RnjFeatures) -> *const c_char {
+    // SAFETY: the caller promises a live handle
+    let Some(features) = (unsafe { features.as_mut() }) else {
+        return ptr::null();
+    };
+    features.current = features.features.next();
+    features.current.as_ref().map_or(ptr::null(), |feature| feature.as_ptr())
+}
+
+/// Frees the features returned by [`rnj_convert`]. Null is ignored.
+///
+/// # Safety
+///
+/// `features` must come from [`rnj_convert`] and not have been freed already.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rnj_features_free(features: *mut RnjFeatures) {
+    if !features.is_null() {
+        // SAFETY: the handle was made by `Box::into_raw` and is freed only once
+        drop(unsafe { Box::from_raw(features) });
+    }
+}
+
+/// The message of the last failure on this thread, or null if nothing failed.
+#[unsafe(no_mangle)]
+pub extern "C" fn rnj_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
@@ -0,0 +1,66 @@
+//! Content fingerprints of features, for `--fingerprint`.
+//!
+//! A fingerprint is the SHA-256 of a feature's geometry and properties in a
+//! normalized form: properties in key order, without the `fingerprint`
+//! property itself, and ordinates rounded to nine decimal places so that
+//! floating-point noise from projecting doesn't change it. Two runs over an
+//! unchanged feature give the same fingerprint whatever order its attributes
+//! were read in.
+
+use crate::geojson::{GeoJsonFeature, Geometry, PropertyValue};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// The property the fingerprint is written to.
+pub const PROPERTY: &str = "fingerprint";
+
+/// Decimal places ordinates are rounded to before hashing.
+const DECIMALS: i32 = 9;
+
+#[derive(Serialize)]
+struct Normalized<'a> {
+    geometry: &'a Geometry,
+    properties: BTreeMap<&'a str, &'a PropertyValue>,
+}
+
+/// The feature's fingerprint, as 64 hex digits.
+pub fn fingerprint(feature: &GeoJsonFeature) -> String {
+    let factor = 10f64.powi(DECIMALS);
+    let mut geometry = feature.geometry.clone();
+    for position in geometry.positions_mut() {
+        for ordinate in position.iter_mut() {
+            // `+ 0.0` turns -0.0 into 0.0
+            *ordinate = (*ordinate * factor).round() / factor + 0.0;
+        }
+    }
+    let properties = feature
+        .properties
+        .iter()
+        .filter(|(name, _)| name.as_str() != PROPERTY)
+        .map(|(name, value)| (name.as_str(), value))
+        .collect();
+    let json = serde_json::to_vec(&Normalized {
+        geometry: &geometry,
+        properties,
+    })
+    .expect("features always serialize");
+    hex(&Sha256::digest(&json))
+}
+
+/// One fingerprint for a whole layer, from the fingerprint of each feature by
+/// ID. It changes when any feature is added, removed or changed.
+pub fn layer_fingerprint(fingerprints: &BTreeMap<String, String>) -> String {
+    let mut hasher = Sha256::new();
+    for (id, fingerprint) in fingerprints {
+        hasher.update(id.as_bytes());
+        hasher.update(b"\t");
+        hasher.update(fingerprint.as_bytes());
+        hasher.update(b"\n");
+    }
+    hex(&hasher.finalize())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,132 @@
+//! Golden-output checks of the parser and projection, run by the
+//! `verify-fixtures` command and by `cargo test`.
+//!
+//! The fixture directory (`fixtures/` in the repository) holds small synthetic
+//! copies of the source datasets. Each one found there is read into every
+//! output CRS and compared with `expected/<layer>.<crs>.geojson`: a
+//! FeatureCollection of its features in file order, each with its ID as the
+//! GeoJSON `id`. Numbers may differ by [`TOLERANCE`], so that the last digits
+//! of a projection can vary by platform without failing the check.
+
+use crate::crs::Crs;
+use crate::dataset::{DATASETS, Dataset};
+use crate::gml::{self, ParseOptions};
+use crate::paths;
+use crate::sink::write_json;
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+use std::fs;
+use std::path::Path;
+
+/// Largest difference between an expected and an actual number. In degrees it
+/// is about 0.1 mm on the ground.
+pub const TOLERANCE: f64 = 1e-9;
+
+/// The CRSs each fixture is read into, with the name used in golden files.
+const CRSS: [(&str, Crs); 2] = [("wgs84", Crs::Wgs84), ("hk80", Crs::Hk80)];
+
+/// Reads one fixture into `crs` as the FeatureCollection its golden file holds.
+fn convert(path: &str, dataset: &Dataset, crs: Crs) -> Result<Value> {
+    let options = ParseOptions {
+        target_crs: crs,
+        ..ParseOptions::default()
+    };
+    let mut features = Vec::new();
+    gml::parse_gml_file(path, dataset.id_field, &options, |id, feature| {
+        let mut value = serde_json::to_value(&feature)?;
+        value["id"] = id.into();
+        features.push(value);
+        Ok(())
+    })?;
+    Ok(json!({ "type": "FeatureCollection", "features": features }))
+}
+
+/// The fixtures in `dir` with the golden file each is compared with.
+fn cases(dir: &Path) -> Vec<(String, &'static Dataset, Crs, String)> {
+    let dir = dir.to_string_lossy();
+    DATASETS
+        .iter()
+        .filter(|dataset| Path::new(&dataset.path_in(&dir)).exists())
+        .flat_map(|dataset| {
+            let dir = &dir;
+            CRSS.iter().map(move |(name, crs)| {
+                let expected = format!("{}/expected/{}.{}.geojson", dir, dataset.output_subdir, name);
+                (dataset.path_in(dir), dataset, *crs, expected)
+            })
+        })
+        .collect()
+}
+
+/// Converts every fixture in `dir` and returns a description of each way the
+/// output differs from its golden file; none when everything matches.
+pub fn verify(dir: &Path) -> Result<Vec<String>> {
+    let cases = cases(dir);
+    if cases.is_empty() {
+        anyhow::bail!("No fixtures found in {}", dir.display());
+    }
+    let mut mismatches = Vec::new();
+    for (path, dataset, crs, expected_path) in cases {
+        let actual = convert(&path, dataset, crs)?;
+        let text = fs::read_to_string(&expected_path)
+            .with_context(|| format!("Failed to read {}; run verify-fixtures --bless to create it", expected_path))?;
+        let expected: Value =
+            serde_json::from_str(&text).with_context(|| format!("Invalid golden file: {}", expected_path))?;
+        let before = mismatches.len();
+        compare(&expected, &actual, "", &mut mismatches);
+        for mismatch in &mut mismatches[before..] {
+            *mismatch = format!("{}: {}", expected_path, mismatch);
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Rewrites every golden file in `dir` from the current output, returning how
+/// many were written.
+pub fn bless(dir: &Path) -> Result<usize> {
+    let cases = cases(dir);
+    fs::create_dir_all(paths::long_path(dir.join("expected"))).context("Failed to create the golden file directory")?;
+    for (path, dataset, crs, expected_path) in &cases {
+        write_json(expected_path, &convert(path, dataset, *crs)?)?;
+    }
+    Ok(cases.len())
+}
+
+/// Records each difference between `expected` and `actual` under the JSON
+/// pointer `at`.
+fn compare(expected: &Value, actual: &Value, at: &str, mismatches: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::Number(e), Value::Number(a)) => {
+            let same_type = e.is_f64() == a.is_f64();
+            let (e, a) = (e.as_f64().unwrap_or_default(), a.as_f64().unwrap_or_default());
+            if !same_type || (e - a).abs() > TOLERANCE {
+                mismatches.push(format!("{} is {}, expected {}", pointer(at), a, e));
+            }
+        }
+        (Value::Array(e), Value::Array(a)) => {
+            if e.len() != a.len() {
+                mismatches.push(format!("{} has {} items, expected {}", pointer(at), a.len(), e.len()));
+                return;
+            }
+            for (i, (e, a)) in e.iter().zip(a).enumerate() {
+                compare(e, a, &format!("{}/{}", at, i), mismatches);
+            }
+        }
+        (Value::Object(e), Value::Object(a)) => {
+            for (key, e) in e {
+                match a.get(key) {
+                    Some(a) => compare(e, a, &format!("{}/{}", at, key), mismatches),
+                    None => mismatches.push(format!("{}/{} is missing", at, key)),
+                }
+            }
+            for key in a.keys().filter(|key| !e.contains_key(*key)) {
+                mismatches.push(format!("{}/{} is unexpected", at, key));
+            }
+        }
+        (e, a) if e != a => mismatches.push(format!("{} is {}, expected {}", pointer(at), a, e)),
+        _ => {}
+    }
+}
+
+fn pointer(at: &str) -> &str {
+    if at.is_empty() { "/" } else { at }
+}
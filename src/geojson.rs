@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// A top-level member added to a GeoJSON object, such as a CRS annotation.
+pub type ForeignMember = (&'static str, serde_json::Value);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GeoJsonFeature {
+    #[serde(rename = "type")]
+    pub feature_type: String,
+    pub geometry: Geometry,
+    /// Written in key order, so the same feature always serializes the same.
+    #[serde(serialize_with = "sorted")]
+    pub properties: HashMap<String, PropertyValue>,
+}
+
+fn sorted<S: serde::Serializer>(properties: &HashMap<String, PropertyValue>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(properties.iter().collect::<BTreeMap<_, _>>())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GeoJsonFeatureCollection {
+    #[serde(rename = "type")]
+    pub collection_type: String,
+    pub features: Vec<GeoJsonFeature>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", content = "coordinates")]
+pub enum Geometry {
+    Point(Vec<f64>),
+    LineString(Vec<Vec<f64>>),
+    MultiLineString(Vec<Vec<Vec<f64>>>),
+    /// An exterior ring followed by any interior rings, each closed.
+    Polygon(Vec<Vec<Vec<f64>>>),
+    MultiPolygon(Vec<Vec<Vec<Vec<f64>>>>),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum PropertyValue {
+    String(String),
+    Int(i64),
+    Float(f64),
+    Null,
+}
+
+impl GeoJsonFeature {
+    pub fn new(geometry: Geometry, properties: HashMap<String, PropertyValue>) -> Self {
+        GeoJsonFeature {
+            feature_type: "Feature".to_string(),
+            geometry,
+            properties,
+        }
+    }
+}
+
+impl GeoJsonFeatureCollection {
+    pub fn new(features: Vec<GeoJsonFeature>) -> Self {
+        GeoJsonFeatureCollection {
+            collection_type: "FeatureCollection".to_string(),
+            features,
+        }
+    }
+}
+
+impl fmt::Display for PropertyValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PropertyValue::String(s) => f.write_str(s),
+            PropertyValue::Int(i) => write!(f, "{}", i),
+            PropertyValue::Float(v) => write!(f, "{}", v),
+            PropertyValue::Null => f.write_str("null"),
+        }
+    }
+}
+
+impl Geometry {
+    /// Every position in the geometry, in order.
+    pub fn positions(&self) -> Vec<&[f64]> {
+        match self {
+            Geometry::Point(p) => vec![p.as_slice()],
+            Geometry::LineString(coords) => coords.iter().map(Vec::as_slice).collect(),
+            Geometry::MultiLineString(lines) | Geometry::Polygon(lines) => {
+                lines.iter().flatten().map(Vec::as_slice).collect()
+            }
+            Geometry::MultiPolygon(polygons) => polygons.iter().flatten().flatten().map(Vec::as_slice).collect(),
+        }
+    }
+
+    /// Every position in the geometry, in order, for changing in place.
+    pub fn positions_mut(&mut self) -> Vec<&mut Vec<f64>> {
+        match self {
+            Geometry::Point(p) => vec![p],
+            Geometry::LineString(coords) => coords.iter_mut().collect(),
+            Geometry::MultiLineString(lines) | Geometry::Polygon(lines) => lines.iter_mut().flatten().collect(),
+            Geometry::MultiPolygon(polygons) => polygons.iter_mut().flatten().flatten().collect(),
+        }
+    }
+
+    /// The polygons of a Polygon or MultiPolygon, each as its exterior ring
+    /// followed by its interior rings; empty for other geometries.
+    pub fn polygons(&self) -> Vec<&[Vec<Vec<f64>>]> {
+        match self {
+            Geometry::Polygon(rings) => vec![rings.as_slice()],
+            Geometry::MultiPolygon(polygons) => polygons.iter().map(Vec::as_slice).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// `[min_lon, min_lat, max_lon, max_lat]`, or `None` for an empty geometry.
+    pub fn bbox(&self) -> Option<[f64; 4]> {
+        let positions = self.positions();
+        if positions.is_empty() {
+            return None;
+        }
+        Some(positions.iter().fold(
+            [f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY],
+            |b, p| [b[0].min(p[0]), b[1].min(p[1]), b[2].max(p[0]), b[3].max(p[1])],
+        ))
+    }
+}
+
+/// `value` with `member` added at its top level, after its own members.
+#[derive(Serialize)]
+pub struct WithMember<'a, T> {
+    #[serde(flatten)]
+    value: &'a T,
+    #[serde(flatten)]
+    member: HashMap<&'static str, &'a serde_json::Value>,
+}
+
+pub fn with_member<'a, T: Serialize>(value: &'a T, member: Option<&'a ForeignMember>) -> WithMember<'a, T> {
+    WithMember {
+        value,
+        member: member.map(|(name, member)| (*name, member)).into_iter().collect(),
+    }
+}
+
+/// A feature reshaped by a [`TemplateConfig`](crate::config::TemplateConfig).
+#[derive(Serialize)]
+pub struct TemplatedFeature<'a> {
+    #[serde(rename = "type")]
+    pub feature_type: &'a str,
+    #[serde(flatten)]
+    pub members: BTreeMap<&'a str, &'a PropertyValue>,
+    pub geometry: &'a Geometry,
+    pub properties: BTreeMap<&'a str, serde_json::Value>,
+}
@@ -0,0 +1,419 @@
+//! Planar and geodesic helpers. Unless stated otherwise, functions expect WGS84
+//! `[lon, lat]` coordinate lists.
+
+const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+/// Great-circle distance between two `[lon, lat]` positions, in metres.
+pub fn haversine_m(a: &[f64], b: &[f64]) -> f64 {
+    let (lon1, lat1) = (a[0].to_radians(), a[1].to_radians());
+    let (lon2, lat2) = (b[0].to_radians(), b[1].to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = lon2 - lon1;
+    let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().asin()
+}
+
+/// Total length of a line in metres.
+pub fn line_length_m(coords: &[Vec<f64>]) -> f64 {
+    coords.windows(2).map(|w| haversine_m(&w[0], &w[1])).sum()
+}
+
+/// Straight-line distance between two projected (grid) positions, in grid units.
+pub fn planar_distance(a: &[f64], b: &[f64]) -> f64 {
+    (b[0] - a[0]).hypot(b[1] - a[1])
+}
+
+/// The point halfway along a line, measured by length rather than vertex count.
+/// `distance` is [`haversine_m`] for geographic coordinates or [`planar_distance`]
+/// for grid coordinates.
+pub fn line_midpoint(coords: &[Vec<f64>], distance: fn(&[f64], &[f64]) -> f64) -> Option<Vec<f64>> {
+    let first = coords.first()?;
+    let half = coords.windows(2).map(|w| distance(&w[0], &w[1])).sum::<f64>() / 2.0;
+    let mut walked = 0.0;
+    for w in coords.windows(2) {
+        let step = distance(&w[0], &w[1]);
+        if step > 0.0 && walked + step >= half {
+            let t = (half - walked) / step;
+            return Some(vec![
+                w[0][0] + (w[1][0] - w[0][0]) * t,
+                w[0][1] + (w[1][1] - w[0][1]) * t,
+            ]);
+        }
+        walked += step;
+    }
+    Some(vec![first[0], first[1]])
+}
+
+/// Area-weighted centroid of a ring. Degenerate rings fall back to the vertex average.
+pub fn ring_centroid(ring: &[Vec<f64>]) -> Option<Vec<f64>> {
+    if ring.is_empty() {
+        return None;
+    }
+    // Work relative to the first vertex to keep the cross products well conditioned.
+    let (ox, oy) = (ring[0][0], ring[0][1]);
+    let mut area2 = 0.0;
+    let (mut cx, mut cy) = (0.0, 0.0);
+    for w in ring.windows(2) {
+        let (x0, y0) = (w[0][0] - ox, w[0][1] - oy);
+        let (x1, y1) = (w[1][0] - ox, w[1][1] - oy);
+        let cross = x0 * y1 - x1 * y0;
+        area2 += cross;
+        cx += (x0 + x1) * cross;
+        cy += (y0 + y1) * cross;
+    }
+    if area2.abs() < f64::EPSILON {
+        let n = ring.len() as f64;
+        let sx: f64 = ring.iter().map(|p| p[0]).sum();
+        let sy: f64 = ring.iter().map(|p| p[1]).sum();
+        return Some(vec![sx / n, sy / n]);
+    }
+    Some(vec![ox + cx / (3.0 * area2), oy + cy / (3.0 * area2)])
+}
+
+/// A point guaranteed to lie inside the ring, suitable for label placement on
+/// concave shapes where the centroid can fall outside.
+///
+/// Scans horizontally through the middle of the ring (nudged off any vertex) and
+/// returns the centre of the widest interior interval.
+pub fn point_on_surface(ring: &[Vec<f64>]) -> Option<Vec<f64>> {
+    scan_point_on_surface(&[ring])
+}
+
+/// [`point_on_surface`] for a polygon's rings, exterior first, keeping the
+/// point out of its holes.
+pub fn polygon_point_on_surface(rings: &[Vec<Vec<f64>>]) -> Option<Vec<f64>> {
+    let rings: Vec<&[Vec<f64>]> = rings.iter().map(Vec::as_slice).collect();
+    scan_point_on_surface(&rings)
+}
+
+fn scan_point_on_surface(rings: &[&[Vec<f64>]]) -> Option<Vec<f64>> {
+    let exterior = *rings.first()?;
+    let (min_y, max_y) = exterior.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), p| {
+        (lo.min(p[1]), hi.max(p[1]))
+    });
+    if !min_y.is_finite() || min_y == max_y {
+        return ring_centroid(exterior);
+    }
+
+    // Pick the scanline halfway between the two vertex heights closest to the middle,
+    // so it never passes exactly through a vertex.
+    let mid = (min_y + max_y) / 2.0;
+    let heights = || rings.iter().flat_map(|ring| ring.iter()).map(|p| p[1]);
+    let below = heights().filter(|&y| y <= mid).fold(min_y, f64::max);
+    let above = heights().filter(|&y| y > mid).fold(max_y, f64::min);
+    let scan_y = (below + above) / 2.0;
+
+    let mut crossings: Vec<f64> = rings
+        .iter()
+        .flat_map(|ring| ring.windows(2))
+        .filter(|w| (w[0][1] > scan_y) != (w[1][1] > scan_y))
+        .map(|w| {
+            let t = (scan_y - w[0][1]) / (w[1][1] - w[0][1]);
+            w[0][0] + (w[1][0] - w[0][0]) * t
+        })
+        .collect();
+    crossings.sort_by(|a, b| a.total_cmp(b));
+
+    crossings
+        .chunks_exact(2)
+        .max_by(|a, b| (a[1] - a[0]).total_cmp(&(b[1] - b[0])))
+        .map(|pair| vec![(pair[0] + pair[1]) / 2.0, scan_y])
+        .or_else(|| ring_centroid(exterior))
+}
+
+/// Initial bearing from `a` towards `b`, in degrees clockwise from true north (0..360).
+pub fn bearing_deg(a: &[f64], b: &[f64]) -> f64 {
+    let (lon1, lat1) = (a[0].to_radians(), a[1].to_radians());
+    let (lon2, lat2) = (b[0].to_radians(), b[1].to_radians());
+    let dlon = lon2 - lon1;
+    let y = dlon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Grid bearing from `a` towards `b` for projected coordinates, in degrees
+/// clockwise from grid north (0..360).
+pub fn planar_bearing_deg(a: &[f64], b: &[f64]) -> f64 {
+    (b[0] - a[0]).atan2(b[1] - a[1]).to_degrees().rem_euclid(360.0)
+}
+
+/// Reference latitude for [`to_local_m`], roughly the middle of Hong Kong.
+const LOCAL_ORIGIN: [f64; 2] = [114.17, 22.35];
+
+/// Projects `[lon, lat]` onto a flat plane in metres around Hong Kong
+/// (equirectangular). Distortion stays well under 0.1% across the territory,
+/// which is plenty for nearest-feature searches and tolerances.
+pub fn to_local_m(p: &[f64]) -> [f64; 2] {
+    let metres_per_degree = EARTH_RADIUS_M.to_radians();
+    [
+        (p[0] - LOCAL_ORIGIN[0]) * metres_per_degree * LOCAL_ORIGIN[1].to_radians().cos(),
+        (p[1] - LOCAL_ORIGIN[1]) * metres_per_degree,
+    ]
+}
+
+/// The position a fraction `t` of the way from `a` to `b`, interpolating every
+/// ordinate the two share (so heights too).
+fn lerp(a: &[f64], b: &[f64], t: f64) -> Vec<f64> {
+    a.iter().zip(b).map(|(a, b)| a + (b - a) * t).collect()
+}
+
+/// Parametric range `(t0, t1)` of the segment `a`→`b` that lies inside `bbox`
+/// (Liang–Barsky), or `None` when it misses or only touches the box.
+pub fn clip_segment(a: &[f64], b: &[f64], bbox: &[f64; 4]) -> Option<(f64, f64)> {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    let (mut t0, mut t1) = (0.0_f64, 1.0_f64);
+    for (p, q) in [(-dx, a[0] - bbox[0]), (dx, bbox[2] - a[0]), (-dy, a[1] - bbox[1]), (dy, bbox[3] - a[1])] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else if p < 0.0 {
+            t0 = t0.max(q / p);
+        } else {
+            t1 = t1.min(q / p);
+        }
+    }
+    (t0 < t1).then_some((t0, t1))
+}
+
+/// Clips a line to a `[min_x, min_y, max_x, max_y]` box, returning the parts that
+/// lie inside it. A line that leaves and re-enters the box yields several parts.
+pub fn clip_line(coords: &[Vec<f64>], bbox: &[f64; 4]) -> Vec<Vec<Vec<f64>>> {
+    let mut parts: Vec<Vec<Vec<f64>>> = Vec::new();
+    // Whether the last part ends at the current segment's start
+    let mut continues = false;
+    for w in coords.windows(2) {
+        let (a, b) = (&w[0], &w[1]);
+        let Some((t0, t1)) = clip_segment(a, b, bbox) else {
+            continues = false;
+            continue;
+        };
+        let at = |t: f64| match t {
+            0.0 => a.clone(),
+            1.0 => b.clone(),
+            _ => lerp(a, b, t),
+        };
+        match parts.last_mut() {
+            Some(part) if continues && t0 == 0.0 => part.push(at(t1)),
+            _ => parts.push(vec![at(t0), at(t1)]),
+        }
+        continues = t1 == 1.0;
+    }
+    // A closed ring whose start lies inside: the last part runs on into the first
+    if parts.len() > 1
+        && coords.first() == coords.last()
+        && parts[0].first() == coords.first()
+        && parts[parts.len() - 1].last() == coords.last()
+    {
+        let first = parts.remove(0);
+        if let Some(last) = parts.last_mut() {
+            last.extend(first.into_iter().skip(1));
+        }
+    }
+    parts
+}
+
+/// Clips a closed ring to a `[min_x, min_y, max_x, max_y]` box
+/// (Sutherland–Hodgman), returning the closed ring of the part inside it, or an
+/// empty ring when nothing is. A ring that leaves and re-enters the box stays a
+/// single ring, joined along the box edge.
+pub fn clip_ring(ring: &[Vec<f64>], bbox: &[f64; 4]) -> Vec<Vec<f64>> {
+    let open = match ring.split_last() {
+        Some((last, rest)) if rest.first() == Some(last) => rest,
+        _ => ring,
+    };
+    let mut points = open.to_vec();
+    // Each box edge as (axis, bound, keep the side above it)
+    for (axis, bound, above) in [(0, bbox[0], true), (1, bbox[1], true), (0, bbox[2], false), (1, bbox[3], false)] {
+        let inside = |p: &[f64]| if above { p[axis] >= bound } else { p[axis] <= bound };
+        let mut kept = Vec::with_capacity(points.len());
+        for (i, b) in points.iter().enumerate() {
+            let a = &points[(i + points.len() - 1) % points.len()];
+            if inside(b) != inside(a) {
+                kept.push(lerp(a, b, (bound - a[axis]) / (b[axis] - a[axis])));
+            }
+            if inside(b) {
+                kept.push(b.clone());
+            }
+        }
+        points = kept;
+    }
+    if points.len() < 3 {
+        return Vec::new();
+    }
+    points.push(points[0].clone());
+    points
+}
+
+/// Whether a point lies inside a closed ring (even-odd rule).
+pub fn point_in_ring(point: &[f64], ring: &[[f64; 2]]) -> bool {
+    let (x, y) = (point[0], point[1]);
+    let mut inside = false;
+    for w in ring.windows(2) {
+        let ([x0, y0], [x1, y1]) = (w[0], w[1]);
+        if (y0 > y) != (y1 > y) && x < x0 + (y - y0) / (y1 - y0) * (x1 - x0) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Where segment `a`→`b` crosses segment `c`→`d`, as a fraction of the way from
+/// `a` to `b`. Parallel segments never cross.
+pub fn segment_crossing(a: &[f64], b: &[f64], c: &[f64], d: &[f64]) -> Option<f64> {
+    let (rx, ry) = (b[0] - a[0], b[1] - a[1]);
+    let (sx, sy) = (d[0] - c[0], d[1] - c[1]);
+    let denominator = rx * sy - ry * sx;
+    if denominator == 0.0 {
+        return None;
+    }
+    let (qx, qy) = (c[0] - a[0], c[1] - a[1]);
+    let t = (qx * sy - qy * sx) / denominator;
+    let u = (qx * ry - qy * rx) / denominator;
+    ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then_some(t)
+}
+
+/// Area enclosed by a `[lon, lat]` ring, in square metres.
+pub fn ring_area_m2(ring: &[Vec<f64>]) -> f64 {
+    let local: Vec<[f64; 2]> = ring.iter().map(|p| to_local_m(p)).collect();
+    let twice: f64 = local.windows(2).map(|w| w[0][0] * w[1][1] - w[1][0] * w[0][1]).sum();
+    twice.abs() / 2.0
+}
+
+/// Area enclosed by a ring of planar (grid) positions, in grid units squared.
+pub fn planar_ring_area(ring: &[Vec<f64>]) -> f64 {
+    let twice: f64 = ring.windows(2).map(|w| w[0][0] * w[1][1] - w[1][0] * w[0][1]).sum();
+    twice.abs() / 2.0
+}
+
+/// Whether two `[min_x, min_y, max_x, max_y]` boxes overlap or touch.
+pub fn bbox_intersects(a: &[f64; 4], b: &[f64; 4]) -> bool {
+    a[0] <= b[2] && b[0] <= a[2] && a[1] <= b[3] && b[1] <= a[3]
+}
+
+/// Rounds a value to `decimals` places. Equal values always round to the same
+/// bits, and -0 comes out as 0, so a vertex shared by two geometries is still
+/// shared once both are rounded.
+pub fn round_to(value: f64, decimals: u32) -> f64 {
+    let scale = 10f64.powi(decimals as i32);
+    (value * scale).round() / scale + 0.0
+}
+
+/// Snaps a position's horizontal ordinates to a grid of `decimals` places,
+/// leaving any height as it is. Snapping an already snapped position keeps it.
+pub fn snap(position: &mut [f64], decimals: u32) {
+    for ordinate in position.iter_mut().take(2) {
+        *ordinate = round_to(*ordinate, decimals);
+    }
+}
+
+/// Drops each vertex that repeats the previous kept one or lies less than
+/// `epsilon` from it, collapsing micro-segments left by precision rounding. The
+/// end vertices always stay, so closed outlines remain closed. Returns how many
+/// vertices were dropped.
+pub fn remove_short_segments(coords: &mut Vec<Vec<f64>>, distance: fn(&[f64], &[f64]) -> f64, epsilon: f64) -> usize {
+    let Some(end) = coords.pop() else {
+        return 0;
+    };
+    let before = coords.len() + 1;
+    coords.dedup_by(|p, kept| p == kept || distance(kept, p) < epsilon);
+    // The end replaces whatever kept vertex it lands too close to
+    while coords.len() > 1 && coords.last().is_some_and(|last| *last == end || distance(last, &end) < epsilon) {
+        coords.pop();
+    }
+    coords.push(end);
+    before - coords.len()
+}
+
+/// Closes a ring whose last position differs from its first, and reverses it
+/// if needed so that it runs counterclockwise when `exterior` and clockwise
+/// otherwise, as RFC 7946 requires. Returns whether it was closed and whether it
+/// was reversed.
+pub fn normalize_ring(ring: &mut Vec<Vec<f64>>, exterior: bool) -> (bool, bool) {
+    if ring.len() < 3 {
+        return (false, false);
+    }
+    let closed = ring.first() != ring.last();
+    if closed {
+        ring.push(ring[0].clone());
+    }
+    let twice: f64 = ring.windows(2).map(|w| w[0][0] * w[1][1] - w[1][0] * w[0][1]).sum();
+    let reversed = if exterior { twice < 0.0 } else { twice > 0.0 };
+    if reversed {
+        ring.reverse();
+    }
+    (closed, reversed)
+}
+
+/// Horizontal length and gradient (rise over run) of each piece of a line with
+/// heights, or `None` when any vertex lacks one. Pieces with no horizontal
+/// extent have a gradient of zero.
+pub fn gradients(coords: &[Vec<f64>], distance: fn(&[f64], &[f64]) -> f64) -> Option<Vec<(f64, f64)>> {
+    coords
+        .windows(2)
+        .map(|w| {
+            let run = distance(&w[0], &w[1]);
+            let rise = w[1].get(2)? - w[0].get(2)?;
+            Some((run, if run > 0.0 { rise / run } else { 0.0 }))
+        })
+        .collect()
+}
+
+/// Interior vertices where the gradient changes by more than `threshold` (as a
+/// fraction, e.g. `0.02` for two percentage points) between adjacent pieces.
+pub fn gradient_breaks(coords: &[Vec<f64>], distance: fn(&[f64], &[f64]) -> f64, threshold: f64) -> Vec<usize> {
+    let Some(pieces) = gradients(coords, distance) else {
+        return Vec::new();
+    };
+    pieces
+        .windows(2)
+        .enumerate()
+        .filter(|(_, w)| (w[1].1 - w[0].1).abs() > threshold)
+        .map(|(i, _)| i + 1)
+        .collect()
+}
+
+/// Splits a line at the given interior vertex indices, each vertex ending one
+/// part and starting the next.
+pub fn split_at_vertices(coords: &[Vec<f64>], breaks: &[usize]) -> Vec<Vec<Vec<f64>>> {
+    let mut bounds = vec![0];
+    bounds.extend(breaks);
+    bounds.push(coords.len().saturating_sub(1));
+    bounds.windows(2).map(|w| coords[w[0]..=w[1]].to_vec()).collect()
+}
+
+/// Splits a line into the fewest parts of equal length no longer than
+/// `max_length`, interpolating a new vertex at each cut. A `max_length` that
+/// isn't above 0 leaves the line whole.
+pub fn subdivide_line(coords: &[Vec<f64>], distance: fn(&[f64], &[f64]) -> f64, max_length: f64) -> Vec<Vec<Vec<f64>>> {
+    if max_length.is_nan() || max_length <= 0.0 {
+        return vec![coords.to_vec()];
+    }
+    let steps: Vec<f64> = coords.windows(2).map(|w| distance(&w[0], &w[1])).collect();
+    let total: f64 = steps.iter().sum();
+    let count = (total / max_length).ceil() as usize;
+    if coords.is_empty() || count <= 1 {
+        return vec![coords.to_vec()];
+    }
+    let piece = total / count as f64;
+
+    let mut parts = Vec::with_capacity(count);
+    let mut part = vec![coords[0].clone()];
+    let mut walked = 0.0;
+    let mut next = piece;
+    for (w, &step) in coords.windows(2).zip(&steps) {
+        // The last part takes whatever rounding leaves at the end
+        while parts.len() < count - 1 && step > 0.0 && walked + step >= next {
+            let cut = lerp(&w[0], &w[1], (next - walked) / step);
+            part.push(cut.clone());
+            parts.push(std::mem::replace(&mut part, vec![cut]));
+            next += piece;
+        }
+        if part.last() != Some(&w[1]) {
+            part.push(w[1].clone());
+        }
+        walked += step;
+    }
+    parts.push(part);
+    parts
+}
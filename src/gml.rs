@@ -0,0 +1,1147 @@
+use crate::attributes::{AttributeType, NumberFormat};
+use crate::crs::{self, Crs, VertexCache};
+use crate::dataset;
+use crate::geojson::{GeoJsonFeature, Geometry, PropertyValue};
+use crate::geometry;
+use crate::gzip::{self, Decompressor};
+use crate::ids::IdFormat;
+use crate::mmap::InputFile;
+use crate::perf::{self, Stage};
+use anyhow::Result;
+use clap::ValueEnum;
+use quick_xml::events::{BytesEnd, BytesStart, Event};
+use quick_xml::Reader;
+use quick_xml::name::QName;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Cursor};
+
+/// Controls how geometry and attributes are read.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// CRS the output coordinates are projected into.
+    pub target_crs: Crs,
+    /// When false, posLists are skipped and every feature gets an empty geometry,
+    /// saving the projection cost when only attributes are wanted.
+    pub geometry: bool,
+    /// Whether to swap each position's axes before projecting. `None` detects the
+    /// order from the nearest `srsName`.
+    pub swap_axes: Option<bool>,
+    /// Keep only features whose envelope, or geometry when they have none,
+    /// intersects this `[min_x, min_y, max_x, max_y]` box in the target CRS.
+    pub bbox: Option<[f64; 4]>,
+    /// Vertices closer than this many metres to the previous one are dropped.
+    /// Repeated vertices are always dropped.
+    pub min_segment_length: f64,
+    /// Stop after handing this many features to the callback.
+    pub limit: Option<usize>,
+    /// Keep only this fraction of features, chosen by a hash of their ID so the
+    /// same features are picked on every run.
+    pub sample: Option<f64>,
+    /// Keep only the feature with this ID, as normalized by `ids`.
+    pub id: Option<String>,
+    /// Which coordinate elements geometries are read from.
+    pub profile: GmlProfile,
+    /// Project each distinct vertex of a file once, through a [`VertexCache`],
+    /// rather than once for every geometry it is in.
+    pub vertex_cache: bool,
+    /// Snap each projected position to a grid of this many decimal places
+    /// before anything else sees it, so that vertices identical in the source
+    /// stay identical however the geometry is cleaned, split or written.
+    pub snap_decimals: Option<u32>,
+    /// Read geometry only from the CityGML properties of this level of detail,
+    /// e.g. `lod0Network` or `lod1Geometry` for 0 and 1. When `None`, each
+    /// feature's lowest level is read, so that the shapes of several levels
+    /// are never mixed.
+    pub lod: Option<u8>,
+    /// Types that named attributes are read as, whatever element they are in.
+    pub attributes: HashMap<String, AttributeType>,
+    /// Keep only features valid at this ISO 8601 date or date-time: those whose
+    /// `valid_from` attribute is no later and whose `valid_to` attribute, if
+    /// any, is later.
+    pub as_of: Option<String>,
+    /// Attribute holding the date a feature came into use.
+    pub valid_from: String,
+    /// Attribute holding the date a feature went out of use.
+    pub valid_to: String,
+    /// How numbers in int and double attributes are written.
+    pub numbers: NumberFormat,
+    /// How feature IDs are normalized before they are handed on.
+    pub ids: IdFormat,
+    /// Features the file is known to hold, e.g. from a pre-scan. When set,
+    /// [`Progress::Objects`] is reported after every feature member.
+    pub expected_objects: Option<usize>,
+    /// Told how far the parser has got; nothing is printed by the parser itself.
+    pub progress: Option<fn(Progress)>,
+}
+
+/// How far [`parse_gml_file`] has got, handed to [`ParseOptions::progress`].
+#[derive(Debug, Clone, Copy)]
+pub enum Progress {
+    /// Features handed to the callback so far, after every 100th.
+    Features(usize),
+    /// Feature members read so far, out of [`ParseOptions::expected_objects`].
+    Objects { read: usize, expected: usize },
+}
+
+/// GML versions, by the coordinate elements their geometries use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+pub enum GmlProfile {
+    /// Read every form of coordinate element.
+    #[default]
+    #[serde(rename = "auto")]
+    Auto,
+    /// GML 2: `gml:coordinates` tuples.
+    #[value(name = "2")]
+    #[serde(rename = "2")]
+    Gml2,
+    /// GML 3.1: `gml:posList` and `gml:pos`, plus the deprecated `gml:coordinates`.
+    #[value(name = "3.1")]
+    #[serde(rename = "3.1")]
+    Gml31,
+    /// GML 3.2 simple features: `gml:posList` and `gml:pos`.
+    #[value(name = "3.2")]
+    #[serde(rename = "3.2")]
+    Gml32,
+}
+
+/// An element holding geometry coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CoordinateElement {
+    PosList,
+    /// A single position; consecutive ones make up a line or ring.
+    Pos,
+    Coordinates,
+}
+
+impl CoordinateElement {
+    fn from_local_name(local: &[u8]) -> Option<Self> {
+        match local {
+            b"posList" => Some(CoordinateElement::PosList),
+            b"pos" => Some(CoordinateElement::Pos),
+            b"coordinates" => Some(CoordinateElement::Coordinates),
+            _ => None,
+        }
+    }
+}
+
+impl GmlProfile {
+    fn reads(self, element: CoordinateElement) -> bool {
+        match self {
+            GmlProfile::Auto | GmlProfile::Gml31 => true,
+            GmlProfile::Gml2 => element == CoordinateElement::Coordinates,
+            GmlProfile::Gml32 => element != CoordinateElement::Coordinates,
+        }
+    }
+}
+
+/// What [`parse_gml_file`] found besides the features themselves.
+#[derive(Debug, Default)]
+pub struct ParseSummary {
+    /// Features handed to the callback.
+    pub features: usize,
+    /// Features dropped by [`ParseOptions::bbox`].
+    pub skipped: usize,
+    /// Features dropped by [`ParseOptions::as_of`] for not being in use then.
+    pub not_current: usize,
+    /// The document's `gml:boundedBy` envelope in the target CRS, if it has one.
+    pub envelope: Option<[f64; 4]>,
+    /// Repeated vertices and vertices closing micro-segments that were dropped.
+    pub vertices_removed: usize,
+    /// Attribute values that didn't parse as their configured type.
+    pub type_mismatches: usize,
+    /// Int and double attribute values that didn't parse as numbers, kept as
+    /// strings.
+    pub unparsed_numbers: usize,
+    /// Coordinate lists skipped for not splitting into positions of 2 or more
+    /// ordinates.
+    pub malformed_coordinates: usize,
+    /// Feature members read, whether or not they were kept.
+    pub objects: usize,
+    /// The feature count declared on the root element, if any.
+    pub declared: Option<usize>,
+    /// Whether reading stopped at [`ParseOptions::limit`] before the end.
+    pub stopped_early: bool,
+    /// Features without geometry at [`ParseOptions::lod`].
+    pub without_lod: usize,
+    /// EPSG codes geometries were projected from, in the order first met. More
+    /// than one means the file mixes source CRSs.
+    pub source_epsg: Vec<u32>,
+    /// `srsName`s that aren't supported, read as [`DEFAULT_SOURCE_EPSG`] instead.
+    pub unsupported_srs_names: Vec<String>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            target_crs: Crs::Wgs84,
+            geometry: true,
+            swap_axes: None,
+            bbox: None,
+            min_segment_length: 0.0,
+            limit: None,
+            sample: None,
+            id: None,
+            profile: GmlProfile::Auto,
+            vertex_cache: false,
+            snap_decimals: None,
+            lod: None,
+            attributes: HashMap::new(),
+            as_of: None,
+            valid_from: "creation_date".to_string(),
+            valid_to: "termination_date".to_string(),
+            numbers: NumberFormat::default(),
+            ids: IdFormat::default(),
+            expected_objects: None,
+            progress: None,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Whether a feature was in use at [`ParseOptions::as_of`]. Features without
+    /// dates count as always in use.
+    fn is_current(&self, properties: &HashMap<String, PropertyValue>) -> bool {
+        let Some(as_of) = &self.as_of else {
+            return true;
+        };
+        let as_of = date_time(as_of);
+        let date = |name: &str| match properties.get(name).map(|value| AttributeType::Date.parse(&value.to_string())) {
+            Some(Some(PropertyValue::String(date))) => Some(date_time(&date)),
+            _ => None,
+        };
+        date(&self.valid_from).is_none_or(|from| from <= as_of) && date(&self.valid_to).is_none_or(|to| to > as_of)
+    }
+}
+
+/// An `xs:date` or `xs:dateTime` without its time zone (`Z`, `+08:00` or
+/// `-05:00`), which lifespan dates are compared without.
+fn without_time_zone(date: &str) -> &str {
+    if let Some(date) = date.strip_suffix('Z') {
+        return date;
+    }
+    // The offset's sign can't be the date's own hyphens: those come before the
+    // day, within the first 10 characters
+    match date.char_indices().rev().find(|&(_, c)| c == '+' || c == '-') {
+        Some((sign, _)) if sign >= 10 && date[sign + 1..].len() == 5 && date.as_bytes()[sign + 3] == b':' => &date[..sign],
+        _ => date,
+    }
+}
+
+/// An ISO 8601 date as the start of its day, so it compares with date-times.
+fn date_time(date: &str) -> String {
+    match date.len() {
+        10 => format!("{}T00:00:00", date),
+        _ => date.to_string(),
+    }
+}
+
+/// Source CRS assumed for geometries without a usable `srsName`.
+pub const DEFAULT_SOURCE_EPSG: u32 = 2326;
+
+/// Vertices a [`VertexCache`] holds before starting again, some 60 MB of them.
+const VERTEX_CACHE_CAPACITY: usize = 1 << 20;
+
+/// The source CRSs declared in one file, so that mixed or unsupported `srsName`s
+/// are recorded once rather than for every geometry.
+struct SourceCrsLog {
+    seen: Vec<u32>,
+    unsupported: Vec<String>,
+    /// The file's projected vertices, under [`ParseOptions::vertex_cache`].
+    vertices: Option<VertexCache>,
+}
+
+impl SourceCrsLog {
+    fn new(options: &ParseOptions) -> Self {
+        SourceCrsLog {
+            seen: Vec::new(),
+            unsupported: Vec::new(),
+            vertices: options.vertex_cache.then(|| VertexCache::new(VERTEX_CACHE_CAPACITY)),
+        }
+    }
+
+    /// Projects flat coordinate values declared in `srs_name` into `target`.
+    fn project(&mut self, values: &[f64], dimension: usize, srs_name: Option<&str>, target: Crs) -> Vec<Vec<f64>> {
+        let source = self.resolve(srs_name);
+        match &mut self.vertices {
+            Some(vertices) => vertices.project(values, dimension, source, target),
+            None => crs::project(values, dimension, source, target),
+        }
+    }
+
+    /// EPSG code to project a geometry from, falling back to HK80 when its
+    /// `srsName` is missing or not supported.
+    fn resolve(&mut self, srs_name: Option<&str>) -> u32 {
+        let code = match srs_name {
+            None => DEFAULT_SOURCE_EPSG,
+            Some(name) => match crs::epsg_code(name).filter(|&code| crs::is_supported(code)) {
+                Some(code) => code,
+                None => {
+                    if !self.unsupported.iter().any(|seen| seen == name) {
+                        self.unsupported.push(name.to_string());
+                    }
+                    DEFAULT_SOURCE_EPSG
+                }
+            },
+        };
+        if !self.seen.contains(&code) {
+            self.seen.push(code);
+        }
+        code
+    }
+}
+
+/// Whether positions under `srs_name` need their axes swapped to read easting first.
+fn swap_axes(options: &ParseOptions, srs_name: Option<&str>) -> bool {
+    options.swap_axes.unwrap_or_else(|| srs_name.is_some_and(crs::northing_first))
+}
+
+fn parse_values(text: &str) -> Vec<f64> {
+    text.split_whitespace().filter_map(|s| s.parse::<f64>().ok()).collect()
+}
+
+/// The separators declared on a GML 2 `gml:coordinates` element.
+#[derive(Debug, Clone)]
+struct CoordinateSeparators {
+    /// Decimal point.
+    decimal: String,
+    /// Between the ordinates of one tuple.
+    cs: String,
+    /// Between tuples.
+    ts: String,
+}
+
+impl Default for CoordinateSeparators {
+    fn default() -> Self {
+        CoordinateSeparators {
+            decimal: ".".to_string(),
+            cs: ",".to_string(),
+            ts: " ".to_string(),
+        }
+    }
+}
+
+impl CoordinateSeparators {
+    /// Reads the `decimal`, `cs` and `ts` attributes, keeping the defaults for
+    /// any that are missing or empty.
+    fn from_element(element: &BytesStart) -> Self {
+        let mut separators = CoordinateSeparators::default();
+        for attr in element.attributes().flatten() {
+            let value = String::from_utf8_lossy(&attr.value).into_owned();
+            if value.is_empty() {
+                continue;
+            }
+            match attr.key.as_ref() {
+                b"decimal" => separators.decimal = value,
+                b"cs" => separators.cs = value,
+                b"ts" => separators.ts = value,
+                _ => {}
+            }
+        }
+        separators
+    }
+}
+
+/// Splits on `separator`, treating a whitespace separator as any run of
+/// whitespace, as GML writers rarely keep to a single space.
+fn split_on<'a>(text: &'a str, separator: &'a str) -> Box<dyn Iterator<Item = &'a str> + 'a> {
+    if separator.trim().is_empty() {
+        Box::new(text.split_whitespace())
+    } else {
+        Box::new(text.split(separator).map(str::trim).filter(|part| !part.is_empty()))
+    }
+}
+
+/// Values of a GML 2 `gml:coordinates` element, returned with the number of
+/// ordinates in each tuple. `None` when a tuple has fewer than 2 ordinates or
+/// a different number from the first, as when the text isn't written with the
+/// separators the element declares.
+fn parse_coordinates(text: &str, separators: &CoordinateSeparators) -> Option<(Vec<f64>, usize)> {
+    let mut tuples = split_on(text, &separators.ts).peekable();
+    let dimension = tuples.peek().map_or(2, |tuple| split_on(tuple, &separators.cs).count());
+    if dimension < 2 {
+        return None;
+    }
+    let mut values = Vec::new();
+    for tuple in tuples {
+        if split_on(tuple, &separators.cs).count() != dimension {
+            return None;
+        }
+        values.extend(split_on(tuple, &separators.cs).filter_map(|value| match separators.decimal.as_str() {
+            "." => value.parse::<f64>().ok(),
+            decimal => value.replace(decimal, ".").parse::<f64>().ok(),
+        }));
+    }
+    Some((values, dimension))
+}
+
+/// Swaps axes if needed and projects flat coordinate values into the target CRS.
+/// Returns `None` for values with fewer than 2 ordinates per position, which
+/// can't be positions.
+fn project_values(
+    mut values: Vec<f64>,
+    dimension: usize,
+    srs_name: Option<&str>,
+    source_crs: &mut SourceCrsLog,
+    options: &ParseOptions,
+) -> Option<Vec<Vec<f64>>> {
+    if dimension < 2 {
+        return None;
+    }
+    let _projection = perf::enter(Stage::Projection);
+    if swap_axes(options, srs_name) {
+        for pair in values.chunks_exact_mut(dimension) {
+            pair.swap(0, 1);
+        }
+    }
+    // Project from the geometry's declared CRS to the target CRS, then round,
+    // so each vertex is rounded from the same projected value wherever it is
+    let mut positions = source_crs.project(&values, dimension, srs_name, options.target_crs);
+    if let Some(decimals) = options.snap_decimals {
+        for position in &mut positions {
+            geometry::snap(position, decimals);
+        }
+    }
+    Some(positions)
+}
+
+/// Projects a `gml:Envelope` into a bbox in the target CRS. All four corners are
+/// projected, since the box's edges needn't stay axis-aligned.
+fn envelope_bbox(
+    lower: &[f64],
+    upper: &[f64],
+    srs_name: Option<&str>,
+    source_crs: &mut SourceCrsLog,
+    options: &ParseOptions,
+) -> Option<[f64; 4]> {
+    let (&[x0, y0], &[x1, y1]) = (lower.get(..2)?, upper.get(..2)?) else {
+        return None;
+    };
+    let _projection = perf::enter(Stage::Projection);
+    let mut corners = vec![x0, y0, x1, y0, x1, y1, x0, y1];
+    if swap_axes(options, srs_name) {
+        for pair in corners.chunks_exact_mut(2) {
+            pair.swap(0, 1);
+        }
+    }
+    Geometry::LineString(source_crs.project(&corners, 2, srs_name, options.target_crs)).bbox()
+}
+
+/// Streams every GenericCityObject in a GML file, handing each feature and its ID
+/// (taken from `id_field`, or its `gml:id` when `id_field` is empty) to `on_feature`.
+/// A gzipped file (`.gz`) is inflated on a background thread as it is parsed;
+/// any other file is memory-mapped.
+pub fn parse_gml_file(
+    file_path: &str,
+    id_field: &str,
+    options: &ParseOptions,
+    on_feature: impl FnMut(String, GeoJsonFeature) -> Result<()>,
+) -> Result<ParseSummary> {
+    let read = perf::enter(Stage::Read);
+    if gzip::is_gzip(file_path) {
+        let source = Decompressor::open(file_path)?;
+        drop(read);
+        return parse_source(file_path, source, id_field, options, on_feature);
+    }
+    let content = InputFile::open(file_path)?;
+    drop(read);
+    parse_source(file_path, Cursor::new(&content[..]), id_field, options, on_feature)
+}
+
+/// Reads every feature of a `CENTERLINE.gml` or `PEDESTRIAN_ZONE.gml` document
+/// held in memory, with their IDs, into WGS84. Zone rings are closed and wound
+/// as RFC 7946 requires, as the command line does. Features of any other
+/// document keep their `gml:id` as ID.
+pub fn convert_document(content: &str) -> Result<Vec<(String, GeoJsonFeature)>> {
+    let mut features = Vec::new();
+    stream_document(content, |id, feature| {
+        features.push((id, feature));
+        Ok(())
+    })?;
+    Ok(features)
+}
+
+/// Like [`convert_document`], handing each feature to `on_feature` as it is read.
+pub fn stream_document(
+    content: &str,
+    mut on_feature: impl FnMut(String, GeoJsonFeature) -> Result<()>,
+) -> Result<ParseSummary> {
+    let id_field = dataset::detect(content).map(|dataset| dataset.id_field).unwrap_or_default();
+    parse_gml("input", content, id_field, &ParseOptions::default(), |id, mut feature| {
+        let polygons = match &mut feature.geometry {
+            Geometry::Polygon(polygon) => vec![polygon],
+            Geometry::MultiPolygon(polygons) => polygons.iter_mut().collect(),
+            _ => Vec::new(),
+        };
+        for polygon in polygons {
+            for (i, ring) in polygon.iter_mut().enumerate() {
+                geometry::normalize_ring(ring, i == 0);
+            }
+        }
+        on_feature(id, feature)
+    })
+}
+
+/// Like [`parse_gml_file`], for a document already in memory. `file_path` only
+/// names it in messages.
+pub fn parse_gml(
+    file_path: &str,
+    content: &str,
+    id_field: &str,
+    options: &ParseOptions,
+    on_feature: impl FnMut(String, GeoJsonFeature) -> Result<()>,
+) -> Result<ParseSummary> {
+    parse_source(file_path, Cursor::new(content.as_bytes()), id_field, options, on_feature)
+}
+
+/// XML being parsed, which can quote itself back for error reports.
+trait Source: BufRead {
+    /// The document, at least up to where the reader has got to.
+    fn text(&self) -> Cow<'_, str>;
+}
+
+impl Source for Cursor<&[u8]> {
+    fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(self.get_ref())
+    }
+}
+
+impl Source for Decompressor {
+    fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(self.contents())
+    }
+}
+
+fn parse_source(
+    file_path: &str,
+    source: impl Source,
+    id_field: &str,
+    options: &ParseOptions,
+    mut on_feature: impl FnMut(String, GeoJsonFeature) -> Result<()>,
+) -> Result<ParseSummary> {
+    let _xml = perf::enter(Stage::Xml);
+    let mut reader = Reader::from_reader(source);
+    reader.config_mut().trim_text(true);
+
+    let mut buf = Vec::new();
+    // The feature being read, fed the events between its start and end tags
+    let mut object: Option<FeatureReader> = None;
+    let mut object_name = Vec::new();
+    let mut object_depth = 0;
+    let mut summary = ParseSummary::default();
+    // srsName declared outside any feature (e.g. on the document envelope), used
+    // by features whose geometry doesn't declare its own
+    let mut document_srs: Option<String> = None;
+    let mut source_crs = SourceCrsLog::new(options);
+    // The envelope being read, either the document's or the current feature's
+    let (mut lower, mut upper) = (String::new(), String::new());
+    let mut in_lower = false;
+    let mut in_upper = false;
+    let mut envelope_srs: Option<String> = None;
+    // Where the current feature starts and its `gml:id`, for error reports
+    let mut object_start = 0;
+    let mut object_id: Option<String> = None;
+    let mut root_seen = false;
+
+    loop {
+        let offset = reader.buffer_position() as usize;
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                let local = e.local_name();
+                let local = local.as_ref();
+                let root_seen = std::mem::replace(&mut root_seen, true);
+                in_lower = local == b"lowerCorner";
+                in_upper = local == b"upperCorner";
+                if local == b"Envelope" {
+                    lower.clear();
+                    upper.clear();
+                    envelope_srs = srs_name(e).or_else(|| document_srs.clone());
+                }
+
+                if local == b"GenericCityObject" {
+                    object = Some(FeatureReader::new(document_srs.as_deref()));
+                    object_depth = 1;
+                    object_start = offset;
+                    object_id = e
+                        .try_get_attribute("gml:id")
+                        .ok()
+                        .flatten()
+                        .map(|id| String::from_utf8_lossy(&id.value).into_owned());
+                    object_name.clear();
+                    object_name.extend_from_slice(e.name().as_ref());
+                } else if let Some(object) = &mut object {
+                    object_depth += 1;
+                    object.start(e, options);
+                } else {
+                    if let Some(srs) = srs_name(e) {
+                        document_srs = Some(srs);
+                    }
+                    if !root_seen {
+                        summary.declared = declared_count(e);
+                    }
+                }
+            }
+            Ok(Event::End(ref e)) => {
+                let local = e.local_name();
+                let local = local.as_ref();
+                in_lower = false;
+                in_upper = false;
+                if let Some(state) = &mut object {
+                    object_depth -= 1;
+                    state.end(e, &mut source_crs, options);
+                    if local == b"Envelope" && state.envelope.is_none() {
+                        state.envelope = envelope_bbox(
+                            &parse_values(&lower),
+                            &parse_values(&upper),
+                            envelope_srs.as_deref(),
+                            &mut source_crs,
+                            options,
+                        );
+                        // Outside the filter: skip straight to the end of the feature
+                        // without reading the rest of its properties or geometry
+                        if let (Some(filter), Some(envelope)) = (&options.bbox, &state.envelope)
+                            && !geometry::bbox_intersects(filter, envelope)
+                        {
+                            let mut skipped = Vec::new();
+                            if let Err(e) = reader.read_to_end_into(QName(&object_name), &mut skipped) {
+                                let feature = FeatureContext { id: object_id.as_deref(), start: object_start };
+                                let offset = reader.error_position() as usize;
+                                let content = reader.get_ref().text();
+                                return Err(xml_error(file_path, &content, Some(offset), Some(feature), &snippet(&content, offset), e));
+                            }
+                            summary.skipped += 1;
+                            summary.objects += 1;
+                            report_objects(summary.objects, options);
+                            object = None;
+                        }
+                    } else if object_depth == 0
+                        && let Some(state) = object.take()
+                    {
+                        summary.objects += 1;
+                        report_objects(summary.objects, options);
+                        let parsed = state.finish(options, &mut summary);
+                        if parsed.is_none() {
+                            summary.skipped += 1;
+                        } else if let Some(mut feature) = parsed
+                            && let Some(id) = match id_field {
+                                "" => object_id.clone().map(PropertyValue::String),
+                                _ => feature.properties.get(id_field).cloned(),
+                            }
+                        {
+                            let _other = perf::enter(Stage::Other);
+                            let distance = options.target_crs.distance_fn();
+                            let lines: Vec<&mut Vec<Vec<f64>>> = match &mut feature.geometry {
+                                Geometry::LineString(coords) => vec![coords],
+                                Geometry::Polygon(rings) => rings.iter_mut().collect(),
+                                Geometry::MultiPolygon(polygons) => polygons.iter_mut().flatten().collect(),
+                                _ => Vec::new(),
+                            };
+                            for coords in lines {
+                                summary.vertices_removed +=
+                                    geometry::remove_short_segments(coords, distance, options.min_segment_length);
+                            }
+                            let id_str = options.ids.normalize(match id {
+                                PropertyValue::String(s) => s,
+                                PropertyValue::Int(i) => i.to_string(),
+                                _ => format!("object_{}", summary.features),
+                            });
+                            if !options.is_current(&feature.properties) {
+                                summary.not_current += 1;
+                            } else if options.sample.is_none_or(|rate| sampled(&id_str, rate))
+                                && options.id.as_ref().is_none_or(|wanted| *wanted == id_str)
+                            {
+                                on_feature(id_str, feature)?;
+                                summary.features += 1;
+
+                                if summary.features % 100 == 0
+                                    && let Some(progress) = options.progress
+                                {
+                                    progress(Progress::Features(summary.features));
+                                }
+                                if options.limit.is_some_and(|limit| summary.features >= limit) {
+                                    summary.stopped_early = true;
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                } else if local == b"Envelope" && summary.envelope.is_none() {
+                    summary.envelope = envelope_bbox(
+                        &parse_values(&lower),
+                        &parse_values(&upper),
+                        envelope_srs.as_deref(),
+                        &mut source_crs,
+                        options,
+                    );
+                }
+            }
+            // Only text that is kept is unescaped, and then without a copy unless
+            // it holds an entity
+            Ok(Event::Text(e)) if in_lower || in_upper || object.as_ref().is_some_and(FeatureReader::wants_text) => {
+                let text = e.unescape().unwrap_or_default();
+                if in_lower {
+                    lower.push_str(&text);
+                } else if in_upper {
+                    upper.push_str(&text);
+                }
+                if let Some(object) = &mut object {
+                    object.text(&text);
+                }
+            }
+            // Text in a CDATA section is taken as it is, without unescaping
+            Ok(Event::CData(e)) if object.as_ref().is_some_and(FeatureReader::wants_text) => {
+                if let Some(object) = &mut object {
+                    object.text(&String::from_utf8_lossy(&e));
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                let feature = object.is_some().then_some(FeatureContext { id: object_id.as_deref(), start: object_start });
+                let offset = reader.error_position() as usize;
+                let content = reader.get_ref().text();
+                return Err(xml_error(file_path, &content, Some(offset), feature, &snippet(&content, offset), e));
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    summary.source_epsg = source_crs.seen;
+    summary.unsupported_srs_names = source_crs.unsupported;
+    Ok(summary)
+}
+
+/// Reports reading `objects` feature members to [`ParseOptions::progress`], when
+/// the file's feature count is known.
+fn report_objects(objects: usize, options: &ParseOptions) {
+    if let (Some(progress), Some(expected)) = (options.progress, options.expected_objects) {
+        progress(Progress::Objects { read: objects, expected });
+    }
+}
+
+/// The element types each attribute is written in across a whole file, read
+/// without parsing any feature, so that a first pass can settle one type per
+/// attribute.
+pub fn attribute_types(file_path: &str) -> Result<HashMap<String, HashSet<AttributeType>>> {
+    if gzip::is_gzip(file_path) {
+        return source_attribute_types(file_path, Decompressor::open(file_path)?);
+    }
+    let content = InputFile::open(file_path)?;
+    source_attribute_types(file_path, Cursor::new(&content[..]))
+}
+
+fn source_attribute_types(file_path: &str, source: impl Source) -> Result<HashMap<String, HashSet<AttributeType>>> {
+    let mut reader = Reader::from_reader(source);
+    let mut buf = Vec::new();
+    let mut types: HashMap<String, HashSet<AttributeType>> = HashMap::new();
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => {
+                let kind = match e.local_name().as_ref() {
+                    b"stringAttribute" => AttributeType::String,
+                    b"intAttribute" => AttributeType::Int,
+                    b"doubleAttribute" => AttributeType::Float,
+                    b"dateAttribute" => AttributeType::Date,
+                    _ => {
+                        buf.clear();
+                        continue;
+                    }
+                };
+                if let Some(name) = e.try_get_attribute("name").ok().flatten() {
+                    let name = String::from_utf8_lossy(&name.value).into_owned();
+                    types.entry(name).or_default().insert(kind);
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                let offset = reader.error_position() as usize;
+                let content = reader.get_ref().text();
+                return Err(xml_error(file_path, &content, Some(offset), None, &snippet(&content, offset), e));
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(types)
+}
+
+/// The feature count a document declares on its root element: WFS 2.0's
+/// `numberReturned`, WFS 1.1's `numberOfFeatures`, or failing both
+/// `numberMatched`.
+fn declared_count(root: &BytesStart) -> Option<usize> {
+    ["numberReturned", "numberOfFeatures", "numberMatched"].iter().find_map(|key| {
+        let value = root.try_get_attribute(*key).ok().flatten()?;
+        String::from_utf8_lossy(&value.value).trim().parse().ok()
+    })
+}
+
+/// The feature being read when a source file turned out to be malformed.
+struct FeatureContext<'a> {
+    /// Its `gml:id`, if it has one.
+    id: Option<&'a str>,
+    /// Byte offset of its start tag.
+    start: usize,
+}
+
+/// Describes malformed XML in a source file well enough to report upstream:
+/// where it is, the feature it falls in, and the XML around it. `offset` is the
+/// byte offset in the file when known; `near` quotes the XML there.
+fn xml_error(
+    file_path: &str,
+    content: &str,
+    offset: Option<usize>,
+    feature: Option<FeatureContext>,
+    near: &str,
+    error: quick_xml::Error,
+) -> anyhow::Error {
+    let mut message = format!("Malformed XML in {}", file_path);
+    if let Some(offset) = offset {
+        let (line, column) = line_column(content, offset);
+        message.push_str(&format!(" at line {}, column {} (byte {})", line, column, offset));
+    }
+    message.push_str(&format!(": {}", error));
+    if let Some(feature) = feature {
+        let (line, _) = line_column(content, feature.start);
+        match feature.id {
+            Some(id) => message.push_str(&format!("\n  in feature {} starting on line {}", id, line)),
+            None => message.push_str(&format!("\n  in the feature starting on line {}", line)),
+        }
+    }
+    message.push_str(&format!("\n  near: {}", near));
+    anyhow::anyhow!(message)
+}
+
+/// 1-based line and column (in characters) of a byte offset.
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..floor_char_boundary(content, offset)];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+/// Up to 40 characters either side of a byte offset, on one line, with `⟨here⟩`
+/// marking the offset. Source files often hold a whole feature, or the whole
+/// dataset, on a single line.
+fn snippet(content: &str, offset: usize) -> String {
+    let offset = floor_char_boundary(content, offset);
+    let (before, after) = content.split_at(offset);
+    let before: String = before.chars().rev().take(40).take_while(|c| *c != '\n').collect::<Vec<_>>().into_iter().rev().collect();
+    let after: String = after.chars().take(40).take_while(|c| *c != '\n').collect();
+    format!("{}⟨here⟩{}", before.trim_start(), after.trim_end())
+}
+
+fn floor_char_boundary(content: &str, offset: usize) -> usize {
+    (0..=offset.min(content.len())).rev().find(|&i| content.is_char_boundary(i)).unwrap_or(0)
+}
+
+/// Whether `id` falls within a `rate` sample. Hashing the ID (FNV-1a) instead of
+/// drawing at random keeps the sample stable between runs.
+fn sampled(id: &str, rate: f64) -> bool {
+    let mut hash = id
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3));
+    // Mix the low bits, where sequential IDs differ, into the high bits
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    (hash as f64 / u64::MAX as f64) < rate
+}
+
+fn srs_name(element: &BytesStart) -> Option<String> {
+    element
+        .attributes()
+        .flatten()
+        .find(|attr| attr.key.as_ref() == b"srsName")
+        .map(|attr| String::from_utf8_lossy(&attr.value).into_owned())
+}
+
+/// The state of one feature being read, fed the events inside its
+/// `GenericCityObject` element as the document is parsed. Elements are told
+/// apart by their local name, so nothing is allocated per element apart from
+/// the values read.
+struct FeatureReader {
+    properties: HashMap<String, PropertyValue>,
+    /// Positions of the line or ring being read.
+    coordinates: Vec<Vec<f64>>,
+    /// The feature's own `gml:boundedBy` envelope in the target CRS, once read.
+    envelope: Option<[f64; 4]>,
+    current_srs: Option<String>,
+    /// Ordinates per position, from the nearest srsDimension.
+    dimension: usize,
+    /// Whether the text being read is a generic attribute's value.
+    in_attribute: bool,
+    /// The property a CityGML `core:creationDate` or `core:terminationDate`
+    /// being read goes to.
+    lifespan_date: Option<&'static str>,
+    /// Rings of the surface being read, and the surfaces read so far.
+    rings: Vec<Vec<Vec<f64>>>,
+    polygons: Vec<Vec<Vec<Vec<f64>>>>,
+    coordinate_element: Option<CoordinateElement>,
+    separators: CoordinateSeparators,
+    /// Values of consecutive `gml:pos` elements, projected together once they end.
+    positions: Vec<f64>,
+    /// Whether the geometry is a `gml:Point`, whose one position is written as
+    /// a GeoJSON Point rather than a LineString of one position.
+    is_point: bool,
+    /// GML 2 boxes and GML 3.0 envelopes hold coordinates too, so skip bounds.
+    in_bounded_by: bool,
+    /// The level of the CityGML `lod<N>...` property being read, if any.
+    lod: Option<u8>,
+    /// The level the geometry read so far came from, with geometry outside
+    /// any `lod<N>...` property ranked after every level; `None` before any.
+    geometry_rank: Option<u8>,
+    attribute_name: String,
+    value: String,
+    type_mismatches: usize,
+    unparsed_numbers: usize,
+    malformed_coordinates: usize,
+}
+
+impl FeatureReader {
+    fn new(document_srs: Option<&str>) -> Self {
+        FeatureReader {
+            properties: HashMap::new(),
+            coordinates: Vec::new(),
+            envelope: None,
+            current_srs: document_srs.map(str::to_string),
+            dimension: 2,
+            in_attribute: false,
+            lifespan_date: None,
+            rings: Vec::new(),
+            polygons: Vec::new(),
+            coordinate_element: None,
+            separators: CoordinateSeparators::default(),
+            positions: Vec::new(),
+            is_point: false,
+            in_bounded_by: false,
+            lod: None,
+            geometry_rank: None,
+            attribute_name: String::new(),
+            value: String::new(),
+            type_mismatches: 0,
+            unparsed_numbers: 0,
+            malformed_coordinates: 0,
+        }
+    }
+
+    /// Whether coordinates are wanted: for the output, or to test against the
+    /// bbox filter a feature without an envelope.
+    fn reads_geometry(&self, options: &ParseOptions) -> bool {
+        options.geometry || (options.bbox.is_some() && self.envelope.is_none())
+    }
+
+    /// Whether geometry starting here is read, given the level of detail it is
+    /// in. Without [`ParseOptions::lod`], geometry of a lower level than that
+    /// read so far replaces it, and geometry of a higher level is skipped.
+    fn takes_lod(&mut self, options: &ParseOptions) -> bool {
+        if let Some(wanted) = options.lod {
+            if self.lod != Some(wanted) {
+                return false;
+            }
+            self.geometry_rank = Some(wanted);
+            return true;
+        }
+        let rank = self.lod.unwrap_or(u8::MAX);
+        match self.geometry_rank {
+            Some(read) if read < rank => return false,
+            Some(read) if read > rank => {
+                self.coordinates.clear();
+                self.positions.clear();
+                self.rings.clear();
+                self.polygons.clear();
+                self.is_point = false;
+            }
+            _ => {}
+        }
+        self.geometry_rank = Some(rank);
+        true
+    }
+
+    fn start(&mut self, e: &BytesStart, options: &ParseOptions) {
+        if let Some(srs) = srs_name(e) {
+            self.current_srs = Some(srs);
+        }
+        if let Some(value) = e.try_get_attribute("srsDimension").ok().flatten()
+            && let Some(value) = std::str::from_utf8(&value.value).ok().and_then(|value| value.parse::<usize>().ok())
+            && value >= 2
+        {
+            self.dimension = value;
+        }
+
+        match e.local_name().as_ref() {
+            b"stringAttribute" | b"intAttribute" | b"doubleAttribute" | b"dateAttribute" => {
+                self.in_attribute = true;
+                self.attribute_name.clear();
+                if let Some(name) = e.try_get_attribute("name").ok().flatten() {
+                    self.attribute_name.push_str(&name.unescape_value().unwrap_or_default());
+                }
+            }
+            b"creationDate" => {
+                self.lifespan_date = Some("creation_date");
+                self.value.clear();
+            }
+            b"terminationDate" => {
+                self.lifespan_date = Some("termination_date");
+                self.value.clear();
+            }
+            b"boundedBy" => self.in_bounded_by = true,
+            b"Point" if self.reads_geometry(options) && !self.in_bounded_by && self.takes_lod(options) => {
+                self.is_point = true
+            }
+            local if lod_level(local).is_some() => self.lod = lod_level(local),
+            local => {
+                if let Some(element) = CoordinateElement::from_local_name(local)
+                    && self.reads_geometry(options)
+                    && !self.in_bounded_by
+                    && options.profile.reads(element)
+                    && self.takes_lod(options)
+                {
+                    if element == CoordinateElement::Coordinates {
+                        self.separators = CoordinateSeparators::from_element(e);
+                    }
+                    self.coordinate_element = Some(element);
+                    self.value.clear();
+                }
+            }
+        }
+    }
+
+    fn end(&mut self, e: &BytesEnd, source_crs: &mut SourceCrsLog, options: &ParseOptions) {
+        let local = e.local_name();
+        let local = local.as_ref();
+        if local != b"pos" && !self.positions.is_empty() {
+            let values = std::mem::take(&mut self.positions);
+            let srs = self.current_srs.as_deref();
+            let projected = project_values(values, self.dimension, srs, source_crs, options);
+            self.push_projected(projected);
+        }
+
+        let written_as = match local {
+            b"stringAttribute" => Some(AttributeType::String),
+            b"intAttribute" => Some(AttributeType::Int),
+            b"doubleAttribute" => Some(AttributeType::Float),
+            b"dateAttribute" => Some(AttributeType::Date),
+            _ => None,
+        };
+        if let Some(written_as) = written_as {
+            let _attributes = perf::enter(Stage::Attributes);
+            // A configured type wins; values that don't fit it become null so
+            // the attribute keeps one type. Values that don't fit their own
+            // element's type are kept as the string they were written as.
+            let configured = options.attributes.get(&self.attribute_name).copied();
+            let value = match configured.unwrap_or(written_as).parse_with(&self.value, &options.numbers) {
+                Some(value) => value,
+                None if configured.is_some() => {
+                    self.type_mismatches += 1;
+                    PropertyValue::Null
+                }
+                None => {
+                    self.unparsed_numbers += 1;
+                    PropertyValue::String(self.value.clone())
+                }
+            };
+            self.properties.insert(self.attribute_name.clone(), value);
+            self.in_attribute = false;
+            self.value.clear();
+        } else if let Some(property) = self.lifespan_date
+            && matches!(local, b"creationDate" | b"terminationDate")
+        {
+            let _attributes = perf::enter(Stage::Attributes);
+            let value = AttributeType::Date.parse(without_time_zone(self.value.trim())).unwrap_or(PropertyValue::Null);
+            self.properties.insert(property.to_string(), value);
+            self.lifespan_date = None;
+            self.value.clear();
+        } else if local == b"boundedBy" {
+            self.in_bounded_by = false;
+        } else if lod_level(local).is_some() {
+            self.lod = None;
+        } else if local == b"LinearRing" && !self.coordinates.is_empty() {
+            self.rings.push(std::mem::take(&mut self.coordinates));
+        } else if matches!(local, b"Polygon" | b"PolygonPatch") && !self.rings.is_empty() {
+            // The exterior comes first in both GML 2 and GML 3
+            self.polygons.push(std::mem::take(&mut self.rings));
+        } else if let Some(element) = self.coordinate_element
+            && CoordinateElement::from_local_name(local) == Some(element)
+        {
+            let srs = self.current_srs.as_deref();
+            match element {
+                CoordinateElement::PosList => {
+                    let values = parse_values(&self.value);
+                    let projected = project_values(values, self.dimension, srs, source_crs, options);
+                    self.push_projected(projected);
+                }
+                CoordinateElement::Pos => {
+                    self.positions.extend(parse_values(&self.value).into_iter().take(self.dimension))
+                }
+                CoordinateElement::Coordinates => {
+                    let projected = parse_coordinates(&self.value, &self.separators).and_then(|(values, tuple_dimension)| {
+                        project_values(values, tuple_dimension, srs, source_crs, options)
+                    });
+                    self.push_projected(projected);
+                }
+            }
+            self.coordinate_element = None;
+            self.value.clear();
+        }
+    }
+
+    /// Adds projected positions to the line or ring being read, or counts a
+    /// coordinate list [`project_values`] refused.
+    fn push_projected(&mut self, projected: Option<Vec<Vec<f64>>>) {
+        match projected {
+            Some(positions) => self.coordinates.extend(positions),
+            None => self.malformed_coordinates += 1,
+        }
+    }
+
+    /// Whether the text at this point is part of a value being read.
+    fn wants_text(&self) -> bool {
+        self.in_attribute || self.lifespan_date.is_some() || self.coordinate_element.is_some()
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.wants_text() {
+            self.value.push_str(text);
+        }
+    }
+
+    /// The feature read, or `None` when it falls outside the bbox filter. A
+    /// feature with an envelope has already been tested against the filter by
+    /// it; without one its geometry is tested instead.
+    fn finish(mut self, options: &ParseOptions, summary: &mut ParseSummary) -> Option<GeoJsonFeature> {
+        summary.type_mismatches += self.type_mismatches;
+        summary.unparsed_numbers += self.unparsed_numbers;
+        summary.malformed_coordinates += self.malformed_coordinates;
+        if options.lod.is_some() && options.geometry && self.geometry_rank.is_none() {
+            summary.without_lod += 1;
+        }
+        if !self.rings.is_empty() {
+            self.polygons.push(self.rings);
+        }
+        let geometry = match self.polygons.len() {
+            0 if self.is_point && self.coordinates.len() == 1 => Geometry::Point(self.coordinates.remove(0)),
+            0 => Geometry::LineString(self.coordinates),
+            1 => Geometry::Polygon(self.polygons.remove(0)),
+            _ => Geometry::MultiPolygon(self.polygons),
+        };
+        if let Some(filter) = &options.bbox
+            && self.envelope.is_none()
+            && !geometry.bbox().is_some_and(|bbox| geometry::bbox_intersects(filter, &bbox))
+        {
+            return None;
+        }
+        if !options.geometry {
+            return Some(GeoJsonFeature::new(Geometry::LineString(Vec::new()), self.properties));
+        }
+        Some(GeoJsonFeature::new(geometry, self.properties))
+    }
+}
+
+/// The level of a CityGML level-of-detail property such as `lod0Network` or
+/// `lod2MultiSurface`, by its local name.
+fn lod_level(local: &[u8]) -> Option<u8> {
+    match local {
+        [b'l', b'o', b'd', level @ b'0'..=b'4', _, ..] => Some(level - b'0'),
+        _ => None,
+    }
+}
@@ -0,0 +1,31 @@
+//! GPX 1.1 track output for handheld GPS units and fitness apps.
+
+use anyhow::Result;
+use quick_xml::escape::escape;
+use std::io::Write;
+
+pub struct Track<'a> {
+    pub name: String,
+    /// `[lon, lat]` positions in travel order.
+    pub coords: &'a [Vec<f64>],
+}
+
+pub fn write_gpx(mut out: impl Write, tracks: &[Track]) -> Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<gpx version="1.1" creator="road-network-json" xmlns="http://www.topografix.com/GPX/1/1">"#
+    )?;
+    for track in tracks {
+        writeln!(out, "  <trk>")?;
+        writeln!(out, "    <name>{}</name>", escape(track.name.as_str()))?;
+        writeln!(out, "    <trkseg>")?;
+        for p in track.coords {
+            writeln!(out, r#"      <trkpt lat="{:.7}" lon="{:.7}"/>"#, p[1], p[0])?;
+        }
+        writeln!(out, "    </trkseg>")?;
+        writeln!(out, "  </trk>")?;
+    }
+    writeln!(out, "</gpx>")?;
+    Ok(())
+}
@@ -0,0 +1,471 @@
+//! Directed routing graph built from centerline segments.
+//!
+//! Segment endpoints with identical coordinates, or within the snap tolerance of
+//! an existing node, become shared nodes; each segment
+//! contributes zero, one or two directed edges depending on its travel direction.
+//! Turn restrictions forbid specific edge-to-edge transitions at a node.
+
+use crate::config::{Direction, DirectionConfig};
+use crate::geojson::{GeoJsonFeature, GeoJsonFeatureCollection, Geometry, PropertyValue};
+use crate::geometry;
+use crate::node_store::NodeStore;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::thread;
+
+/// What [`Graph::add_segments`] works out for one feature off the main thread.
+struct Prepared {
+    direction: Direction,
+    /// Provisional IDs of the end nodes, unless the segment is closed.
+    ends: Option<(usize, usize)>,
+    length_m: f64,
+}
+
+/// `None` for features that add nothing to the graph.
+fn prepare(feature: &GeoJsonFeature, directions: &DirectionConfig, store: &NodeStore) -> Option<Prepared> {
+    let Geometry::LineString(coords) = &feature.geometry else {
+        return None;
+    };
+    if coords.len() < 2 {
+        return None;
+    }
+    let direction = directions.resolve(&feature.properties);
+    if direction == Direction::Closed {
+        return Some(Prepared { direction, ends: None, length_m: 0.0 });
+    }
+    Some(Prepared {
+        direction,
+        ends: Some((store.intern(&coords[0]), store.intern(&coords[coords.len() - 1]))),
+        length_m: geometry::line_length_m(coords),
+    })
+}
+
+pub struct Node {
+    pub coord: Vec<f64>,
+}
+
+/// A source centerline segment as it entered the graph.
+pub struct Segment {
+    pub id: String,
+    pub direction: Direction,
+    pub properties: HashMap<String, PropertyValue>,
+    /// Vertices in digitised order.
+    pub coords: Vec<Vec<f64>>,
+}
+
+pub struct Edge {
+    pub segment_id: String,
+    pub from: usize,
+    pub to: usize,
+    /// True when the edge runs against the segment's digitised direction.
+    pub reversed: bool,
+    pub length_m: f64,
+    /// Vertices in travel order.
+    pub coords: Vec<Vec<f64>>,
+}
+
+/// One row of a turn-restriction table: travelling from segment `from_edge` onto
+/// segment `to_edge` through `via_node` is not allowed. Edges are segment IDs; the
+/// via node is a graph node ID and may be left empty when the segments share a
+/// single node.
+#[derive(Debug, Deserialize)]
+pub struct RestrictionRow {
+    pub from_edge: String,
+    pub via_node: Option<usize>,
+    pub to_edge: String,
+}
+
+/// Reads a turn-restriction CSV with a `from_edge,via_node,to_edge` header.
+pub fn load_restrictions(path: &Path) -> Result<Vec<RestrictionRow>> {
+    let mut reader = csv::Reader::from_path(path)
+        .with_context(|| format!("Failed to read restrictions: {}", path.display()))?;
+    reader
+        .deserialize()
+        .collect::<Result<_, _>>()
+        .with_context(|| format!("Invalid restrictions file: {}", path.display()))
+}
+
+#[derive(Default)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+    pub segments: Vec<Segment>,
+    /// Forbidden `(from edge, to edge)` transitions, by edge index.
+    pub banned_turns: HashSet<(usize, usize)>,
+    /// Distinct endpoint positions welded onto a nearby node.
+    pub snapped: usize,
+    node_index: HashMap<(u64, u64), usize>,
+    /// Endpoints within this many metres of an existing node are welded onto it.
+    snap_tolerance_m: f64,
+    /// Nodes by `snap_tolerance_m`-sized grid cell, when snapping.
+    snap_cells: HashMap<(i64, i64), Vec<usize>>,
+}
+
+impl Graph {
+    /// A graph that welds endpoints lying within `tolerance_m` metres of a node
+    /// added earlier onto that node, instead of requiring identical coordinates.
+    pub fn with_snap_tolerance(tolerance_m: f64) -> Self {
+        Graph {
+            snap_tolerance_m: tolerance_m,
+            ..Graph::default()
+        }
+    }
+
+    fn node_for(&mut self, coord: &[f64]) -> usize {
+        let key = (coord[0].to_bits(), coord[1].to_bits());
+        if let Some(&node) = self.node_index.get(&key) {
+            return node;
+        }
+        if self.snap_tolerance_m <= 0.0 {
+            self.nodes.push(Node { coord: coord.to_vec() });
+            self.node_index.insert(key, self.nodes.len() - 1);
+            return self.nodes.len() - 1;
+        }
+
+        let local = geometry::to_local_m(coord);
+        let cell = (
+            (local[0] / self.snap_tolerance_m).floor() as i64,
+            (local[1] / self.snap_tolerance_m).floor() as i64,
+        );
+        let nearest = (-1..=1)
+            .flat_map(|dx| (-1..=1).map(move |dy| (cell.0 + dx, cell.1 + dy)))
+            .filter_map(|neighbour| self.snap_cells.get(&neighbour))
+            .flatten()
+            .map(|&node| (node, geometry::haversine_m(coord, &self.nodes[node].coord)))
+            .filter(|&(_, distance)| distance <= self.snap_tolerance_m)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        let node = match nearest {
+            Some((node, _)) => {
+                self.snapped += 1;
+                node
+            }
+            None => {
+                self.nodes.push(Node { coord: coord.to_vec() });
+                self.snap_cells.entry(cell).or_default().push(self.nodes.len() - 1);
+                self.nodes.len() - 1
+            }
+        };
+        self.node_index.insert(key, node);
+        node
+    }
+
+    /// Adds the edges for one centerline feature, oriented by `directions`.
+    pub fn add_segment(&mut self, segment_id: &str, feature: &GeoJsonFeature, directions: &DirectionConfig) {
+        let Geometry::LineString(coords) = &feature.geometry else {
+            return;
+        };
+        if coords.len() < 2 {
+            return;
+        }
+        let direction = directions.resolve(&feature.properties);
+        if direction == Direction::Closed {
+            self.push_closed(segment_id, feature, coords);
+            return;
+        }
+        let start = self.node_for(&coords[0]);
+        let end = self.node_for(&coords[coords.len() - 1]);
+        self.push_segment(segment_id, feature, coords, direction, (start, end), None);
+    }
+
+    /// Adds the edges for many centerline features, as [`Graph::add_segment`]
+    /// would one at a time, spreading the work of welding their endpoints over
+    /// `threads` threads. Node IDs, and the nodes endpoints snap onto, come out
+    /// the same as adding the features in order.
+    pub fn add_segments(&mut self, features: &[(String, GeoJsonFeature)], directions: &DirectionConfig, threads: usize) {
+        // Endpoints may weld onto nodes already in the graph, which only a
+        // serial build looks for
+        if !self.nodes.is_empty() {
+            for (segment_id, feature) in features {
+                self.add_segment(segment_id, feature, directions);
+            }
+            return;
+        }
+        let threads = threads.max(1);
+        let store = NodeStore::default();
+        let chunk_size = features.len().div_ceil(threads).max(1);
+        let prepared: Vec<Option<Prepared>> = thread::scope(|scope| {
+            let workers: Vec<_> = features
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    let store = &store;
+                    scope.spawn(move || chunk.iter().map(|(_, feature)| prepare(feature, directions, store)).collect::<Vec<_>>())
+                })
+                .collect();
+            workers.into_iter().flat_map(|worker| worker.join().expect("graph worker panicked")).collect()
+        });
+
+        // Provisional IDs follow thread timing; ranking each distinct position
+        // by where it first appears in the input gives the order of a serial build
+        let mut ranks: Vec<Option<usize>> = vec![None; store.len()];
+        let mut positions: Vec<&[f64]> = Vec::new();
+        for ((_, feature), prepared) in features.iter().zip(&prepared) {
+            let (Some(Prepared { ends: Some((start, end)), .. }), Geometry::LineString(coords)) = (prepared, &feature.geometry)
+            else {
+                continue;
+            };
+            for (id, coord) in [(*start, &coords[0]), (*end, &coords[coords.len() - 1])] {
+                ranks[id].get_or_insert_with(|| {
+                    positions.push(coord);
+                    positions.len() - 1
+                });
+            }
+        }
+        let nodes = self.weld(&positions, threads);
+
+        for ((segment_id, feature), prepared) in features.iter().zip(prepared) {
+            let (Some(prepared), Geometry::LineString(coords)) = (prepared, &feature.geometry) else {
+                continue;
+            };
+            let Some((start, end)) = prepared.ends else {
+                self.push_closed(segment_id, feature, coords);
+                continue;
+            };
+            let node = |id: usize| nodes[ranks[id].expect("every endpoint is ranked")];
+            self.push_segment(segment_id, feature, coords, prepared.direction, (node(start), node(end)), Some(prepared.length_m));
+        }
+    }
+
+    /// Makes nodes for distinct endpoint positions, given in the order a serial
+    /// build meets them, into an empty graph, and returns each one's node.
+    ///
+    /// Which earlier positions lie within the snap tolerance of each one is
+    /// searched for on `threads` threads. A pass in order then welds each
+    /// position onto the nearest of those that became a node, or makes it a
+    /// node, which is what [`Graph::node_for`] decides one position at a time.
+    fn weld(&mut self, positions: &[&[f64]], threads: usize) -> Vec<usize> {
+        let tolerance = self.snap_tolerance_m;
+        let cells: Vec<(i64, i64)> = if tolerance > 0.0 {
+            positions
+                .iter()
+                .map(|coord| {
+                    let local = geometry::to_local_m(coord);
+                    ((local[0] / tolerance).floor() as i64, (local[1] / tolerance).floor() as i64)
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let mut by_cell: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+        for (rank, cell) in cells.iter().enumerate() {
+            by_cell.entry(*cell).or_default().push(rank);
+        }
+
+        // Nearest first; ties keep the order node_for visits cells and nodes in
+        let nearby = |rank: usize| -> Vec<usize> {
+            let cell = cells[rank];
+            let mut near: Vec<(usize, f64)> = (-1..=1)
+                .flat_map(|dx| (-1..=1).map(move |dy| (cell.0 + dx, cell.1 + dy)))
+                .filter_map(|neighbour| by_cell.get(&neighbour))
+                .flat_map(|ranks| ranks.iter().take_while(|&&earlier| earlier < rank))
+                .map(|&earlier| (earlier, geometry::haversine_m(positions[rank], positions[earlier])))
+                .filter(|&(_, distance)| distance <= tolerance)
+                .collect();
+            near.sort_by(|a, b| a.1.total_cmp(&b.1));
+            near.into_iter().map(|(earlier, _)| earlier).collect()
+        };
+        let candidates: Vec<Vec<usize>> = if cells.is_empty() {
+            vec![Vec::new(); positions.len()]
+        } else {
+            let chunk_size = positions.len().div_ceil(threads).max(1);
+            thread::scope(|scope| {
+                let workers: Vec<_> = (0..positions.len())
+                    .step_by(chunk_size)
+                    .map(|first| {
+                        let nearby = &nearby;
+                        let last = (first + chunk_size).min(positions.len());
+                        scope.spawn(move || (first..last).map(nearby).collect::<Vec<_>>())
+                    })
+                    .collect();
+                workers.into_iter().flat_map(|worker| worker.join().expect("graph worker panicked")).collect()
+            })
+        };
+
+        let mut nodes: Vec<usize> = Vec::with_capacity(positions.len());
+        let mut is_node = vec![false; positions.len()];
+        for (rank, coord) in positions.iter().enumerate() {
+            let node = match candidates[rank].iter().find(|&&earlier| is_node[earlier]) {
+                Some(&earlier) => {
+                    self.snapped += 1;
+                    nodes[earlier]
+                }
+                None => {
+                    self.nodes.push(Node { coord: coord.to_vec() });
+                    if let Some(cell) = cells.get(rank) {
+                        self.snap_cells.entry(*cell).or_default().push(self.nodes.len() - 1);
+                    }
+                    is_node[rank] = true;
+                    self.nodes.len() - 1
+                }
+            };
+            self.node_index.insert((coord[0].to_bits(), coord[1].to_bits()), node);
+            nodes.push(node);
+        }
+        nodes
+    }
+
+    fn push_closed(&mut self, segment_id: &str, feature: &GeoJsonFeature, coords: &[Vec<f64>]) {
+        self.segments.push(Segment {
+            id: segment_id.to_string(),
+            direction: Direction::Closed,
+            properties: feature.properties.clone(),
+            coords: coords.to_vec(),
+        });
+    }
+
+    /// Adds a segment running from node `ends.0` to node `ends.1` and its edges.
+    /// `length_m` is the length of `coords` as read, if already known.
+    fn push_segment(
+        &mut self,
+        segment_id: &str,
+        feature: &GeoJsonFeature,
+        coords: &[Vec<f64>],
+        direction: Direction,
+        (start, end): (usize, usize),
+        length_m: Option<f64>,
+    ) {
+        // Move snapped ends onto their nodes so edges meet exactly
+        let mut coords = coords.to_vec();
+        let last = coords.len() - 1;
+        let moved = coords[0] != self.nodes[start].coord || coords[last] != self.nodes[end].coord;
+        coords[0] = self.nodes[start].coord.clone();
+        coords[last] = self.nodes[end].coord.clone();
+        let coords = &coords;
+        self.segments.push(Segment {
+            id: segment_id.to_string(),
+            direction,
+            properties: feature.properties.clone(),
+            coords: coords.clone(),
+        });
+        let length_m = match length_m {
+            Some(length_m) if !moved => length_m,
+            _ => geometry::line_length_m(coords),
+        };
+
+        if matches!(direction, Direction::Both | Direction::Forward) {
+            self.edges.push(Edge {
+                segment_id: segment_id.to_string(),
+                from: start,
+                to: end,
+                reversed: false,
+                length_m,
+                coords: coords.clone(),
+            });
+        }
+        if matches!(direction, Direction::Both | Direction::Backward) {
+            self.edges.push(Edge {
+                segment_id: segment_id.to_string(),
+                from: end,
+                to: start,
+                reversed: true,
+                length_m,
+                coords: coords.iter().rev().cloned().collect(),
+            });
+        }
+    }
+
+    /// Resolves restriction rows onto directed edges. Returns how many rows could not
+    /// be matched to any pair of edges meeting at the via node.
+    pub fn apply_restrictions(&mut self, rows: &[RestrictionRow]) -> usize {
+        let mut by_segment: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (id, edge) in self.edges.iter().enumerate() {
+            by_segment.entry(edge.segment_id.as_str()).or_default().push(id);
+        }
+
+        let mut banned = Vec::new();
+        let mut unmatched = 0;
+        for row in rows {
+            let from = by_segment.get(row.from_edge.as_str()).map(Vec::as_slice).unwrap_or_default();
+            let to = by_segment.get(row.to_edge.as_str()).map(Vec::as_slice).unwrap_or_default();
+            let before = banned.len();
+            for &f in from {
+                for &t in to {
+                    let via = self.edges[f].to;
+                    if via == self.edges[t].from && row.via_node.is_none_or(|node| node == via) {
+                        banned.push((f, t));
+                    }
+                }
+            }
+            if banned.len() == before {
+                unmatched += 1;
+            }
+        }
+        self.banned_turns.extend(banned);
+        unmatched
+    }
+
+    /// Outgoing edge indices for every node.
+    pub fn outgoing(&self) -> Vec<Vec<usize>> {
+        let mut outgoing = vec![Vec::new(); self.nodes.len()];
+        for (id, edge) in self.edges.iter().enumerate() {
+            outgoing[edge.from].push(id);
+        }
+        outgoing
+    }
+
+    /// The node closest to a `[lon, lat]` position.
+    pub fn nearest_node(&self, coord: &[f64]) -> Option<usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                geometry::haversine_m(&a.coord, coord).total_cmp(&geometry::haversine_m(&b.coord, coord))
+            })
+            .map(|(id, _)| id)
+    }
+
+    pub fn nodes_geojson(&self) -> GeoJsonFeatureCollection {
+        let features = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(id, node)| {
+                let properties = HashMap::from([("node_id".to_string(), PropertyValue::Int(id as i64))]);
+                GeoJsonFeature::new(Geometry::Point(node.coord.clone()), properties)
+            })
+            .collect();
+        GeoJsonFeatureCollection::new(features)
+    }
+
+    pub fn edges_geojson(&self) -> GeoJsonFeatureCollection {
+        let features = self
+            .edges
+            .iter()
+            .enumerate()
+            .map(|(id, edge)| {
+                let properties = HashMap::from([
+                    ("edge_id".to_string(), PropertyValue::Int(id as i64)),
+                    ("segment_id".to_string(), PropertyValue::String(edge.segment_id.clone())),
+                    ("from".to_string(), PropertyValue::Int(edge.from as i64)),
+                    ("to".to_string(), PropertyValue::Int(edge.to as i64)),
+                    ("reversed".to_string(), PropertyValue::Int(edge.reversed as i64)),
+                    ("length_m".to_string(), PropertyValue::Float(edge.length_m)),
+                ]);
+                GeoJsonFeature::new(Geometry::LineString(edge.coords.clone()), properties)
+            })
+            .collect();
+        GeoJsonFeatureCollection::new(features)
+    }
+
+    /// Turn restrictions as points at their via node, naming the directed edges involved.
+    pub fn restrictions_geojson(&self) -> GeoJsonFeatureCollection {
+        let mut turns: Vec<_> = self.banned_turns.iter().copied().collect();
+        turns.sort_unstable();
+        let features = turns
+            .into_iter()
+            .map(|(from, to)| {
+                let via = self.edges[from].to;
+                let properties = HashMap::from([
+                    ("from_edge".to_string(), PropertyValue::Int(from as i64)),
+                    ("to_edge".to_string(), PropertyValue::Int(to as i64)),
+                    ("via_node".to_string(), PropertyValue::Int(via as i64)),
+                    ("from_segment".to_string(), PropertyValue::String(self.edges[from].segment_id.clone())),
+                    ("to_segment".to_string(), PropertyValue::String(self.edges[to].segment_id.clone())),
+                ]);
+                GeoJsonFeature::new(Geometry::Point(self.nodes[via].coord.clone()), properties)
+            })
+            .collect();
+        GeoJsonFeatureCollection::new(features)
+    }
+}
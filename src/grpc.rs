@@ -0,0 +1,119 @@
+//! gRPC conversion service (`grpc` feature), run by `serve-grpc`. Clients can be
+//! generated from `proto/road_network.proto`.
+
+use crate::gml;
+use crate::metrics::{self, Metrics};
+use anyhow::{Context, Result};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+
+include!(concat!(env!("OUT_DIR"), "/road_network.Converter.rs"));
+
+use converter_server::{Converter, ConverterServer};
+
+/// Features in flight between the parser and the response stream.
+const CHANNEL_CAPACITY: usize = 256;
+
+/// A piece of a GML document.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GmlChunk {
+    #[prost(bytes = "vec", tag = "1")]
+    pub data: Vec<u8>,
+}
+
+/// One converted feature, as a GeoJSON string.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Feature {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(string, tag = "2")]
+    pub geojson: String,
+}
+
+pub struct ConverterService {
+    /// Documents larger than this are refused, since each is held in memory.
+    pub max_document_bytes: usize,
+    pub metrics: Arc<Metrics>,
+}
+
+impl ConverterService {
+    /// Collects a client's chunks into one document.
+    async fn receive(&self, mut chunks: Streaming<GmlChunk>) -> Result<String, Status> {
+        let mut document = Vec::new();
+        while let Some(chunk) = chunks.message().await? {
+            document.extend(chunk.data);
+            if document.len() > self.max_document_bytes {
+                return Err(Status::resource_exhausted(format!(
+                    "GML is larger than {} bytes",
+                    self.max_document_bytes
+                )));
+            }
+        }
+        String::from_utf8(document).map_err(|e| Status::invalid_argument(format!("GML is not UTF-8: {}", e)))
+    }
+}
+
+#[tonic::async_trait]
+impl Converter for ConverterService {
+    type ConvertStreamStream = ReceiverStream<Result<Feature, Status>>;
+
+    async fn convert_stream(
+        &self,
+        request: Request<Streaming<GmlChunk>>,
+    ) -> Result<Response<Self::ConvertStreamStream>, Status> {
+        let start = Instant::now();
+        let content = match self.receive(request.into_inner()).await {
+            Ok(content) => content,
+            Err(status) => {
+                self.metrics.record_conversion(start.elapsed(), false);
+                return Err(status);
+            }
+        };
+
+        let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        let metrics = Arc::clone(&self.metrics);
+        tokio::task::spawn_blocking(move || {
+            let result = gml::stream_document(&content, |id, feature| {
+                let feature = Feature {
+                    id,
+                    geojson: serde_json::to_string(&feature)?,
+                };
+                // A closed channel means the client went away
+                sender.blocking_send(Ok(feature)).context("Client disconnected")?;
+                metrics.add_features(1);
+                Ok(())
+            });
+            metrics.record_conversion(start.elapsed(), result.is_ok());
+            if let Err(e) = result {
+                let _ = sender.blocking_send(Err(Status::invalid_argument(format!("{:#}", e))));
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+}
+
+/// Serves [`ConverterService`] on `address`, and Prometheus metrics on
+/// `metrics_address` if given, until the process is stopped.
+pub async fn serve(address: SocketAddr, max_document_bytes: usize, metrics_address: Option<SocketAddr>) -> Result<()> {
+    let metrics = Arc::new(Metrics::default());
+    let service = ConverterService {
+        max_document_bytes,
+        metrics: Arc::clone(&metrics),
+    };
+    let grpc = async {
+        eprintln!("Serving gRPC on {}", address);
+        tonic::transport::Server::builder()
+            .add_service(ConverterServer::new(service))
+            .serve(address)
+            .await
+            .with_context(|| format!("Failed to serve on {}", address))
+    };
+    match metrics_address {
+        Some(metrics_address) => tokio::try_join!(grpc, metrics::serve(metrics_address, metrics)).map(|_| ()),
+        None => grpc.await,
+    }
+}
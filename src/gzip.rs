@@ -0,0 +1,99 @@
+//! Gzipped inputs (`.gml.gz`), read without unpacking them to disk first.
+//!
+//! A background thread reads and inflates the file while the caller parses what
+//! has already arrived, so decompression overlaps with XML parsing instead of
+//! running before it.
+
+use crate::perf::{self, Stage};
+use anyhow::{Context, Result};
+use flate2::read::MultiGzDecoder;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// Bytes inflated per message from the background thread.
+const CHUNK_SIZE: usize = 256 * 1024;
+/// Chunks the background thread may run ahead of the parser.
+const CHUNKS_AHEAD: usize = 8;
+
+/// Whether `path` names a gzipped file, by its `.gz` extension.
+pub fn is_gzip(path: &str) -> bool {
+    path.to_lowercase().ends_with(".gz")
+}
+
+/// The decompressed contents of a gzipped file, inflated on a background thread.
+/// Everything read so far is kept, so that errors can quote and locate the XML
+/// the same way as for an uncompressed file held in memory.
+pub struct Decompressor {
+    receiver: Receiver<io::Result<Vec<u8>>>,
+    data: Vec<u8>,
+    position: usize,
+    finished: bool,
+}
+
+impl Decompressor {
+    pub fn open(path: &str) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Failed to read file: {}", path))?;
+        let (sender, receiver) = mpsc::sync_channel(CHUNKS_AHEAD);
+        thread::spawn(move || {
+            let mut decoder = MultiGzDecoder::new(BufReader::new(file));
+            loop {
+                let mut chunk = vec![0; CHUNK_SIZE];
+                let result = match decoder.read(&mut chunk) {
+                    Ok(0) => return,
+                    Ok(length) => {
+                        chunk.truncate(length);
+                        Ok(chunk)
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => Err(e),
+                };
+                let failed = result.is_err();
+                // The reader has been dropped, e.g. after `--limit` stopped it early
+                if sender.send(result).is_err() || failed {
+                    return;
+                }
+            }
+        });
+        Ok(Decompressor {
+            receiver,
+            data: Vec::new(),
+            position: 0,
+            finished: false,
+        })
+    }
+
+    /// The decompressed bytes received so far.
+    pub fn contents(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl Read for Decompressor {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let length = available.len().min(buf.len());
+        buf[..length].copy_from_slice(&available[..length]);
+        self.consume(length);
+        Ok(length)
+    }
+}
+
+impl BufRead for Decompressor {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let _read = (self.position == self.data.len()).then(|| perf::enter(Stage::Read));
+        while self.position == self.data.len() && !self.finished {
+            match self.receiver.recv() {
+                Ok(chunk) => self.data.extend_from_slice(&chunk?),
+                // The thread has inflated the whole file
+                Err(_) => self.finished = true,
+            }
+        }
+        Ok(&self.data[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = (self.position + amount).min(self.data.len());
+    }
+}
@@ -0,0 +1,140 @@
+//! A long-lived store of every version of every feature, kept by `--format
+//! history` across runs.
+//!
+//! `changes.jsonl` is append-only. Each run adds a line per feature that was
+//! added, changed or removed since the run before. A line that opens a version
+//! holds the feature and its `valid_from` time. A line that closes one holds its
+//! `valid_from` and `valid_to` but no feature. `current.json` records the hash
+//! and start of each open version, so a run doesn't have to replay the log to
+//! find what changed.
+
+use crate::geojson::GeoJsonFeature;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const LOG_FILE: &str = "changes.jsonl";
+pub const CURRENT_FILE: &str = "current.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Added,
+    Modified,
+    Removed,
+}
+
+/// One line of `changes.jsonl`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub layer: String,
+    pub id: String,
+    pub change: Change,
+    /// UTC time, as `YYYY-MM-DDTHH:MM:SSZ`, of the run that wrote the version.
+    pub valid_from: String,
+    /// UTC time of the run that replaced or removed the version.
+    pub valid_to: Option<String>,
+    /// The version's feature, on lines that open one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feature: Option<GeoJsonFeature>,
+}
+
+/// An open version, as recorded in `current.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Current {
+    pub hash: String,
+    pub valid_from: String,
+}
+
+/// Open versions by layer and ID.
+pub type CurrentIndex = BTreeMap<String, BTreeMap<String, Current>>;
+
+/// One version of a feature, rebuilt from the log.
+#[derive(Debug)]
+pub struct Version {
+    pub layer: String,
+    pub id: String,
+    pub valid_from: String,
+    pub valid_to: Option<String>,
+    pub feature: GeoJsonFeature,
+}
+
+impl Version {
+    /// Whether the version was current at `time`, an ISO 8601 date or UTC
+    /// date-time.
+    pub fn valid_at(&self, time: &str) -> bool {
+        let time = time.trim_end_matches('Z');
+        let time = match time.len() {
+            10 => format!("{}T00:00:00", time),
+            _ => time.to_string(),
+        };
+        let time = time.as_str();
+        self.valid_from.trim_end_matches('Z') <= time
+            && self.valid_to.as_deref().is_none_or(|to| to.trim_end_matches('Z') > time)
+    }
+}
+
+/// Every version in the store at `dir`, in the order they were written.
+pub fn read_versions(dir: &Path) -> Result<Vec<Version>> {
+    let path = dir.join(LOG_FILE);
+    let file = File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut versions: Vec<Version> = Vec::new();
+    // The open version of each feature, by layer and ID
+    let mut open: BTreeMap<(String, String), usize> = BTreeMap::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: Entry = serde_json::from_str(&line)
+            .with_context(|| format!("Invalid entry on line {} of {}", number + 1, path.display()))?;
+        let key = (entry.layer.clone(), entry.id.clone());
+        match entry.feature {
+            Some(feature) => {
+                open.insert(key, versions.len());
+                versions.push(Version {
+                    layer: entry.layer,
+                    id: entry.id,
+                    valid_from: entry.valid_from,
+                    valid_to: entry.valid_to,
+                    feature,
+                });
+            }
+            None => {
+                if let Some(index) = open.remove(&key) {
+                    versions[index].valid_to = entry.valid_to;
+                }
+            }
+        }
+    }
+    Ok(versions)
+}
+
+/// The current UTC time as `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn now() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
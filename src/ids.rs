@@ -0,0 +1,46 @@
+//! Feature ID normalization set under `[input.ids]`, so a feature gets the same
+//! ID whether its source writes `12345` or `0000012345`.
+
+use serde::Deserialize;
+
+/// How feature IDs are rewritten before anything uses them: file names, the
+/// `id` written with each feature, the search index and `--links`. By default
+/// IDs are kept as they are, e.g.
+///
+/// ```toml
+/// [input.ids]
+/// strip_leading_zeros = true
+/// pad_to = 10
+/// lowercase = true
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdFormat {
+    /// Drop leading zeros, leaving `0` for an ID of zeros only.
+    pub strip_leading_zeros: bool,
+    /// Left-pad shorter IDs with zeros to this many characters. Applied after
+    /// `strip_leading_zeros`, so the two together give every ID one width.
+    pub pad_to: Option<usize>,
+    /// Lowercase letters in IDs.
+    pub lowercase: bool,
+}
+
+impl IdFormat {
+    /// `id` rewritten as configured.
+    pub fn normalize(&self, mut id: String) -> String {
+        if self.strip_leading_zeros && id.starts_with('0') {
+            let trimmed = id.trim_start_matches('0');
+            id = if trimmed.is_empty() { "0".to_string() } else { trimmed.to_string() };
+        }
+        if let Some(width) = self.pad_to {
+            let length = id.chars().count();
+            if length < width {
+                id.insert_str(0, &"0".repeat(width - length));
+            }
+        }
+        if self.lowercase {
+            id = id.to_lowercase();
+        }
+        id
+    }
+}
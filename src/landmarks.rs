@@ -0,0 +1,163 @@
+//! Landmark preprocessing for A* routing (ALT: A*, landmarks and the triangle
+//! inequality).
+//!
+//! A handful of nodes spread to the edges of the network are picked as
+//! landmarks, and the shortest distance from each landmark to every node and
+//! from every node back to it is stored. By the triangle inequality, those
+//! distances give a lower bound on the distance between any two nodes, which
+//! steers the search towards the target and lets it settle far fewer edges.
+//!
+//! Distances are taken over nodes, without turn restrictions. Restrictions only
+//! ever make routes longer, so the bounds hold for the restricted search too.
+//!
+//! The tables are written next to the graph by `graph --landmarks` and read back
+//! by `route --landmarks`, together with a hash of the graph so that tables
+//! computed for another graph are refused.
+
+use crate::graph::Graph;
+use crate::paths;
+use crate::routing;
+use anyhow::{Context, Result, bail};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+/// Default name of the tables under the graph directory.
+pub const FILE_NAME: &str = "landmarks.bin";
+
+const MAGIC: &[u8; 8] = b"RNJALT1\0";
+
+pub struct Landmarks {
+    /// Landmark node IDs.
+    pub nodes: Vec<usize>,
+    /// For each landmark, the distance from it to every node.
+    from: Vec<Vec<f64>>,
+    /// For each landmark, the distance from every node to it.
+    to: Vec<Vec<f64>>,
+    graph_hash: [u8; 32],
+}
+
+impl Landmarks {
+    /// Picks up to `count` landmarks by farthest-point selection: each new one is
+    /// the node farthest from those already picked.
+    pub fn build(graph: &Graph, count: usize) -> Self {
+        let (outgoing, incoming) = routing::adjacency(graph);
+        let mut landmarks = Landmarks {
+            nodes: Vec::new(),
+            from: Vec::new(),
+            to: Vec::new(),
+            graph_hash: graph_hash(graph),
+        };
+        // Distance from the nearest landmark so far, either way round
+        let mut nearest = vec![f64::INFINITY; graph.nodes.len()];
+        let mut next = (0..graph.nodes.len()).max_by_key(|&node| outgoing[node].len() + incoming[node].len());
+        while let Some(node) = next.filter(|_| landmarks.nodes.len() < count) {
+            let from = routing::node_distances(graph, &outgoing, &[(node, 0.0)], false);
+            let to = routing::node_distances(graph, &incoming, &[(node, 0.0)], true);
+            for (nearest, distance) in nearest.iter_mut().zip(from.iter().zip(&to)) {
+                *nearest = nearest.min(distance.0.min(*distance.1));
+            }
+            landmarks.nodes.push(node);
+            landmarks.from.push(from);
+            landmarks.to.push(to);
+            // Nodes no landmark reaches either way are left out; they have no bound
+            next = nearest
+                .iter()
+                .enumerate()
+                .filter(|(_, distance)| distance.is_finite() && **distance > 0.0)
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map(|(node, _)| node);
+        }
+        landmarks
+    }
+
+    /// A lower bound on the distance from `node` to `target`, in metres.
+    pub fn lower_bound(&self, node: usize, target: usize) -> f64 {
+        let mut bound: f64 = 0.0;
+        for (from, to) in self.from.iter().zip(&self.to) {
+            // d(L, t) <= d(L, v) + d(v, t) and d(v, L) <= d(v, t) + d(t, L)
+            for difference in [from[target] - from[node], to[node] - to[target]] {
+                if !difference.is_nan() {
+                    bound = bound.max(difference);
+                }
+            }
+        }
+        bound
+    }
+
+    pub fn write(&self, path: &str) -> Result<()> {
+        let file = File::create(paths::long_path(path)).with_context(|| format!("Failed to create {}", path))?;
+        let mut out = BufWriter::new(file);
+        out.write_all(MAGIC)?;
+        out.write_all(&self.graph_hash)?;
+        out.write_all(&(self.nodes.len() as u64).to_le_bytes())?;
+        out.write_all(&(self.from.first().map_or(0, Vec::len) as u64).to_le_bytes())?;
+        for &node in &self.nodes {
+            out.write_all(&(node as u64).to_le_bytes())?;
+        }
+        for distances in self.from.iter().chain(&self.to) {
+            for distance in distances {
+                out.write_all(&distance.to_le_bytes())?;
+            }
+        }
+        out.flush().with_context(|| format!("Failed to write {}", path))
+    }
+
+    /// Reads tables written by [`Landmarks::write`], failing unless they were
+    /// computed for `graph`.
+    pub fn read(path: &str, graph: &Graph) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Failed to read {}", path))?;
+        let mut input = BufReader::new(file);
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic).with_context(|| format!("Failed to read {}", path))?;
+        if &magic != MAGIC {
+            bail!("{} is not a landmarks file", path);
+        }
+        let mut graph_hash = [0; 32];
+        input.read_exact(&mut graph_hash)?;
+        if graph_hash != self::graph_hash(graph) {
+            bail!("{} was computed for a different graph; run `graph --landmarks` again", path);
+        }
+        let count = read_u64(&mut input)? as usize;
+        let nodes = read_u64(&mut input)? as usize;
+        let ids = (0..count).map(|_| Ok(read_u64(&mut input)? as usize)).collect::<Result<_>>()?;
+        let mut tables = (0..2 * count)
+            .map(|_| (0..nodes).map(|_| read_f64(&mut input)).collect::<Result<Vec<_>>>())
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("{} is truncated", path))?;
+        let to = tables.split_off(count);
+        Ok(Landmarks {
+            nodes: ids,
+            from: tables,
+            to,
+            graph_hash,
+        })
+    }
+}
+
+fn read_u64(input: &mut impl Read) -> Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f64(input: &mut impl Read) -> Result<f64> {
+    Ok(f64::from_bits(read_u64(input)?))
+}
+
+/// Identifies a graph by its nodes and edges, which are numbered the same on
+/// every build from the same data.
+fn graph_hash(graph: &Graph) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for node in &graph.nodes {
+        for ordinate in &node.coord {
+            hasher.update(ordinate.to_le_bytes());
+        }
+    }
+    for edge in &graph.edges {
+        hasher.update((edge.from as u64).to_le_bytes());
+        hasher.update((edge.to as u64).to_le_bytes());
+        hasher.update(edge.length_m.to_le_bytes());
+    }
+    hasher.finalize().into()
+}
@@ -0,0 +1,78 @@
+//! Reads the Hong Kong Road Network (2nd Generation) CityGML datasets.
+//!
+//! The `road-network-json` binary is built on these modules. Rust programs can
+//! use [`read_features`] to get each feature's geometry as a
+//! [`geo_types::Geometry`], ready for the algorithms in the `geo` crate, without
+//! going through GeoJSON files:
+//!
+//! ```no_run
+//! use road_network_json::dataset::DATASETS;
+//! use road_network_json::gml::ParseOptions;
+//!
+//! let centerlines = &DATASETS[0];
+//! let features = road_network_json::read_features(
+//!     "input/CENTERLINE.gml",
+//!     centerlines.id_field,
+//!     &ParseOptions::default(),
+//! )?;
+//! for feature in &features {
+//!     println!("{}: {:?}", feature.id, feature.properties.get("STREET_ENAME"));
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod aggregate;
+pub mod attributes;
+pub mod citygml;
+pub mod config;
+pub mod crs;
+pub mod dataset;
+pub mod districts;
+pub mod feature;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fingerprint;
+pub mod fixtures;
+pub mod geojson;
+pub mod geometry;
+pub mod gml;
+pub mod gpx;
+pub mod gzip;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod graph;
+pub mod history;
+pub mod ids;
+pub mod landmarks;
+pub mod links;
+#[cfg(feature = "grpc")]
+pub mod metrics;
+pub mod mmap;
+pub mod models;
+pub mod neo4j;
+pub mod node_store;
+pub mod osm;
+pub mod paths;
+pub mod pedestrian;
+pub mod perf;
+pub mod polyline;
+pub mod prescan;
+pub mod profile;
+pub mod raster;
+#[cfg(feature = "tokio")]
+pub mod pipeline;
+pub mod qa;
+pub mod routing;
+pub mod schema;
+pub mod search;
+pub mod sink;
+pub mod spatial;
+pub mod tiles;
+pub mod transform;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod well_known;
+
+pub use feature::{Feature, read_features};
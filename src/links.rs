@@ -0,0 +1,108 @@
+//! Cross-references between layers, resolved with `--links`.
+//!
+//! Each configured [`LinkConfig`] names an attribute whose value is the ID of a
+//! feature in another layer. Resolved references are added to the feature as a
+//! `links` property (`layer:id`, comma-separated) and collected into a
+//! relationships table.
+
+use crate::config::LinkConfig;
+use crate::geojson::{GeoJsonFeature, PropertyValue};
+use crate::ids::IdFormat;
+use crate::paths;
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug)]
+struct Relationship {
+    from_layer: String,
+    from_id: String,
+    attribute: String,
+    to_layer: String,
+    to_id: String,
+}
+
+pub struct LinkResolver<'a> {
+    rules: &'a [LinkConfig],
+    /// Applied to referenced IDs, as it was to the targets' own.
+    ids: &'a IdFormat,
+    /// IDs present in each target layer.
+    targets: HashMap<String, HashSet<String>>,
+    relationships: Vec<Relationship>,
+    dangling: usize,
+}
+
+impl<'a> LinkResolver<'a> {
+    pub fn new(rules: &'a [LinkConfig], ids: &'a IdFormat) -> Self {
+        LinkResolver {
+            rules,
+            ids,
+            targets: HashMap::new(),
+            relationships: Vec::new(),
+            dangling: 0,
+        }
+    }
+
+    /// Layers whose IDs must be known before references to them can be resolved.
+    pub fn target_layers(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().map(|rule| rule.to.as_str())
+    }
+
+    pub fn add_target(&mut self, layer: &str, id: String) {
+        self.targets.entry(layer.to_string()).or_default().insert(id);
+    }
+
+    /// Records the references held by a feature of `layer` and adds its `links`
+    /// property. References to IDs missing from the target layer are counted but
+    /// not linked.
+    pub fn resolve(&mut self, layer: &str, id: &str, feature: &mut GeoJsonFeature) {
+        let mut links = Vec::new();
+        for rule in self.rules.iter().filter(|rule| rule.from == layer) {
+            let Some(value) = feature.properties.get(&rule.attribute) else {
+                continue;
+            };
+            if matches!(value, PropertyValue::Null) {
+                continue;
+            }
+            for target in value.to_string().split(rule.separator.as_str()).map(str::trim) {
+                if target.is_empty() {
+                    continue;
+                }
+                let target = self.ids.normalize(target.to_string());
+                if !self.targets.get(&rule.to).is_some_and(|ids| ids.contains(&target)) {
+                    self.dangling += 1;
+                    continue;
+                }
+                links.push(format!("{}:{}", rule.to, target));
+                self.relationships.push(Relationship {
+                    from_layer: layer.to_string(),
+                    from_id: id.to_string(),
+                    attribute: rule.attribute.clone(),
+                    to_layer: rule.to.clone(),
+                    to_id: target,
+                });
+            }
+        }
+        if !links.is_empty() {
+            feature
+                .properties
+                .insert("links".to_string(), PropertyValue::String(links.join(",")));
+        }
+    }
+
+    /// Writes the relationships table as CSV.
+    pub fn write(&self, path: &str) -> Result<()> {
+        let mut writer = csv::Writer::from_path(paths::long_path(path)).with_context(|| format!("Failed to create {}", path))?;
+        writer.write_record(["from_layer", "from_id", "attribute", "to_layer", "to_id"])?;
+        for r in &self.relationships {
+            writer.write_record([&r.from_layer, &r.from_id, &r.attribute, &r.to_layer, &r.to_id])?;
+        }
+        writer.flush()?;
+        eprintln!(
+            "Resolved {} link(s) to {}; {} reference(s) had no matching feature",
+            self.relationships.len(),
+            path,
+            self.dangling
+        );
+        Ok(())
+    }
+}
@@ -1,299 +1,2923 @@
+use road_network_json::{
+    aggregate,
+    attributes,
+    citygml,
+    config,
+    crs,
+    dataset,
+    districts,
+    fingerprint,
+    fixtures,
+    geojson,
+    geometry,
+    gml,
+    gpx,
+    graph,
+    gzip,
+    history,
+    landmarks,
+    links,
+    neo4j,
+    osm,
+    paths,
+    pedestrian,
+    perf,
+    prescan,
+    profile,
+    qa,
+    raster,
+    routing,
+    schema,
+    search,
+    sink,
+    spatial,
+    tiles,
+    transform,
+};
+use aggregate::Aggregation;
+use attributes::AttributeType;
 use anyhow::{Context, Result};
-use quick_xml::events::Event;
-use quick_xml::Reader;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use config::{Config, Direction, DirectionConfig, Language, TransformStep};
+use crs::{Crs, CrsAnnotation};
+use dataset::{DATASETS, Dataset, DatasetKind};
+use districts::Districts;
+use geojson::{ForeignMember, GeoJsonFeature, GeoJsonFeatureCollection, Geometry, PropertyValue};
+use gml::{GmlProfile, ParseOptions, ParseSummary, Progress};
+use graph::Graph;
+use landmarks::Landmarks;
+use links::LinkResolver;
+use pedestrian::PedestrianNetwork;
+use perf::{Stage, StageTimes};
+use prescan::Prescanner;
+use qa::Checker;
+use raster::Raster;
+use schema::SchemaDiff;
+use search::{SearchIndex, StreetNames};
+use tiles::TileReport;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs::{self, File};
-use std::io::Write;
-use std::path::Path;
+#[cfg(feature = "arrow")]
+use sink::ArrowSink;
+#[cfg(feature = "http")]
+use sink::{HttpSink, PostOptions};
+#[cfg(feature = "kafka")]
+use sink::KafkaSink;
+#[cfg(feature = "parquet")]
+use sink::ParquetSink;
+use sink::{
+    CityJsonSink, CsvSink, DuckDbSink, DxfSink, Encoding, FileOptions, FileParts, FileSink, GeobufSink, HistorySink, KeplerGlSink, PagesSink, Sink, SinkSet,
+    WellKnownSink, write_collection, write_json,
+};
+use spatial::SpatialIndex;
+use transform::TransformChain;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct GeoJsonFeature {
-    #[serde(rename = "type")]
-    feature_type: String,
-    geometry: Geometry,
-    properties: HashMap<String, PropertyValue>,
+const DATA_DIR: &str = "./input";
+const OUTPUT_DIR: &str = "./output";
+
+/// The input and output directories given by `--input-dir` and `--output-dir`,
+/// set once at startup.
+static DIRS: OnceLock<(String, String)> = OnceLock::new();
+
+fn data_dir() -> &'static str {
+    DIRS.get().map_or(DATA_DIR, |(input, _)| input)
+}
+
+fn output_dir() -> &'static str {
+    DIRS.get().map_or(OUTPUT_DIR, |(_, output)| output)
+}
+
+/// Features named in each kind of per-feature warning; the rest are only counted.
+const MAX_MISSING_WARNINGS: usize = 10;
+/// Features per page of `--format pages` without `--chunk-size`.
+const DEFAULT_CHUNK_SIZE: usize = 1000;
+/// Exit status of a `--continue-on-failure` run in which some datasets failed.
+const PARTIAL_FAILURE_EXIT_CODE: i32 = 3;
+
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "Convert Road Network (2nd Generation) GML files to GeoJSON",
+    args_conflicts_with_subcommands = true
+)]
+struct Cli {
+    /// TOML configuration file; built-in defaults are used for anything it omits.
+    #[arg(long, global = true, env = "RNJ_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Directory the source GML files are read from.
+    #[arg(long, global = true, value_name = "DIR", env = "RNJ_INPUT_DIR", default_value = DATA_DIR)]
+    input_dir: String,
+
+    /// Directory output is written to, and where commands look for earlier output.
+    #[arg(long, global = true, value_name = "DIR", env = "RNJ_OUTPUT_DIR", default_value = OUTPUT_DIR)]
+    output_dir: String,
+
+    /// Read GML positions as northing/latitude first (`--swap-axes`) or easting/
+    /// longitude first (`--swap-axes=false`) instead of detecting it from `srsName`.
+    #[arg(long, global = true, num_args = 0..=1, default_missing_value = "true", env = "RNJ_SWAP_AXES")]
+    swap_axes: Option<bool>,
+
+    /// Drop vertices closer than this many metres to the previous one, collapsing
+    /// micro-segments left by precision rounding. Repeated vertices always go.
+    #[arg(long, global = true, value_name = "METRES", env = "RNJ_MIN_SEGMENT_LENGTH")]
+    min_segment_length: Option<f64>,
+
+    /// Read only the coordinate elements of this GML version: `gml:coordinates`
+    /// for 2, `gml:posList`/`gml:pos` for 3.2, all of them for 3.1. By default
+    /// every form is read.
+    #[arg(long, global = true, value_enum, env = "RNJ_GML_PROFILE")]
+    gml_profile: Option<GmlProfile>,
+
+    /// Read geometry only from the CityGML properties of this level of detail,
+    /// e.g. `lod0Network` for 0. By default each feature's lowest level is read.
+    #[arg(long, global = true, value_name = "LEVEL", value_parser = clap::value_parser!(u8).range(0..=4), env = "RNJ_LOD")]
+    lod: Option<u8>,
+
+    /// Project each distinct vertex of a file once and reuse the result for
+    /// every geometry sharing it, such as the common end of adjacent segments.
+    #[arg(long, global = true, env = "RNJ_VERTEX_CACHE")]
+    vertex_cache: bool,
+
+    /// Snap every projected position to this many decimal places, in the units
+    /// of `--crs`, so vertices shared in the source are shared in the output.
+    #[arg(long, global = true, value_name = "DECIMALS", value_parser = clap::value_parser!(u32).range(0..=15), env = "RNJ_SNAP_DECIMALS")]
+    snap_decimals: Option<u32>,
+
+    /// Built-in description of a known dataset, e.g. `hk-rdnet`. It sets the
+    /// types and required attributes the config leaves out, and conversion checks
+    /// each feature's geometry type and coded attributes against it.
+    #[arg(long, global = true, value_name = "NAME", env = "RNJ_PROFILE")]
+    profile: Option<String>,
+
+    /// Read only the features in use on this date (`YYYY-MM-DD`, optionally with a
+    /// time), by their CityGML creation and termination dates, to reproduce the
+    /// network as it was then.
+    #[arg(long, global = true, value_name = "DATE", value_parser = parse_date, env = "RNJ_AS_OF")]
+    as_of: Option<String>,
+
+    /// Split GeoJSON FeatureCollection output (`graph`, `pedestrian-network`,
+    /// `aggregate`, `qa`) into numbered parts of at most this size, e.g. `10MB`.
+    #[arg(long, global = true, value_name = "SIZE", value_parser = parse_size, env = "RNJ_MAX_FILE_SIZE")]
+    max_file_size: Option<u64>,
+
+    /// Keep only English (`en`) or Chinese (`zh`) names in the output, and use
+    /// them alone for the search index and labels. `both` keeps every name.
+    #[arg(long, global = true, value_enum, env = "RNJ_LANG")]
+    lang: Option<Language>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    convert: ConvertArgs,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Build a directed routing graph from the centerlines and write it to `output/graph`.
+    Graph(GraphExportArgs),
+    /// Find the shortest route between two points and print it as a GeoJSON Feature.
+    Route(RouteArgs),
+    /// Compute network distances from every origin to every destination and
+    /// print them as CSV or JSON.
+    Matrix(MatrixArgs),
+    /// Give every centerline (and optionally pedestrian zone) the network
+    /// distance to its nearest facility, and write them to `output/accessibility`.
+    Accessibility(AccessibilityArgs),
+    /// Look up features by street name or ID in the index written by the last conversion.
+    Search(SearchArgs),
+    /// Find the nearest named centerline to a point, or to every point in a CSV.
+    ReverseGeocode(ReverseGeocodeArgs),
+    /// Print the given centerlines as GPX tracks.
+    Gpx(GpxArgs),
+    /// Build a walkable network from pedestrian zones, footbridges, subways and
+    /// crossings and write it to `output/pedestrian_network`.
+    PedestrianNetwork(PedestrianNetworkArgs),
+    /// Sum road length and pedestrian zone area inside each polygon of a GeoJSON
+    /// file and write the polygons back with the totals as properties.
+    Aggregate(AggregateArgs),
+    /// Write a GeoTIFF grid of road length or pedestrian zone coverage per cell.
+    Rasterize(RasterizeArgs),
+    /// Print one feature, found by ID, as a GeoJSON Feature.
+    Extract(ExtractArgs),
+    /// Print features from the store kept by `--format history`: the layer as it
+    /// was at `--as-of` (or is now), or every version of one feature.
+    History(HistoryArgs),
+    /// Convert a GeoJSON FeatureCollection back into a CityGML file in the source
+    /// datasets' layout.
+    ToGml(ToGmlArgs),
+    /// Check every layer for self-intersecting features and for overlapping or
+    /// duplicate features, and write their locations to a GeoJSON file.
+    Qa(QaArgs),
+    /// Report the attributes added, removed or written in another type between
+    /// two versions of a GML dataset.
+    SchemaDiff(SchemaDiffArgs),
+    /// Convert the bundled fixture datasets and compare them with their golden
+    /// GeoJSON, to check changes to the parser and projection.
+    VerifyFixtures(VerifyFixturesArgs),
+    /// Project surveyed control points both ways between the HK80 grid and WGS84
+    /// and report how far each lands from its published coordinates.
+    CheckCrs(CheckCrsArgs),
+    /// Check the `--config` file before a long run.
+    Config(ConfigArgs),
+    /// Print a tab-completion script for a shell to stdout.
+    Completions(CompletionsArgs),
+    /// Write man pages: the main page to stdout, or a page per command into
+    /// `--out-dir`.
+    Manpage(ManpageArgs),
+    /// Serve conversions over gRPC: clients stream GML in and get features back.
+    #[cfg(feature = "grpc")]
+    ServeGrpc(ServeGrpcArgs),
+}
+
+#[cfg(feature = "grpc")]
+#[derive(Debug, Args)]
+struct ServeGrpcArgs {
+    /// Address to listen on.
+    #[arg(long, default_value = "127.0.0.1:50051")]
+    listen: std::net::SocketAddr,
+
+    /// Refuse documents larger than this many megabytes, since each is held in
+    /// memory while it is converted.
+    #[arg(long, default_value_t = 512)]
+    max_document_mb: usize,
+
+    /// Also serve Prometheus metrics at `http://<address>/metrics`.
+    #[arg(long, value_name = "ADDRESS")]
+    metrics_listen: Option<std::net::SocketAddr>,
+}
+
+#[derive(Debug, Args)]
+struct HistoryArgs {
+    /// Layer to read, e.g. `centerlines` or `pedestrian_zones`.
+    #[arg(long, default_value = "centerlines")]
+    dataset: String,
+
+    /// Print every version of the feature with this ID instead.
+    #[arg(long)]
+    id: Option<String>,
+
+    /// Directory of the store. Defaults to `history/` in the output directory.
+    #[arg(long)]
+    store: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct ExtractArgs {
+    /// ID of the feature, e.g. a centerline's `ROUTE_ID`.
+    #[arg(long)]
+    id: String,
+
+    /// Layer to look in, e.g. `centerlines` or `pedestrian_zones`.
+    #[arg(long, default_value = "centerlines")]
+    dataset: String,
+
+    /// Output coordinate system.
+    #[arg(long, value_enum, default_value_t = Crs::Wgs84)]
+    crs: Crs,
+
+    /// How to label output in a CRS other than WGS84, which is refused otherwise.
+    #[arg(long, value_enum)]
+    crs_annotation: Option<CrsAnnotation>,
+}
+
+#[derive(Debug, Args)]
+struct ToGmlArgs {
+    /// GeoJSON FeatureCollection of lines, e.g. an edited conversion output.
+    input: PathBuf,
+
+    /// Layer the features belong to, which sets the GML layout and file name.
+    #[arg(long, default_value = "centerlines")]
+    dataset: String,
+
+    /// Coordinate system of the GeoJSON. The GML is always written in HK80.
+    #[arg(long, value_enum, default_value_t = Crs::Wgs84)]
+    crs: Crs,
+
+    /// Where to write the GML. Defaults to the dataset's file name under `output/`.
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct QaArgs {
+    /// How close, in metres, two stretches must run to count as overlapping.
+    #[arg(long, default_value_t = 0.1)]
+    tolerance: f64,
+
+    /// Where to write the problem locations. Defaults to `qa.geojson` in the
+    /// output directory.
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct SchemaDiffArgs {
+    /// The earlier GML file, gzipped or not.
+    old: PathBuf,
+
+    /// The later GML file.
+    new: PathBuf,
+
+    /// Print the differences as JSON instead of one line per attribute.
+    #[arg(long)]
+    json: bool,
+
+    /// Exit with status 1 when the schemas differ, e.g. to stop a scheduled
+    /// import.
+    #[arg(long)]
+    check: bool,
+}
+
+#[derive(Debug, Args)]
+struct VerifyFixturesArgs {
+    /// Directory holding the fixture GML files and their `expected/` outputs.
+    #[arg(long, default_value = "./fixtures")]
+    dir: PathBuf,
+
+    /// Rewrite the golden files from the current output instead of checking
+    /// them, after a deliberate change to it.
+    #[arg(long)]
+    bless: bool,
+}
+
+#[derive(Debug, Args)]
+struct CheckCrsArgs {
+    /// CSV of control points with `id,easting,northing,longitude,latitude`
+    /// columns, e.g. from the Lands Department's geodetic control listings.
+    points: PathBuf,
+
+    /// Fail when any residual is larger than this many metres.
+    #[arg(long, value_name = "METRES")]
+    tolerance: Option<f64>,
+}
+
+#[derive(Debug, Args)]
+struct ConfigArgs {
+    #[command(subcommand)]
+    command: ConfigCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    /// Report every problem in the config at once, such as layer names that
+    /// match no dataset, and exit with an error if there are any.
+    Validate(ConfigValidateArgs),
+}
+
+#[derive(Debug, Args)]
+struct ConfigValidateArgs {
+    /// Also check that the attributes the config reads are in the source files
+    /// under `./input`, which reads each file once.
+    #[arg(long)]
+    input: bool,
+}
+
+#[derive(Debug, Args)]
+struct CompletionsArgs {
+    /// The shell to complete commands and flags in.
+    shell: clap_complete::Shell,
+}
+
+#[derive(Debug, Args)]
+struct ManpageArgs {
+    /// Write `road-network-json.1` and a `road-network-json-<command>.1` page
+    /// per command into this directory instead of printing the main page.
+    #[arg(long, value_name = "DIR")]
+    out_dir: Option<PathBuf>,
+}
+
+/// A surveyed point with published coordinates in both systems.
+#[derive(Debug, Deserialize)]
+struct ControlPoint {
+    id: String,
+    easting: f64,
+    northing: f64,
+    longitude: f64,
+    latitude: f64,
+}
+
+/// Where a control point's coordinates in each system project to in the other,
+/// and how many metres that is from the published position.
+#[derive(Debug, Serialize)]
+struct ControlResidual {
+    id: String,
+    projected_longitude: f64,
+    projected_latitude: f64,
+    wgs84_residual_m: f64,
+    projected_easting: f64,
+    projected_northing: f64,
+    hk80_residual_m: f64,
+}
+
+#[derive(Debug, Args)]
+struct AggregateArgs {
+    /// GeoJSON FeatureCollection of WGS84 Polygon/MultiPolygon areas.
+    areas: PathBuf,
+
+    /// Centerline attribute to break road length down by, e.g. `ROAD_TYPE`.
+    #[arg(long)]
+    type_attribute: Option<String>,
+
+    /// Where to write the enriched polygons. Defaults to `aggregate.geojson` in
+    /// the output directory.
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum RasterMeasure {
+    /// Metres of centerline in each cell.
+    Density,
+    /// Fraction of each cell covered by pedestrian zones, from 0 to 1.
+    Coverage,
+}
+
+#[derive(Debug, Args)]
+struct RasterizeArgs {
+    /// What each cell holds.
+    #[arg(long, value_enum, default_value = "density")]
+    measure: RasterMeasure,
+
+    /// Cell size in metres, on the HK80 Grid.
+    #[arg(long, value_name = "METRES", default_value_t = 100.0)]
+    resolution: f64,
+
+    /// Where to write the GeoTIFF. Defaults to `output/density.tif` or
+    /// `output/coverage.tif`.
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct PedestrianNetworkArgs {
+    /// GML file of footbridge lines.
+    #[arg(long)]
+    footbridges: Option<PathBuf>,
+
+    /// GML file of subway (underpass) lines.
+    #[arg(long)]
+    subways: Option<PathBuf>,
+
+    /// GML file of road-crossing lines.
+    #[arg(long)]
+    crossings: Option<PathBuf>,
+
+    /// Attribute holding the feature ID in the footbridge, subway and crossing files.
+    #[arg(long, default_value = "OBJECTID")]
+    id_field: String,
+
+    /// Also walk along road centerlines, as a stand-in for footways.
+    #[arg(long)]
+    include_roads: bool,
+
+    /// Largest gap, in metres, that a connector edge may bridge.
+    #[arg(long, default_value_t = 5.0)]
+    connect_tolerance: f64,
+}
+
+#[derive(Debug, Args)]
+struct GpxArgs {
+    /// Segment ID to export; repeat for several tracks.
+    #[arg(long = "route-id", required = true)]
+    route_ids: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+struct ReverseGeocodeArgs {
+    /// Position as `lon,lat`; the result is printed as JSON.
+    #[arg(long, value_parser = parse_lon_lat)]
+    point: Option<[f64; 2]>,
+
+    /// CSV with `lon,lat` columns; results are printed as CSV, one row per point.
+    #[arg(long)]
+    csv: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+struct GeocodeResult {
+    lon: f64,
+    lat: f64,
+    route_id: Option<String>,
+    name: Option<String>,
+    distance_m: Option<f64>,
+}
+
+/// Summary of a conversion run, written to `manifest.json`.
+#[derive(Debug, Serialize)]
+struct Manifest {
+    crs: String,
+    bbox_filter: Option<[f64; 4]>,
+    /// ID prefix length naming the directory of each feature file, under `--shard`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shard_prefix: Option<usize>,
+    /// The `--profile` features were checked against.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    profile: Option<&'static str>,
+    /// The `--as-of` date features were filtered by.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    as_of: Option<String>,
+    datasets: Vec<ManifestDataset>,
+    /// Source files that failed to convert under `--continue-on-failure`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    failed: Vec<ManifestFailure>,
+    /// Time spent finishing the outputs after the last dataset, under
+    /// `--perf-report`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finish_perf: Option<StageTimes>,
+}
+
+#[derive(Debug, Serialize)]
+struct ManifestFailure {
+    layer: &'static str,
+    source: String,
+    error: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ManifestDataset {
+    layer: &'static str,
+    source: String,
+    features: usize,
+    skipped: usize,
+    /// Features not in use on the `--as-of` date.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    not_current: Option<usize>,
+    /// Features without geometry at the `--lod` level of detail.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    without_lod: Option<usize>,
+    /// Features dropped by a `filter` step of `[[transforms]]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filtered: Option<usize>,
+    /// Features dropped by `--min-length-m` or `--min-area-m2`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    too_small: Option<usize>,
+    /// Features with nothing left inside `--clip-bbox` once clipped.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    clipped_out: Option<usize>,
+    /// The feature count declared in the source file's header, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    declared_features: Option<usize>,
+    /// The source file's `gml:boundedBy` envelope in the output CRS.
+    envelope: Option<[f64; 4]>,
+    /// Repeated and near-duplicate vertices dropped while cleaning.
+    vertices_removed: usize,
+    /// Attribute values set to null for not matching their configured type.
+    type_mismatches: usize,
+    /// Int and double attribute values kept as strings for not being numbers.
+    unparsed_numbers: usize,
+    /// Coordinate lists skipped for not splitting into positions of 2 or more
+    /// ordinates.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    malformed_coordinates: Option<usize>,
+    /// Features lacking each required attribute.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    missing_attributes: BTreeMap<String, usize>,
+    /// Features whose geometry isn't the type `--profile` gives the layer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unexpected_geometries: Option<usize>,
+    /// Values outside `--profile`'s code list, by attribute.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    unknown_codes: BTreeMap<String, usize>,
+    /// Outline corrections, for zone layers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rings: Option<RingCorrections>,
+    /// A hash of every feature's fingerprint, under `--fingerprint`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fingerprint: Option<String>,
+    /// Time spent in each stage of the conversion, under `--perf-report`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    perf: Option<StageTimes>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct RingCorrections {
+    /// Rings whose last position had to be added to close them.
+    closed: usize,
+    /// Rings reversed so that exteriors run counterclockwise and holes clockwise.
+    reversed: usize,
+}
+
+#[derive(Debug, Args)]
+struct SearchArgs {
+    /// Street name words (prefixes are fine) or an exact feature ID.
+    query: String,
+
+    /// Maximum number of results to print.
+    #[arg(long, default_value_t = 20)]
+    limit: usize,
+}
+
+#[derive(Debug, Args)]
+struct GraphExportArgs {
+    #[command(flatten)]
+    graph: GraphArgs,
+
+    #[arg(long, value_enum, default_value_t = GraphFormat::Geojson)]
+    format: GraphFormat,
+
+    /// Also pick this many landmarks and write their distance tables to
+    /// `landmarks.bin`, for fast repeated `route --landmarks` queries.
+    #[arg(long, value_name = "N")]
+    landmarks: Option<usize>,
+}
+
+/// Options shared by every subcommand that builds the routing graph.
+#[derive(Debug, Args)]
+struct GraphArgs {
+    /// Turn-restriction CSV with `from_edge,via_node,to_edge` columns, where edges are
+    /// segment IDs and the via node is optional.
+    #[arg(long)]
+    restrictions: Option<PathBuf>,
+
+    /// Weld segment endpoints that lie within this many metres of each other into
+    /// one node, repairing near-miss junctions in the source data.
+    #[arg(long, value_name = "METRES", default_value_t = 0.0)]
+    snap_tolerance: f64,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum GraphFormat {
+    /// `nodes.geojson` and `edges.geojson` (plus `restrictions.geojson`).
+    Geojson,
+    /// `network.osm`, an OSM XML file that `osrm-extract` can ingest directly.
+    #[value(alias = "osrm")]
+    Osm,
+    /// `nodes.csv` and `relationships.csv` for `neo4j-admin database import`.
+    Neo4j,
+}
+
+#[derive(Debug, Args)]
+struct RouteArgs {
+    #[command(flatten)]
+    graph: GraphArgs,
+
+    /// Start position as `lon,lat`; snapped to the nearest graph node.
+    #[arg(long, value_parser = parse_lon_lat)]
+    from: [f64; 2],
+
+    /// End position as `lon,lat`; snapped to the nearest graph node.
+    #[arg(long, value_parser = parse_lon_lat)]
+    to: [f64; 2],
+
+    /// Print the route as a GPX track instead of GeoJSON.
+    #[arg(long)]
+    gpx: bool,
+
+    /// Landmark tables written by `graph --landmarks` for the same data and
+    /// options, to search with A* instead of Dijkstra.
+    #[arg(long, value_name = "PATH")]
+    landmarks: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct MatrixArgs {
+    #[command(flatten)]
+    graph: GraphArgs,
+
+    /// CSV with `lon,lat` columns; each point joins the network at the nearest graph node.
+    #[arg(long)]
+    origins: PathBuf,
+
+    /// CSV with `lon,lat` columns. Defaults to the origins.
+    #[arg(long)]
+    destinations: Option<PathBuf>,
+
+    /// Also give travel times at this speed in km/h, as `duration_s`.
+    #[arg(long, value_name = "KMH")]
+    speed: Option<f64>,
+
+    #[arg(long, value_enum, default_value_t = MatrixFormat::Csv)]
+    format: MatrixFormat,
+}
+
+#[derive(Debug, Args)]
+struct AccessibilityArgs {
+    #[command(flatten)]
+    graph: GraphArgs,
+
+    /// CSV with `lon,lat` columns of the facilities, e.g. MTR station exits.
+    #[arg(long)]
+    facilities: PathBuf,
+
+    /// Treat every road as walkable both ways, ignoring one-way and closed roads.
+    #[arg(long)]
+    walking: bool,
+
+    /// Also give each pedestrian zone the distance from its centroid.
+    #[arg(long)]
+    zones: bool,
+
+    /// Property the distance in metres is written to.
+    #[arg(long, default_value = "facility_distance_m")]
+    property: String,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum MatrixFormat {
+    /// One `origin,destination,distance_m` row per pair, with 0-based point
+    /// indices; unreachable pairs have an empty distance.
+    Csv,
+    /// `distances` (and `durations`) as arrays of rows, one row per origin, with
+    /// `null` for unreachable pairs.
+    Json,
+}
+
+#[derive(Serialize)]
+struct MatrixRow {
+    origin: usize,
+    destination: usize,
+    distance_m: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_s: Option<Option<f64>>,
+}
+
+#[derive(Serialize)]
+struct Matrix {
+    origins: Vec<[f64; 2]>,
+    destinations: Vec<[f64; 2]>,
+    distances: Vec<Vec<Option<f64>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    durations: Option<Vec<Vec<Option<f64>>>>,
+}
+
+/// A size such as `10MB`, `512KB`, `2GB` or a plain number of bytes. Units are
+/// powers of 1024.
+fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let factor: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        _ => return Err(format!("expected a size such as `10MB`, got `{}`", value)),
+    };
+    match number.parse::<f64>() {
+        Ok(number) if number > 0.0 => Ok((number * factor as f64) as u64),
+        _ => Err(format!("expected a size such as `10MB`, got `{}`", value)),
+    }
+}
+
+fn parse_date(value: &str) -> Result<String, String> {
+    match AttributeType::Date.parse(value.trim()) {
+        Some(PropertyValue::String(date)) => Ok(date),
+        _ => Err(format!("expected a date such as `2023-05-01`, got `{}`", value)),
+    }
+}
+
+fn parse_lon_lat(value: &str) -> Result<[f64; 2], String> {
+    let parts: Vec<f64> = value
+        .split(',')
+        .map(|part| part.trim().parse::<f64>())
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    match parts[..] {
+        [lon, lat] => Ok([lon, lat]),
+        _ => Err(format!("expected `lon,lat`, got `{}`", value)),
+    }
+}
+
+fn parse_bbox(value: &str) -> Result<[f64; 4], String> {
+    let parts: Vec<f64> = value
+        .split(',')
+        .map(|part| part.trim().parse::<f64>())
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    match parts[..] {
+        [min_x, min_y, max_x, max_y] if min_x <= max_x && min_y <= max_y => Ok([min_x, min_y, max_x, max_y]),
+        _ => Err(format!("expected `min_x,min_y,max_x,max_y`, got `{}`", value)),
+    }
+}
+
+fn parse_fraction(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        Ok(_) => Err(format!("expected a fraction between 0 and 1, got `{}`", value)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_positive(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(number) if number.is_finite() && number > 0.0 => Ok(number),
+        Ok(_) => Err(format!("expected a number greater than 0, got `{}`", value)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Options for the default conversion run.
+#[derive(Debug, Args)]
+struct ConvertArgs {
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Files, env = "RNJ_FORMAT")]
+    format: OutputFormat,
+
+    /// Output coordinate system. Defaults to `hk80` for DXF and CityJSON and `wgs84` otherwise.
+    #[arg(long, value_enum, env = "RNJ_TARGET_CRS")]
+    crs: Option<Crs>,
+
+    /// How to label GeoJSON in a CRS other than WGS84 (`files` only). Such output
+    /// is refused without one, as GeoJSON readers would take it for WGS84.
+    #[arg(long, value_enum)]
+    crs_annotation: Option<CrsAnnotation>,
+
+    /// Stream the output to stdout instead of the output directory (`arrow` only).
+    #[arg(long)]
+    stdout: bool,
+
+    /// Database file created by `--format duckdb`. Defaults to `roads.duckdb` in
+    /// the output directory.
+    #[arg(long)]
+    database: Option<String>,
+
+    /// POST features to this URL in batches instead of writing them out.
+    #[cfg(feature = "http")]
+    #[arg(long, value_name = "URL", conflicts_with = "format")]
+    post_url: Option<String>,
+
+    /// Features per request with `--post-url`.
+    #[cfg(feature = "http")]
+    #[arg(long, default_value_t = 500)]
+    post_batch_size: usize,
+
+    /// Requests in flight at once with `--post-url`.
+    #[cfg(feature = "http")]
+    #[arg(long, default_value_t = 4)]
+    post_concurrency: usize,
+
+    /// Retries of a request that fails with a network error, 429 or 5xx, with
+    /// exponential backoff.
+    #[cfg(feature = "http")]
+    #[arg(long, default_value_t = 5)]
+    post_retries: u32,
+
+    /// Kafka bootstrap servers for `--format kafka`.
+    #[cfg(feature = "kafka")]
+    #[arg(long, default_value = "localhost:9092")]
+    kafka_brokers: String,
+
+    /// Prefix of the Kafka topic each layer is published to, e.g.
+    /// `road_network.centerlines`.
+    #[cfg(feature = "kafka")]
+    #[arg(long, default_value = "road_network.")]
+    kafka_topic_prefix: String,
+
+    /// Also write a companion point dataset (`<dataset>_points`) for label placement.
+    /// Centerlines always use their midpoint; this selects the method for zones.
+    #[arg(long, value_enum)]
+    points: Option<PointMethod>,
+
+    /// Also write the start and end node of every centerline segment, with its
+    /// exit/entry bearing, to `<dataset>_endpoints`.
+    #[arg(long)]
+    endpoints: bool,
+
+    /// Extract the attribute table only: skip reading and projecting geometry and
+    /// drop the geometry column (`csv` and `parquet` only).
+    #[arg(long, conflicts_with_all = ["crs", "points", "endpoints"])]
+    no_geometry: bool,
+
+    /// Keep only features intersecting `min_x,min_y,max_x,max_y`, given in the
+    /// output CRS. Features with a `gml:boundedBy` envelope are tested on that
+    /// alone, without reading their geometry.
+    #[arg(long, value_parser = parse_bbox, allow_hyphen_values = true)]
+    bbox_filter: Option<[f64; 4]>,
+
+    /// Like `--bbox-filter`, but also cut geometries at the box edges. Lines that
+    /// leave and re-enter the box become MultiLineStrings.
+    #[arg(long, value_parser = parse_bbox, allow_hyphen_values = true, conflicts_with = "bbox_filter")]
+    clip_bbox: Option<[f64; 4]>,
+
+    /// Stop after this many features per dataset.
+    #[arg(long, value_name = "N")]
+    limit: Option<usize>,
+
+    /// Convert only this fraction of features, e.g. `0.01`. Features are picked by
+    /// a hash of their ID, so repeated runs produce the same sample.
+    #[arg(long, value_name = "FRACTION", value_parser = parse_fraction)]
+    sample: Option<f64>,
+
+    /// Resolve the cross-layer references configured under `[[links]]` into a
+    /// `links` property and `links.csv`.
+    #[arg(long)]
+    links: bool,
+
+    /// GeoJSON of boundary polygons, in the output CRS. Each feature is assigned to
+    /// the polygon containing its label point and written under
+    /// `<output>/<district>/`; features outside every polygon go to `unassigned/`.
+    #[arg(long, requires = "split_key", conflicts_with = "no_geometry")]
+    split_by: Option<PathBuf>,
+
+    /// Property of the `--split-by` polygons that names each district.
+    #[arg(long, requires = "split_by")]
+    split_key: Option<String>,
+
+    /// GeoJSON of named areas, in the output CRS. Each feature is clipped to the
+    /// bounding box of every area it meets and written under `<output>/<area>/`,
+    /// as `--clip-bbox` would for each area, in a single pass.
+    #[arg(
+        long,
+        requires = "area_key",
+        conflicts_with_all = ["no_geometry", "split_by", "bbox_filter", "clip_bbox"]
+    )]
+    clip_areas: Option<PathBuf>,
+
+    /// Property of the `--clip-areas` polygons that names each area.
+    #[arg(long, requires = "clip_areas")]
+    area_key: Option<String>,
+
+    /// Split 3D centerlines at vertices where the gradient changes by more than
+    /// this many percentage points. Pieces get `<id>_<n>` IDs and a `parent_id`.
+    #[arg(long, value_name = "PCT", conflicts_with = "no_geometry")]
+    split_gradient: Option<f64>,
+
+    /// Split centerlines longer than this many metres into equal pieces, with
+    /// `<id>_<n>` IDs and a `parent_id`, cutting at interpolated vertices.
+    #[arg(long, value_name = "METRES", value_parser = parse_positive, conflicts_with = "no_geometry")]
+    max_segment_length: Option<f64>,
+
+    /// Drop line features shorter than this many metres, such as stub segments.
+    #[arg(long, value_name = "METRES", conflicts_with = "no_geometry")]
+    min_length_m: Option<f64>,
+
+    /// Drop area features smaller than this many square metres, holes excluded,
+    /// such as slivers.
+    #[arg(long, value_name = "M2", conflicts_with = "no_geometry")]
+    min_area_m2: Option<f64>,
+
+    /// Put each feature file under a directory named after the first N
+    /// characters of its ID, e.g. `centerlines/10/1001.json` for N = 2, so large
+    /// layers don't put every file in one directory (`files` only).
+    #[arg(long, value_name = "N")]
+    shard: Option<usize>,
+
+    /// Threads writing feature files (`files` only). Defaults to one per CPU.
+    #[arg(long, value_name = "N")]
+    write_threads: Option<usize>,
+
+    /// Store feature files under `objects/` by content hash, with an ID to hash
+    /// index per layer under `index/`, so repeated runs into the same output
+    /// directory share unchanged features (`files` only).
+    #[arg(long)]
+    content_addressed: bool,
+
+    /// Write line and zone coordinates as Google encoded polylines with 5 or 6
+    /// decimal places, for compact API payloads (`files` only).
+    #[arg(long, value_name = "PRECISION", value_parser = clap::value_parser!(u32).range(5..=6))]
+    polyline: Option<u32>,
+
+    /// Write each feature whole, or only its geometry or properties, or both
+    /// in separate `<layer>/geometry/` and `<layer>/props/` trees so clients can
+    /// fetch attributes lazily (`files` only).
+    #[arg(long, value_enum, default_value_t = FileParts::Whole)]
+    file_parts: FileParts,
+
+    /// Fail instead of warning when a source file holds a different number of
+    /// features than its header declares, as a truncated download would.
+    #[arg(long)]
+    strict_counts: bool,
+
+    /// Read each source file twice: first to find the element types every
+    /// attribute is written in, then to convert with one type per attribute.
+    #[arg(long)]
+    consistent_types: bool,
+
+    /// Add a `fingerprint` property to every feature: a hash of its geometry and
+    /// properties that only changes when they do. Each layer's fingerprint, over
+    /// all of its features, goes to `manifest.json`.
+    #[arg(long)]
+    fingerprint: bool,
+
+    /// Count each source file's features up front with a quick byte scan, to
+    /// show progress as a percentage. Each file is scanned while the one before
+    /// it converts.
+    #[arg(long)]
+    prescan: bool,
+
+    /// Write `tiles.csv`, listing every z/x/y web map tile at this zoom that any
+    /// converted feature touches, with how many do.
+    #[arg(long, value_name = "ZOOM", value_parser = clap::value_parser!(u8).range(0..=tiles::MAX_ZOOM as i64), conflicts_with = "no_geometry")]
+    tile_report: Option<u8>,
+
+    /// Features per page with `--format pages`. Defaults to 1000.
+    #[arg(long, value_name = "N")]
+    chunk_size: Option<usize>,
+
+    /// Write `street_names.json`, mapping every normalized street name to the
+    /// IDs of the features named it, by layer.
+    #[arg(long)]
+    street_names: bool,
+
+    /// Carry on with the remaining source files when one fails to convert,
+    /// listing the failures under `failed` in `manifest.json` and exiting with
+    /// status 3. Features the failed file gave before its error are kept.
+    #[arg(long)]
+    continue_on_failure: bool,
+
+    /// Time each stage of every dataset's conversion (reading, XML parsing,
+    /// attribute parsing, projection, serialization and IO) and add the
+    /// breakdown to `manifest.json`.
+    #[arg(long)]
+    perf_report: bool,
+
+    /// Show each dataset's progress, warnings and throughput in a terminal UI
+    /// with a live log, where `p` pauses, `s` skips the current dataset and `q`
+    /// stops the run.
+    #[cfg(feature = "tui")]
+    #[arg(long, conflicts_with = "stdout")]
+    tui: bool,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    /// One GeoJSON file per feature under `<layer>/<id>.json`.
+    Files,
+    /// A single kepler.gl map file (`keplergl.json`) with one layer per dataset.
+    Keplergl,
+    /// A single AutoCAD R12 DXF file (`roads.dxf`) with one CAD layer per dataset.
+    Dxf,
+    /// A single CityJSON file (`roads.city.json`) of GenericCityObjects.
+    Cityjson,
+    /// Geometry only: `<layer>.wkt` with an `id<TAB>WKT` line per feature.
+    Wkt,
+    /// Geometry only: `<layer>.wkb` with an `id<TAB>hex WKB` line per feature.
+    Wkb,
+    /// A DuckDB database (`--database`) with a spatial GEOMETRY table per layer.
+    Duckdb,
+    /// `<layer>.csv` with a WKT `geometry` column and one column per attribute.
+    Csv,
+    /// `<layer>.pbf` per layer: a Geobuf FeatureCollection, compact protobuf GeoJSON.
+    Geobuf,
+    /// Numbered FeatureCollections of `--chunk-size` features per layer,
+    /// `<layer>-0001.geojson` onwards, indexed in `pages.json`.
+    Pages,
+    /// Every version of every feature across runs, in an append-only change log
+    /// under `history/`.
+    History,
+    /// `<layer>.parquet` with GeoArrow WKB geometry and typed attribute columns.
+    #[cfg(feature = "parquet")]
+    Parquet,
+    /// A single Arrow IPC stream (`features.arrows`) with GeoArrow WKB geometry.
+    #[cfg(feature = "arrow")]
+    Arrow,
+    /// A Kafka message per feature on `<--kafka-topic-prefix><layer>`, keyed by ID.
+    #[cfg(feature = "kafka")]
+    Kafka,
+}
+
+impl OutputFormat {
+    fn default_crs(self) -> Crs {
+        match self {
+            OutputFormat::Dxf | OutputFormat::Cityjson => Crs::Hk80,
+            _ => Crs::Wgs84,
+        }
+    }
+
+    fn supports_stdout(self) -> bool {
+        match self {
+            #[cfg(feature = "arrow")]
+            OutputFormat::Arrow => true,
+            _ => false,
+        }
+    }
+
+    fn supports_no_geometry(self) -> bool {
+        match self {
+            OutputFormat::Csv => true,
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the format writes into a directory, so that `--split-by` can give
+    /// each district its own.
+    fn supports_split(self) -> bool {
+        match self {
+            OutputFormat::Duckdb => false,
+            #[cfg(feature = "kafka")]
+            OutputFormat::Kafka => false,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum PointMethod {
+    /// Area-weighted centroid (may fall outside concave zones).
+    Centroid,
+    /// A point guaranteed to lie inside the zone.
+    PointOnSurface,
+}
+
+fn main() -> Result<()> {
+    // Variables already set in the environment win over the `.env` file
+    if let Err(error) = dotenvy::dotenv()
+        && !error.not_found()
+    {
+        return Err(error).context("Failed to read .env");
+    }
+    let cli = Cli::parse();
+    DIRS.set((cli.input_dir.clone(), cli.output_dir.clone())).expect("directories are set once");
+    let mut config = Config::load(cli.config.as_deref())?;
+    if cli.swap_axes.is_some() {
+        config.input.swap_axes = cli.swap_axes;
+    }
+    if let Some(min_segment_length) = cli.min_segment_length {
+        config.input.min_segment_length = min_segment_length;
+    }
+    if let Some(profile) = cli.gml_profile {
+        config.input.gml_profile = profile;
+    }
+    if cli.lod.is_some() {
+        config.input.lod = cli.lod;
+    }
+    if cli.vertex_cache {
+        config.input.vertex_cache = true;
+    }
+    if cli.snap_decimals.is_some() {
+        config.input.snap_decimals = cli.snap_decimals;
+    }
+    if cli.profile.is_some() {
+        config.input.profile = cli.profile;
+    }
+    if cli.as_of.is_some() {
+        config.input.as_of = cli.as_of;
+    }
+    if let Some(as_of) = &config.input.as_of {
+        config.input.as_of = Some(parse_date(as_of).map_err(anyhow::Error::msg)?);
+    }
+    if let Some(name) = &config.input.profile {
+        profile::find(name)?.apply(&mut config);
+    }
+    if cli.lang.is_some() {
+        config.names.lang = cli.lang;
+    }
+    config.apply_language();
+
+    match cli.command {
+        Some(Command::Graph(args)) => build_graph(&config, &args, cli.max_file_size),
+        Some(Command::Route(args)) => route(&config, &args),
+        Some(Command::Matrix(args)) => matrix(&config, &args),
+        Some(Command::Accessibility(args)) => accessibility(&config, &args, cli.max_file_size),
+        Some(Command::Search(args)) => search(&args),
+        Some(Command::ReverseGeocode(args)) => reverse_geocode(&config, &args),
+        Some(Command::Gpx(args)) => export_gpx(&config, &args),
+        Some(Command::PedestrianNetwork(args)) => pedestrian_network(&config, &args, cli.max_file_size),
+        Some(Command::Aggregate(args)) => aggregate(&config, &args, cli.max_file_size),
+        Some(Command::Rasterize(args)) => rasterize(&config, &args),
+        Some(Command::Extract(args)) => extract(&config, &args),
+        Some(Command::History(args)) => print_history(&config, &args),
+        Some(Command::ToGml(args)) => to_gml(&args),
+        Some(Command::Qa(args)) => qa(&config, &args, cli.max_file_size),
+        Some(Command::SchemaDiff(args)) => schema_diff(&args),
+        Some(Command::VerifyFixtures(args)) => verify_fixtures(&args),
+        Some(Command::CheckCrs(args)) => check_crs(&args),
+        Some(Command::Config(ConfigArgs { command: ConfigCommand::Validate(args) })) => {
+            validate_config(&config, cli.config.as_deref(), &args)
+        }
+        Some(Command::Completions(args)) => completions(&args),
+        Some(Command::Manpage(args)) => manpage(&args),
+        #[cfg(feature = "grpc")]
+        Some(Command::ServeGrpc(args)) => serve_grpc(&args),
+        None => convert(&config, &cli.convert),
+    }
+}
+
+fn convert(config: &Config, args: &ConvertArgs) -> Result<()> {
+    #[cfg(feature = "tui")]
+    let converted = if args.tui {
+        road_network_json::tui::run(|| convert_datasets(config, args))?
+    } else {
+        convert_datasets(config, args)?
+    };
+    #[cfg(not(feature = "tui"))]
+    let converted = convert_datasets(config, args)?;
+    if !converted {
+        std::process::exit(PARTIAL_FAILURE_EXIT_CODE);
+    }
+    Ok(())
+}
+
+/// Converts every source file, returning whether they all converted in full.
+fn convert_datasets(config: &Config, args: &ConvertArgs) -> Result<bool> {
+    let data_dir = data_dir();
+    let output_dir = output_dir();
+
+    let target_crs = args.crs.unwrap_or(args.format.default_crs());
+    if args.stdout && !args.format.supports_stdout() {
+        anyhow::bail!("--stdout is only supported for --format arrow");
+    }
+    if args.no_geometry && !args.format.supports_no_geometry() {
+        anyhow::bail!("--no-geometry is only supported for --format csv and parquet");
+    }
+
+    let crs_member = match args.format {
+        OutputFormat::Files => crs_member(target_crs, args.crs_annotation)?,
+        _ if args.crs_annotation.is_some() => anyhow::bail!("--crs-annotation is only supported for --format files"),
+        _ => None,
+    };
+    if !matches!(args.format, OutputFormat::Files)
+        && (args.shard.is_some()
+            || args.write_threads.is_some()
+            || args.content_addressed
+            || args.polyline.is_some()
+            || args.file_parts != FileParts::Whole)
+    {
+        anyhow::bail!(
+            "--shard, --write-threads, --content-addressed, --polyline and --file-parts are only supported for --format files"
+        );
+    }
+    if args.chunk_size.is_some() && !matches!(args.format, OutputFormat::Pages) {
+        anyhow::bail!("--chunk-size is only supported for --format pages");
+    }
+    if args.chunk_size == Some(0) {
+        anyhow::bail!("--chunk-size must be at least 1");
+    }
+    if args.content_addressed && args.shard.is_some() {
+        anyhow::bail!("--content-addressed already spreads files over directories; drop --shard");
+    }
+    if args.shard == Some(0) || args.write_threads == Some(0) {
+        anyhow::bail!("--shard and --write-threads must be at least 1");
+    }
+    let file_options = FileOptions {
+        crs_member,
+        templates: config.templates.clone(),
+        shard_prefix: args.shard,
+        threads: args
+            .write_threads
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(4, |threads| threads.get())),
+        content_addressed: args.content_addressed,
+        polyline: args.polyline,
+        parts: args.file_parts,
+    };
+
+    // A partial run would record the features it left out as removed
+    if matches!(args.format, OutputFormat::History) {
+        let partial = [
+            ("--limit", args.limit.is_some()),
+            ("--sample", args.sample.is_some()),
+            ("--bbox-filter", args.bbox_filter.is_some()),
+            ("--clip-bbox", args.clip_bbox.is_some()),
+            ("--clip-areas", args.clip_areas.is_some()),
+            ("--points", args.points.is_some()),
+            ("--endpoints", args.endpoints),
+            ("--continue-on-failure", args.continue_on_failure),
+            ("--as-of", config.input.as_of.is_some()),
+            ("--lod", config.input.lod.is_some()),
+            ("--min-length-m", args.min_length_m.is_some()),
+            ("--min-area-m2", args.min_area_m2.is_some()),
+            (
+                "a `filter` step in [[transforms]]",
+                config.transforms.iter().any(|transform| matches!(transform.step, TransformStep::Filter { .. })),
+            ),
+        ];
+        let used: Vec<&str> = partial.iter().filter(|(_, used)| *used).map(|(option, _)| *option).collect();
+        if !used.is_empty() {
+            anyhow::bail!("--format history records whole datasets, so it can't be combined with {}", used.join(", "));
+        }
+    }
+    #[cfg(feature = "tui")]
+    if args.tui && matches!(args.format, OutputFormat::History) {
+        anyhow::bail!("--format history records whole datasets, so it can't be run under --tui, which can skip one");
+    }
+    if (args.split_by.is_some() || args.clip_areas.is_some()) && (!args.format.supports_split() || args.stdout) {
+        anyhow::bail!("--split-by and --clip-areas need an output format that writes to a directory");
+    }
+    let districts = match (&args.split_by, &args.split_key) {
+        (Some(path), Some(key)) => Some(Districts::load(path, key)?),
+        _ => None,
+    };
+    let areas = match (&args.clip_areas, &args.area_key) {
+        (Some(path), Some(key)) => Some(Districts::load(path, key)?),
+        _ => None,
+    };
+
+    // Create output directory if it doesn't exist
+    fs::create_dir_all(paths::long_path(output_dir)).context("Failed to create output directory")?;
+    // Made up front to report a bad configuration; Kafka output is never split
+    #[cfg(feature = "kafka")]
+    let mut kafka = match args.format {
+        OutputFormat::Kafka => Some(KafkaSink::new(&args.kafka_brokers, &args.kafka_topic_prefix)?),
+        _ => None,
+    };
+    #[cfg(feature = "http")]
+    if args.post_url.is_some() && (args.split_by.is_some() || args.clip_areas.is_some()) {
+        anyhow::bail!("--split-by and --clip-areas can't be used with --post-url");
+    }
+    let database = args.database.clone().unwrap_or_else(|| format!("{}/roads.duckdb", output_dir));
+    let mut sinks = SinkSet::new(|dir: &str| -> Box<dyn Sink> {
+        #[cfg(feature = "http")]
+        if let Some(url) = &args.post_url {
+            return Box::new(HttpSink::new(PostOptions {
+                url: url.clone(),
+                batch_size: args.post_batch_size,
+                concurrency: args.post_concurrency,
+                retries: args.post_retries,
+            }));
+        }
+        match args.format {
+            OutputFormat::Files => Box::new(FileSink::new(dir, file_options.clone())),
+            OutputFormat::Keplergl => Box::new(KeplerGlSink::new(dir)),
+            OutputFormat::Dxf => Box::new(DxfSink::new(dir)),
+            OutputFormat::Cityjson => Box::new(CityJsonSink::new(dir, target_crs)),
+            OutputFormat::Wkt => Box::new(WellKnownSink::new(dir, Encoding::Wkt)),
+            OutputFormat::Wkb => Box::new(WellKnownSink::new(dir, Encoding::WkbHex)),
+            OutputFormat::Duckdb => Box::new(DuckDbSink::new(dir, &database)),
+            OutputFormat::Csv => Box::new(CsvSink::new(dir, !args.no_geometry)),
+            OutputFormat::Geobuf => Box::new(GeobufSink::new(dir, target_crs)),
+            OutputFormat::Pages => Box::new(PagesSink::new(dir, args.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE))),
+            OutputFormat::History => Box::new(HistorySink::new(dir)),
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => Box::new(ParquetSink::new(dir, (!args.no_geometry).then_some(target_crs))),
+            #[cfg(feature = "arrow")]
+            OutputFormat::Arrow => Box::new(ArrowSink::new((!args.stdout).then_some(dir), target_crs)),
+            #[cfg(feature = "kafka")]
+            OutputFormat::Kafka => Box::new(kafka.take().expect("Kafka output is never split")),
+        }
+    });
+    if districts.is_none() && areas.is_none() {
+        sinks.sink_for(output_dir)?;
+    }
+    let options = ParseOptions {
+        target_crs,
+        geometry: !args.no_geometry,
+        // Features outside every area are skipped before their geometry is read
+        bbox: args.bbox_filter.or(args.clip_bbox).or_else(|| {
+            areas.as_ref().map(|areas| {
+                areas.bboxes().fold(
+                    [f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY],
+                    |b, (_, area)| [b[0].min(area[0]), b[1].min(area[1]), b[2].max(area[2]), b[3].max(area[3])],
+                )
+            })
+        }),
+        limit: args.limit,
+        sample: args.sample,
+        ..config.input.parse_options()
+    };
+
+    eprintln!("Parsing GML files...");
+
+    let mut links = args.links.then(|| LinkResolver::new(&config.links, &config.input.ids));
+    if let Some(links) = &mut links {
+        collect_link_targets(config, links, data_dir)?;
+    }
+
+    for layer in config.datasets.keys() {
+        if !DATASETS.iter().any(|dataset| dataset.output_subdir == layer) {
+            eprintln!("Warning: [datasets.{}] in the config matches no dataset", layer);
+        }
+    }
+
+    let profile = config.input.profile.as_deref().map(profile::find).transpose()?;
+    let transforms = TransformChain::new(&config.transforms);
+    let mut index = SearchIndex::default();
+    let mut street_names = args.street_names.then(StreetNames::default);
+    let mut tile_report = args.tile_report.map(|zoom| TileReport::new(zoom, target_crs));
+    let mut manifest = Manifest {
+        crs: format!("EPSG:{}", target_crs.epsg()),
+        bbox_filter: options.bbox,
+        shard_prefix: args.shard,
+        profile: profile.map(|profile| profile.name),
+        as_of: options.as_of.clone(),
+        datasets: Vec::new(),
+        failed: Vec::new(),
+        finish_perf: None,
+    };
+    let files = input_files(data_dir)?;
+    let mut prescanner = args
+        .prescan
+        .then(|| Prescanner::new(files.iter().map(|(file_name, _)| format!("{}/{}", data_dir, file_name)).collect()));
+    if args.perf_report {
+        perf::enable();
+    }
+    for (file_name, dataset) in files {
+        let path = format!("{}/{}", data_dir, file_name);
+        eprintln!("Processing {}...", file_name);
+        // Time since the previous dataset, e.g. writing its manifest entry, isn't its own
+        perf::take();
+        let mut options = options.clone();
+        if let Some(prescanner) = &mut prescanner {
+            let objects = prescanner.next_count()?;
+            options.expected_objects = Some(objects);
+            if districts.is_none() && areas.is_none() {
+                sinks.sink_for(output_dir)?.reserve(dataset.output_subdir, objects);
+            }
+        }
+        #[cfg(feature = "tui")]
+        road_network_json::tui::start_dataset(&file_name, options.expected_objects);
+        if args.consistent_types
+            && let Err(error) = settle_attribute_types(&path, &mut options.attributes)
+        {
+            dataset_failed(&mut manifest, args, dataset, file_name, error)?;
+            continue;
+        }
+        let mut rings = RingCorrections::default();
+        let required = config.datasets.get(dataset.output_subdir).map(|d| d.required.as_slice()).unwrap_or_default();
+        let mut missing_attributes: BTreeMap<String, usize> = BTreeMap::new();
+        let mut features_missing = 0;
+        let mut transformed_out = 0;
+        let mut too_small = 0;
+        let mut clipped_out = 0;
+        let layer_profile = profile.and_then(|profile| profile.layer_for(dataset));
+        let mut unexpected_geometries = 0;
+        let mut unknown_codes: BTreeMap<String, usize> = BTreeMap::new();
+        let mut fingerprints: BTreeMap<String, String> = BTreeMap::new();
+        let parsed = parse_gml_file(&path, dataset.id_field, &options, |id, mut feature| {
+            #[cfg(feature = "tui")]
+            road_network_json::tui::checkpoint()?;
+            if dataset.kind == DatasetKind::Zone {
+                let polygons: Vec<&mut Vec<Vec<Vec<f64>>>> = match &mut feature.geometry {
+                    Geometry::Polygon(polygon) => vec![polygon],
+                    Geometry::MultiPolygon(polygons) => polygons.iter_mut().collect(),
+                    _ => Vec::new(),
+                };
+                for polygon in polygons {
+                    for (i, ring) in polygon.iter_mut().enumerate() {
+                        let (closed, reversed) = geometry::normalize_ring(ring, i == 0);
+                        rings.closed += usize::from(closed);
+                        rings.reversed += usize::from(reversed);
+                    }
+                }
+            }
+            let mut feature = match args.clip_bbox {
+                Some(bbox) => match clip_feature(feature, &bbox) {
+                    Some(clipped) => clipped,
+                    None => {
+                        clipped_out += 1;
+                        return Ok(());
+                    }
+                },
+                None => feature,
+            };
+            if below_minimum_size(&feature.geometry, args, target_crs) {
+                too_small += 1;
+                return Ok(());
+            }
+            let missing: Vec<&str> = required
+                .iter()
+                .filter(|attribute| match feature.properties.get(*attribute) {
+                    None | Some(PropertyValue::Null) => true,
+                    Some(PropertyValue::String(value)) => value.is_empty(),
+                    Some(_) => false,
+                })
+                .map(String::as_str)
+                .collect();
+            if !missing.is_empty() {
+                features_missing += 1;
+                if features_missing <= MAX_MISSING_WARNINGS {
+                    eprintln!("  Warning: {} {} lacks {}", dataset.output_subdir, id, missing.join(", "));
+                }
+                for attribute in missing {
+                    *missing_attributes.entry(attribute.to_string()).or_default() += 1;
+                }
+            }
+            if let Some(layer) = layer_profile {
+                if options.geometry && !layer.geometry_matches(&feature.geometry) {
+                    unexpected_geometries += 1;
+                    if unexpected_geometries <= MAX_MISSING_WARNINGS {
+                        eprintln!("  Warning: {} {} is not a {}", dataset.output_subdir, id, layer.geometry.name());
+                    }
+                }
+                for (attribute, value) in layer.unknown_codes(&feature.properties) {
+                    let count = unknown_codes.entry(attribute.to_string()).or_default();
+                    *count += 1;
+                    if *count <= MAX_MISSING_WARNINGS {
+                        eprintln!("  Warning: {} {} has unknown {} `{}`", dataset.output_subdir, id, attribute, value);
+                    }
+                }
+            }
+            if let Some(links) = &mut links {
+                links.resolve(dataset.output_subdir, &id, &mut feature);
+            }
+            if !transforms.apply(dataset.output_subdir, &id, &mut feature) {
+                transformed_out += 1;
+                return Ok(());
+            }
+            let mut pieces = vec![(id, feature)];
+            if dataset.kind == DatasetKind::Line {
+                let distance = target_crs.distance_fn();
+                if let Some(pct) = args.split_gradient {
+                    pieces = split_features(pieces, |coords| {
+                        geometry::split_at_vertices(coords, &geometry::gradient_breaks(coords, distance, pct / 100.0))
+                    });
+                }
+                if let Some(max_length) = args.max_segment_length {
+                    pieces = split_features(pieces, |coords| geometry::subdivide_line(coords, distance, max_length));
+                    // Cuts are interpolated, so put them on the grid too; each is
+                    // shared by the pieces either side of it
+                    if let Some(decimals) = config.input.snap_decimals {
+                        for (_, piece) in &mut pieces {
+                            for position in piece.geometry.positions_mut() {
+                                geometry::snap(position, decimals);
+                            }
+                        }
+                    }
+                }
+            }
+            for (id, mut feature) in pieces {
+                if dataset.kind == DatasetKind::Line {
+                    add_gradient_properties(&mut feature, target_crs);
+                }
+                if args.fingerprint {
+                    let fingerprint = fingerprint::fingerprint(&feature);
+                    feature.properties.insert(fingerprint::PROPERTY.to_string(), PropertyValue::String(fingerprint.clone()));
+                    fingerprints.insert(id.clone(), fingerprint);
+                }
+                index.add(dataset.output_subdir, &id, &feature, &config.search.name_attributes);
+                if let Some(street_names) = &mut street_names {
+                    street_names.add(dataset.output_subdir, &id, &feature, &config.search.name_attributes);
+                }
+                if let Some(tile_report) = &mut tile_report {
+                    tile_report.add(&feature.geometry);
+                }
+                if let Some(areas) = &areas {
+                    let Some(bbox) = feature.geometry.bbox() else {
+                        continue;
+                    };
+                    for (area, area_bbox) in areas.bboxes() {
+                        if !geometry::bbox_intersects(&bbox, area_bbox) {
+                            continue;
+                        }
+                        if let Some(clipped) = clip_feature(feature.clone(), area_bbox) {
+                            let dir = format!("{}/{}", output_dir, paths::file_name(area));
+                            write_dataset_feature(sinks.sink_for(&dir)?, dataset, args, target_crs, &id, &clipped)?;
+                        }
+                    }
+                    continue;
+                }
+                let dir = match &districts {
+                    Some(districts) => {
+                        let district = label_point(&feature, dataset.kind, PointMethod::PointOnSurface, target_crs)
+                            .and_then(|point| districts.locate(point.geometry.positions()[0]).map(str::to_string))
+                            .unwrap_or_else(|| "unassigned".to_string());
+                        format!("{}/{}", output_dir, paths::file_name(&district))
+                    }
+                    None => output_dir.to_string(),
+                };
+                write_dataset_feature(sinks.sink_for(&dir)?, dataset, args, options.target_crs, &id, &feature)?;
+            }
+            Ok(())
+        });
+        let summary = match parsed {
+            Ok(summary) => summary,
+            #[cfg(feature = "tui")]
+            Err(error) if error.downcast_ref::<road_network_json::tui::Skipped>().is_some() => {
+                // Recorded whether or not --continue-on-failure is given, as the user asked for it
+                eprintln!("  Skipped the rest of {}", file_name);
+                manifest.failed.push(ManifestFailure {
+                    layer: dataset.output_subdir,
+                    source: file_name,
+                    error: error.to_string(),
+                });
+                continue;
+            }
+            Err(error) => {
+                dataset_failed(&mut manifest, args, dataset, file_name, error)?;
+                continue;
+            }
+        };
+        if args.strict_counts
+            && let Some(declared) = summary.declared
+            && declared != summary.objects
+            && !summary.stopped_early
+        {
+            let error = anyhow::anyhow!(
+                "{} declares {} features but holds {}; it may be truncated",
+                path,
+                declared,
+                summary.objects
+            );
+            dataset_failed(&mut manifest, args, dataset, file_name, error)?;
+            continue;
+        }
+        if rings.closed + rings.reversed > 0 {
+            eprintln!("  Closed {} and reversed {} zone rings", rings.closed, rings.reversed);
+        }
+        if features_missing > 0 {
+            let counts: Vec<String> =
+                missing_attributes.iter().map(|(attribute, count)| format!("{} ({})", attribute, count)).collect();
+            eprintln!("  {} features lack required attributes: {}", features_missing, counts.join(", "));
+        }
+        if unexpected_geometries > 0 {
+            eprintln!("  {} features have an unexpected geometry type", unexpected_geometries);
+        }
+        if !unknown_codes.is_empty() {
+            let counts: Vec<String> =
+                unknown_codes.iter().map(|(attribute, count)| format!("{} ({})", attribute, count)).collect();
+            eprintln!("  Values outside the {} code lists: {}", profile.map_or("", |p| p.name), counts.join(", "));
+        }
+        if transformed_out > 0 {
+            eprintln!("  Dropped {} features by [[transforms]] filters", transformed_out);
+        }
+        if too_small > 0 {
+            eprintln!("  Dropped {} features below the minimum length or area", too_small);
+        }
+        if clipped_out > 0 {
+            eprintln!("  Dropped {} features with nothing inside the clip box", clipped_out);
+        }
+        let times = args.perf_report.then(perf::take);
+        if let Some(times) = &times {
+            eprintln!("  Time {}", times.describe());
+        }
+        manifest.datasets.push(ManifestDataset {
+            layer: dataset.output_subdir,
+            source: file_name,
+            features: summary.features - transformed_out - too_small - clipped_out,
+            skipped: summary.skipped,
+            not_current: options.as_of.is_some().then_some(summary.not_current),
+            without_lod: options.lod.is_some().then_some(summary.without_lod),
+            filtered: (transformed_out > 0).then_some(transformed_out),
+            too_small: (args.min_length_m.is_some() || args.min_area_m2.is_some()).then_some(too_small),
+            clipped_out: args.clip_bbox.is_some().then_some(clipped_out),
+            declared_features: summary.declared,
+            envelope: summary.envelope,
+            vertices_removed: summary.vertices_removed,
+            type_mismatches: summary.type_mismatches,
+            unparsed_numbers: summary.unparsed_numbers,
+            malformed_coordinates: (summary.malformed_coordinates > 0).then_some(summary.malformed_coordinates),
+            missing_attributes,
+            unexpected_geometries: layer_profile.map(|_| unexpected_geometries),
+            unknown_codes,
+            rings: (dataset.kind == DatasetKind::Zone).then_some(rings),
+            fingerprint: args.fingerprint.then(|| fingerprint::layer_fingerprint(&fingerprints)),
+            perf: times,
+        });
+    }
+    perf::take();
+    {
+        let _io = perf::enter(Stage::Io);
+        sinks.finish()?;
+    }
+    if args.perf_report {
+        manifest.finish_perf = Some(perf::take());
+    }
+    index.write(&format!("{}/search_index.json", output_dir))?;
+    if let Some(street_names) = &street_names {
+        street_names.write(&format!("{}/street_names.json", output_dir))?;
+    }
+    write_json(&format!("{}/manifest.json", output_dir), &manifest)?;
+    if let Some(links) = &links {
+        links.write(&format!("{}/links.csv", output_dir))?;
+    }
+    if let Some(tile_report) = &tile_report {
+        tile_report.write(&format!("{}/tiles.csv", output_dir))?;
+    }
+
+    eprintln!("Done! Output has been written to {}/", output_dir);
+    if !manifest.failed.is_empty() {
+        let sources: Vec<&str> = manifest.failed.iter().map(|failure| failure.source.as_str()).collect();
+        eprintln!("Failed to convert {}", sources.join(", "));
+    }
+    Ok(manifest.failed.is_empty())
+}
+
+/// Under `--continue-on-failure`, reports that `source` failed and records it in
+/// the manifest so the run can move on. Otherwise the error ends the run.
+fn dataset_failed(
+    manifest: &mut Manifest,
+    args: &ConvertArgs,
+    dataset: &Dataset,
+    source: String,
+    error: anyhow::Error,
+) -> Result<()> {
+    if !args.continue_on_failure {
+        return Err(error);
+    }
+    eprintln!("  Error: {:#}", error);
+    manifest.failed.push(ManifestFailure {
+        layer: dataset.output_subdir,
+        source,
+        error: format!("{:#}", error),
+    });
+    Ok(())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Geometry {
-    #[serde(rename = "type")]
-    geometry_type: String,
-    coordinates: Vec<Vec<f64>>,
+/// The GML files in the input directory, each with the dataset it holds. Files
+/// with a known name, gzipped or not, come first. Any other `.gml` or `.gml.gz`
+/// file is sniffed for its dataset, and converted generically when it matches
+/// none.
+fn input_files(data_dir: &str) -> Result<Vec<(String, &'static Dataset)>> {
+    let mut files: Vec<(String, &'static Dataset)> = Vec::new();
+    let mut others: Vec<String> = Vec::new();
+    if let Ok(entries) = fs::read_dir(data_dir) {
+        for entry in entries {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            let lowercase = name.to_lowercase();
+            let known = DATASETS
+                .iter()
+                .any(|dataset| name.strip_suffix(".gz").unwrap_or(&name) == dataset.file_name);
+            if (lowercase.ends_with(".gml") || lowercase.ends_with(".gml.gz")) && !known {
+                others.push(name);
+            }
+        }
+    }
+    others.sort();
+    for name in others {
+        let head = read_head(&format!("{}/{}", data_dir, name))?;
+        let dataset = match dataset::detect(&head) {
+            Some(dataset) => {
+                eprintln!("{} looks like {}; converting it into {}", name, dataset.file_name, dataset.output_subdir);
+                dataset
+            }
+            None => {
+                let dataset = dataset::generic(&head);
+                eprintln!(
+                    "Warning: {} matches no known dataset; converting it into {} with gml:id as the ID",
+                    name, dataset.output_subdir
+                );
+                dataset
+            }
+        };
+        files.push((name, dataset));
+    }
+    let mut known = Vec::new();
+    for dataset in DATASETS {
+        let path = dataset.path_in(data_dir);
+        if Path::new(&path).exists() {
+            known.push((path[data_dir.len() + 1..].to_string(), dataset));
+        } else if !files.iter().any(|(_, found)| found.file_name == dataset.file_name) {
+            eprintln!("Warning: {}/{} not found", data_dir, dataset.file_name);
+        }
+    }
+    known.extend(files);
+    Ok(known)
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(untagged)]
-enum PropertyValue {
-    String(String),
-    Int(i64),
-    Float(f64),
-    Null,
+/// Up to the first 64 KiB of a file, enough to reach its first features. A
+/// gzipped file is inflated that far.
+fn read_head(path: &str) -> Result<String> {
+    use std::io::Read;
+    let mut head = Vec::new();
+    let reader: Box<dyn Read> = match gzip::is_gzip(path) {
+        true => Box::new(gzip::Decompressor::open(path)?),
+        false => Box::new(fs::File::open(path).with_context(|| format!("Failed to read file: {}", path))?),
+    };
+    reader
+        .take(64 * 1024)
+        .read_to_end(&mut head)
+        .with_context(|| format!("Failed to read file: {}", path))?;
+    Ok(String::from_utf8_lossy(&head).into_owned())
 }
 
-fn main() -> Result<()> {
-    let data_dir = "./input";
-    let output_dir = "./output";
-    
-    // Create output directory if it doesn't exist
-    fs::create_dir_all(output_dir).context("Failed to create output directory")?;
-    fs::create_dir_all(format!("{}/centerlines", output_dir))
-        .context("Failed to create centerlines directory")?;
-    fs::create_dir_all(format!("{}/pedestrian_zones", output_dir))
-        .context("Failed to create pedestrian_zones directory")?;
-
-    println!("Parsing GML files...");
-
-    // Parse centerlines
-    let cl_path = format!("{}/CENTERLINE.gml", data_dir);
-    if Path::new(&cl_path).exists() {
-        println!("Processing CENTERLINE.gml...");
-        parse_gml_file(&cl_path, "ROUTE_ID", "centerlines", output_dir)?;
+/// Parses a GML file as [`gml::parse_gml_file`] does, printing its progress and
+/// what it found to stderr.
+fn parse_gml_file(
+    path: &str,
+    id_field: &str,
+    options: &ParseOptions,
+    on_feature: impl FnMut(String, GeoJsonFeature) -> Result<()>,
+) -> Result<ParseSummary> {
+    let options = ParseOptions {
+        progress: Some(print_progress),
+        ..options.clone()
+    };
+    let summary = gml::parse_gml_file(path, id_field, &options, on_feature)?;
+    print_summary(path, &summary, &options);
+    Ok(summary)
+}
+
+/// Prints a line every 100 features, and how far through the expected features
+/// the parser is: a line rewritten at every percent on a terminal, or a line per
+/// 10% otherwise.
+fn print_progress(progress: Progress) {
+    let (objects, expected) = match progress {
+        Progress::Features(features) => {
+            eprintln!("  Processed {} features...", features);
+            return;
+        }
+        Progress::Objects { read, expected } if expected > 0 => (read, expected),
+        Progress::Objects { .. } => return,
+    };
+    let terminal = std::io::stderr().is_terminal();
+    let step = if terminal { 1 } else { 10 };
+    let percent = |objects: usize| (objects * 100 / expected).min(100) / step * step;
+    if percent(objects) == percent(objects - 1) {
+        return;
+    }
+    if terminal {
+        eprint!("\r  {}% ({}/{} features)", percent(objects), objects, expected);
     } else {
-        println!("Warning: {} not found", cl_path);
+        eprintln!("  {}% ({}/{} features)", percent(objects), objects, expected);
     }
+}
 
-    // Parse pedestrian zones
-    let pz_path = format!("{}/PEDESTRIAN_ZONE.gml", data_dir);
-    if Path::new(&pz_path).exists() {
-        println!("Processing PEDESTRIAN_ZONE.gml...");
-        parse_gml_file(&pz_path, "PED_ZONE_ID", "pedestrian_zones", output_dir)?;
-    } else {
-        println!("Warning: {} not found", pz_path);
+fn print_summary(path: &str, summary: &ParseSummary, options: &ParseOptions) {
+    if options.expected_objects.is_some_and(|expected| expected > 0) && std::io::stderr().is_terminal() {
+        eprintln!();
+    }
+    for name in &summary.unsupported_srs_names {
+        eprintln!(
+            "  Warning: unsupported srsName \"{}\" in {}; assuming EPSG:{}",
+            name,
+            path,
+            gml::DEFAULT_SOURCE_EPSG
+        );
+    }
+    if let Some((first, others)) = summary.source_epsg.split_first() {
+        for code in others {
+            eprintln!(
+                "  Warning: {} mixes EPSG:{} and EPSG:{} geometries; each is projected from its own srsName",
+                path, first, code
+            );
+        }
+    }
+    eprintln!("  Total features processed: {}", summary.features);
+    if summary.skipped > 0 {
+        eprintln!("  Skipped {} features outside the bounding box", summary.skipped);
+    }
+    if summary.not_current > 0
+        && let Some(as_of) = &options.as_of
+    {
+        eprintln!("  Skipped {} features not in use on {}", summary.not_current, as_of);
+    }
+    if summary.vertices_removed > 0 {
+        eprintln!("  Removed {} repeated or near-duplicate vertices", summary.vertices_removed);
+    }
+    if summary.without_lod > 0
+        && let Some(lod) = options.lod
+    {
+        eprintln!("  {} features have no LoD {} geometry", summary.without_lod, lod);
+    }
+    if let Some(declared) = summary.declared
+        && declared != summary.objects
+        && !summary.stopped_early
+    {
+        eprintln!(
+            "  Warning: the file declares {} features but holds {}; it may be truncated",
+            declared, summary.objects
+        );
+    }
+    if summary.type_mismatches > 0 {
+        eprintln!("  Set {} attribute values that didn't match their configured type to null", summary.type_mismatches);
+    }
+    if summary.unparsed_numbers > 0 {
+        eprintln!("  Kept {} int or double attribute values that aren't numbers as strings", summary.unparsed_numbers);
     }
+    if summary.malformed_coordinates > 0 {
+        eprintln!(
+            "  Warning: skipped {} coordinate lists that don't split into positions of 2 or more ordinates; \
+             check the cs and ts separators declared on gml:coordinates",
+            summary.malformed_coordinates
+        );
+    }
+}
 
-    println!("Done! JSON files have been written to {}/", output_dir);
+/// Reads the IDs of every layer that `[[links]]` point into, without geometry.
+fn collect_link_targets(config: &Config, links: &mut LinkResolver, data_dir: &str) -> Result<()> {
+    let layers: BTreeSet<String> = links.target_layers().map(str::to_string).collect();
+    for layer in layers {
+        let Some(dataset) = DATASETS.iter().find(|dataset| dataset.output_subdir == layer) else {
+            eprintln!("Warning: links target unknown layer `{}`", layer);
+            continue;
+        };
+        let path = dataset.path_in(data_dir);
+        if !Path::new(&path).exists() {
+            continue;
+        }
+        eprintln!("Reading link targets from {}...", dataset.file_name);
+        let options = ParseOptions {
+            geometry: false,
+            ..config.input.parse_options()
+        };
+        parse_gml_file(&path, dataset.id_field, &options, |id, _| {
+            links.add_target(&layer, id);
+            Ok(())
+        })?;
+    }
     Ok(())
 }
 
-fn parse_gml_file(
-    file_path: &str,
-    id_field: &str,
-    output_subdir: &str,
-    output_dir: &str,
-) -> Result<()> {
-    let content = fs::read_to_string(file_path)
-        .with_context(|| format!("Failed to read file: {}", file_path))?;
-
-    let mut reader = Reader::from_str(&content);
-    reader.config_mut().trim_text(true);
-
-    let mut buf = Vec::new();
-    let mut in_city_object = false;
-    let mut current_object = String::new();
-    let mut object_depth = 0;
-    let mut count = 0;
-
-    loop {
-        match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(ref e)) => {
-                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
-                if name.ends_with(":GenericCityObject") {
-                    in_city_object = true;
-                    object_depth = 1;
-                    current_object.clear();
-                    current_object.push_str(&format!("<{}", name));
-                    for attr in e.attributes() {
-                        if let Ok(attr) = attr {
-                            current_object.push_str(&format!(
-                                " {}=\"{}\"",
-                                String::from_utf8_lossy(attr.key.as_ref()),
-                                String::from_utf8_lossy(&attr.value)
-                            ));
-                        }
-                    }
-                    current_object.push('>');
-                } else if in_city_object {
-                    object_depth += 1;
-                    current_object.push_str(&format!("<{}", name));
-                    for attr in e.attributes() {
-                        if let Ok(attr) = attr {
-                            current_object.push_str(&format!(
-                                " {}=\"{}\"",
-                                String::from_utf8_lossy(attr.key.as_ref()),
-                                String::from_utf8_lossy(&attr.value)
-                            ));
-                        }
-                    }
-                    current_object.push('>');
-                }
+/// Builds the routing graph from the centerlines, applying any turn restrictions.
+fn load_graph(config: &Config, args: &GraphArgs) -> Result<Graph> {
+    load_graph_with(config, args, &config.graph.direction)
+}
+
+/// Like [`load_graph`], orienting the centerlines by `directions`.
+fn load_graph_with(config: &Config, args: &GraphArgs, directions: &DirectionConfig) -> Result<Graph> {
+    let dataset = &DATASETS[0];
+    let path = dataset.path_in(data_dir());
+    eprintln!("Processing {}...", dataset.file_name);
+
+    let mut features = Vec::new();
+    parse_gml_file(&path, dataset.id_field, &config.input.parse_options(), |id, feature| {
+        features.push((id, feature));
+        Ok(())
+    })?;
+    let mut graph = Graph::with_snap_tolerance(args.snap_tolerance);
+    let threads = std::thread::available_parallelism().map_or(4, |threads| threads.get());
+    graph.add_segments(&features, directions, threads);
+    if args.snap_tolerance > 0.0 {
+        eprintln!("  Snapped {} endpoints onto nearby nodes", graph.snapped);
+    }
+
+    if let Some(path) = &args.restrictions {
+        let rows = graph::load_restrictions(path)?;
+        let unmatched = graph.apply_restrictions(&rows);
+        eprintln!(
+            "  Loaded {} turn restrictions ({} did not match any turn)",
+            rows.len(),
+            unmatched
+        );
+    }
+    Ok(graph)
+}
+
+fn build_graph(config: &Config, args: &GraphExportArgs, max_file_size: Option<u64>) -> Result<()> {
+    let graph = load_graph(config, &args.graph)?;
+
+    let graph_dir = format!("{}/graph", output_dir());
+    fs::create_dir_all(paths::long_path(&graph_dir)).context("Failed to create graph directory")?;
+    match args.format {
+        GraphFormat::Geojson => {
+            let path = format!("{}/nodes.geojson", graph_dir);
+            write_collection(&path, &graph.nodes_geojson().features, max_file_size)?;
+            let path = format!("{}/edges.geojson", graph_dir);
+            write_collection(&path, &graph.edges_geojson().features, max_file_size)?;
+            if !graph.banned_turns.is_empty() {
+                let path = format!("{}/restrictions.geojson", graph_dir);
+                write_collection(&path, &graph.restrictions_geojson().features, max_file_size)?;
             }
-            Ok(Event::End(ref e)) => {
-                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
-                if in_city_object {
-                    current_object.push_str(&format!("</{}>", name));
-                    object_depth -= 1;
-                    if object_depth == 0 {
-                        // Process the complete city object
-                        if let Ok(feature) = parse_city_object(&current_object, id_field) {
-                            if let Some(id) = feature.properties.get(id_field) {
-                                let id_str = match id {
-                                    PropertyValue::String(s) => s.clone(),
-                                    PropertyValue::Int(i) => i.to_string(),
-                                    _ => format!("object_{}", count),
-                                };
-                                
-                                let output_path = format!("{}/{}/{}.json", output_dir, output_subdir, id_str);
-                                let json = serde_json::to_string_pretty(&feature)?;
-                                let mut file = File::create(&output_path)?;
-                                file.write_all(json.as_bytes())?;
-                                count += 1;
-                                
-                                if count % 100 == 0 {
-                                    println!("  Processed {} features...", count);
-                                }
-                            }
-                        }
-                        in_city_object = false;
-                    }
+        }
+        GraphFormat::Osm => {
+            osm::write_osm_xml(&graph, &config.graph.osm, &format!("{}/network.osm", graph_dir))?;
+        }
+        GraphFormat::Neo4j => neo4j::write_bulk_import(&graph, &graph_dir)?,
+    }
+    if let Some(count) = args.landmarks {
+        let landmarks = Landmarks::build(&graph, count);
+        landmarks.write(&format!("{}/{}", graph_dir, landmarks::FILE_NAME))?;
+        eprintln!("  Picked {} landmarks", landmarks.nodes.len());
+    }
+
+    println!(
+        "Done! Graph with {} nodes and {} edges written to {}/",
+        graph.nodes.len(),
+        graph.edges.len(),
+        graph_dir
+    );
+    Ok(())
+}
+
+fn route(config: &Config, args: &RouteArgs) -> Result<()> {
+    let graph = load_graph(config, &args.graph)?;
+    let from = graph.nearest_node(&args.from).context("The routing graph is empty")?;
+    let to = graph.nearest_node(&args.to).context("The routing graph is empty")?;
+    let route = match &args.landmarks {
+        Some(path) => routing::shortest_path_with(&graph, &Landmarks::read(path, &graph)?, from, to),
+        None => routing::shortest_path(&graph, from, to),
+    }
+    .with_context(|| format!("No route from node {} to node {}", from, to))?;
+    let feature = route.to_feature(&graph);
+    if args.gpx {
+        let Geometry::LineString(coords) = &feature.geometry else {
+            unreachable!("routes are always LineStrings");
+        };
+        let name = format!("Route via {}", feature.properties["segments"]);
+        gpx::write_gpx(std::io::stdout().lock(), &[gpx::Track { name, coords }])?;
+    } else {
+        println!("{}", serde_json::to_string_pretty(&feature)?);
+    }
+    Ok(())
+}
+
+fn matrix(config: &Config, args: &MatrixArgs) -> Result<()> {
+    let read_points = |path: &PathBuf| -> Result<Vec<[f64; 2]>> {
+        let mut reader = csv::Reader::from_path(path)
+            .with_context(|| format!("Failed to read points: {}", path.display()))?;
+        reader
+            .deserialize()
+            .map(|record| {
+                let (lon, lat): (f64, f64) = record.with_context(|| format!("Invalid point in {}", path.display()))?;
+                Ok([lon, lat])
+            })
+            .collect()
+    };
+    let origins = read_points(&args.origins)?;
+    let destinations = match &args.destinations {
+        Some(path) => read_points(path)?,
+        None => origins.clone(),
+    };
+    if args.speed.is_some_and(|speed| speed <= 0.0) {
+        anyhow::bail!("--speed must be positive");
+    }
+
+    let graph = load_graph(config, &args.graph)?;
+    // Points join the network at their nearest node, in a straight line
+    let snap = |points: &[[f64; 2]]| -> Result<Vec<(usize, f64)>> {
+        points
+            .iter()
+            .map(|point| {
+                let node = graph.nearest_node(point).context("The routing graph is empty")?;
+                Ok((node, geometry::haversine_m(&graph.nodes[node].coord, point)))
+            })
+            .collect()
+    };
+    let (from, to) = (snap(&origins)?, snap(&destinations)?);
+
+    // One search per origin, spread over every core
+    let outgoing = graph.outgoing();
+    let threads = std::thread::available_parallelism().map_or(4, |threads| threads.get());
+    let chunk_size = from.len().div_ceil(threads).max(1);
+    let mut distances: Vec<Vec<Option<f64>>> = std::thread::scope(|scope| {
+        let workers: Vec<_> = from
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let (graph, outgoing, to) = (&graph, &outgoing, &to);
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|&(origin, origin_leg)| {
+                            let reached = routing::distances_from(graph, outgoing, origin);
+                            to.iter()
+                                .map(|&(node, leg)| Some(origin_leg + reached[node] + leg).filter(|d| d.is_finite()))
+                                .collect()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        workers.into_iter().flat_map(|worker| worker.join().expect("matrix worker panicked")).collect()
+    });
+    // Rather than walking to the network and back
+    for (origin, row) in origins.iter().zip(&mut distances) {
+        for (destination, distance) in destinations.iter().zip(row) {
+            if origin == destination {
+                *distance = Some(0.0);
+            }
+        }
+    }
+    eprintln!("  Computed {} x {} distances", origins.len(), destinations.len());
+
+    let duration = |distance: Option<f64>| distance.zip(args.speed).map(|(distance, kmh)| distance / (kmh / 3.6));
+    match args.format {
+        MatrixFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            for (origin, row) in distances.iter().enumerate() {
+                for (destination, &distance_m) in row.iter().enumerate() {
+                    writer.serialize(MatrixRow {
+                        origin,
+                        destination,
+                        distance_m,
+                        duration_s: args.speed.map(|_| duration(distance_m)),
+                    })?;
                 }
             }
-            Ok(Event::Text(e)) => {
-                if in_city_object {
-                    let text = e.unescape().unwrap_or_default();
-                    current_object.push_str(&text);
+            writer.flush()?;
+        }
+        MatrixFormat::Json => {
+            let durations = args.speed.map(|_| {
+                distances.iter().map(|row| row.iter().map(|&distance| duration(distance)).collect()).collect()
+            });
+            let matrix = Matrix {
+                origins,
+                destinations,
+                distances,
+                durations,
+            };
+            println!("{}", serde_json::to_string_pretty(&matrix)?);
+        }
+    }
+    Ok(())
+}
+
+fn accessibility(config: &Config, args: &AccessibilityArgs, max_file_size: Option<u64>) -> Result<()> {
+    let mut reader = csv::Reader::from_path(&args.facilities)
+        .with_context(|| format!("Failed to read facilities: {}", args.facilities.display()))?;
+    let facilities: Vec<(f64, f64)> = reader
+        .deserialize()
+        .collect::<Result<_, _>>()
+        .with_context(|| format!("Invalid point in {}", args.facilities.display()))?;
+
+    let walkable = DirectionConfig {
+        attribute: String::new(),
+        default: Direction::Both,
+        values: HashMap::new(),
+    };
+    let graph = load_graph_with(config, &args.graph, if args.walking { &walkable } else { &config.graph.direction })?;
+    let nodes = SpatialIndex::build(graph.nodes.iter().enumerate().map(|(id, node)| (id, std::slice::from_ref(&node.coord))));
+    // Points join the network at their nearest node, in a straight line
+    let sources: Vec<(usize, f64)> = facilities
+        .iter()
+        .filter_map(|&(lon, lat)| nodes.nearest_where(&[lon, lat], |_| true))
+        .collect();
+    let (_, incoming) = routing::adjacency(&graph);
+    let distances = routing::node_distances(&graph, &incoming, &sources, true);
+    let distance_of = |distance: f64| match distance.is_finite() {
+        true => PropertyValue::Float(distance),
+        false => PropertyValue::Null,
+    };
+
+    let mut by_segment: HashMap<&str, f64> = HashMap::new();
+    for edge in &graph.edges {
+        let nearest = by_segment.entry(edge.segment_id.as_str()).or_insert(f64::INFINITY);
+        *nearest = nearest.min(distances[edge.from]).min(distances[edge.to]);
+    }
+    let centerlines: Vec<GeoJsonFeature> = graph
+        .segments
+        .iter()
+        .map(|segment| {
+            let mut properties = segment.properties.clone();
+            let distance = by_segment.get(segment.id.as_str()).copied().unwrap_or(f64::INFINITY);
+            properties.insert(args.property.clone(), distance_of(distance));
+            GeoJsonFeature::new(Geometry::LineString(segment.coords.clone()), properties)
+        })
+        .collect();
+    let reached = centerlines.iter().filter(|feature| !matches!(feature.properties[&args.property], PropertyValue::Null)).count();
+    eprintln!("  {} of {} centerlines reach a facility", reached, centerlines.len());
+
+    let output_dir = format!("{}/accessibility", output_dir());
+    fs::create_dir_all(paths::long_path(&output_dir)).context("Failed to create accessibility directory")?;
+    write_collection(&format!("{}/{}.geojson", output_dir, DATASETS[0].output_subdir), &centerlines, max_file_size)?;
+
+    if args.zones {
+        let mut zones = load_dataset(config, &DATASETS[1])?;
+        for (_, feature) in &mut zones {
+            let distance = label_point(feature, DatasetKind::Zone, PointMethod::Centroid, Crs::Wgs84)
+                .and_then(|point| nodes.nearest_where(point.geometry.positions()[0], |_| true))
+                .map_or(f64::INFINITY, |(node, leg)| distances[node] + leg);
+            feature.properties.insert(args.property.clone(), distance_of(distance));
+        }
+        let zones: Vec<GeoJsonFeature> = zones.into_iter().map(|(_, feature)| feature).collect();
+        write_collection(&format!("{}/{}.geojson", output_dir, DATASETS[1].output_subdir), &zones, max_file_size)?;
+    }
+    eprintln!("Done! Output has been written to {}/", output_dir);
+    Ok(())
+}
+
+fn pedestrian_network(config: &Config, args: &PedestrianNetworkArgs, max_file_size: Option<u64>) -> Result<()> {
+    let mut network = PedestrianNetwork::default();
+    let options = config.input.parse_options();
+
+    for dataset in DATASETS {
+        let include = match dataset.kind {
+            DatasetKind::Zone => true,
+            DatasetKind::Line => args.include_roads,
+        };
+        let path = dataset.path_in(data_dir());
+        if !include || !Path::new(&path).exists() {
+            continue;
+        }
+        eprintln!("Processing {}...", dataset.file_name);
+        parse_gml_file(&path, dataset.id_field, &options, |id, feature| {
+            match dataset.kind {
+                DatasetKind::Zone => network.add_zone(&id, &feature),
+                DatasetKind::Line => network.add_line("road", &id, &feature),
+            }
+            Ok(())
+        })?;
+    }
+
+    let layers = [
+        ("footbridge", &args.footbridges),
+        ("subway", &args.subways),
+        ("crossing", &args.crossings),
+    ];
+    for (kind, path) in layers {
+        let Some(path) = path else {
+            continue;
+        };
+        eprintln!("Processing {}...", path.display());
+        parse_gml_file(&path.to_string_lossy(), &args.id_field, &options, |id, feature| {
+            network.add_line(kind, &id, &feature);
+            Ok(())
+        })?;
+    }
+
+    let connectors = network.connect(args.connect_tolerance);
+    eprintln!("  Added {} connectors", connectors);
+
+    let network_dir = format!("{}/pedestrian_network", output_dir());
+    fs::create_dir_all(paths::long_path(&network_dir)).context("Failed to create pedestrian network directory")?;
+    let path = format!("{}/nodes.geojson", network_dir);
+    write_collection(&path, &network.graph.nodes_geojson().features, max_file_size)?;
+    let path = format!("{}/edges.geojson", network_dir);
+    write_collection(&path, &network.edges_geojson().features, max_file_size)?;
+
+    println!(
+        "Done! Pedestrian network with {} nodes and {} edges written to {}/",
+        network.graph.nodes.len(),
+        network.graph.edges.len(),
+        network_dir
+    );
+    Ok(())
+}
+
+fn aggregate(config: &Config, args: &AggregateArgs, max_file_size: Option<u64>) -> Result<()> {
+    let output = args.output.clone().unwrap_or_else(|| Path::new(output_dir()).join("aggregate.geojson"));
+    let text = fs::read_to_string(&args.areas).with_context(|| format!("Failed to read {}", args.areas.display()))?;
+    let mut collection: serde_json::Value =
+        serde_json::from_str(&text).with_context(|| format!("Invalid GeoJSON: {}", args.areas.display()))?;
+    let Some(features) = collection["features"].as_array_mut() else {
+        anyhow::bail!("{} is not a GeoJSON FeatureCollection", args.areas.display());
+    };
+    let mut aggregation = Aggregation::new(features);
+
+    for dataset in DATASETS {
+        let path = dataset.path_in(data_dir());
+        if !Path::new(&path).exists() {
+            eprintln!("Warning: {} not found", path);
+            continue;
+        }
+        eprintln!("Processing {}...", dataset.file_name);
+        parse_gml_file(&path, dataset.id_field, &config.input.parse_options(), |_, feature| {
+            match (dataset.kind, &feature.geometry) {
+                (DatasetKind::Line, Geometry::LineString(coords)) => {
+                    let road_type = args
+                        .type_attribute
+                        .as_ref()
+                        .and_then(|attribute| feature.properties.get(attribute))
+                        .map(|value| value.to_string());
+                    aggregation.add_road(coords, road_type.as_deref());
                 }
+                (DatasetKind::Zone, geometry) => aggregation.add_zone(&geometry.polygons()),
+                _ => {}
             }
-            Ok(Event::Eof) => break,
-            Err(e) => {
-                eprintln!("Error at position {}: {:?}", reader.buffer_position(), e);
-                break;
+            Ok(())
+        })?;
+    }
+
+    aggregation.enrich(features);
+    let count = features.len();
+    if let Some(dir) = output.parent() {
+        fs::create_dir_all(paths::long_path(dir)).context("Failed to create output directory")?;
+    }
+    // Parts are plain FeatureCollections, without the input's other members
+    match max_file_size {
+        Some(_) => write_collection(&output.to_string_lossy(), features, max_file_size)?,
+        None => write_json(&output.to_string_lossy(), &collection)?,
+    }
+    println!("Done! Aggregated {} areas into {}", count, output.display());
+    Ok(())
+}
+
+fn rasterize(config: &Config, args: &RasterizeArgs) -> Result<()> {
+    if args.resolution.is_nan() || args.resolution <= 0.0 {
+        anyhow::bail!("--resolution must be greater than 0");
+    }
+    let kind = match args.measure {
+        RasterMeasure::Density => DatasetKind::Line,
+        RasterMeasure::Coverage => DatasetKind::Zone,
+    };
+    let options = ParseOptions {
+        target_crs: Crs::Hk80,
+        ..config.input.parse_options()
+    };
+    // Read everything first: the grid is sized to the features' extent
+    let mut lines: Vec<Vec<Vec<f64>>> = Vec::new();
+    let mut polygons: Vec<Vec<Vec<Vec<f64>>>> = Vec::new();
+    for dataset in DATASETS.iter().filter(|dataset| dataset.kind == kind) {
+        let path = dataset.path_in(data_dir());
+        if !Path::new(&path).exists() {
+            eprintln!("Warning: {} not found", path);
+            continue;
+        }
+        eprintln!("Processing {}...", dataset.file_name);
+        parse_gml_file(&path, dataset.id_field, &options, |_, feature| {
+            match feature.geometry {
+                Geometry::LineString(coords) => lines.push(coords),
+                Geometry::MultiLineString(parts) => lines.extend(parts),
+                Geometry::Polygon(rings) => polygons.push(rings),
+                Geometry::MultiPolygon(members) => polygons.extend(members),
+                Geometry::Point(_) => {}
             }
-            _ => {}
+            Ok(())
+        })?;
+    }
+
+    let mut bbox = [f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY];
+    for position in lines.iter().flatten().chain(polygons.iter().flatten().flatten()) {
+        bbox = [bbox[0].min(position[0]), bbox[1].min(position[1]), bbox[2].max(position[0]), bbox[3].max(position[1])];
+    }
+    if bbox[0] > bbox[2] {
+        anyhow::bail!("No features to rasterize");
+    }
+    let mut raster = Raster::covering(bbox, args.resolution)?;
+    for coords in &lines {
+        raster.add_line(coords);
+    }
+    for rings in &polygons {
+        raster.add_polygon(rings);
+    }
+    let name = match args.measure {
+        RasterMeasure::Density => "density",
+        RasterMeasure::Coverage => {
+            raster.area_to_coverage();
+            "coverage"
         }
-        buf.clear();
+    };
+
+    let output = args.output.clone().unwrap_or_else(|| PathBuf::from(format!("{}/{}.tif", output_dir(), name)));
+    if let Some(dir) = output.parent() {
+        fs::create_dir_all(paths::long_path(dir)).context("Failed to create output directory")?;
+    }
+    raster.write_geotiff(&output.to_string_lossy())?;
+    println!(
+        "Done! Wrote a {} x {} {} grid of {} m cells (max {:.3}) to {}",
+        raster.width(),
+        raster.height(),
+        name,
+        args.resolution,
+        raster.max(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// The dataset whose output layer is named `layer`.
+fn find_dataset(layer: &str) -> Result<&'static Dataset> {
+    DATASETS.iter().find(|dataset| dataset.output_subdir == layer).with_context(|| {
+        let layers: Vec<&str> = DATASETS.iter().map(|dataset| dataset.output_subdir).collect();
+        format!("Unknown dataset `{}`; expected one of {}", layer, layers.join(", "))
+    })
+}
+
+/// Scans a layer for one feature, stopping as soon as it is found.
+fn extract(config: &Config, args: &ExtractArgs) -> Result<()> {
+    let dataset = find_dataset(&args.dataset)?;
+    let crs_member = crs_member(args.crs, args.crs_annotation)?;
+    let options = ParseOptions {
+        target_crs: args.crs,
+        id: Some(config.input.ids.normalize(args.id.clone())),
+        limit: Some(1),
+        ..config.input.parse_options()
+    };
+    let path = dataset.path_in(data_dir());
+    eprintln!("Processing {}...", dataset.file_name);
+    let mut found = None;
+    parse_gml_file(&path, dataset.id_field, &options, |_, feature| {
+        found = Some(feature);
+        Ok(())
+    })?;
+    let feature = found.with_context(|| format!("No {} feature with ID {}", dataset.output_subdir, args.id))?;
+    println!("{}", serde_json::to_string_pretty(&geojson::with_member(&feature, crs_member.as_ref()))?);
+    Ok(())
+}
+
+fn print_history(config: &Config, args: &HistoryArgs) -> Result<()> {
+    let store = args.store.clone().unwrap_or_else(|| Path::new(output_dir()).join("history"));
+    let as_of = config.input.as_of.as_deref();
+    let features: Vec<GeoJsonFeature> = history::read_versions(&store)?
+        .into_iter()
+        .filter(|version| version.layer == args.dataset)
+        .filter(|version| match (&args.id, as_of) {
+            (Some(id), _) => version.id == *id && as_of.is_none_or(|time| version.valid_at(time)),
+            (None, Some(time)) => version.valid_at(time),
+            (None, None) => version.valid_to.is_none(),
+        })
+        .map(|version| {
+            let mut feature = version.feature;
+            feature.properties.insert("valid_from".to_string(), PropertyValue::String(version.valid_from));
+            let valid_to = version.valid_to.map_or(PropertyValue::Null, PropertyValue::String);
+            feature.properties.insert("valid_to".to_string(), valid_to);
+            feature
+        })
+        .collect();
+    eprintln!("{} versions", features.len());
+    let collection = GeoJsonFeatureCollection {
+        collection_type: "FeatureCollection".to_string(),
+        features,
+    };
+    println!("{}", serde_json::to_string_pretty(&collection)?);
+    Ok(())
+}
+
+/// The member labelling GeoJSON written in `crs`, if it needs one. Readers take
+/// unlabelled GeoJSON for WGS84, so other output without an annotation is
+/// refused rather than silently mislabelled.
+fn crs_member(crs: Crs, annotation: Option<CrsAnnotation>) -> Result<Option<ForeignMember>> {
+    match (crs, annotation) {
+        (Crs::Wgs84, _) => Ok(None),
+        (_, Some(annotation)) => Ok(Some(annotation.member(crs))),
+        (_, None) => anyhow::bail!(
+            "GeoJSON in EPSG:{} would be read as WGS84; pass --crs-annotation legacy or x-crs to label it",
+            crs.epsg()
+        ),
     }
+}
+
+fn to_gml(args: &ToGmlArgs) -> Result<()> {
+    let dataset = find_dataset(&args.dataset)?;
+    let text = fs::read_to_string(&args.input).with_context(|| format!("Failed to read {}", args.input.display()))?;
+    let collection: GeoJsonFeatureCollection = serde_json::from_str(&text)
+        .with_context(|| format!("Not a FeatureCollection of lines: {}", args.input.display()))?;
 
-    println!("  Total features processed: {}", count);
+    let output = match &args.output {
+        Some(path) => path.clone(),
+        None => Path::new(output_dir()).join(dataset.file_name),
+    };
+    if let Some(dir) = output.parent() {
+        fs::create_dir_all(paths::long_path(dir)).context("Failed to create output directory")?;
+    }
+    let written = citygml::write_city_model(&output.to_string_lossy(), dataset, &collection.features, args.crs)?;
+    if written < collection.features.len() {
+        eprintln!("  Skipped {} points or empty features", collection.features.len() - written);
+    }
+    println!("Done! Wrote {} features to {}", written, output.display());
     Ok(())
 }
 
-fn parse_city_object(xml: &str, _id_field: &str) -> Result<GeoJsonFeature> {
-    let mut properties = HashMap::new();
-    let mut coordinates = Vec::new();
-
-    let mut reader = Reader::from_str(xml);
-    reader.config_mut().trim_text(true);
-
-    let mut buf = Vec::new();
-    let mut in_string_attr = false;
-    let mut in_int_attr = false;
-    let mut in_double_attr = false;
-    let mut in_pos_list = false;
-    let mut current_attr_name = String::new();
-    let mut current_value = String::new();
-
-    loop {
-        match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(ref e)) => {
-                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
-                
-                if name.ends_with(":stringAttribute") {
-                    in_string_attr = true;
-                    for attr in e.attributes() {
-                        if let Ok(attr) = attr {
-                            let key = String::from_utf8_lossy(attr.key.as_ref());
-                            if key == "name" {
-                                current_attr_name = String::from_utf8_lossy(&attr.value).to_string();
-                            }
-                        }
-                    }
-                } else if name.ends_with(":intAttribute") {
-                    in_int_attr = true;
-                    for attr in e.attributes() {
-                        if let Ok(attr) = attr {
-                            let key = String::from_utf8_lossy(attr.key.as_ref());
-                            if key == "name" {
-                                current_attr_name = String::from_utf8_lossy(&attr.value).to_string();
-                            }
-                        }
-                    }
-                } else if name.ends_with(":doubleAttribute") {
-                    in_double_attr = true;
-                    for attr in e.attributes() {
-                        if let Ok(attr) = attr {
-                            let key = String::from_utf8_lossy(attr.key.as_ref());
-                            if key == "name" {
-                                current_attr_name = String::from_utf8_lossy(&attr.value).to_string();
-                            }
-                        }
+fn qa(config: &Config, args: &QaArgs, max_file_size: Option<u64>) -> Result<()> {
+    let output = args.output.clone().unwrap_or_else(|| Path::new(output_dir()).join("qa.geojson"));
+    let mut checker = Checker::new(args.tolerance);
+    for dataset in DATASETS {
+        let path = dataset.path_in(data_dir());
+        if !Path::new(&path).exists() {
+            eprintln!("Warning: {} not found", path);
+            continue;
+        }
+        eprintln!("Processing {}...", dataset.file_name);
+        parse_gml_file(&path, dataset.id_field, &config.input.parse_options(), |id, feature| {
+            match &feature.geometry {
+                Geometry::LineString(coords) => checker.add(dataset.output_subdir, &id, coords, true),
+                // Each ring on its own, so holes are checked against their exterior
+                geometry => {
+                    for ring in geometry.polygons().into_iter().flatten() {
+                        checker.add(dataset.output_subdir, &id, ring, false);
                     }
-                } else if name.ends_with(":posList") {
-                    in_pos_list = true;
-                    current_value.clear();
                 }
             }
-            Ok(Event::End(ref e)) => {
-                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
-                
-                if name.ends_with(":stringAttribute") {
-                    properties.insert(current_attr_name.clone(), PropertyValue::String(current_value.clone()));
-                    in_string_attr = false;
-                    current_value.clear();
-                } else if name.ends_with(":intAttribute") {
-                    if let Ok(val) = current_value.parse::<i64>() {
-                        properties.insert(current_attr_name.clone(), PropertyValue::Int(val));
-                    }
-                    in_int_attr = false;
-                    current_value.clear();
-                } else if name.ends_with(":doubleAttribute") {
-                    if let Ok(val) = current_value.parse::<f64>() {
-                        properties.insert(current_attr_name.clone(), PropertyValue::Float(val));
-                    }
-                    in_double_attr = false;
-                    current_value.clear();
-                } else if name.ends_with(":posList") {
-                    // Parse coordinates from posList
-                    let coords: Vec<f64> = current_value
-                        .split_whitespace()
-                        .filter_map(|s| s.parse::<f64>().ok())
-                        .collect();
-                    
-                    // Convert HK80 to WGS84
-                    let from_proj = "+proj=tmerc +lat_0=22.31213333333334 +lon_0=114.1785555555556 +k=1 +x_0=836694.05 +y_0=819069.8 +ellps=intl +towgs84=-162.619,-276.959,-161.764,0.067753,-2.24365,-1.15883,-1.09425 +units=m +no_defs";
-                    let to_proj = "+proj=longlat +datum=WGS84 +no_defs";
-                    
-                    if let (Ok(from), Ok(to)) = (
-                        proj4rs::Proj::from_proj_string(from_proj),
-                        proj4rs::Proj::from_proj_string(to_proj),
-                    ) {
-                        for chunk in coords.chunks(2) {
-                            if chunk.len() == 2 {
-                                let mut point = (chunk[0], chunk[1], 0.0);
-                                // Transform from HK80 to WGS84
-                                if proj4rs::transform::transform(&from, &to, &mut point).is_ok() {
-                                    // point now contains (longitude, latitude, z)
-                                    coordinates.push(vec![point.0, point.1]);
-                                }
-                            }
-                        }
-                    }
-                    
-                    in_pos_list = false;
-                    current_value.clear();
-                } else if name.ends_with(":value") {
-                    // Value is already collected in current_value
+            Ok(())
+        })?;
+    }
+
+    let (problems, summary) = checker.run();
+    eprintln!(
+        "  {} self-intersections, {} overlaps, {} duplicates",
+        summary.self_intersections, summary.overlaps, summary.duplicates
+    );
+    if let Some(dir) = output.parent() {
+        fs::create_dir_all(paths::long_path(dir)).context("Failed to create output directory")?;
+    }
+    write_collection(&output.to_string_lossy(), &problems, max_file_size)?;
+    println!("Done! QA report has been written to {}", output.display());
+    Ok(())
+}
+
+fn schema_diff(args: &SchemaDiffArgs) -> Result<()> {
+    let old = schema::read(&args.old.to_string_lossy())?;
+    let new = schema::read(&args.new.to_string_lossy())?;
+    let diff = SchemaDiff::new(&old, &new);
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+    } else {
+        for line in diff.lines() {
+            println!("{}", line);
+        }
+    }
+    eprintln!(
+        "{} added, {} removed and {} retyped attributes",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.retyped.len()
+    );
+    if args.check && !diff.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn verify_fixtures(args: &VerifyFixturesArgs) -> Result<()> {
+    if args.bless {
+        let written = fixtures::bless(&args.dir)?;
+        println!("Wrote {} golden files to {}/expected", written, args.dir.display());
+        return Ok(());
+    }
+    let mismatches = fixtures::verify(&args.dir)?;
+    if !mismatches.is_empty() {
+        for mismatch in &mismatches {
+            eprintln!("  {}", mismatch);
+        }
+        anyhow::bail!("{} differences from the golden files", mismatches.len());
+    }
+    println!("Fixtures match their golden files");
+    Ok(())
+}
+
+fn validate_config(config: &Config, path: Option<&Path>, args: &ConfigValidateArgs) -> Result<()> {
+    let name = path.map_or("the default config".to_string(), |path| path.display().to_string());
+    let mut problems = config.problems();
+    if args.input {
+        let mut layers: HashMap<&str, BTreeSet<String>> = HashMap::new();
+        for (file_name, dataset) in input_files(data_dir())? {
+            let schema = schema::read(&format!("{}/{}", data_dir(), file_name))?;
+            layers.entry(dataset.output_subdir).or_default().extend(schema.into_keys());
+        }
+        if layers.is_empty() {
+            anyhow::bail!("No GML files in {} to check the config against", data_dir());
+        }
+        for (key, layer, attribute) in config.source_attributes() {
+            match layer {
+                Some(layer) if layers.get(layer).is_some_and(|names| !names.contains(attribute)) => {
+                    problems.push(format!("{}: no {} layer feature has a {} attribute", key, layer, attribute));
+                }
+                None if !layers.values().any(|names| names.contains(attribute)) => {
+                    problems.push(format!("{}: no input file has a {} attribute", key, attribute));
                 }
+                _ => {}
+            }
+        }
+    }
+    if problems.is_empty() {
+        println!("No problems in {}", name);
+        return Ok(());
+    }
+    for problem in &problems {
+        eprintln!("  {}", problem);
+    }
+    anyhow::bail!("{} problems in {}", problems.len(), name)
+}
+
+fn completions(args: &CompletionsArgs) -> Result<()> {
+    use std::io::Write;
+
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    let mut script = Vec::new();
+    clap_complete::generate(args.shell, &mut command, name, &mut script);
+    std::io::stdout().write_all(&script).context("Failed to write the completion script")
+}
+
+fn manpage(args: &ManpageArgs) -> Result<()> {
+    let command = Cli::command();
+    match &args.out_dir {
+        Some(dir) => {
+            fs::create_dir_all(paths::long_path(dir)).with_context(|| format!("Failed to create {}", dir.display()))?;
+            clap_mangen::generate_to(command, dir)
+                .with_context(|| format!("Failed to write man pages to {}", dir.display()))?;
+            eprintln!("Wrote man pages to {}", dir.display());
+        }
+        None => clap_mangen::Man::new(command).render(&mut std::io::stdout()).context("Failed to write the man page")?,
+    }
+    Ok(())
+}
+
+fn check_crs(args: &CheckCrsArgs) -> Result<()> {
+    let mut reader = csv::Reader::from_path(&args.points)
+        .with_context(|| format!("Failed to read control points: {}", args.points.display()))?;
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+    let mut residuals = Vec::new();
+    for record in reader.deserialize() {
+        let point: ControlPoint = record.with_context(|| format!("Invalid control point in {}", args.points.display()))?;
+        let wgs84 = crs::project(&[point.easting, point.northing], 2, Crs::Hk80.epsg(), Crs::Wgs84);
+        let hk80 = crs::project(&[point.longitude, point.latitude], 2, Crs::Wgs84.epsg(), Crs::Hk80);
+        let (Some(wgs84), Some(hk80)) = (wgs84.first(), hk80.first()) else {
+            anyhow::bail!("Control point {} failed to project", point.id);
+        };
+        let residual = ControlResidual {
+            projected_longitude: wgs84[0],
+            projected_latitude: wgs84[1],
+            wgs84_residual_m: geometry::haversine_m(wgs84, &[point.longitude, point.latitude]),
+            projected_easting: hk80[0],
+            projected_northing: hk80[1],
+            hk80_residual_m: geometry::planar_distance(hk80, &[point.easting, point.northing]),
+            id: point.id,
+        };
+        writer.serialize(&residual)?;
+        residuals.push(residual);
+    }
+    writer.flush()?;
+    if residuals.is_empty() {
+        anyhow::bail!("{} holds no control points", args.points.display());
+    }
+
+    let distances: Vec<f64> = residuals
+        .iter()
+        .flat_map(|residual| [residual.wgs84_residual_m, residual.hk80_residual_m])
+        .collect();
+    let mean = distances.iter().sum::<f64>() / distances.len() as f64;
+    let rms = (distances.iter().map(|distance| distance * distance).sum::<f64>() / distances.len() as f64).sqrt();
+    let worst = residuals
+        .iter()
+        .max_by(|a, b| {
+            a.wgs84_residual_m.max(a.hk80_residual_m).total_cmp(&b.wgs84_residual_m.max(b.hk80_residual_m))
+        })
+        .expect("residuals is not empty");
+    let max = worst.wgs84_residual_m.max(worst.hk80_residual_m);
+    eprintln!(
+        "{} control points: mean residual {:.3} m, RMS {:.3} m, largest {:.3} m at {}",
+        residuals.len(),
+        mean,
+        rms,
+        max,
+        worst.id
+    );
+    if let Some(tolerance) = args.tolerance
+        && max > tolerance
+    {
+        anyhow::bail!("Control point {} is {:.3} m off, over the {} m tolerance", worst.id, max, tolerance);
+    }
+    Ok(())
+}
+
+fn export_gpx(config: &Config, args: &GpxArgs) -> Result<()> {
+    let features: HashMap<String, GeoJsonFeature> = load_dataset(config, &DATASETS[0])?.into_iter().collect();
+    let mut tracks = Vec::new();
+    for id in &args.route_ids {
+        let feature = features
+            .get(id)
+            .with_context(|| format!("No centerline with ID {}", id))?;
+        let Geometry::LineString(coords) = &feature.geometry else {
+            continue;
+        };
+        let name = config
+            .search
+            .name_of(&feature.properties)
+            .map_or_else(|| id.clone(), |name| format!("{} ({})", name, id));
+        tracks.push(gpx::Track { name, coords });
+    }
+    gpx::write_gpx(std::io::stdout().lock(), &tracks)
+}
+
+#[cfg(feature = "grpc")]
+fn serve_grpc(args: &ServeGrpcArgs) -> Result<()> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(road_network_json::grpc::serve(
+        args.listen,
+        args.max_document_mb * 1024 * 1024,
+        args.metrics_listen,
+    ))
+}
+
+fn search(args: &SearchArgs) -> Result<()> {
+    let index = SearchIndex::load(&format!("{}/search_index.json", output_dir()))?;
+    let results: Vec<_> = index.search(&args.query).into_iter().take(args.limit).collect();
+    println!("{}", serde_json::to_string_pretty(&results)?);
+    Ok(())
+}
+
+/// Reads every feature of a dataset into memory.
+fn load_dataset(config: &Config, dataset: &Dataset) -> Result<Vec<(String, GeoJsonFeature)>> {
+    let path = dataset.path_in(data_dir());
+    eprintln!("Processing {}...", dataset.file_name);
+    let mut features = Vec::new();
+    parse_gml_file(&path, dataset.id_field, &config.input.parse_options(), |id, feature| {
+        features.push((id, feature));
+        Ok(())
+    })?;
+    Ok(features)
+}
+
+fn reverse_geocode(config: &Config, args: &ReverseGeocodeArgs) -> Result<()> {
+    let features = load_dataset(config, &DATASETS[0])?;
+    let names: Vec<Option<String>> = features
+        .iter()
+        .map(|(_, feature)| config.search.name_of(&feature.properties))
+        .collect();
+    let index = SpatialIndex::build(features.iter().enumerate().filter_map(|(i, (_, feature))| {
+        match &feature.geometry {
+            Geometry::LineString(coords) => Some((i, coords.as_slice())),
+            _ => None,
+        }
+    }));
+
+    let lookup = |lon: f64, lat: f64| {
+        let nearest = index.nearest_where(&[lon, lat], |i| names[i].is_some());
+        GeocodeResult {
+            lon,
+            lat,
+            route_id: nearest.map(|(i, _)| features[i].0.clone()),
+            name: nearest.and_then(|(i, _)| names[i].clone()),
+            distance_m: nearest.map(|(_, distance)| distance),
+        }
+    };
+
+    if let Some([lon, lat]) = args.point {
+        println!("{}", serde_json::to_string_pretty(&lookup(lon, lat))?);
+    } else if let Some(path) = &args.csv {
+        let mut reader = csv::Reader::from_path(path)
+            .with_context(|| format!("Failed to read points: {}", path.display()))?;
+        let mut writer = csv::Writer::from_writer(std::io::stdout());
+        for record in reader.deserialize() {
+            let (lon, lat): (f64, f64) =
+                record.with_context(|| format!("Invalid point in {}", path.display()))?;
+            writer.serialize(lookup(lon, lat))?;
+        }
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Adds the common type of every attribute in `path` to `attributes`, keeping
+/// any type already configured, and reports the attributes written in mixed
+/// types.
+fn settle_attribute_types(path: &str, attributes: &mut HashMap<String, AttributeType>) -> Result<()> {
+    let mut mixed = BTreeSet::new();
+    for (name, types) in gml::attribute_types(path)? {
+        if types.len() > 1 && !attributes.contains_key(&name) {
+            mixed.insert(format!("{} as {}", name, attributes::common_type(&types).name()));
+        }
+        attributes.entry(name).or_insert_with(|| attributes::common_type(&types));
+    }
+    if !mixed.is_empty() {
+        eprintln!("  Attributes written in mixed types: {}", mixed.into_iter().collect::<Vec<_>>().join(", "));
+    }
+    Ok(())
+}
+
+/// Cuts a feature's geometry at the box edges, or returns `None` when nothing of
+/// it lies inside.
+fn clip_feature(mut feature: GeoJsonFeature, bbox: &[f64; 4]) -> Option<GeoJsonFeature> {
+    feature.geometry = match &feature.geometry {
+        Geometry::Point(p) => {
+            let inside = p[0] >= bbox[0] && p[0] <= bbox[2] && p[1] >= bbox[1] && p[1] <= bbox[3];
+            return inside.then_some(feature);
+        }
+        Geometry::LineString(coords) => {
+            let mut parts = geometry::clip_line(coords, bbox);
+            match parts.len() {
+                0 => return None,
+                1 => Geometry::LineString(parts.remove(0)),
+                _ => Geometry::MultiLineString(parts),
             }
-            Ok(Event::Text(e)) => {
-                let text = e.unescape().unwrap_or_default();
-                if in_string_attr || in_int_attr || in_double_attr || in_pos_list {
-                    current_value.push_str(&text);
+        }
+        Geometry::MultiLineString(lines) => {
+            let parts: Vec<_> = lines.iter().flat_map(|coords| geometry::clip_line(coords, bbox)).collect();
+            if parts.is_empty() {
+                return None;
+            }
+            Geometry::MultiLineString(parts)
+        }
+        Geometry::Polygon(_) | Geometry::MultiPolygon(_) => {
+            // A member whose exterior falls outside is dropped with its holes
+            let mut polygons: Vec<Vec<Vec<Vec<f64>>>> = Vec::new();
+            for rings in feature.geometry.polygons() {
+                let clipped: Vec<_> = rings.iter().map(|ring| geometry::clip_ring(ring, bbox)).collect();
+                if clipped.first().is_some_and(|exterior| !exterior.is_empty()) {
+                    polygons.push(clipped.into_iter().filter(|ring| !ring.is_empty()).collect());
                 }
             }
-            Ok(Event::Eof) => break,
-            Err(_) => break,
-            _ => {}
+            match polygons.len() {
+                0 => return None,
+                1 => Geometry::Polygon(polygons.remove(0)),
+                _ => Geometry::MultiPolygon(polygons),
+            }
+        }
+    };
+    Some(feature)
+}
+
+/// Splits every line feature into the parts `split` returns, giving each an
+/// `<id>_<n>` ID and a `parent_id` naming the original feature. Each line of a
+/// MultiLineString, such as one `--clip-bbox` leaves, is split in turn and its
+/// parts numbered on from the line before. Features that aren't lines, or come
+/// back in as many parts as they had lines, are kept whole.
+fn split_features(
+    features: Vec<(String, GeoJsonFeature)>,
+    split: impl Fn(&[Vec<f64>]) -> Vec<Vec<Vec<f64>>>,
+) -> Vec<(String, GeoJsonFeature)> {
+    let mut pieces = Vec::new();
+    for (id, feature) in features {
+        let (parts, lines) = match &feature.geometry {
+            Geometry::LineString(coords) => (split(coords), 1),
+            Geometry::MultiLineString(lines) => (lines.iter().flat_map(|line| split(line)).collect(), lines.len()),
+            _ => (Vec::new(), 0),
+        };
+        if parts.len() <= lines.max(1) {
+            pieces.push((id, feature));
+            continue;
+        }
+        for (n, part) in parts.into_iter().enumerate() {
+            let mut properties = feature.properties.clone();
+            properties
+                .entry("parent_id".to_string())
+                .or_insert_with(|| PropertyValue::String(id.clone()));
+            pieces.push((format!("{}_{}", id, n + 1), GeoJsonFeature::new(Geometry::LineString(part), properties)));
         }
-        buf.clear();
     }
+    pieces
+}
 
-    Ok(GeoJsonFeature {
-        feature_type: "Feature".to_string(),
-        geometry: Geometry {
-            geometry_type: "LineString".to_string(),
-            coordinates,
-        },
-        properties,
-    })
+/// Whether a line is shorter than `--min-length-m` or an area smaller than
+/// `--min-area-m2`. Other geometries are never too small.
+fn below_minimum_size(geometry: &Geometry, args: &ConvertArgs, crs: Crs) -> bool {
+    let distance = crs.distance_fn();
+    let length = |coords: &[Vec<f64>]| -> f64 { coords.windows(2).map(|w| distance(&w[0], &w[1])).sum() };
+    let area = |rings: &[Vec<Vec<f64>>]| -> f64 {
+        let ring_area = crs.ring_area_fn();
+        rings.iter().enumerate().map(|(i, ring)| if i == 0 { ring_area(ring) } else { -ring_area(ring) }).sum()
+    };
+    match geometry {
+        Geometry::LineString(coords) => args.min_length_m.is_some_and(|min| length(coords) < min),
+        Geometry::MultiLineString(lines) => {
+            args.min_length_m.is_some_and(|min| lines.iter().map(|line| length(line)).sum::<f64>() < min)
+        }
+        Geometry::Polygon(_) | Geometry::MultiPolygon(_) => {
+            args.min_area_m2.is_some_and(|min| geometry.polygons().into_iter().map(area).sum::<f64>() < min)
+        }
+        _ => false,
+    }
+}
+
+/// Adds `avg_gradient_pct` (length-weighted) and `max_gradient_pct`, both
+/// absolute, to a line whose every vertex has a height.
+fn add_gradient_properties(feature: &mut GeoJsonFeature, crs: Crs) {
+    let Geometry::LineString(coords) = &feature.geometry else {
+        return;
+    };
+    let Some(pieces) = geometry::gradients(coords, crs.distance_fn()) else {
+        return;
+    };
+    let length: f64 = pieces.iter().map(|(run, _)| run).sum();
+    if length <= 0.0 {
+        return;
+    }
+    let average = pieces.iter().map(|(run, gradient)| run * gradient.abs()).sum::<f64>() / length;
+    let max = pieces.iter().map(|(_, gradient)| gradient.abs()).fold(0.0, f64::max);
+    feature.properties.insert("avg_gradient_pct".to_string(), PropertyValue::Float(average * 100.0));
+    feature.properties.insert("max_gradient_pct".to_string(), PropertyValue::Float(max * 100.0));
+}
+
+/// Writes one converted feature plus any companion layers enabled in `args`.
+fn write_dataset_feature(
+    sink: &mut dyn Sink,
+    dataset: &Dataset,
+    args: &ConvertArgs,
+    crs: Crs,
+    id: &str,
+    feature: &GeoJsonFeature,
+) -> Result<()> {
+    let layer = dataset.output_subdir;
+    perf::time(Stage::Serialization, || sink.write(layer, id, feature))?;
+
+    if let Some(method) = args.points
+        && let Some(point) = label_point(feature, dataset.kind, method, crs)
+    {
+        perf::time(Stage::Serialization, || sink.write(&format!("{}_points", layer), id, &point))?;
+    }
+    if args.endpoints
+        && dataset.kind == DatasetKind::Line
+        && let Some(nodes) = endpoint_features(feature, id, crs)
+    {
+        perf::time(Stage::Serialization, || sink.write_group(&format!("{}_endpoints", layer), id, &nodes))?;
+    }
+    Ok(())
+}
+
+/// Builds the label point for a feature: the midpoint of a line, or the
+/// centroid / point-on-surface of a zone's largest member.
+fn label_point(feature: &GeoJsonFeature, kind: DatasetKind, method: PointMethod, crs: Crs) -> Option<GeoJsonFeature> {
+    if let Geometry::Polygon(_) | Geometry::MultiPolygon(_) = &feature.geometry {
+        let rings = largest_polygon(&feature.geometry, crs)?;
+        let point = match method {
+            PointMethod::Centroid => geometry::ring_centroid(rings.first()?)?,
+            PointMethod::PointOnSurface => geometry::polygon_point_on_surface(rings)?,
+        };
+        return Some(GeoJsonFeature::new(Geometry::Point(point), feature.properties.clone()));
+    }
+    let Geometry::LineString(coords) = &feature.geometry else {
+        return None;
+    };
+    let point = match (kind, method) {
+        (DatasetKind::Line, _) => geometry::line_midpoint(coords, crs.distance_fn())?,
+        (DatasetKind::Zone, PointMethod::Centroid) => geometry::ring_centroid(coords)?,
+        (DatasetKind::Zone, PointMethod::PointOnSurface) => geometry::point_on_surface(coords)?,
+    };
+    Some(GeoJsonFeature::new(Geometry::Point(point), feature.properties.clone()))
+}
+
+/// The rings of a polygon's member with the largest exterior, measured in `crs`.
+fn largest_polygon(geometry: &Geometry, crs: Crs) -> Option<&[Vec<Vec<f64>>]> {
+    let ring_area = crs.ring_area_fn();
+    let exterior_area = |rings: &&[Vec<Vec<f64>>]| rings.first().map_or(0.0, |ring| ring_area(ring));
+    geometry
+        .polygons()
+        .into_iter()
+        .max_by(|a, b| exterior_area(a).total_cmp(&exterior_area(b)))
+}
+
+/// Builds the start and end node points of a segment. The start node carries the
+/// bearing the segment leaves it on; the end node the bearing it arrives on.
+fn endpoint_features(feature: &GeoJsonFeature, id: &str, crs: Crs) -> Option<Vec<GeoJsonFeature>> {
+    let Geometry::LineString(coords) = &feature.geometry else {
+        return None;
+    };
+    // Skip repeated vertices so a duplicated first/last point still yields a bearing.
+    let start = coords.first()?;
+    let end = coords.last()?;
+    let after_start = coords.iter().find(|p| *p != start)?;
+    let before_end = coords.iter().rev().find(|p| *p != end)?;
+
+    let node = |sequence: i64, role: &str, position: &[f64], bearing: f64| {
+        let mut properties = HashMap::new();
+        properties.insert("segment_id".to_string(), PropertyValue::String(id.to_string()));
+        properties.insert("sequence".to_string(), PropertyValue::Int(sequence));
+        properties.insert("node".to_string(), PropertyValue::String(role.to_string()));
+        properties.insert("bearing".to_string(), PropertyValue::Float(bearing));
+        GeoJsonFeature::new(Geometry::Point(position.to_vec()), properties)
+    };
+
+    let bearing = crs.bearing_fn();
+    Some(vec![
+        node(0, "start", start, bearing(start, after_start)),
+        node(1, "end", end, bearing(before_end, end)),
+    ])
 }
@@ -0,0 +1,108 @@
+//! Counters for the long-running `serve-grpc` mode, served as Prometheus text
+//! on `/metrics`.
+
+use anyhow::{Context, Result};
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Upper bounds of the conversion duration histogram, in seconds.
+const DURATION_BUCKETS: &[f64] = &[0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0];
+
+#[derive(Default)]
+pub struct Metrics {
+    features: AtomicU64,
+    conversions: AtomicU64,
+    errors: AtomicU64,
+    /// Conversions at or under each of [`DURATION_BUCKETS`].
+    duration_buckets: [AtomicU64; DURATION_BUCKETS.len()],
+    duration_micros: AtomicU64,
+}
+
+impl Metrics {
+    pub fn add_features(&self, count: u64) {
+        self.features.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Records a finished conversion, successful or not.
+    pub fn record_conversion(&self, duration: Duration, succeeded: bool) {
+        self.conversions.fetch_add(1, Ordering::Relaxed);
+        if !succeeded {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        let seconds = duration.as_secs_f64();
+        for (bound, count) in DURATION_BUCKETS.iter().zip(&self.duration_buckets) {
+            if seconds <= *bound {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.duration_micros.fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// The Prometheus text exposition of every metric.
+    pub fn render(&self) -> String {
+        let conversions = self.conversions.load(Ordering::Relaxed);
+        let mut text = String::new();
+        let _ = writeln!(text, "# HELP road_network_features_converted_total Features sent to clients.");
+        let _ = writeln!(text, "# TYPE road_network_features_converted_total counter");
+        let _ = writeln!(text, "road_network_features_converted_total {}", self.features.load(Ordering::Relaxed));
+        let _ = writeln!(text, "# HELP road_network_conversion_errors_total Documents that failed to convert.");
+        let _ = writeln!(text, "# TYPE road_network_conversion_errors_total counter");
+        let _ = writeln!(text, "road_network_conversion_errors_total {}", self.errors.load(Ordering::Relaxed));
+        let _ = writeln!(text, "# HELP road_network_conversion_duration_seconds Time to convert a document.");
+        let _ = writeln!(text, "# TYPE road_network_conversion_duration_seconds histogram");
+        for (bound, count) in DURATION_BUCKETS.iter().zip(&self.duration_buckets) {
+            let _ = writeln!(
+                text,
+                "road_network_conversion_duration_seconds_bucket{{le=\"{}\"}} {}",
+                bound,
+                count.load(Ordering::Relaxed)
+            );
+        }
+        let _ = writeln!(text, "road_network_conversion_duration_seconds_bucket{{le=\"+Inf\"}} {}", conversions);
+        let _ = writeln!(
+            text,
+            "road_network_conversion_duration_seconds_sum {}",
+            self.duration_micros.load(Ordering::Relaxed) as f64 / 1e6
+        );
+        let _ = writeln!(text, "road_network_conversion_duration_seconds_count {}", conversions);
+        text
+    }
+}
+
+/// Answers `GET /metrics` on `address` until the process is stopped.
+pub async fn serve(address: SocketAddr, metrics: Arc<Metrics>) -> Result<()> {
+    let listener = TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Failed to listen on {}", address))?;
+    eprintln!("Serving metrics on http://{}/metrics", address);
+    loop {
+        let Ok((mut stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let metrics = Arc::clone(&metrics);
+        tokio::spawn(async move {
+            // The request line is all that matters; scrapers send short requests
+            let mut request = [0; 1024];
+            let Ok(read) = stream.read(&mut request).await else {
+                return;
+            };
+            let request = String::from_utf8_lossy(&request[..read]);
+            let response = if request.starts_with("GET /metrics ") {
+                let body = metrics.render();
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            } else {
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+            };
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
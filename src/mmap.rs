@@ -0,0 +1,43 @@
+//! Uncompressed inputs, memory-mapped rather than read onto the heap.
+//!
+//! A multi-GB GML file would otherwise need an allocation of its full size
+//! before parsing could start. Mapped, its pages are read in as the parser
+//! reaches them and can be dropped again by the OS under memory pressure.
+
+use anyhow::{Context, Result};
+use memmap2::Mmap;
+use std::fs::{self, File};
+use std::ops::Deref;
+
+/// The bytes of an input file.
+pub enum InputFile {
+    Mapped(Mmap),
+    /// Read into memory, where the file could not be mapped.
+    Read(Vec<u8>),
+}
+
+impl InputFile {
+    pub fn open(path: &str) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Failed to read file: {}", path))?;
+        // SAFETY: inputs are not expected to change while they are converted. If
+        // one does, the parser sees garbled XML, which it reports as an error.
+        match unsafe { Mmap::map(&file) } {
+            Ok(map) => Ok(InputFile::Mapped(map)),
+            Err(_) => {
+                let bytes = fs::read(path).with_context(|| format!("Failed to read file: {}", path))?;
+                Ok(InputFile::Read(bytes))
+            }
+        }
+    }
+}
+
+impl Deref for InputFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            InputFile::Mapped(map) => map,
+            InputFile::Read(bytes) => bytes,
+        }
+    }
+}
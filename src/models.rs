@@ -0,0 +1,142 @@
+//! Typed views of the two datasets' features, for applications that want
+//! checked access to the common attributes instead of a property map.
+//!
+//! ```no_run
+//! use road_network_json::dataset::DATASETS;
+//! use road_network_json::gml::{self, ParseOptions};
+//! use road_network_json::models::Centerline;
+//!
+//! gml::parse_gml_file("input/CENTERLINE.gml", DATASETS[0].id_field, &ParseOptions::default(), |_, feature| {
+//!     let centerline = Centerline::try_from(feature)?;
+//!     println!("{} {:?}", centerline.route_id, centerline.street_name_en);
+//!     Ok(())
+//! })?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use crate::geojson::{GeoJsonFeature, Geometry, PropertyValue};
+use anyhow::{Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A feature of `CENTERLINE.gml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Centerline {
+    /// `ROUTE_ID`.
+    pub route_id: String,
+    /// `STREET_ENAME`.
+    pub street_name_en: Option<String>,
+    /// `STREET_CNAME`, in traditional Chinese.
+    pub street_name_tc: Option<String>,
+    /// `TRAVEL_DIRECTION`: 1 both ways, 2 along the line, 3 against it.
+    pub travel_direction: Option<i64>,
+    /// `EXIT_NUM`, kept as text since some exit numbers have leading zeros.
+    pub exit_num: Option<String>,
+    /// `SHAPE_LENGTH`, in metres as measured by the source.
+    pub shape_length: Option<f64>,
+    /// A `LineString` or `MultiLineString`.
+    pub geometry: Geometry,
+    /// Every other attribute.
+    pub other: HashMap<String, PropertyValue>,
+}
+
+/// A feature of `PEDESTRIAN_ZONE.gml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PedestrianZone {
+    /// `PED_ZONE_ID`.
+    pub ped_zone_id: String,
+    /// `STREET_ENAME`.
+    pub street_name_en: Option<String>,
+    /// `STREET_CNAME`, in traditional Chinese.
+    pub street_name_tc: Option<String>,
+    /// A `Polygon` or `MultiPolygon`.
+    pub geometry: Geometry,
+    /// Every other attribute.
+    pub other: HashMap<String, PropertyValue>,
+}
+
+impl TryFrom<GeoJsonFeature> for Centerline {
+    type Error = anyhow::Error;
+
+    fn try_from(feature: GeoJsonFeature) -> Result<Self> {
+        // An empty line stands in for geometry that wasn't read
+        if !matches!(feature.geometry, Geometry::LineString(_) | Geometry::MultiLineString(_)) {
+            bail!("A centerline must be a line, not {}", geometry_type(&feature.geometry));
+        }
+        let mut properties = feature.properties;
+        Ok(Centerline {
+            route_id: take_text(&mut properties, "ROUTE_ID")?.ok_or_else(|| anyhow!("Feature has no ROUTE_ID"))?,
+            street_name_en: take_string(&mut properties, "STREET_ENAME")?,
+            street_name_tc: take_string(&mut properties, "STREET_CNAME")?,
+            travel_direction: take_int(&mut properties, "TRAVEL_DIRECTION")?,
+            exit_num: take_text(&mut properties, "EXIT_NUM")?,
+            shape_length: take_float(&mut properties, "SHAPE_LENGTH")?,
+            geometry: feature.geometry,
+            other: properties,
+        })
+    }
+}
+
+impl TryFrom<GeoJsonFeature> for PedestrianZone {
+    type Error = anyhow::Error;
+
+    fn try_from(feature: GeoJsonFeature) -> Result<Self> {
+        let empty = matches!(&feature.geometry, Geometry::LineString(line) if line.is_empty());
+        if !empty && !matches!(feature.geometry, Geometry::Polygon(_) | Geometry::MultiPolygon(_)) {
+            bail!("A pedestrian zone must be a polygon, not {}", geometry_type(&feature.geometry));
+        }
+        let mut properties = feature.properties;
+        Ok(PedestrianZone {
+            ped_zone_id: take_text(&mut properties, "PED_ZONE_ID")?.ok_or_else(|| anyhow!("Feature has no PED_ZONE_ID"))?,
+            street_name_en: take_string(&mut properties, "STREET_ENAME")?,
+            street_name_tc: take_string(&mut properties, "STREET_CNAME")?,
+            geometry: feature.geometry,
+            other: properties,
+        })
+    }
+}
+
+fn geometry_type(geometry: &Geometry) -> &'static str {
+    match geometry {
+        Geometry::Point(_) => "a Point",
+        Geometry::LineString(_) => "a LineString",
+        Geometry::MultiLineString(_) => "a MultiLineString",
+        Geometry::Polygon(_) => "a Polygon",
+        Geometry::MultiPolygon(_) => "a MultiPolygon",
+    }
+}
+
+/// A code such as an ID, which sources write as either a string or an int.
+fn take_text(properties: &mut HashMap<String, PropertyValue>, name: &str) -> Result<Option<String>> {
+    match properties.remove(name) {
+        Some(PropertyValue::String(value)) => Ok(Some(value)),
+        Some(PropertyValue::Int(value)) => Ok(Some(value.to_string())),
+        Some(PropertyValue::Null) | None => Ok(None),
+        Some(other) => Err(anyhow!("{} is {}, expected a string", name, other)),
+    }
+}
+
+fn take_string(properties: &mut HashMap<String, PropertyValue>, name: &str) -> Result<Option<String>> {
+    match properties.remove(name) {
+        Some(PropertyValue::String(value)) => Ok(Some(value)),
+        Some(PropertyValue::Null) | None => Ok(None),
+        Some(other) => Err(anyhow!("{} is {}, expected a string", name, other)),
+    }
+}
+
+fn take_int(properties: &mut HashMap<String, PropertyValue>, name: &str) -> Result<Option<i64>> {
+    match properties.remove(name) {
+        Some(PropertyValue::Int(value)) => Ok(Some(value)),
+        Some(PropertyValue::Null) | None => Ok(None),
+        Some(other) => Err(anyhow!("{} is {}, expected an int", name, other)),
+    }
+}
+
+fn take_float(properties: &mut HashMap<String, PropertyValue>, name: &str) -> Result<Option<f64>> {
+    match properties.remove(name) {
+        Some(PropertyValue::Float(value)) => Ok(Some(value)),
+        Some(PropertyValue::Int(value)) => Ok(Some(value as f64)),
+        Some(PropertyValue::Null) | None => Ok(None),
+        Some(other) => Err(anyhow!("{} is {}, expected a float", name, other)),
+    }
+}
@@ -0,0 +1,49 @@
+//! `neo4j-admin database import` CSV export of the routing graph.
+//!
+//! Writes `nodes.csv` (label `Junction`, with a WGS-84 `location` point) and
+//! `relationships.csv` (type `ROAD`, one per directed edge).
+
+use crate::graph::Graph;
+use crate::paths;
+use anyhow::{Context, Result};
+
+pub fn write_bulk_import(graph: &Graph, dir: &str) -> Result<()> {
+    let nodes_path = format!("{}/nodes.csv", dir);
+    let mut nodes = csv::Writer::from_path(paths::long_path(&nodes_path))
+        .with_context(|| format!("Failed to create {}", nodes_path))?;
+    nodes.write_record(["nodeId:ID", "location:Point{crs:WGS-84}", ":LABEL"])?;
+    for (id, node) in graph.nodes.iter().enumerate() {
+        nodes.write_record([
+            id.to_string(),
+            format!("{{longitude:{},latitude:{}}}", node.coord[0], node.coord[1]),
+            "Junction".to_string(),
+        ])?;
+    }
+    nodes.flush()?;
+
+    let relationships_path = format!("{}/relationships.csv", dir);
+    let mut relationships = csv::Writer::from_path(paths::long_path(&relationships_path))
+        .with_context(|| format!("Failed to create {}", relationships_path))?;
+    relationships.write_record([
+        ":START_ID",
+        ":END_ID",
+        ":TYPE",
+        "edgeId:long",
+        "segmentId",
+        "lengthM:double",
+        "reversed:boolean",
+    ])?;
+    for (id, edge) in graph.edges.iter().enumerate() {
+        relationships.write_record([
+            edge.from.to_string(),
+            edge.to.to_string(),
+            "ROAD".to_string(),
+            id.to_string(),
+            edge.segment_id.clone(),
+            edge.length_m.to_string(),
+            edge.reversed.to_string(),
+        ])?;
+    }
+    relationships.flush()?;
+    Ok(())
+}
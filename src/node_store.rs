@@ -0,0 +1,51 @@
+//! Segment endpoints deduplicated across threads, for building the routing
+//! graph in parallel.
+//!
+//! The store is a hash of exact positions split into shards, each behind its
+//! own lock, so threads interning endpoints of different segments rarely wait
+//! on each other. Each distinct position gets a provisional ID the first time
+//! any thread meets it; which ID depends on thread timing, so
+//! [`Graph::add_segments`](crate::graph::Graph::add_segments) renumbers them in
+//! the order the segments were read.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Shards of the store; a power of two.
+const SHARDS: usize = 64;
+
+pub struct NodeStore {
+    shards: Vec<Mutex<HashMap<(u64, u64), usize>>>,
+    next: AtomicUsize,
+}
+
+impl Default for NodeStore {
+    fn default() -> Self {
+        NodeStore {
+            shards: (0..SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl NodeStore {
+    /// The provisional ID of the node at `coord`, added if this is the first
+    /// time the position is seen. Only the first two ordinates count.
+    pub fn intern(&self, coord: &[f64]) -> usize {
+        let key = (coord[0].to_bits(), coord[1].to_bits());
+        let mixed = (key.0 ^ key.1.rotate_left(32)).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        let shard = (mixed >> (64 - SHARDS.trailing_zeros())) as usize;
+        let mut nodes = self.shards[shard].lock().expect("node store shard poisoned");
+        *nodes.entry(key).or_insert_with(|| self.next.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Distinct positions interned so far; provisional IDs run from 0 to this.
+    pub fn len(&self) -> usize {
+        self.next.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
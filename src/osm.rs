@@ -0,0 +1,97 @@
+//! OSM XML export of the routing graph, readable by `osrm-extract` and other
+//! OpenStreetMap tooling.
+//!
+//! Each centerline segment becomes a way tagged with `highway`, `name`, `ref` and
+//! `oneway`; turn restrictions become `type=restriction` relations.
+
+use crate::config::{Direction, OsmConfig};
+use crate::graph::Graph;
+use crate::paths;
+use anyhow::{Context, Result};
+use quick_xml::escape::escape;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+pub fn write_osm_xml(graph: &Graph, config: &OsmConfig, path: &str) -> Result<()> {
+    let file = File::create(paths::long_path(path)).with_context(|| format!("Failed to create {}", path))?;
+    let mut out = BufWriter::new(file);
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(out, r#"<osm version="0.6" generator="road-network-json">"#)?;
+
+    // Every distinct vertex becomes a node; shared vertices share an ID so ways connect.
+    let mut node_ids: HashMap<(u64, u64), i64> = HashMap::new();
+    for segment in &graph.segments {
+        for coord in &segment.coords {
+            let key = (coord[0].to_bits(), coord[1].to_bits());
+            if !node_ids.contains_key(&key) {
+                let id = node_ids.len() as i64 + 1;
+                node_ids.insert(key, id);
+                writeln!(
+                    out,
+                    r#"  <node id="{}" version="1" lat="{:.7}" lon="{:.7}"/>"#,
+                    id, coord[1], coord[0]
+                )?;
+            }
+        }
+    }
+
+    let mut way_ids: HashMap<&str, i64> = HashMap::new();
+    for (index, segment) in graph.segments.iter().enumerate() {
+        let way_id = index as i64 + 1;
+        way_ids.insert(segment.id.as_str(), way_id);
+        writeln!(out, r#"  <way id="{}" version="1">"#, way_id)?;
+        for coord in &segment.coords {
+            writeln!(out, r#"    <nd ref="{}"/>"#, node_ids[&(coord[0].to_bits(), coord[1].to_bits())])?;
+        }
+
+        let mut tags = vec![
+            ("highway", config.highway(&segment.properties).to_string()),
+            ("ref", segment.id.clone()),
+        ];
+        if let Some(name) = segment.properties.get(&config.name_attribute)
+            && !name.to_string().is_empty()
+        {
+            tags.push(("name", name.to_string()));
+        }
+        match segment.direction {
+            Direction::Both => {}
+            Direction::Forward => tags.push(("oneway", "yes".to_string())),
+            Direction::Backward => tags.push(("oneway", "-1".to_string())),
+            Direction::Closed => tags.push(("access", "no".to_string())),
+        }
+        for (key, value) in tags {
+            writeln!(out, r#"    <tag k="{}" v="{}"/>"#, key, escape(value.as_str()))?;
+        }
+        writeln!(out, "  </way>")?;
+    }
+
+    let mut turns: Vec<_> = graph.banned_turns.iter().copied().collect();
+    turns.sort_unstable();
+    for (index, (from, to)) in turns.into_iter().enumerate() {
+        let via = &graph.nodes[graph.edges[from].to].coord;
+        writeln!(out, r#"  <relation id="{}" version="1">"#, index + 1)?;
+        writeln!(
+            out,
+            r#"    <member type="way" ref="{}" role="from"/>"#,
+            way_ids[graph.edges[from].segment_id.as_str()]
+        )?;
+        writeln!(
+            out,
+            r#"    <member type="node" ref="{}" role="via"/>"#,
+            node_ids[&(via[0].to_bits(), via[1].to_bits())]
+        )?;
+        writeln!(
+            out,
+            r#"    <member type="way" ref="{}" role="to"/>"#,
+            way_ids[graph.edges[to].segment_id.as_str()]
+        )?;
+        writeln!(out, r#"    <tag k="type" v="restriction"/>"#)?;
+        writeln!(out, r#"    <tag k="restriction" v="no_entry"/>"#)?;
+        writeln!(out, "  </relation>")?;
+    }
+
+    writeln!(out, "</osm>")?;
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! Output paths built from feature IDs, street names and district names, kept
+//! valid on every platform.
+//!
+//! Windows forbids some characters and device names such as `CON` or `COM1` in
+//! file names, and refuses paths longer than 260 characters unless they are
+//! written in the `\\?\` form. Names are cut to a length every common file system
+//! takes, without splitting a UTF-8 character, so Chinese names stay readable.
+
+use std::path::{Path, PathBuf};
+
+/// Longest file name [`file_name`] returns, in bytes. NTFS and ext4 allow 255,
+/// less the room callers need for an extension.
+pub const MAX_NAME_BYTES: usize = 240;
+
+/// Device names Windows reserves, with or without an extension.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1",
+    "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Turns an ID or name into a single file name that is valid on Windows, macOS
+/// and Linux. Path separators, characters Windows forbids and control characters
+/// become `_`, trailing dots and spaces are dropped, and reserved device names
+/// get a `_` after them. Names that are already valid are returned unchanged.
+///
+/// ```
+/// use road_network_json::paths::file_name;
+///
+/// assert_eq!(file_name("1001"), "1001");
+/// assert_eq!(file_name("彌敦道 Nathan Road"), "彌敦道 Nathan Road");
+/// assert_eq!(file_name("A/B:C?"), "A_B_C_");
+/// assert_eq!(file_name("con.txt"), "con_.txt");
+/// assert_eq!(file_name(".."), "_");
+/// ```
+pub fn file_name(name: &str) -> String {
+    let mut safe: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    if safe.len() > MAX_NAME_BYTES {
+        let mut end = MAX_NAME_BYTES;
+        while !safe.is_char_boundary(end) {
+            end -= 1;
+        }
+        safe.truncate(end);
+    }
+    let trimmed = safe.trim_end_matches(['.', ' ']).len();
+    safe.truncate(trimmed);
+    if safe.is_empty() {
+        return "_".to_string();
+    }
+    let stem = safe.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(stem)) {
+        safe.insert(stem.len(), '_');
+    }
+    safe
+}
+
+/// The path to hand to the file system for `path`. On Windows, paths near the
+/// 260-character limit are made absolute and given the `\\?\` prefix, or
+/// `\\?\UNC\` for network shares, which lifts the limit. Elsewhere the path is
+/// returned as it is.
+pub fn long_path(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    #[cfg(windows)]
+    {
+        // Directories are limited to 248 characters, leaving room for an 8.3 name
+        const LIMIT: usize = 248;
+        let absolute = std::path::absolute(path).ok();
+        if let Some(absolute) = absolute.as_deref().and_then(std::path::Path::to_str)
+            && !absolute.starts_with(r"\\?\")
+            && absolute.encode_utf16().count() >= LIMIT
+        {
+            return match absolute.strip_prefix(r"\\") {
+                Some(share) => PathBuf::from(format!(r"\\?\UNC\{}", share)),
+                None => PathBuf::from(format!(r"\\?\{}", absolute)),
+            };
+        }
+    }
+    path.to_path_buf()
+}
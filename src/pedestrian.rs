@@ -0,0 +1,125 @@
+//! Walkable network derived from pedestrian zones, footbridges, subways and road
+//! crossings, optionally using road centerlines as a stand-in for footways.
+//!
+//! Every piece is walkable in both directions. Zone outlines are added side by
+//! side so each vertex becomes a node, and the loose ends of footbridges,
+//! subways and crossings are joined to the nearest other node within a tolerance
+//! by short `connector` edges, since separately digitised layers rarely share
+//! exact vertices.
+
+use crate::config::{Direction, DirectionConfig};
+use crate::geojson::{GeoJsonFeature, GeoJsonFeatureCollection, Geometry, PropertyValue};
+use crate::graph::Graph;
+use crate::spatial::SpatialIndex;
+use std::collections::{HashMap, HashSet};
+
+/// Layers whose dangling ends are joined to the rest of the network.
+const CONNECTING_KINDS: &[&str] = &["footbridge", "subway", "crossing"];
+
+pub struct PedestrianNetwork {
+    pub graph: Graph,
+    walkable: DirectionConfig,
+    /// Layer kind of every segment, by segment ID.
+    kinds: HashMap<String, &'static str>,
+}
+
+impl Default for PedestrianNetwork {
+    fn default() -> Self {
+        PedestrianNetwork {
+            graph: Graph::default(),
+            walkable: DirectionConfig {
+                attribute: String::new(),
+                default: Direction::Both,
+                values: HashMap::new(),
+            },
+            kinds: HashMap::new(),
+        }
+    }
+}
+
+impl PedestrianNetwork {
+    /// Adds a line feature of the given kind (`footbridge`, `road`, ...).
+    pub fn add_line(&mut self, kind: &'static str, id: &str, feature: &GeoJsonFeature) {
+        let segment_id = format!("{}:{}", kind, id);
+        self.graph.add_segment(&segment_id, feature, &self.walkable);
+        self.kinds.insert(segment_id, kind);
+    }
+
+    /// Adds a pedestrian zone's rings, one segment per side, numbered across
+    /// every ring of every member.
+    pub fn add_zone(&mut self, id: &str, feature: &GeoJsonFeature) {
+        let polygons = feature.geometry.polygons();
+        let sides = polygons.iter().flat_map(|rings| rings.iter()).flat_map(|ring| ring.windows(2));
+        for (i, side) in sides.enumerate() {
+            let side = GeoJsonFeature::new(Geometry::LineString(side.to_vec()), feature.properties.clone());
+            let segment_id = format!("zone:{}:{}", id, i);
+            self.graph.add_segment(&segment_id, &side, &self.walkable);
+            self.kinds.insert(segment_id, "zone");
+        }
+    }
+
+    /// Joins each dangling end of a footbridge, subway or crossing to the nearest
+    /// node of another segment within `tolerance_m`. Returns how many connectors
+    /// were added.
+    pub fn connect(&mut self, tolerance_m: f64) -> usize {
+        let mut degree = vec![0usize; self.graph.nodes.len()];
+        let mut segments_at: Vec<HashSet<&str>> = vec![HashSet::new(); self.graph.nodes.len()];
+        for edge in &self.graph.edges {
+            degree[edge.from] += 1;
+            segments_at[edge.from].insert(edge.segment_id.as_str());
+            segments_at[edge.to].insert(edge.segment_id.as_str());
+        }
+
+        let dangling: Vec<usize> = (0..self.graph.nodes.len())
+            .filter(|&node| {
+                // A two-way segment leaves one outgoing edge at each of its ends
+                degree[node] == 1
+                    && segments_at[node]
+                        .iter()
+                        .any(|segment| self.kinds.get(*segment).is_some_and(|kind| CONNECTING_KINDS.contains(kind)))
+            })
+            .collect();
+        let index = SpatialIndex::build(
+            self.graph
+                .nodes
+                .iter()
+                .enumerate()
+                .map(|(id, node)| (id, std::slice::from_ref(&node.coord))),
+        );
+
+        let mut joined = HashSet::new();
+        let mut connectors = Vec::new();
+        for node in dangling {
+            let own = &segments_at[node];
+            let nearest = index.nearest_where(&self.graph.nodes[node].coord, |other| {
+                other != node && segments_at[other].iter().all(|segment| !own.contains(segment))
+            });
+            if let Some((other, distance)) = nearest
+                && distance <= tolerance_m
+                && joined.insert((node.min(other), node.max(other)))
+            {
+                let coords = vec![self.graph.nodes[node].coord.clone(), self.graph.nodes[other].coord.clone()];
+                connectors.push(GeoJsonFeature::new(Geometry::LineString(coords), HashMap::new()));
+            }
+        }
+
+        let added = connectors.len();
+        for (i, connector) in connectors.iter().enumerate() {
+            self.add_line("connector", &i.to_string(), connector);
+        }
+        added
+    }
+
+    /// The graph's edges with a `kind` property naming the layer each came from.
+    pub fn edges_geojson(&self) -> GeoJsonFeatureCollection {
+        let mut collection = self.graph.edges_geojson();
+        for (feature, edge) in collection.features.iter_mut().zip(&self.graph.edges) {
+            if let Some(kind) = self.kinds.get(&edge.segment_id) {
+                feature
+                    .properties
+                    .insert("kind".to_string(), PropertyValue::String(kind.to_string()));
+            }
+        }
+        collection
+    }
+}
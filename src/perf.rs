@@ -0,0 +1,154 @@
+//! Wall-time breakdown of a conversion by stage, for `--perf-report`.
+//!
+//! Code marks the stage it is in with [`enter`]; the time until the returned
+//! guard is dropped is charged to that stage, minus any time spent in stages
+//! entered inside it. Stages are tracked per thread, so they add up to the
+//! wall time of the thread doing the conversion. Work done on other threads,
+//! such as inflating a gzipped input or writing files from the writer pool,
+//! only shows up where the converting thread waits for it.
+//!
+//! Nothing is timed until [`enable`] is called, so the marks cost a flag check
+//! on ordinary runs.
+
+use serde::Serialize;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// What the converting thread is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Anything not marked otherwise: transforms, filters, companion layers.
+    Other,
+    /// Opening input files and waiting for data from them.
+    Read,
+    /// Tokenizing the XML and picking out features, attributes and coordinates.
+    Xml,
+    /// Turning attribute text into typed values.
+    Attributes,
+    /// Swapping axes and projecting coordinates.
+    Projection,
+    /// Encoding features in the output format.
+    Serialization,
+    /// Writing output, or waiting for the writer threads.
+    Io,
+}
+
+const STAGES: usize = 7;
+
+/// Time spent in each stage, in milliseconds.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StageTimes {
+    pub total_ms: f64,
+    pub read_ms: f64,
+    pub xml_parse_ms: f64,
+    pub attribute_parse_ms: f64,
+    pub projection_ms: f64,
+    pub serialization_ms: f64,
+    pub io_ms: f64,
+    pub other_ms: f64,
+}
+
+impl StageTimes {
+    fn from_totals(totals: &[Duration; STAGES]) -> Self {
+        // To the microsecond, which is finer than the timer's own cost
+        let rounded = |duration: Duration| (duration.as_secs_f64() * 1e6).round() / 1000.0;
+        let ms = |stage: Stage| rounded(totals[stage as usize]);
+        StageTimes {
+            total_ms: rounded(totals.iter().sum()),
+            read_ms: ms(Stage::Read),
+            xml_parse_ms: ms(Stage::Xml),
+            attribute_parse_ms: ms(Stage::Attributes),
+            projection_ms: ms(Stage::Projection),
+            serialization_ms: ms(Stage::Serialization),
+            io_ms: ms(Stage::Io),
+            other_ms: ms(Stage::Other),
+        }
+    }
+
+    /// A one-line summary for the console, in seconds.
+    pub fn describe(&self) -> String {
+        let stages = [
+            ("read", self.read_ms),
+            ("XML", self.xml_parse_ms),
+            ("attributes", self.attribute_parse_ms),
+            ("projection", self.projection_ms),
+            ("serialization", self.serialization_ms),
+            ("IO", self.io_ms),
+            ("other", self.other_ms),
+        ];
+        let parts: Vec<String> = stages.iter().map(|(name, ms)| format!("{} {:.2}s", name, ms / 1000.0)).collect();
+        format!("{:.2}s: {}", self.total_ms / 1000.0, parts.join(", "))
+    }
+}
+
+struct Timer {
+    stage: Stage,
+    since: Instant,
+    totals: [Duration; STAGES],
+}
+
+impl Timer {
+    /// Charges the time since the last switch to the current stage and moves
+    /// to `stage`.
+    fn switch(&mut self, stage: Stage) -> Stage {
+        let now = Instant::now();
+        self.totals[self.stage as usize] += now - self.since;
+        self.since = now;
+        std::mem::replace(&mut self.stage, stage)
+    }
+}
+
+thread_local! {
+    static TIMER: RefCell<Timer> = RefCell::new(Timer {
+        stage: Stage::Other,
+        since: Instant::now(),
+        totals: [Duration::ZERO; STAGES],
+    });
+}
+
+/// Starts timing stages, for the rest of the process.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Marks the current thread as in `stage` until the guard is dropped.
+#[must_use = "the stage ends when the guard is dropped"]
+pub fn enter(stage: Stage) -> Guard {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return Guard(None);
+    }
+    Guard(Some(TIMER.with(|timer| timer.borrow_mut().switch(stage))))
+}
+
+/// Runs `work` in `stage`.
+pub fn time<T>(stage: Stage, work: impl FnOnce() -> T) -> T {
+    let _guard = enter(stage);
+    work()
+}
+
+/// Returns the current thread's time per stage since the last call, or since
+/// it started, and starts counting again from zero.
+pub fn take() -> StageTimes {
+    TIMER.with(|timer| {
+        let mut timer = timer.borrow_mut();
+        let stage = timer.stage;
+        timer.switch(stage);
+        let times = StageTimes::from_totals(&timer.totals);
+        timer.totals = [Duration::ZERO; STAGES];
+        times
+    })
+}
+
+/// Returns to the stage that was current before [`enter`] when dropped.
+pub struct Guard(Option<Stage>);
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.0 {
+            TIMER.with(|timer| timer.borrow_mut().switch(previous));
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Async conversion (`tokio` feature), for services that run on tokio and write
+//! to async destinations such as object stores, databases or HTTP APIs.
+//!
+//! Files are read with async IO. Parsing is CPU-bound, so it runs on a blocking
+//! thread and hands features over a bounded channel; a slow sink holds up the
+//! parser instead of features piling up in memory.
+//!
+//! ```no_run
+//! use road_network_json::pipeline::{self, AsyncFileSink};
+//! use road_network_json::gml::ParseOptions;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let mut sink = AsyncFileSink::new("output");
+//! pipeline::convert_async("input", &ParseOptions::default(), &mut sink).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::dataset::{DATASETS, Dataset};
+use crate::geojson::GeoJsonFeature;
+use crate::gml::{self, ParseOptions, ParseSummary};
+use crate::paths;
+use anyhow::{Context, Result, anyhow};
+use std::collections::HashSet;
+use std::future::Future;
+use tokio::sync::mpsc;
+
+/// Features in flight between the parser and the sink.
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Where [`convert_async`] sends features: the async counterpart of
+/// [`Sink`](crate::sink::Sink).
+pub trait AsyncSink {
+    fn write(&mut self, layer: &str, id: &str, feature: GeoJsonFeature) -> impl Future<Output = Result<()>> + Send;
+
+    /// Flushes anything buffered once every dataset has been converted.
+    fn finish(&mut self) -> impl Future<Output = Result<()>> + Send {
+        async { Ok(()) }
+    }
+}
+
+/// Converts every dataset found in `data_dir`, skipping those whose file is
+/// missing, and finishes the sink. Returns each converted dataset's summary.
+pub async fn convert_async<S: AsyncSink>(
+    data_dir: &str,
+    options: &ParseOptions,
+    sink: &mut S,
+) -> Result<Vec<(&'static Dataset, ParseSummary)>> {
+    let mut summaries = Vec::new();
+    for dataset in DATASETS {
+        let path = dataset.path_in(data_dir);
+        if !tokio::fs::try_exists(&path).await.unwrap_or(false) {
+            continue;
+        }
+        summaries.push((dataset, convert_file_async(&path, dataset, options, sink).await?));
+    }
+    sink.finish().await?;
+    Ok(summaries)
+}
+
+/// Converts one dataset file into `sink`, under the dataset's layer.
+pub async fn convert_file_async<S: AsyncSink>(
+    path: &str,
+    dataset: &'static Dataset,
+    options: &ParseOptions,
+    sink: &mut S,
+) -> Result<ParseSummary> {
+    let (sender, mut receiver) = mpsc::channel(CHANNEL_CAPACITY);
+    let (path, options) = (path.to_string(), options.clone());
+    // The file is mapped (or inflated) by the parser itself, off the runtime
+    let parser = tokio::task::spawn_blocking(move || {
+        gml::parse_gml_file(&path, dataset.id_field, &options, |id, feature| {
+            // A closed channel means the sink failed; its error is reported instead
+            sender.blocking_send((id, feature)).map_err(|_| anyhow!("Conversion stopped"))
+        })
+    });
+    while let Some((id, feature)) = receiver.recv().await {
+        sink.write(dataset.output_subdir, &id, feature).await?;
+    }
+    parser.await.map_err(|e| anyhow!("Parser failed: {}", e))?
+}
+
+/// One pretty-printed GeoJSON file per feature under `<output>/<layer>/<id>.json`,
+/// written with async IO.
+pub struct AsyncFileSink {
+    output_dir: String,
+    created: HashSet<String>,
+}
+
+impl AsyncFileSink {
+    pub fn new(output_dir: &str) -> Self {
+        AsyncFileSink {
+            output_dir: output_dir.to_string(),
+            created: HashSet::new(),
+        }
+    }
+}
+
+impl AsyncSink for AsyncFileSink {
+    async fn write(&mut self, layer: &str, id: &str, feature: GeoJsonFeature) -> Result<()> {
+        let dir = format!("{}/{}", self.output_dir, layer);
+        if !self.created.contains(&dir) {
+            tokio::fs::create_dir_all(paths::long_path(&dir))
+                .await
+                .with_context(|| format!("Failed to create {}", dir))?;
+            self.created.insert(dir.clone());
+        }
+        let path = format!("{}/{}.json", dir, paths::file_name(id));
+        let json = serde_json::to_vec_pretty(&feature)?;
+        tokio::fs::write(paths::long_path(&path), json)
+            .await
+            .with_context(|| format!("Failed to create {}", path))
+    }
+}
@@ -0,0 +1,146 @@
+//! Google encoded polylines, for `--polyline`: each run of positions becomes one
+//! ASCII string of zigzag-encoded deltas, rounded to 5 or 6 decimal places.
+//!
+//! As in the Google format, each position is encoded latitude first, so any
+//! polyline decoder reads the strings. [`decode`] turns them back into GeoJSON
+//! `[lon, lat]` positions.
+
+use crate::geojson::Geometry;
+use anyhow::{Context, Result, anyhow, bail};
+use serde_json::Value;
+
+/// Encodes `[lon, lat, ...]` positions; ordinates beyond the second are dropped.
+pub fn encode(positions: &[Vec<f64>], precision: u32) -> String {
+    let factor = 10f64.powi(precision as i32);
+    let mut encoded = String::new();
+    let (mut previous_lat, mut previous_lon) = (0i64, 0i64);
+    for position in positions {
+        let (Some(&lon), Some(&lat)) = (position.first(), position.get(1)) else {
+            continue;
+        };
+        let (lat, lon) = ((lat * factor).round() as i64, (lon * factor).round() as i64);
+        encode_value(lat - previous_lat, &mut encoded);
+        encode_value(lon - previous_lon, &mut encoded);
+        (previous_lat, previous_lon) = (lat, lon);
+    }
+    encoded
+}
+
+fn encode_value(delta: i64, encoded: &mut String) {
+    let mut value = if delta < 0 { !(delta << 1) } else { delta << 1 } as u64;
+    while value >= 0x20 {
+        encoded.push(char::from((0x20 | (value & 0x1f)) as u8 + 63));
+        value >>= 5;
+    }
+    encoded.push(char::from(value as u8 + 63));
+}
+
+/// Decodes a polyline into `[lon, lat]` positions.
+pub fn decode(encoded: &str, precision: u32) -> Result<Vec<Vec<f64>>> {
+    let factor = 10f64.powi(precision as i32);
+    let mut bytes = encoded.bytes();
+    let mut next = || -> Result<Option<i64>> {
+        let (mut value, mut shift) = (0u64, 0);
+        loop {
+            let Some(byte) = bytes.next() else {
+                return match shift {
+                    0 => Ok(None),
+                    _ => Err(anyhow!("Polyline ends in the middle of a value")),
+                };
+            };
+            if !(63..127).contains(&byte) || shift > 60 {
+                bail!("Invalid polyline character `{}`", char::from(byte));
+            }
+            let chunk = u64::from(byte - 63);
+            value |= (chunk & 0x1f) << shift;
+            shift += 5;
+            if chunk < 0x20 {
+                let delta = (value >> 1) as i64;
+                return Ok(Some(if value & 1 == 1 { !delta } else { delta }));
+            }
+        }
+    };
+    let mut positions = Vec::new();
+    let (mut lat, mut lon) = (0i64, 0i64);
+    while let Some(delta_lat) = next()? {
+        let delta_lon = next()?.context("Polyline ends after a latitude")?;
+        lat += delta_lat;
+        lon += delta_lon;
+        positions.push(vec![lon as f64 / factor, lat as f64 / factor]);
+    }
+    Ok(positions)
+}
+
+/// Replaces the coordinates of every GeoJSON geometry in a Feature or
+/// FeatureCollection with polylines, and marks each geometry with an
+/// `encoding` member such as `"polyline6"`. Points are left as they are.
+pub fn encode_geometries(value: &mut Value, precision: u32) {
+    if let Some(features) = value.get_mut("features").and_then(Value::as_array_mut) {
+        for feature in features {
+            encode_geometries(feature, precision);
+        }
+    }
+    let Some(geometry) = value.get_mut("geometry").and_then(Value::as_object_mut) else {
+        return;
+    };
+    let depth = match geometry.get("type").and_then(Value::as_str) {
+        Some("LineString") => 0,
+        Some("MultiLineString" | "Polygon") => 1,
+        Some("MultiPolygon") => 2,
+        _ => return,
+    };
+    if let Some(coordinates) = geometry.get_mut("coordinates") {
+        *coordinates = encode_coordinates(coordinates, depth, precision);
+        geometry.insert("encoding".to_string(), Value::String(format!("polyline{}", precision)));
+    }
+}
+
+fn encode_coordinates(coordinates: &Value, depth: usize, precision: u32) -> Value {
+    let items = coordinates.as_array().map(Vec::as_slice).unwrap_or_default();
+    if depth > 0 {
+        return Value::Array(items.iter().map(|item| encode_coordinates(item, depth - 1, precision)).collect());
+    }
+    let positions: Vec<Vec<f64>> = items
+        .iter()
+        .map(|position| position.as_array().into_iter().flatten().filter_map(Value::as_f64).collect())
+        .collect();
+    Value::String(encode(&positions, precision))
+}
+
+/// Reads a geometry written with `--polyline` back into a [`Geometry`]. The
+/// precision comes from its `encoding` member; geometries without one are read
+/// as plain GeoJSON.
+pub fn decode_geometry(geometry: &Value) -> Result<Geometry> {
+    let Some(encoding) = geometry.get("encoding").and_then(Value::as_str) else {
+        return Ok(serde_json::from_value(geometry.clone())?);
+    };
+    let precision: u32 = encoding
+        .strip_prefix("polyline")
+        .and_then(|digits| digits.parse().ok())
+        .with_context(|| format!("Unknown geometry encoding `{}`", encoding))?;
+    let coordinates = geometry.get("coordinates").context("Geometry has no coordinates")?;
+    let lines = |value: &Value| -> Result<Vec<Vec<Vec<f64>>>> {
+        value
+            .as_array()
+            .context("Expected an array of polylines")?
+            .iter()
+            .map(|line| decode(line.as_str().context("Expected a polyline string")?, precision))
+            .collect()
+    };
+    Ok(match geometry.get("type").and_then(Value::as_str) {
+        Some("LineString") => {
+            Geometry::LineString(decode(coordinates.as_str().context("Expected a polyline string")?, precision)?)
+        }
+        Some("MultiLineString") => Geometry::MultiLineString(lines(coordinates)?),
+        Some("Polygon") => Geometry::Polygon(lines(coordinates)?),
+        Some("MultiPolygon") => Geometry::MultiPolygon(
+            coordinates
+                .as_array()
+                .context("Expected an array of polygons")?
+                .iter()
+                .map(lines)
+                .collect::<Result<_>>()?,
+        ),
+        other => bail!("Can't decode a {} geometry", other.unwrap_or("typeless")),
+    })
+}
@@ -0,0 +1,109 @@
+//! A quick count of the features in each input file, ahead of converting it.
+//!
+//! The count comes from scanning the raw bytes for `GenericCityObject` start
+//! tags, without parsing any XML, so it is a small fraction of the cost of the
+//! conversion. It lets progress be shown as a percentage and buffering sinks
+//! allocate once. Each file is scanned on a background thread while the one
+//! before it converts.
+
+use crate::gzip::{self, Decompressor};
+use crate::mmap::InputFile;
+use anyhow::{Context, Result, anyhow};
+use std::collections::VecDeque;
+use std::io::Read;
+use std::thread::{self, JoinHandle};
+
+/// Start tags are only collected up to this length; longer ones can't be a
+/// `GenericCityObject`.
+const MAX_TAG_NAME: usize = 64;
+
+/// Counts of a list of files, each scanned one file ahead of the caller.
+pub struct Prescanner {
+    paths: VecDeque<String>,
+    scan: Option<JoinHandle<Result<usize>>>,
+}
+
+impl Prescanner {
+    /// Starts scanning the first of `paths`.
+    pub fn new(paths: Vec<String>) -> Self {
+        let mut prescanner = Prescanner {
+            paths: paths.into(),
+            scan: None,
+        };
+        prescanner.start_next();
+        prescanner
+    }
+
+    /// The feature count of the next file, waiting for its scan to finish, and
+    /// starts scanning the file after it.
+    pub fn next_count(&mut self) -> Result<usize> {
+        let scan = self.scan.take().ok_or_else(|| anyhow!("No file left to scan"))?;
+        self.start_next();
+        scan.join().map_err(|_| anyhow!("Pre-scan failed"))?
+    }
+
+    fn start_next(&mut self) {
+        if let Some(path) = self.paths.pop_front() {
+            self.scan = Some(thread::spawn(move || count_objects(&path)));
+        }
+    }
+}
+
+/// The number of `GenericCityObject` start tags in a GML file, gzipped or not.
+pub fn count_objects(path: &str) -> Result<usize> {
+    let mut counter = TagCounter::default();
+    if gzip::is_gzip(path) {
+        let mut decompressor = Decompressor::open(path)?;
+        let mut chunk = vec![0; 256 * 1024];
+        loop {
+            let length = decompressor.read(&mut chunk).with_context(|| format!("Failed to read file: {}", path))?;
+            if length == 0 {
+                break;
+            }
+            counter.feed(&chunk[..length]);
+        }
+    } else {
+        counter.feed(&InputFile::open(path)?);
+    }
+    Ok(counter.count)
+}
+
+/// Counts `GenericCityObject` start tags, in any namespace, across bytes fed in
+/// pieces.
+#[derive(Default)]
+struct TagCounter {
+    /// The name of the tag being read, while inside one.
+    name: Option<Vec<u8>>,
+    count: usize,
+}
+
+impl TagCounter {
+    fn feed(&mut self, bytes: &[u8]) {
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            let Some(name) = &mut self.name else {
+                match rest.iter().position(|&byte| byte == b'<') {
+                    Some(start) => {
+                        self.name = Some(Vec::new());
+                        rest = &rest[start + 1..];
+                    }
+                    None => return,
+                }
+                continue;
+            };
+            let end = rest
+                .iter()
+                .position(|&byte| byte.is_ascii_whitespace() || byte == b'>' || byte == b'/' || byte == b'<');
+            // One byte past the limit marks a name too long to match
+            let taken = end.unwrap_or(rest.len()).min((MAX_TAG_NAME + 1).saturating_sub(name.len()));
+            name.extend_from_slice(&rest[..taken]);
+            let Some(end) = end else { return };
+            let local = name.rsplit(|&byte| byte == b':').next().unwrap_or_default();
+            if name.len() <= MAX_TAG_NAME && local == b"GenericCityObject" {
+                self.count += 1;
+            }
+            self.name = None;
+            rest = &rest[end..];
+        }
+    }
+}
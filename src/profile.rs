@@ -0,0 +1,212 @@
+//! Built-in descriptions of known datasets, selected with `--profile`, so the ID
+//! field, geometry type and code lists of each layer don't have to be worked out
+//! from the files.
+//!
+//! A profile fills in `[input.attributes]` and `[datasets.<layer>] required` where
+//! the config leaves them unset, and conversion checks each feature's geometry
+//! type and coded attributes against it.
+
+use crate::attributes::AttributeType;
+use crate::config::Config;
+use crate::dataset::{DATASETS, Dataset};
+use crate::geojson::{Geometry, PropertyValue};
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+
+pub struct Profile {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub layers: &'static [LayerProfile],
+}
+
+/// One layer of a profile. Layers this tool doesn't convert yet are listed too,
+/// for reference.
+pub struct LayerProfile {
+    pub file_name: &'static str,
+    pub layer: &'static str,
+    pub id_field: &'static str,
+    pub geometry: GeometryType,
+    pub attributes: &'static [AttributeProfile],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeometryType {
+    Point,
+    Line,
+    Polygon,
+}
+
+pub struct AttributeProfile {
+    pub name: &'static str,
+    pub kind: AttributeType,
+    pub required: bool,
+    /// The values the attribute may take, with their meaning. Empty for free text.
+    pub codes: &'static [(&'static str, &'static str)],
+}
+
+const fn attribute(name: &'static str, kind: AttributeType, required: bool) -> AttributeProfile {
+    AttributeProfile {
+        name,
+        kind,
+        required,
+        codes: &[],
+    }
+}
+
+pub const PROFILES: &[Profile] = &[Profile {
+    name: "hk-rdnet",
+    description: "Lands Department / Transport Department Road Network (2nd Generation)",
+    layers: &[
+        LayerProfile {
+            file_name: "CENTERLINE.gml",
+            layer: "centerlines",
+            id_field: "ROUTE_ID",
+            geometry: GeometryType::Line,
+            attributes: &[
+                attribute("ROUTE_ID", AttributeType::String, true),
+                attribute("STREET_ENAME", AttributeType::String, false),
+                attribute("STREET_CNAME", AttributeType::String, false),
+                AttributeProfile {
+                    name: "TRAVEL_DIRECTION",
+                    kind: AttributeType::Int,
+                    required: true,
+                    codes: &[("1", "both ways"), ("2", "along the line"), ("3", "against the line")],
+                },
+                AttributeProfile {
+                    name: "ELEVATION",
+                    kind: AttributeType::Int,
+                    required: false,
+                    codes: &[("-1", "underground"), ("0", "at grade"), ("1", "elevated")],
+                },
+                // Some exit numbers have leading zeros
+                attribute("EXIT_NUM", AttributeType::String, false),
+                attribute("ROUTE_NUM", AttributeType::String, false),
+                attribute("SHAPE_LENGTH", AttributeType::Float, false),
+            ],
+        },
+        LayerProfile {
+            file_name: "PEDESTRIAN_ZONE.gml",
+            layer: "pedestrian_zones",
+            id_field: "PED_ZONE_ID",
+            geometry: GeometryType::Polygon,
+            attributes: &[
+                attribute("PED_ZONE_ID", AttributeType::String, true),
+                attribute("STREET_ENAME", AttributeType::String, false),
+                attribute("STREET_CNAME", AttributeType::String, false),
+                AttributeProfile {
+                    name: "ZONE_TYPE",
+                    kind: AttributeType::Int,
+                    required: false,
+                    codes: &[("1", "full-time pedestrian street"), ("2", "part-time pedestrian street")],
+                },
+            ],
+        },
+        LayerProfile {
+            file_name: "INTERSECTION.gml",
+            layer: "intersections",
+            id_field: "INT_ID",
+            geometry: GeometryType::Point,
+            attributes: &[
+                attribute("INT_ID", AttributeType::String, true),
+                AttributeProfile {
+                    name: "INT_TYPE",
+                    kind: AttributeType::Int,
+                    required: false,
+                    codes: &[("1", "junction"), ("2", "roundabout"), ("3", "interchange")],
+                },
+            ],
+        },
+        LayerProfile {
+            file_name: "TRAFFIC_AID.gml",
+            layer: "traffic_aids",
+            id_field: "TRAFFIC_AID_ID",
+            geometry: GeometryType::Point,
+            attributes: &[
+                attribute("TRAFFIC_AID_ID", AttributeType::String, true),
+                attribute("ROUTE_ID", AttributeType::String, false),
+                AttributeProfile {
+                    name: "AID_TYPE",
+                    kind: AttributeType::Int,
+                    required: true,
+                    codes: &[
+                        ("1", "traffic signal"),
+                        ("2", "zebra crossing"),
+                        ("3", "pelican crossing"),
+                        ("4", "yellow box"),
+                        ("5", "speed table"),
+                    ],
+                },
+            ],
+        },
+    ],
+}];
+
+/// The built-in profile with this name.
+pub fn find(name: &str) -> Result<&'static Profile> {
+    PROFILES.iter().find(|profile| profile.name == name).ok_or_else(|| {
+        let names: Vec<&str> = PROFILES.iter().map(|profile| profile.name).collect();
+        anyhow!("Unknown profile `{}`; expected one of {}", name, names.join(", "))
+    })
+}
+
+impl Profile {
+    /// The profile's description of a dataset, matched by source file name.
+    pub fn layer_for(&self, dataset: &Dataset) -> Option<&'static LayerProfile> {
+        self.layers.iter().find(|layer| layer.file_name == dataset.file_name)
+    }
+
+    /// Fills in attribute types and required attributes the config doesn't set.
+    /// Only layers this tool converts get required attributes.
+    pub fn apply(&self, config: &mut Config) {
+        for layer in self.layers {
+            for attribute in layer.attributes {
+                config.input.attributes.entry(attribute.name.to_string()).or_insert(attribute.kind);
+            }
+            if !DATASETS.iter().any(|dataset| dataset.output_subdir == layer.layer) {
+                continue;
+            }
+            let settings = config.datasets.entry(layer.layer.to_string()).or_default();
+            if settings.required.is_empty() {
+                settings.required =
+                    layer.attributes.iter().filter(|a| a.required).map(|a| a.name.to_string()).collect();
+            }
+        }
+    }
+}
+
+impl LayerProfile {
+    pub fn geometry_matches(&self, geometry: &Geometry) -> bool {
+        matches!(
+            (self.geometry, geometry),
+            (GeometryType::Point, Geometry::Point(_))
+                | (GeometryType::Line, Geometry::LineString(_) | Geometry::MultiLineString(_))
+                | (GeometryType::Polygon, Geometry::Polygon(_) | Geometry::MultiPolygon(_))
+        )
+    }
+
+    /// Coded attributes whose value isn't in their code list. Null values are
+    /// left to the required-attribute check.
+    pub fn unknown_codes<'a>(
+        &'a self,
+        properties: &'a HashMap<String, PropertyValue>,
+    ) -> impl Iterator<Item = (&'static str, String)> + 'a {
+        self.attributes.iter().filter(|attribute| !attribute.codes.is_empty()).filter_map(|attribute| {
+            let value = properties.get(attribute.name)?;
+            if matches!(value, PropertyValue::Null) {
+                return None;
+            }
+            let value = value.to_string();
+            (!attribute.codes.iter().any(|(code, _)| *code == value)).then_some((attribute.name, value))
+        })
+    }
+}
+
+impl GeometryType {
+    pub fn name(self) -> &'static str {
+        match self {
+            GeometryType::Point => "point",
+            GeometryType::Line => "line",
+            GeometryType::Polygon => "polygon",
+        }
+    }
+}
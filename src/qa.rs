@@ -0,0 +1,213 @@
+//! Geometry QA: self-intersecting features, and pairs of features in one layer
+//! that overlap or duplicate each other.
+//!
+//! Checks run in the local metric plane from [`geometry::to_local_m`], so the
+//! tolerance is in metres. Each problem becomes a feature at the offending
+//! location: a point where a feature crosses itself, or the shared stretch of an
+//! overlapping or duplicate pair.
+
+use crate::geojson::{GeoJsonFeature, Geometry, PropertyValue};
+use crate::geometry;
+use rstar::primitives::{GeomWithData, Line};
+use rstar::{AABB, RTree};
+use std::collections::{BTreeMap, HashMap};
+
+/// A segment of an added feature: `(feature, segment)` indices.
+type IndexedSegment = GeomWithData<Line<[f64; 2]>, (usize, usize)>;
+
+/// Part of a segment that runs along another feature: `(segment, t0, t1,
+/// segment length in metres)`.
+type Stretch = (usize, f64, f64, f64);
+
+struct Item {
+    layer: String,
+    id: String,
+    coords: Vec<Vec<f64>>,
+    local: Vec<[f64; 2]>,
+    /// Whether partial overlaps are problems. Adjacent zones legitimately share
+    /// edges, so their outlines are only checked for duplicates.
+    overlaps: bool,
+}
+
+#[derive(Default)]
+pub struct Summary {
+    pub self_intersections: usize,
+    pub overlaps: usize,
+    pub duplicates: usize,
+}
+
+pub struct Checker {
+    tolerance_m: f64,
+    items: Vec<Item>,
+}
+
+impl Checker {
+    pub fn new(tolerance_m: f64) -> Self {
+        Checker {
+            tolerance_m,
+            items: Vec::new(),
+        }
+    }
+
+    /// Adds a `[lon, lat]` line or outline. Pass `overlaps = false` for layers
+    /// whose features may share stretches with their neighbours.
+    pub fn add(&mut self, layer: &str, id: &str, coords: &[Vec<f64>], overlaps: bool) {
+        if coords.len() < 2 {
+            return;
+        }
+        self.items.push(Item {
+            layer: layer.to_string(),
+            id: id.to_string(),
+            coords: coords.to_vec(),
+            local: coords.iter().map(|p| geometry::to_local_m(p)).collect(),
+            overlaps,
+        });
+    }
+
+    /// Runs every check, returning the problem features and their counts.
+    pub fn run(&self) -> (Vec<GeoJsonFeature>, Summary) {
+        let mut summary = Summary::default();
+        let mut problems = Vec::new();
+        for item in &self.items {
+            for point in self_intersections(item) {
+                summary.self_intersections += 1;
+                problems.push(problem("self_intersection", item, None, Geometry::Point(point)));
+            }
+        }
+        for ((a, b), pieces) in self.shared_stretches() {
+            let (a, b) = (&self.items[a], &self.items[b]);
+            let shared: f64 = pieces.iter().map(|&(_, t0, t1, length)| (t1 - t0) * length).sum();
+            let duplicate = shared >= line_length(&a.local).max(line_length(&b.local)) - 2.0 * self.tolerance_m;
+            let issue = if duplicate {
+                summary.duplicates += 1;
+                "duplicate"
+            } else if a.overlaps {
+                summary.overlaps += 1;
+                "overlap"
+            } else {
+                continue;
+            };
+            problems.push(problem(issue, a, Some(b), stretch_geometry(a, &pieces)));
+        }
+        (problems, summary)
+    }
+
+    /// For every pair of features in the same layer, the stretches of the first
+    /// that run along the second within the tolerance, in segment order.
+    fn shared_stretches(&self) -> BTreeMap<(usize, usize), Vec<Stretch>> {
+        let segments = self
+            .items
+            .iter()
+            .enumerate()
+            .flat_map(|(n, item)| {
+                item.local
+                    .windows(2)
+                    .enumerate()
+                    .map(move |(s, w)| GeomWithData::new(Line::new(w[0], w[1]), (n, s)))
+            })
+            .collect();
+        let tree: RTree<IndexedSegment> = RTree::bulk_load(segments);
+
+        let tolerance = self.tolerance_m;
+        let mut stretches: BTreeMap<(usize, usize), Vec<Stretch>> = BTreeMap::new();
+        for (n, item) in self.items.iter().enumerate() {
+            for (s, w) in item.local.windows(2).enumerate() {
+                let (a, b) = (w[0], w[1]);
+                let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+                let length = dx.hypot(dy);
+                if length <= tolerance {
+                    continue;
+                }
+                let envelope = AABB::from_corners(
+                    [a[0].min(b[0]) - tolerance, a[1].min(b[1]) - tolerance],
+                    [a[0].max(b[0]) + tolerance, a[1].max(b[1]) + tolerance],
+                );
+                // Parameter along a→b and distance from its line, of a point
+                let along = |p: [f64; 2]| {
+                    let (px, py) = (p[0] - a[0], p[1] - a[1]);
+                    ((px * dx + py * dy) / (length * length), (px * dy - py * dx).abs() / length)
+                };
+                for other in tree.locate_in_envelope_intersecting(&envelope) {
+                    let (m, _) = other.data;
+                    if m <= n || self.items[m].layer != item.layer {
+                        continue;
+                    }
+                    let (tc, dc) = along(other.geom().from);
+                    let (td, dd) = along(other.geom().to);
+                    if dc > tolerance || dd > tolerance {
+                        continue;
+                    }
+                    let (t0, t1) = (tc.min(td).max(0.0), tc.max(td).min(1.0));
+                    if (t1 - t0) * length > tolerance {
+                        stretches.entry((n, m)).or_default().push((s, t0, t1, length));
+                    }
+                }
+            }
+        }
+        for pieces in stretches.values_mut() {
+            pieces.sort_by(|x, y| x.0.cmp(&y.0).then(x.1.total_cmp(&y.1)));
+        }
+        stretches
+    }
+}
+
+/// Points where non-adjacent segments of a feature cross or touch.
+fn self_intersections(item: &Item) -> Vec<Vec<f64>> {
+    let local = &item.local;
+    let count = local.len() - 1;
+    let closed = local.first() == local.last();
+    let mut points = Vec::new();
+    for i in 0..count {
+        for j in i + 2..count {
+            // A closed outline's first and last segments meet at its start
+            if closed && i == 0 && j == count - 1 {
+                continue;
+            }
+            if let Some(t) = geometry::segment_crossing(&local[i], &local[i + 1], &local[j], &local[j + 1]) {
+                let (a, b) = (&item.coords[i], &item.coords[i + 1]);
+                points.push(vec![a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t]);
+            }
+        }
+    }
+    points
+}
+
+fn line_length(local: &[[f64; 2]]) -> f64 {
+    local.windows(2).map(|w| (w[1][0] - w[0][0]).hypot(w[1][1] - w[0][1])).sum()
+}
+
+/// The shared stretches of `item` as a LineString, or a MultiLineString when
+/// they are not contiguous.
+fn stretch_geometry(item: &Item, pieces: &[Stretch]) -> Geometry {
+    let at = |s: usize, t: f64| {
+        let (a, b) = (&item.coords[s], &item.coords[s + 1]);
+        match t {
+            0.0 => a.clone(),
+            1.0 => b.clone(),
+            _ => vec![a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t],
+        }
+    };
+    let mut lines: Vec<Vec<Vec<f64>>> = Vec::new();
+    for &(s, t0, t1, _) in pieces {
+        let (start, end) = (at(s, t0), at(s, t1));
+        match lines.last_mut() {
+            Some(line) if line.last() == Some(&start) => line.push(end),
+            _ => lines.push(vec![start, end]),
+        }
+    }
+    match lines.len() {
+        1 => Geometry::LineString(lines.remove(0)),
+        _ => Geometry::MultiLineString(lines),
+    }
+}
+
+fn problem(issue: &str, item: &Item, other: Option<&Item>, geometry: Geometry) -> GeoJsonFeature {
+    let mut properties = HashMap::new();
+    properties.insert("issue".to_string(), PropertyValue::String(issue.to_string()));
+    properties.insert("layer".to_string(), PropertyValue::String(item.layer.clone()));
+    properties.insert("id".to_string(), PropertyValue::String(item.id.clone()));
+    if let Some(other) = other {
+        properties.insert("other_id".to_string(), PropertyValue::String(other.id.clone()));
+    }
+    GeoJsonFeature::new(geometry, properties)
+}
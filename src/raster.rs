@@ -0,0 +1,211 @@
+//! Grids of road density or pedestrian zone coverage, written as GeoTIFF.
+//!
+//! Cells are square, in the Hong Kong 1980 Grid, so every cell covers the same
+//! ground area. Line length is split exactly between the cells a segment
+//! crosses, and zone area is clipped to each cell, so totals over the grid
+//! match the totals of the features.
+//!
+//! The GeoTIFF is written by hand: one uncompressed strip of 32-bit floats,
+//! with the georeferencing tags and keys GIS software needs to place it.
+
+use crate::crs::Crs;
+use crate::geometry;
+use crate::paths;
+use anyhow::{Context, Result, bail};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// TIFF field types used here.
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const DOUBLE: u16 = 12;
+
+pub struct Raster {
+    /// Top-left corner of the grid.
+    origin: [f64; 2],
+    /// Cell width and height, in metres.
+    resolution: f64,
+    width: usize,
+    height: usize,
+    /// Row-major, top row first.
+    cells: Vec<f64>,
+}
+
+impl Raster {
+    /// A grid of `resolution`-metre cells covering `bbox`
+    /// (`[min_x, min_y, max_x, max_y]` in HK80), with edges on multiples of the
+    /// resolution so that grids of the same area line up.
+    pub fn covering(bbox: [f64; 4], resolution: f64) -> Result<Self> {
+        let min_x = (bbox[0] / resolution).floor() * resolution;
+        let max_y = (bbox[3] / resolution).ceil() * resolution;
+        let width = (((bbox[2] - min_x) / resolution).ceil() as usize).max(1);
+        let height = (((max_y - bbox[1]) / resolution).ceil() as usize).max(1);
+        if width.saturating_mul(height).saturating_mul(4) > u32::MAX as usize {
+            bail!("A {} x {} grid is too large for one GeoTIFF; raise --resolution", width, height);
+        }
+        Ok(Raster {
+            origin: [min_x, max_y],
+            resolution,
+            width,
+            height,
+            cells: vec![0.0; width * height],
+        })
+    }
+
+    /// Adds the length of each part of a line to the cell it lies in.
+    pub fn add_line(&mut self, coords: &[Vec<f64>]) {
+        for w in coords.windows(2) {
+            let (a, b) = (&w[0], &w[1]);
+            let length = geometry::planar_distance(a, b);
+            let (min, max) = self.cell_range(&[a.as_slice(), b.as_slice()]);
+            for row in min.1..=max.1 {
+                for column in min.0..=max.0 {
+                    if let Some((t0, t1)) = geometry::clip_segment(a, b, &self.cell_bbox(column, row)) {
+                        self.cells[row * self.width + column] += length * (t1 - t0);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Adds the area of a polygon (exterior ring, then holes) inside each cell.
+    pub fn add_polygon(&mut self, rings: &[Vec<Vec<f64>>]) {
+        let Some(exterior) = rings.first() else {
+            return;
+        };
+        let positions: Vec<&[f64]> = exterior.iter().map(Vec::as_slice).collect();
+        let (min, max) = self.cell_range(&positions);
+        for row in min.1..=max.1 {
+            for column in min.0..=max.0 {
+                let bbox = self.cell_bbox(column, row);
+                let mut area = geometry::planar_ring_area(&geometry::clip_ring(exterior, &bbox));
+                for hole in &rings[1..] {
+                    area -= geometry::planar_ring_area(&geometry::clip_ring(hole, &bbox));
+                }
+                self.cells[row * self.width + column] += area.max(0.0);
+            }
+        }
+    }
+
+    /// Turns sums of zone area into the fraction of each cell covered, capped
+    /// at 1 where zones overlap.
+    pub fn area_to_coverage(&mut self) {
+        let cell_area = self.resolution * self.resolution;
+        for cell in &mut self.cells {
+            *cell = (*cell / cell_area).min(1.0);
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The largest cell value.
+    pub fn max(&self) -> f64 {
+        self.cells.iter().copied().fold(0.0, f64::max)
+    }
+
+    /// Writes the grid as a single-band 32-bit float GeoTIFF.
+    pub fn write_geotiff(&self, path: &str) -> Result<()> {
+        let file = File::create(paths::long_path(path)).with_context(|| format!("Failed to create {}", path))?;
+        let mut out = BufWriter::new(file);
+        let image_bytes = (self.width * self.height * 4) as u32;
+
+        let geo_keys: Vec<u16> = vec![
+            // Version 1.1.0, then four keys of (key, location, count, value)
+            1, 1, 0, 4,
+            1024, 0, 1, 1, // GTModelTypeGeoKey: projected
+            1025, 0, 1, 1, // GTRasterTypeGeoKey: pixel is area
+            3072, 0, 1, Crs::Hk80.epsg() as u16, // ProjectedCSTypeGeoKey
+            3076, 0, 1, 9001, // ProjLinearUnitsGeoKey: metre
+        ];
+        let (width, height) = (self.width as u32, self.height as u32);
+        // (tag, type, values as little-endian bytes), in ascending tag order
+        let entries: Vec<(u16, u16, Vec<u8>)> = vec![
+            (256, LONG, width.to_le_bytes().to_vec()),
+            (257, LONG, height.to_le_bytes().to_vec()),
+            (258, SHORT, 32u16.to_le_bytes().to_vec()),
+            (259, SHORT, 1u16.to_le_bytes().to_vec()),
+            (262, SHORT, 1u16.to_le_bytes().to_vec()),
+            (273, LONG, 8u32.to_le_bytes().to_vec()),
+            (277, SHORT, 1u16.to_le_bytes().to_vec()),
+            (278, LONG, height.to_le_bytes().to_vec()),
+            (279, LONG, image_bytes.to_le_bytes().to_vec()),
+            (284, SHORT, 1u16.to_le_bytes().to_vec()),
+            (339, SHORT, 3u16.to_le_bytes().to_vec()),
+            (33550, DOUBLE, doubles(&[self.resolution, self.resolution, 0.0])),
+            (33922, DOUBLE, doubles(&[0.0, 0.0, 0.0, self.origin[0], self.origin[1], 0.0])),
+            (34735, SHORT, geo_keys.iter().flat_map(|key| key.to_le_bytes()).collect()),
+        ];
+
+        // Header, image, values too long for their entry, then the directory
+        let mut extra_offset = 8 + image_bytes;
+        let mut extra: Vec<u8> = Vec::new();
+        let mut directory: Vec<u8> = Vec::new();
+        directory.extend((entries.len() as u16).to_le_bytes());
+        for (tag, field_type, bytes) in &entries {
+            let size = match *field_type {
+                SHORT => 2,
+                LONG => 4,
+                _ => 8,
+            };
+            directory.extend(tag.to_le_bytes());
+            directory.extend(field_type.to_le_bytes());
+            directory.extend(((bytes.len() / size) as u32).to_le_bytes());
+            if bytes.len() <= 4 {
+                let mut inline = bytes.clone();
+                inline.resize(4, 0);
+                directory.extend(inline);
+            } else {
+                directory.extend(extra_offset.to_le_bytes());
+                extra.extend(bytes);
+                extra_offset += bytes.len() as u32;
+            }
+        }
+        directory.extend(0u32.to_le_bytes());
+
+        out.write_all(b"II*\0")?;
+        out.write_all(&extra_offset.to_le_bytes())?;
+        for cell in &self.cells {
+            out.write_all(&(*cell as f32).to_le_bytes())?;
+        }
+        out.write_all(&extra)?;
+        out.write_all(&directory)?;
+        out.flush().with_context(|| format!("Failed to write {}", path))
+    }
+
+    /// The `(column, row)` of the top-left and bottom-right cells any of the
+    /// positions fall in, kept within the grid.
+    fn cell_range(&self, positions: &[&[f64]]) -> ((usize, usize), (usize, usize)) {
+        let cell = |position: &[f64]| {
+            let column = ((position[0] - self.origin[0]) / self.resolution).floor();
+            let row = ((self.origin[1] - position[1]) / self.resolution).floor();
+            (
+                column.clamp(0.0, (self.width - 1) as f64) as usize,
+                row.clamp(0.0, (self.height - 1) as f64) as usize,
+            )
+        };
+        let mut min = (usize::MAX, usize::MAX);
+        let mut max = (0, 0);
+        for position in positions {
+            let (column, row) = cell(position);
+            min = (min.0.min(column), min.1.min(row));
+            max = (max.0.max(column), max.1.max(row));
+        }
+        (min, max)
+    }
+
+    fn cell_bbox(&self, column: usize, row: usize) -> [f64; 4] {
+        let min_x = self.origin[0] + column as f64 * self.resolution;
+        let max_y = self.origin[1] - row as f64 * self.resolution;
+        [min_x, max_y - self.resolution, min_x + self.resolution, max_y]
+    }
+}
+
+fn doubles(values: &[f64]) -> Vec<u8> {
+    values.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
@@ -0,0 +1,219 @@
+//! Shortest-path search over the routing graph.
+//!
+//! The search runs over edges rather than nodes so that turn restrictions, which
+//! forbid particular edge-to-edge transitions, can be honoured. With
+//! [`Landmarks`] it runs as A*, guided by their lower bounds.
+
+use crate::geojson::{GeoJsonFeature, Geometry, PropertyValue};
+use crate::graph::Graph;
+use crate::landmarks::Landmarks;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+pub struct Route {
+    /// Edge indices in travel order.
+    pub edges: Vec<usize>,
+    pub length_m: f64,
+}
+
+#[derive(PartialEq)]
+struct State {
+    /// Cost so far plus the lower bound on the rest, which orders the search.
+    priority: f64,
+    cost: f64,
+    edge: usize,
+}
+
+impl Eq for State {}
+
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so the BinaryHeap pops the cheapest state first.
+        other.priority.total_cmp(&self.priority).then_with(|| self.edge.cmp(&other.edge))
+    }
+}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Finds the shortest route between two nodes, or `None` if `to` is unreachable.
+pub fn shortest_path(graph: &Graph, from: usize, to: usize) -> Option<Route> {
+    search(graph, from, to, |_| 0.0)
+}
+
+/// Like [`shortest_path`], settling far fewer edges on a large graph by steering
+/// towards `to` with `landmarks` computed for `graph`.
+pub fn shortest_path_with(graph: &Graph, landmarks: &Landmarks, from: usize, to: usize) -> Option<Route> {
+    search(graph, from, to, |node| landmarks.lower_bound(node, to))
+}
+
+/// A* over edges, where `bound` gives a lower bound on the distance from a node
+/// to `to` that never drops by more than the length of an edge along it.
+fn search(graph: &Graph, from: usize, to: usize, bound: impl Fn(usize) -> f64) -> Option<Route> {
+    if from == to {
+        return Some(Route {
+            edges: Vec::new(),
+            length_m: 0.0,
+        });
+    }
+
+    let outgoing = graph.outgoing();
+    let mut best: HashMap<usize, f64> = HashMap::new();
+    let mut previous: HashMap<usize, usize> = HashMap::new();
+    let mut heap = BinaryHeap::new();
+
+    for &edge in &outgoing[from] {
+        let cost = graph.edges[edge].length_m;
+        best.insert(edge, cost);
+        heap.push(State { priority: cost + bound(graph.edges[edge].to), cost, edge });
+    }
+
+    while let Some(State { cost, edge, .. }) = heap.pop() {
+        if cost > best.get(&edge).copied().unwrap_or(f64::INFINITY) {
+            continue;
+        }
+        let node = graph.edges[edge].to;
+        if node == to {
+            let mut edges = vec![edge];
+            let mut current = edge;
+            while let Some(&prev) = previous.get(&current) {
+                edges.push(prev);
+                current = prev;
+            }
+            edges.reverse();
+            return Some(Route { edges, length_m: cost });
+        }
+        for &next in &outgoing[node] {
+            if graph.banned_turns.contains(&(edge, next)) {
+                continue;
+            }
+            let next_cost = cost + graph.edges[next].length_m;
+            if next_cost < best.get(&next).copied().unwrap_or(f64::INFINITY) {
+                best.insert(next, next_cost);
+                previous.insert(next, edge);
+                heap.push(State {
+                    priority: next_cost + bound(graph.edges[next].to),
+                    cost: next_cost,
+                    edge: next,
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Shortest distances from `from` to every node, honouring turn restrictions,
+/// with `outgoing` from [`Graph::outgoing`]. Unreachable nodes are infinitely
+/// far.
+pub fn distances_from(graph: &Graph, outgoing: &[Vec<usize>], from: usize) -> Vec<f64> {
+    let mut to_node = vec![f64::INFINITY; graph.nodes.len()];
+    to_node[from] = 0.0;
+    let mut best = vec![f64::INFINITY; graph.edges.len()];
+    let mut heap = BinaryHeap::new();
+    for &edge in &outgoing[from] {
+        let cost = graph.edges[edge].length_m;
+        best[edge] = cost;
+        heap.push(State { priority: cost, cost, edge });
+    }
+    while let Some(State { cost, edge, .. }) = heap.pop() {
+        if cost > best[edge] {
+            continue;
+        }
+        let node = graph.edges[edge].to;
+        to_node[node] = to_node[node].min(cost);
+        for &next in &outgoing[node] {
+            if graph.banned_turns.contains(&(edge, next)) {
+                continue;
+            }
+            let next_cost = cost + graph.edges[next].length_m;
+            if next_cost < best[next] {
+                best[next] = next_cost;
+                heap.push(State { priority: next_cost, cost: next_cost, edge: next });
+            }
+        }
+    }
+    to_node
+}
+
+/// Outgoing and incoming edge indices for every node.
+pub fn adjacency(graph: &Graph) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+    let mut incoming = vec![Vec::new(); graph.nodes.len()];
+    for (id, edge) in graph.edges.iter().enumerate() {
+        incoming[edge.to].push(id);
+    }
+    (graph.outgoing(), incoming)
+}
+
+#[derive(PartialEq)]
+struct NodeState {
+    cost: f64,
+    node: usize,
+}
+
+impl Eq for NodeState {}
+
+impl Ord for NodeState {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so the BinaryHeap pops the cheapest state first.
+        other.cost.total_cmp(&self.cost).then_with(|| self.node.cmp(&other.node))
+    }
+}
+
+impl PartialOrd for NodeState {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Shortest distances from the nearest of `sources`, each a node and a distance
+/// already covered to reach it, to every node along `adjacency` from
+/// [`adjacency`]. With `reverse`, `adjacency` holds incoming edges and the
+/// distances run from every node to the nearest source instead. Turn
+/// restrictions are ignored. Unreachable nodes are infinitely far.
+pub fn node_distances(graph: &Graph, adjacency: &[Vec<usize>], sources: &[(usize, f64)], reverse: bool) -> Vec<f64> {
+    let mut best = vec![f64::INFINITY; graph.nodes.len()];
+    let mut heap = BinaryHeap::new();
+    for &(node, cost) in sources {
+        if cost < best[node] {
+            best[node] = cost;
+            heap.push(NodeState { cost, node });
+        }
+    }
+    while let Some(NodeState { cost, node }) = heap.pop() {
+        if cost > best[node] {
+            continue;
+        }
+        for &edge in &adjacency[node] {
+            let edge = &graph.edges[edge];
+            let next = if reverse { edge.from } else { edge.to };
+            let next_cost = cost + edge.length_m;
+            if next_cost < best[next] {
+                best[next] = next_cost;
+                heap.push(NodeState { cost: next_cost, node: next });
+            }
+        }
+    }
+    best
+}
+
+impl Route {
+    /// The route as a single LineString feature.
+    pub fn to_feature(&self, graph: &Graph) -> GeoJsonFeature {
+        let mut coords: Vec<Vec<f64>> = Vec::new();
+        for &edge in &self.edges {
+            let edge_coords = &graph.edges[edge].coords;
+            // Consecutive edges share their joining vertex.
+            let skip = usize::from(!coords.is_empty());
+            coords.extend(edge_coords.iter().skip(skip).cloned());
+        }
+        let segments: Vec<&str> = self.edges.iter().map(|&e| graph.edges[e].segment_id.as_str()).collect();
+        let properties = HashMap::from([
+            ("length_m".to_string(), PropertyValue::Float(self.length_m)),
+            ("segments".to_string(), PropertyValue::String(segments.join(","))),
+        ]);
+        GeoJsonFeature::new(Geometry::LineString(coords), properties)
+    }
+}
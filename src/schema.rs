@@ -0,0 +1,84 @@
+//! Attribute schema differences between two drops of a dataset, for the
+//! `schema-diff` command.
+//!
+//! A file's schema is the `gen:*Attribute` element types each attribute name is
+//! written in, read with [`gml::attribute_types`] without parsing any feature.
+
+use crate::gml;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The element types (`string`, `int`, `float`, `date`) each attribute is
+/// written in, by attribute name.
+pub type Schema = BTreeMap<String, BTreeSet<&'static str>>;
+
+/// Reads the schema of a GML file, gzipped or not.
+pub fn read(path: &str) -> Result<Schema> {
+    Ok(gml::attribute_types(path)?
+        .into_iter()
+        .map(|(name, types)| (name, types.into_iter().map(|kind| kind.name()).collect()))
+        .collect())
+}
+
+/// An attribute written in different types in the two files.
+#[derive(Debug, Serialize)]
+pub struct Retyped {
+    pub old: String,
+    pub new: String,
+}
+
+/// Attributes added, removed and retyped from one schema to the next, each
+/// with its types written like `int` or `int/float`.
+#[derive(Debug, Default, Serialize)]
+pub struct SchemaDiff {
+    pub added: BTreeMap<String, String>,
+    pub removed: BTreeMap<String, String>,
+    pub retyped: BTreeMap<String, Retyped>,
+}
+
+impl SchemaDiff {
+    pub fn new(old: &Schema, new: &Schema) -> Self {
+        let mut diff = SchemaDiff::default();
+        for (name, types) in new {
+            match old.get(name) {
+                None => {
+                    diff.added.insert(name.clone(), type_names(types));
+                }
+                Some(old_types) if old_types != types => {
+                    diff.retyped.insert(
+                        name.clone(),
+                        Retyped {
+                            old: type_names(old_types),
+                            new: type_names(types),
+                        },
+                    );
+                }
+                Some(_) => {}
+            }
+        }
+        for (name, types) in old {
+            if !new.contains_key(name) {
+                diff.removed.insert(name.clone(), type_names(types));
+            }
+        }
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.retyped.is_empty()
+    }
+
+    /// One line per change: `+ NAME (type)`, `- NAME (type)` or
+    /// `~ NAME: old -> new`.
+    pub fn lines(&self) -> Vec<String> {
+        let added = self.added.iter().map(|(name, types)| format!("+ {} ({})", name, types));
+        let removed = self.removed.iter().map(|(name, types)| format!("- {} ({})", name, types));
+        let retyped = self.retyped.iter().map(|(name, types)| format!("~ {}: {} -> {}", name, types.old, types.new));
+        added.chain(removed).chain(retyped).collect()
+    }
+}
+
+fn type_names(types: &BTreeSet<&'static str>) -> String {
+    types.iter().copied().collect::<Vec<_>>().join("/")
+}
@@ -0,0 +1,160 @@
+//! On-disk street name index written during conversion (`search_index.json`) and
+//! queried by the `search` subcommand.
+//!
+//! Names are split into lowercase words; CJK characters are indexed one per token
+//! so Chinese names match without a word segmenter. A query matches a feature when
+//! every query token is a prefix of one of its tokens, or when it equals the ID.
+//!
+//! [`StreetNames`] is the simpler `street_names.json` lookup written with
+//! `--street-names`, from each whole normalized name to the features carrying it.
+
+use crate::geojson::GeoJsonFeature;
+use crate::paths;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub dataset: String,
+    pub id: String,
+    pub names: Vec<String>,
+    pub bbox: Option<[f64; 4]>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SearchIndex {
+    pub entries: Vec<Entry>,
+    /// Token to entry positions, sorted for prefix lookups.
+    pub tokens: BTreeMap<String, Vec<usize>>,
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32, 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x2FA1F)
+}
+
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if is_cjk(c) {
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+            tokens.push(c.to_string());
+        } else if c.is_alphanumeric() {
+            word.push(c);
+        } else if !word.is_empty() {
+            tokens.push(std::mem::take(&mut word));
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    tokens
+}
+
+/// A name lowercased, with every run of spaces and punctuation made a single
+/// space, so that `Des Voeux Road  Central` and `DES VOEUX ROAD CENTRAL` match.
+pub fn normalize_name(name: &str) -> String {
+    let mut normalized = String::new();
+    for c in name.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            normalized.push(c);
+        } else if !normalized.is_empty() && !normalized.ends_with(' ') {
+            normalized.push(' ');
+        }
+    }
+    normalized.truncate(normalized.trim_end().len());
+    normalized
+}
+
+/// Normalized street name to the IDs of the features named it, by layer.
+#[derive(Debug, Default, Serialize)]
+pub struct StreetNames(BTreeMap<String, BTreeMap<String, Vec<String>>>);
+
+impl StreetNames {
+    pub fn add(&mut self, dataset: &str, id: &str, feature: &GeoJsonFeature, name_attributes: &[String]) {
+        let names: BTreeSet<String> = name_attributes
+            .iter()
+            .filter_map(|attribute| feature.properties.get(attribute))
+            .map(|value| normalize_name(&value.to_string()))
+            .filter(|name| !name.is_empty())
+            .collect();
+        for name in names {
+            self.0.entry(name).or_default().entry(dataset.to_string()).or_default().push(id.to_string());
+        }
+    }
+
+    pub fn write(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string(self)?;
+        fs::write(paths::long_path(path), json).with_context(|| format!("Failed to write {}", path))?;
+        eprintln!("Wrote {} street names to {}", self.0.len(), path);
+        Ok(())
+    }
+}
+
+impl SearchIndex {
+    pub fn add(&mut self, dataset: &str, id: &str, feature: &GeoJsonFeature, name_attributes: &[String]) {
+        let names: Vec<String> = name_attributes
+            .iter()
+            .filter_map(|attribute| feature.properties.get(attribute))
+            .map(|value| value.to_string())
+            .filter(|name| !name.is_empty())
+            .collect();
+
+        let position = self.entries.len();
+        let tokens: BTreeSet<String> = names.iter().flat_map(|name| tokenize(name)).collect();
+        for token in tokens {
+            self.tokens.entry(token).or_default().push(position);
+        }
+        self.entries.push(Entry {
+            dataset: dataset.to_string(),
+            id: id.to_string(),
+            names,
+            bbox: feature.geometry.bbox(),
+        });
+    }
+
+    pub fn write(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string(self)?;
+        fs::write(paths::long_path(path), json).with_context(|| format!("Failed to write {}", path))
+    }
+
+    pub fn load(path: &str) -> Result<SearchIndex> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}; run a conversion first", path))?;
+        serde_json::from_str(&text).with_context(|| format!("Invalid search index: {}", path))
+    }
+
+    fn prefix_matches(&self, token: &str) -> BTreeSet<usize> {
+        self.tokens
+            .range(token.to_string()..)
+            .take_while(|(key, _)| key.starts_with(token))
+            .flat_map(|(_, positions)| positions.iter().copied())
+            .collect()
+    }
+
+    pub fn search(&self, query: &str) -> Vec<&Entry> {
+        let mut matches: Option<BTreeSet<usize>> = None;
+        for token in tokenize(query) {
+            let found = self.prefix_matches(&token);
+            matches = Some(match matches {
+                Some(current) => current.intersection(&found).copied().collect(),
+                None => found,
+            });
+        }
+
+        let query = query.trim();
+        let mut results: Vec<&Entry> = self.entries.iter().filter(|entry| entry.id == query).collect();
+        results.extend(
+            matches
+                .unwrap_or_default()
+                .into_iter()
+                .map(|position| &self.entries[position])
+                .filter(|entry| entry.id != query),
+        );
+        results
+    }
+}
@@ -0,0 +1,140 @@
+use super::Sink;
+use super::schema::{ColumnType, Table};
+use crate::crs::Crs;
+use crate::geojson::{GeoJsonFeature, PropertyValue};
+use crate::paths;
+use crate::well_known;
+use anyhow::{Context, Result};
+use arrow_array::builder::{BinaryBuilder, Float64Builder, Int64Builder, StringBuilder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{DataType, Field, Schema};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+/// Buffers every layer and writes one Arrow IPC stream, either to
+/// `features.arrows` or to stdout. Columns are `layer`, `id`, `geometry`
+/// (GeoArrow `geoarrow.wkb`) and one typed, nullable column per property seen in
+/// any layer.
+pub struct ArrowSink {
+    destination: Option<String>,
+    crs: Crs,
+    layers: Vec<String>,
+    table: Table,
+}
+
+impl ArrowSink {
+    /// Writes to `<output_dir>/features.arrows`, or stdout when `output_dir` is `None`.
+    pub fn new(output_dir: Option<&str>, crs: Crs) -> Self {
+        ArrowSink {
+            destination: output_dir.map(|dir| format!("{}/features.arrows", dir)),
+            crs,
+            layers: Vec::new(),
+            table: Table::default(),
+        }
+    }
+}
+
+/// Converts a buffered table into one record batch. A leading `layer` column is
+/// added when `layers` (one name per row) is given, and the WKB `geometry` column
+/// only when a CRS is given.
+pub(super) fn record_batch(table: &Table, layers: Option<&[String]>, geometry: Option<Crs>) -> Result<RecordBatch> {
+    let mut fields = Vec::new();
+    let mut arrays: Vec<ArrayRef> = Vec::new();
+
+    if let Some(layers) = layers {
+        let mut builder = StringBuilder::new();
+        for layer in layers {
+            builder.append_value(layer);
+        }
+        fields.push(Field::new("layer", DataType::Utf8, false));
+        arrays.push(Arc::new(builder.finish()));
+    }
+
+    let mut ids = StringBuilder::new();
+    for row in &table.rows {
+        ids.append_value(&row.id);
+    }
+    fields.push(Field::new("id", DataType::Utf8, false));
+    arrays.push(Arc::new(ids.finish()));
+
+    if let Some(crs) = geometry {
+        let geometry_metadata = HashMap::from([
+            ("ARROW:extension:name".to_string(), "geoarrow.wkb".to_string()),
+            (
+                "ARROW:extension:metadata".to_string(),
+                format!(r#"{{"crs":"EPSG:{}","crs_type":"authority_code"}}"#, crs.epsg()),
+            ),
+        ]);
+        let mut geometries = BinaryBuilder::new();
+        for row in &table.rows {
+            geometries.append_value(well_known::to_wkb(&row.feature.geometry));
+        }
+        fields.push(Field::new("geometry", DataType::Binary, false).with_metadata(geometry_metadata));
+        arrays.push(Arc::new(geometries.finish()));
+    }
+
+    for (name, column) in &table.columns {
+        let values = table.rows.iter().map(|row| row.feature.properties.get(name));
+        let array: ArrayRef = match column {
+            ColumnType::Int => {
+                let mut builder = Int64Builder::new();
+                for value in values {
+                    match value {
+                        Some(PropertyValue::Int(i)) => builder.append_value(*i),
+                        _ => builder.append_null(),
+                    }
+                }
+                Arc::new(builder.finish())
+            }
+            ColumnType::Float => {
+                let mut builder = Float64Builder::new();
+                for value in values {
+                    match value {
+                        Some(PropertyValue::Int(i)) => builder.append_value(*i as f64),
+                        Some(PropertyValue::Float(f)) => builder.append_value(*f),
+                        _ => builder.append_null(),
+                    }
+                }
+                Arc::new(builder.finish())
+            }
+            ColumnType::String => {
+                let mut builder = StringBuilder::new();
+                for value in values {
+                    match value {
+                        None | Some(PropertyValue::Null) => builder.append_null(),
+                        Some(value) => builder.append_value(value.to_string()),
+                    }
+                }
+                Arc::new(builder.finish())
+            }
+        };
+        let data_type = array.data_type().clone();
+        fields.push(Field::new(name, data_type, true));
+        arrays.push(array);
+    }
+
+    Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)?)
+}
+
+impl Sink for ArrowSink {
+    fn write(&mut self, layer: &str, id: &str, feature: &GeoJsonFeature) -> Result<()> {
+        self.layers.push(layer.to_string());
+        self.table.push(id, feature);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let batch = record_batch(&self.table, Some(&self.layers), Some(self.crs))?;
+        let out: Box<dyn Write> = match &self.destination {
+            Some(path) => Box::new(File::create(paths::long_path(path)).with_context(|| format!("Failed to create {}", path))?),
+            None => Box::new(io::stdout().lock()),
+        };
+        let mut writer = StreamWriter::try_new(out, &batch.schema())?;
+        writer.write(&batch)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
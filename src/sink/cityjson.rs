@@ -0,0 +1,156 @@
+use super::{Sink, write_json};
+use crate::crs::Crs;
+use crate::dataset::{DATASETS, DatasetKind};
+use crate::geojson::{GeoJsonFeature, Geometry};
+use anyhow::Result;
+use serde_json::{Map, Value, json};
+use std::collections::{HashMap, HashSet};
+
+/// Buffers every layer and writes a single CityJSON 2.0 file (`roads.city.json`)
+/// in which each feature is a `GenericCityObject` keyed `<layer>:<id>`, with its
+/// properties as attributes. Centerlines become LoD0 MultiLineStrings, zones
+/// LoD1 MultiSurfaces with one surface per polygon and points LoD0 MultiPoints. Vertices are shared and
+/// stored as integers under the file's `transform`, as CityJSON requires.
+pub struct CityJsonSink {
+    path: String,
+    crs: Crs,
+    objects: Vec<(String, String, GeoJsonFeature)>,
+    keys: HashSet<String>,
+}
+
+impl CityJsonSink {
+    pub fn new(output_dir: &str, crs: Crs) -> Self {
+        CityJsonSink {
+            path: format!("{}/roads.city.json", output_dir),
+            crs,
+            objects: Vec::new(),
+            keys: HashSet::new(),
+        }
+    }
+
+    /// Quantisation step per axis: a millimetre on the grid, about a centimetre
+    /// in degrees.
+    fn scale(&self) -> [f64; 3] {
+        match self.crs {
+            Crs::Hk80 => [0.001, 0.001, 0.001],
+            Crs::Wgs84 => [1e-7, 1e-7, 0.001],
+        }
+    }
+}
+
+fn is_zone_layer(layer: &str) -> bool {
+    DATASETS
+        .iter()
+        .any(|dataset| dataset.output_subdir == layer && dataset.kind == DatasetKind::Zone)
+}
+
+/// Vertex indices of a ring. Surfaces list each ring vertex once, without the
+/// closing repeat.
+fn open_ring(coords: &[Vec<f64>], index_of: &mut impl FnMut(&[f64]) -> usize) -> Vec<usize> {
+    let open = if coords.len() > 1 && coords.first() == coords.last() {
+        &coords[..coords.len() - 1]
+    } else {
+        coords
+    };
+    open.iter().map(|p| index_of(p)).collect()
+}
+
+impl Sink for CityJsonSink {
+    fn write(&mut self, layer: &str, id: &str, feature: &GeoJsonFeature) -> Result<()> {
+        // Companion layers repeat their source ID, e.g. a segment's two endpoints
+        let mut key = format!("{}:{}", layer, id);
+        let mut n = 1;
+        while self.keys.contains(&key) {
+            n += 1;
+            key = format!("{}:{}:{}", layer, id, n);
+        }
+        self.keys.insert(key.clone());
+        self.objects.push((key, layer.to_string(), feature.clone()));
+        Ok(())
+    }
+
+    fn reserve(&mut self, _layer: &str, features: usize) {
+        self.objects.reserve(features);
+        self.keys.reserve(features);
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let scale = self.scale();
+        let mut translate = [f64::INFINITY; 3];
+        for (_, _, feature) in &self.objects {
+            for p in feature.geometry.positions() {
+                for (axis, min) in translate.iter_mut().enumerate() {
+                    *min = min.min(p.get(axis).copied().unwrap_or(0.0));
+                }
+            }
+        }
+        if translate.iter().any(|t| !t.is_finite()) {
+            translate = [0.0; 3];
+        }
+
+        let mut vertices: Vec<[i64; 3]> = Vec::new();
+        let mut vertex_index: HashMap<[i64; 3], usize> = HashMap::new();
+        let mut index_of = |p: &[f64]| {
+            let vertex: [i64; 3] = std::array::from_fn(|axis| {
+                let value = p.get(axis).copied().unwrap_or(0.0);
+                ((value - translate[axis]) / scale[axis]).round() as i64
+            });
+            *vertex_index.entry(vertex).or_insert_with(|| {
+                vertices.push(vertex);
+                vertices.len() - 1
+            })
+        };
+
+        let mut city_objects = Map::new();
+        for (key, layer, feature) in &self.objects {
+            let geometry = match &feature.geometry {
+                Geometry::Point(p) if p.is_empty() => None,
+                Geometry::Point(p) => Some(json!({ "type": "MultiPoint", "lod": "0", "boundaries": [index_of(p)] })),
+                Geometry::LineString(coords) if coords.is_empty() => None,
+                Geometry::LineString(coords) if is_zone_layer(layer) => {
+                    let ring = open_ring(coords, &mut index_of);
+                    Some(json!({ "type": "MultiSurface", "lod": "1", "boundaries": [[ring]] }))
+                }
+                Geometry::LineString(coords) => {
+                    let line: Vec<usize> = coords.iter().map(|p| index_of(p)).collect();
+                    Some(json!({ "type": "MultiLineString", "lod": "0", "boundaries": [line] }))
+                }
+                Geometry::MultiLineString(lines) => {
+                    let lines: Vec<Vec<usize>> = lines.iter().map(|line| line.iter().map(|p| index_of(p)).collect()).collect();
+                    Some(json!({ "type": "MultiLineString", "lod": "0", "boundaries": lines }))
+                }
+                Geometry::Polygon(_) | Geometry::MultiPolygon(_) => {
+                    let surfaces: Vec<Vec<Vec<usize>>> = feature
+                        .geometry
+                        .polygons()
+                        .into_iter()
+                        .map(|rings| rings.iter().map(|ring| open_ring(ring, &mut index_of)).collect())
+                        .collect();
+                    Some(json!({ "type": "MultiSurface", "lod": "1", "boundaries": surfaces }))
+                }
+            };
+            let mut attributes = json!(feature.properties);
+            attributes["layer"] = json!(layer);
+            city_objects.insert(
+                key.clone(),
+                json!({
+                    "type": "GenericCityObject",
+                    "attributes": attributes,
+                    "geometry": geometry.into_iter().collect::<Vec<_>>(),
+                }),
+            );
+        }
+
+        let document = json!({
+            "type": "CityJSON",
+            "version": "2.0",
+            "transform": { "scale": scale, "translate": translate },
+            "metadata": {
+                "referenceSystem": format!("https://www.opengis.net/def/crs/EPSG/0/{}", self.crs.epsg()),
+            },
+            "CityObjects": Value::Object(city_objects),
+            "vertices": vertices,
+        });
+        write_json(&self.path, &document)
+    }
+}
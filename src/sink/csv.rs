@@ -0,0 +1,60 @@
+use super::Sink;
+use super::schema::Table;
+use crate::geojson::{GeoJsonFeature, PropertyValue};
+use crate::paths;
+use crate::well_known;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+
+/// Writes `<layer>.csv` per layer: `id`, an optional WKT `geometry` column and one
+/// column per property seen in that layer. Missing and null values are left empty.
+pub struct CsvSink {
+    output_dir: String,
+    geometry: bool,
+    layers: BTreeMap<String, Table>,
+}
+
+impl CsvSink {
+    pub fn new(output_dir: &str, geometry: bool) -> Self {
+        CsvSink {
+            output_dir: output_dir.to_string(),
+            geometry,
+            layers: BTreeMap::new(),
+        }
+    }
+}
+
+impl Sink for CsvSink {
+    fn write(&mut self, layer: &str, id: &str, feature: &GeoJsonFeature) -> Result<()> {
+        self.layers.entry(layer.to_string()).or_default().push(id, feature);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        for (name, table) in &self.layers {
+            let path = format!("{}/{}.csv", self.output_dir, name);
+            let mut writer = csv::Writer::from_path(paths::long_path(&path)).with_context(|| format!("Failed to create {}", path))?;
+
+            let mut header = vec!["id"];
+            if self.geometry {
+                header.push("geometry");
+            }
+            header.extend(table.columns.keys().map(String::as_str));
+            writer.write_record(&header)?;
+
+            for row in &table.rows {
+                let mut record = vec![row.id.clone()];
+                if self.geometry {
+                    record.push(well_known::to_wkt(&row.feature.geometry));
+                }
+                record.extend(table.columns.keys().map(|column| match row.feature.properties.get(column) {
+                    None | Some(PropertyValue::Null) => String::new(),
+                    Some(value) => value.to_string(),
+                }));
+                writer.write_record(&record)?;
+            }
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+use super::Sink;
+use super::schema::{ColumnType, Table};
+use crate::geojson::{GeoJsonFeature, PropertyValue};
+use crate::paths;
+use crate::well_known;
+use anyhow::{Context, Result, bail};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, ErrorKind, Write};
+use std::process::{Command, Stdio};
+
+const INSERT_BATCH: usize = 1000;
+
+/// Builds a DuckDB database with one table per layer: `id`, a spatial-extension
+/// `geom GEOMETRY` column and one typed column per property.
+///
+/// The tables are created by a SQL script (`duckdb_load.sql`) that is piped into
+/// the `duckdb` CLI. When the CLI is not installed, or fails, the run fails and
+/// the script is left in place to be run by hand.
+pub struct DuckDbSink {
+    script_path: String,
+    database: String,
+    layers: BTreeMap<String, Table>,
+}
+
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn sql_value(value: Option<&PropertyValue>, column: ColumnType) -> String {
+    match (value, column) {
+        (None | Some(PropertyValue::Null), _) => "NULL".to_string(),
+        (Some(PropertyValue::Int(i)), ColumnType::Int | ColumnType::Float) => i.to_string(),
+        (Some(PropertyValue::Float(f)), ColumnType::Float) if f.is_finite() => f.to_string(),
+        (Some(value), _) => quote_literal(&value.to_string()),
+    }
+}
+
+impl DuckDbSink {
+    pub fn new(output_dir: &str, database: &str) -> Self {
+        DuckDbSink {
+            script_path: format!("{}/duckdb_load.sql", output_dir),
+            database: database.to_string(),
+            layers: BTreeMap::new(),
+        }
+    }
+
+    fn write_script(&self) -> Result<()> {
+        let file = File::create(paths::long_path(&self.script_path))
+            .with_context(|| format!("Failed to create {}", self.script_path))?;
+        let mut out = BufWriter::new(file);
+        writeln!(out, "INSTALL spatial;")?;
+        writeln!(out, "LOAD spatial;")?;
+        writeln!(out, "BEGIN TRANSACTION;")?;
+        for (name, layer) in &self.layers {
+            let table = quote_identifier(name);
+            let mut definitions = vec!["id VARCHAR".to_string(), "geom GEOMETRY".to_string()];
+            definitions.extend(layer.columns.iter().map(|(column, kind)| {
+                let sql_type = match kind {
+                    ColumnType::Int => "BIGINT",
+                    ColumnType::Float => "DOUBLE",
+                    ColumnType::String => "VARCHAR",
+                };
+                format!("{} {}", quote_identifier(column), sql_type)
+            }));
+            writeln!(out, "CREATE OR REPLACE TABLE {} ({});", table, definitions.join(", "))?;
+
+            for chunk in layer.rows.chunks(INSERT_BATCH) {
+                writeln!(out, "INSERT INTO {} VALUES", table)?;
+                for (i, row) in chunk.iter().enumerate() {
+                    let mut values = vec![
+                        quote_literal(&row.id),
+                        format!("ST_GeomFromText({})", quote_literal(&well_known::to_wkt(&row.feature.geometry))),
+                    ];
+                    values.extend(
+                        layer
+                            .columns
+                            .iter()
+                            .map(|(column, kind)| sql_value(row.feature.properties.get(column), *kind)),
+                    );
+                    let separator = if i + 1 == chunk.len() { ";" } else { "," };
+                    writeln!(out, "  ({}){}", values.join(", "), separator)?;
+                }
+            }
+        }
+        writeln!(out, "COMMIT;")?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+impl Sink for DuckDbSink {
+    fn write(&mut self, layer: &str, id: &str, feature: &GeoJsonFeature) -> Result<()> {
+        self.layers.entry(layer.to_string()).or_default().push(id, feature);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.write_script()?;
+
+        let script = File::open(&self.script_path)?;
+        let child = Command::new("duckdb")
+            .arg(&self.database)
+            .stdin(script)
+            .stdout(Stdio::null())
+            .status();
+        match child {
+            Ok(status) if status.success() => {
+                eprintln!("Loaded {} table(s) into {}", self.layers.len(), self.database);
+                fs::remove_file(&self.script_path)?;
+            }
+            Ok(status) => bail!("duckdb exited with {} while loading {}", status, self.script_path),
+            Err(e) if e.kind() == ErrorKind::NotFound => bail!(
+                "DuckDB CLI not found, so {} was not created; install duckdb and run: duckdb {} < {}",
+                self.database,
+                self.database,
+                self.script_path
+            ),
+            Err(e) => return Err(e).context("Failed to run duckdb"),
+        }
+        Ok(())
+    }
+}
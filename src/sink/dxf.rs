@@ -0,0 +1,174 @@
+use super::Sink;
+use crate::geojson::{GeoJsonFeature, Geometry};
+use crate::paths;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+
+/// Registered application name for the extended entity data carrying attributes.
+const APP_ID: &str = "ROADNET";
+
+/// Writes every layer into a single AutoCAD R12 DXF file (`roads.dxf`) for CAD
+/// users. Each output layer becomes a DXF layer; lines become POLYLINEs and points
+/// become POINTs, with the feature ID and attributes attached as extended entity
+/// data (XDATA) under the `ROADNET` application.
+pub struct DxfSink {
+    path: String,
+    /// Layer name to ACI colour, assigned in order of first use.
+    layers: BTreeMap<String, usize>,
+    entities: String,
+}
+
+/// DXF layer names are upper case and may not contain `<>/\":;?*|=,`.
+fn layer_name(layer: &str) -> String {
+    layer
+        .chars()
+        .map(|c| if "<>/\\\":;?*|=,".contains(c) { '_' } else { c.to_ascii_uppercase() })
+        .collect()
+}
+
+/// R12 strings are single-byte; other characters use the `\U+XXXX` escape, and
+/// values are capped at the 255-byte group limit.
+fn dxf_string(value: &str) -> String {
+    let mut out = String::new();
+    for c in value.chars() {
+        if c.is_ascii() && !c.is_ascii_control() {
+            out.push(c);
+        } else {
+            let _ = write!(out, "\\U+{:04X}", c as u32);
+        }
+        if out.len() > 240 {
+            break;
+        }
+    }
+    out
+}
+
+/// Appends one `code`/`value` group pair.
+fn group(out: &mut String, code: u16, value: impl std::fmt::Display) {
+    let _ = write!(out, "{}\n{}\n", code, value);
+}
+
+impl DxfSink {
+    pub fn new(output_dir: &str) -> Self {
+        DxfSink {
+            path: format!("{}/roads.dxf", output_dir),
+            layers: BTreeMap::new(),
+            entities: String::new(),
+        }
+    }
+
+    fn xdata(&mut self, id: &str, feature: &GeoJsonFeature) {
+        let out = &mut self.entities;
+        group(out, 1001, APP_ID);
+        group(out, 1000, format!("ID={}", dxf_string(id)));
+        let mut properties: Vec<_> = feature.properties.iter().collect();
+        properties.sort_by(|a, b| a.0.cmp(b.0));
+        for (key, value) in properties {
+            group(out, 1000, dxf_string(&format!("{}={}", key, value)));
+        }
+    }
+
+    fn polyline(&mut self, layer: &str, id: &str, feature: &GeoJsonFeature, coords: &[Vec<f64>]) {
+        let closed = coords.len() > 2 && coords.first() == coords.last();
+        group(&mut self.entities, 0, "POLYLINE");
+        group(&mut self.entities, 8, layer);
+        group(&mut self.entities, 66, 1);
+        group(&mut self.entities, 70, if closed { 1 } else { 0 });
+        self.xdata(id, feature);
+        let vertices = if closed { &coords[..coords.len() - 1] } else { coords };
+        for p in vertices {
+            group(&mut self.entities, 0, "VERTEX");
+            group(&mut self.entities, 8, layer);
+            group(&mut self.entities, 10, p[0]);
+            group(&mut self.entities, 20, p[1]);
+            group(&mut self.entities, 30, 0.0);
+        }
+        group(&mut self.entities, 0, "SEQEND");
+        group(&mut self.entities, 8, layer);
+    }
+}
+
+impl Sink for DxfSink {
+    fn write(&mut self, layer: &str, id: &str, feature: &GeoJsonFeature) -> Result<()> {
+        let layer = layer_name(layer);
+        let next_colour = self.layers.len() % 6 + 1;
+        self.layers.entry(layer.clone()).or_insert(next_colour);
+
+        match &feature.geometry {
+            Geometry::Point(p) => {
+                group(&mut self.entities, 0, "POINT");
+                group(&mut self.entities, 8, &layer);
+                group(&mut self.entities, 10, p[0]);
+                group(&mut self.entities, 20, p[1]);
+                group(&mut self.entities, 30, 0.0);
+                self.xdata(id, feature);
+            }
+            Geometry::LineString(coords) => self.polyline(&layer, id, feature, coords),
+            // Each part becomes its own polyline carrying the feature's data
+            Geometry::MultiLineString(lines) => {
+                for coords in lines {
+                    self.polyline(&layer, id, feature, coords);
+                }
+            }
+            // Likewise each ring, holes included
+            Geometry::Polygon(_) | Geometry::MultiPolygon(_) => {
+                for ring in feature.geometry.polygons().into_iter().flatten() {
+                    self.polyline(&layer, id, feature, ring);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let mut out = String::new();
+        group(&mut out, 0, "SECTION");
+        group(&mut out, 2, "HEADER");
+        group(&mut out, 9, "$ACADVER");
+        group(&mut out, 1, "AC1009");
+        group(&mut out, 0, "ENDSEC");
+
+        group(&mut out, 0, "SECTION");
+        group(&mut out, 2, "TABLES");
+        group(&mut out, 0, "TABLE");
+        group(&mut out, 2, "LTYPE");
+        group(&mut out, 70, 1);
+        group(&mut out, 0, "LTYPE");
+        group(&mut out, 2, "CONTINUOUS");
+        group(&mut out, 70, 0);
+        group(&mut out, 3, "Solid line");
+        group(&mut out, 72, 65);
+        group(&mut out, 73, 0);
+        group(&mut out, 40, 0.0);
+        group(&mut out, 0, "ENDTAB");
+        group(&mut out, 0, "TABLE");
+        group(&mut out, 2, "LAYER");
+        group(&mut out, 70, self.layers.len());
+        for (name, colour) in &self.layers {
+            group(&mut out, 0, "LAYER");
+            group(&mut out, 2, name);
+            group(&mut out, 70, 0);
+            group(&mut out, 62, colour);
+            group(&mut out, 6, "CONTINUOUS");
+        }
+        group(&mut out, 0, "ENDTAB");
+        group(&mut out, 0, "TABLE");
+        group(&mut out, 2, "APPID");
+        group(&mut out, 70, 1);
+        group(&mut out, 0, "APPID");
+        group(&mut out, 2, APP_ID);
+        group(&mut out, 70, 0);
+        group(&mut out, 0, "ENDTAB");
+        group(&mut out, 0, "ENDSEC");
+
+        group(&mut out, 0, "SECTION");
+        group(&mut out, 2, "ENTITIES");
+        out.push_str(&self.entities);
+        group(&mut out, 0, "ENDSEC");
+        group(&mut out, 0, "EOF");
+
+        fs::write(paths::long_path(&self.path), out).with_context(|| format!("Failed to write {}", self.path))
+    }
+}
@@ -0,0 +1,293 @@
+use super::{Sink, write_json};
+use crate::config::TemplateConfig;
+use crate::geojson::{self, ForeignMember, GeoJsonFeature, GeoJsonFeatureCollection};
+use crate::perf::{self, Stage};
+use crate::{paths, polyline};
+use anyhow::{Context, Result, anyhow};
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::fs;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// How [`FileSink`] lays out and writes its files.
+#[derive(Debug, Clone, Default)]
+pub struct FileOptions {
+    /// Added to every file to name a CRS other than WGS84.
+    pub crs_member: Option<ForeignMember>,
+    /// Output shapes by layer; layers without one are written as they are.
+    pub templates: HashMap<String, TemplateConfig>,
+    /// Put each file under a directory named after this many leading characters
+    /// of its ID, so no directory holds more than a fraction of the layer.
+    pub shard_prefix: Option<usize>,
+    /// Threads writing files; at least one.
+    pub threads: usize,
+    /// Store each file once under `objects/` by the SHA-256 of its contents,
+    /// with an index per layer from ID to hash, instead of under its ID.
+    pub content_addressed: bool,
+    /// Write line and polygon coordinates as encoded polylines with this many
+    /// decimal places.
+    pub polyline: Option<u32>,
+    /// Which parts of each feature are written.
+    pub parts: FileParts,
+}
+
+/// Which parts of a feature [`FileSink`] writes, so that clients can fetch
+/// geometry and attributes separately.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum FileParts {
+    /// The whole Feature, `<layer>/<id>.json`.
+    #[default]
+    Whole,
+    /// The Feature with null properties, `<layer>/geometry/<id>.json`.
+    Geometry,
+    /// The properties object alone, `<layer>/props/<id>.json`.
+    Properties,
+    /// Both of the above, in their two trees.
+    Split,
+}
+
+/// One pretty-printed GeoJSON file per feature: `<output>/<layer>/<id>.json`, or
+/// `<output>/<layer>/<prefix>/<id>.json` when sharded, with the ID made safe as a
+/// file name by [`paths::file_name`]. Features are serialized on the caller's
+/// thread and written by a pool of threads.
+///
+/// When content-addressed, files go to `<output>/objects/<ab>/<cdef...>.json`
+/// after their hash instead, and `<output>/index/<layer>.json` maps each ID to
+/// its hash. Objects already present from an earlier run into the same
+/// directory are left alone, so unchanged features cost nothing to write or sync.
+pub struct FileSink {
+    output_dir: String,
+    created: HashSet<String>,
+    options: FileOptions,
+    pool: WriterPool,
+    /// Hash of every feature written, by layer and ID, when content-addressed.
+    index: BTreeMap<String, BTreeMap<String, String>>,
+    /// Hashes of the objects queued by this run.
+    queued: HashSet<String>,
+    /// Objects written by this run, and those found from an earlier one.
+    stored: usize,
+    reused: usize,
+}
+
+/// A FeatureCollection of reshaped features.
+#[derive(Serialize)]
+struct Collection<T> {
+    #[serde(rename = "type")]
+    collection_type: &'static str,
+    features: Vec<T>,
+}
+
+impl FileSink {
+    pub fn new(output_dir: &str, options: FileOptions) -> Self {
+        FileSink {
+            output_dir: output_dir.to_string(),
+            created: HashSet::new(),
+            pool: WriterPool::new(options.threads.max(1)),
+            options,
+            index: BTreeMap::new(),
+            queued: HashSet::new(),
+            stored: 0,
+            reused: 0,
+        }
+    }
+
+    fn create_dir(&mut self, dir: String) -> Result<String> {
+        if !self.created.contains(&dir) {
+            fs::create_dir_all(paths::long_path(&dir)).with_context(|| format!("Failed to create {}", dir))?;
+            self.created.insert(dir.clone());
+        }
+        Ok(dir)
+    }
+
+    /// Queues one feature's file, under its ID or its hash.
+    fn store(&mut self, layer: &str, id: &str, json: Vec<u8>) -> Result<()> {
+        if !self.options.content_addressed {
+            let name = paths::file_name(id);
+            let dir = match self.options.shard_prefix {
+                Some(length) => {
+                    let prefix: String = name.chars().take(length).collect();
+                    format!("{}/{}/{}", self.output_dir, layer, paths::file_name(&prefix))
+                }
+                None => format!("{}/{}", self.output_dir, layer),
+            };
+            let dir = self.create_dir(dir)?;
+            return self.pool.write(format!("{}/{}.json", dir, name), json);
+        }
+        let hash: String = Sha256::digest(&json).iter().map(|byte| format!("{:02x}", byte)).collect();
+        self.index.entry(layer.to_string()).or_default().insert(id.to_string(), hash.clone());
+        let dir = self.create_dir(format!("{}/objects/{}", self.output_dir, &hash[..2]))?;
+        let path = format!("{}/{}.json", dir, &hash[2..]);
+        if !self.queued.insert(hash) {
+            // Identical to a feature earlier in this run
+            return Ok(());
+        }
+        if Path::new(&path).exists() {
+            self.reused += 1;
+            return Ok(());
+        }
+        self.stored += 1;
+        self.pool.write(path, json)
+    }
+
+    /// The file of a Feature or FeatureCollection, or the files of the parts
+    /// asked for, each with the tree it goes in.
+    fn encode<T: Serialize>(&self, layer: &str, value: &T) -> Result<Vec<(String, Vec<u8>)>> {
+        let value = geojson::with_member(value, self.options.crs_member.as_ref());
+        let parts = self.options.parts;
+        if self.options.polyline.is_none() && parts == FileParts::Whole {
+            return Ok(vec![(layer.to_string(), serde_json::to_vec_pretty(&value)?)]);
+        }
+        let mut json = serde_json::to_value(&value)?;
+        if let Some(precision) = self.options.polyline {
+            polyline::encode_geometries(&mut json, precision);
+        }
+        if parts == FileParts::Whole {
+            return Ok(vec![(layer.to_string(), serde_json::to_vec_pretty(&json)?)]);
+        }
+        let properties = take_properties(&mut json);
+        let mut files = Vec::new();
+        if parts != FileParts::Properties {
+            files.push((format!("{}/geometry", layer), serde_json::to_vec_pretty(&json)?));
+        }
+        if parts != FileParts::Geometry {
+            files.push((format!("{}/props", layer), serde_json::to_vec_pretty(&properties)?));
+        }
+        Ok(files)
+    }
+
+    fn store_all(&mut self, id: &str, files: Vec<(String, Vec<u8>)>) -> Result<()> {
+        for (tree, json) in files {
+            self.store(&tree, id, json)?;
+        }
+        Ok(())
+    }
+}
+
+/// Nulls the properties of a Feature, or of every Feature in a collection, and
+/// returns them: the Feature's object, or an array in the collection's order.
+fn take_properties(json: &mut Value) -> Value {
+    if let Some(features) = json.get_mut("features").and_then(Value::as_array_mut) {
+        return Value::Array(features.iter_mut().map(take_properties).collect());
+    }
+    json.get_mut("properties").map(Value::take).unwrap_or(Value::Null)
+}
+
+impl Sink for FileSink {
+    fn write(&mut self, layer: &str, id: &str, feature: &GeoJsonFeature) -> Result<()> {
+        let files = match self.options.templates.get(layer) {
+            Some(template) => self.encode(layer, &template.apply(feature))?,
+            None => self.encode(layer, feature)?,
+        };
+        self.store_all(id, files)
+    }
+
+    fn write_group(&mut self, layer: &str, id: &str, features: &[GeoJsonFeature]) -> Result<()> {
+        let files = match self.options.templates.get(layer) {
+            Some(template) => self.encode(
+                layer,
+                &Collection {
+                    collection_type: "FeatureCollection",
+                    features: features.iter().map(|feature| template.apply(feature)).collect(),
+                },
+            )?,
+            None => self.encode(layer, &GeoJsonFeatureCollection::new(features.to_vec()))?,
+        };
+        self.store_all(id, files)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.pool.finish()?;
+        if !self.options.content_addressed {
+            return Ok(());
+        }
+        let dir = self.create_dir(format!("{}/index", self.output_dir))?;
+        let index = std::mem::take(&mut self.index);
+        for (layer, hashes) in &index {
+            // Split layers are indexed per part, e.g. `centerlines/props.json`
+            let path = format!("{}/{}.json", dir, layer);
+            if let Some(parent) = Path::new(&path).parent() {
+                self.create_dir(parent.to_string_lossy().into_owned())?;
+            }
+            write_json(&path, hashes)?;
+        }
+        eprintln!(
+            "Stored {} new objects and reused {} from an earlier run",
+            self.stored, self.reused
+        );
+        Ok(())
+    }
+}
+
+/// A file waiting for a writer thread: its path and contents.
+type Job = (String, Vec<u8>);
+
+/// Threads writing queued files. The queue is bounded, so a slow disk holds up
+/// the conversion rather than buffering the whole output in memory.
+struct WriterPool {
+    sender: Option<SyncSender<Job>>,
+    workers: Vec<JoinHandle<Result<()>>>,
+}
+
+impl WriterPool {
+    fn new(threads: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<Job>(threads * 64);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..threads)
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                thread::spawn(move || write_jobs(&receiver))
+            })
+            .collect();
+        WriterPool {
+            sender: Some(sender),
+            workers,
+        }
+    }
+
+    fn write(&mut self, path: String, contents: Vec<u8>) -> Result<()> {
+        let _io = perf::enter(Stage::Io);
+        let sent = match &self.sender {
+            Some(sender) => sender.send((path, contents)).is_ok(),
+            None => false,
+        };
+        if sent {
+            return Ok(());
+        }
+        // Every writer has stopped, so one of them failed
+        self.finish()?;
+        Err(anyhow!("File writers stopped unexpectedly"))
+    }
+
+    /// Waits for every queued file to be written, returning the first error.
+    fn finish(&mut self) -> Result<()> {
+        self.sender = None;
+        let mut result = Ok(());
+        for worker in self.workers.drain(..) {
+            let outcome = worker.join().unwrap_or_else(|_| Err(anyhow!("A file writer panicked")));
+            if result.is_ok() {
+                result = outcome;
+            }
+        }
+        result
+    }
+}
+
+fn write_jobs(receiver: &Mutex<Receiver<Job>>) -> Result<()> {
+    loop {
+        let job = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => return Err(anyhow!("A file writer panicked")),
+        };
+        // The sink has finished and the queue is drained
+        let Ok((path, contents)) = job else {
+            return Ok(());
+        };
+        fs::write(paths::long_path(&path), contents).with_context(|| format!("Failed to create {}", path))?;
+    }
+}
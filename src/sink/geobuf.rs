@@ -0,0 +1,216 @@
+use super::Sink;
+use crate::crs::Crs;
+use crate::geojson::{GeoJsonFeature, Geometry, PropertyValue};
+use crate::paths;
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+/// Writes `<layer>.pbf` per layer: a [Geobuf](https://github.com/mapbox/geobuf)
+/// v3 `Data` message holding one FeatureCollection, with two-dimensional
+/// coordinates at 6 decimal places for WGS84 or 3 (millimetres) for HK80.
+///
+/// The protobuf wire format is written by hand; the schema is small and stable,
+/// and this keeps the format out of the `grpc` feature's dependencies.
+pub struct GeobufSink {
+    output_dir: String,
+    precision: u32,
+    layers: BTreeMap<String, Layer>,
+}
+
+#[derive(Default)]
+struct Layer {
+    /// Property names in the order they were first seen; features refer to them
+    /// by index.
+    keys: Vec<String>,
+    key_index: HashMap<String, u32>,
+    /// The encoded `features` fields of the FeatureCollection.
+    features: Vec<u8>,
+}
+
+// Wire types
+const VARINT: u8 = 0;
+const FIXED64: u8 = 1;
+const LENGTH_DELIMITED: u8 = 2;
+
+// Data.Geometry.Type
+const POINT: u64 = 0;
+const LINESTRING: u64 = 2;
+const MULTILINESTRING: u64 = 3;
+const POLYGON: u64 = 4;
+const MULTIPOLYGON: u64 = 5;
+
+impl GeobufSink {
+    pub fn new(output_dir: &str, crs: Crs) -> Self {
+        GeobufSink {
+            output_dir: output_dir.to_string(),
+            precision: match crs {
+                Crs::Wgs84 => 6,
+                Crs::Hk80 => 3,
+            },
+            layers: BTreeMap::new(),
+        }
+    }
+}
+
+impl Sink for GeobufSink {
+    fn write(&mut self, layer: &str, id: &str, feature: &GeoJsonFeature) -> Result<()> {
+        let factor = 10f64.powi(self.precision as i32);
+        let layer = self.layers.entry(layer.to_string()).or_default();
+
+        let mut message = Vec::new();
+        length_delimited(&mut message, 1, &encode_geometry(&feature.geometry, factor));
+        length_delimited(&mut message, 11, id.as_bytes());
+        let mut properties: Vec<(&String, &PropertyValue)> = feature.properties.iter().collect();
+        properties.sort_by_key(|(name, _)| *name);
+        let mut indexes = Vec::with_capacity(properties.len() * 2);
+        for (value_index, (name, value)) in properties.into_iter().enumerate() {
+            let key = match layer.key_index.get(name) {
+                Some(&key) => key,
+                None => {
+                    let key = layer.keys.len() as u32;
+                    layer.keys.push(name.clone());
+                    layer.key_index.insert(name.clone(), key);
+                    key
+                }
+            };
+            indexes.extend([u64::from(key), value_index as u64]);
+            length_delimited(&mut message, 13, &encode_value(value));
+        }
+        packed(&mut message, 14, &indexes);
+
+        length_delimited(&mut layer.features, 1, &message);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        for (name, layer) in &self.layers {
+            let mut data = Vec::with_capacity(layer.features.len() + 16);
+            for key in &layer.keys {
+                length_delimited(&mut data, 1, key.as_bytes());
+            }
+            if self.precision != 6 {
+                field_key(&mut data, 3, VARINT);
+                varint(&mut data, u64::from(self.precision));
+            }
+            length_delimited(&mut data, 4, &layer.features);
+            let path = format!("{}/{}.pbf", self.output_dir, name);
+            fs::write(paths::long_path(&path), data).with_context(|| format!("Failed to write {}", path))?;
+        }
+        Ok(())
+    }
+}
+
+fn encode_geometry(geometry: &Geometry, factor: f64) -> Vec<u8> {
+    let mut message = Vec::new();
+    let mut lengths = Vec::new();
+    let mut coords = Vec::new();
+    let kind = match geometry {
+        Geometry::Point(position) => {
+            coords.extend(position.iter().take(2).map(|value| (value * factor).round() as i64));
+            POINT
+        }
+        Geometry::LineString(line) => {
+            push_line(&mut coords, line, false, factor);
+            LINESTRING
+        }
+        Geometry::MultiLineString(lines) | Geometry::Polygon(lines) => {
+            // Polygon rings are closed, so their last position is implied
+            let closed = matches!(geometry, Geometry::Polygon(_));
+            if lines.len() != 1 {
+                lengths.extend(lines.iter().map(|line| (line.len() - usize::from(closed)) as u64));
+            }
+            for line in lines {
+                push_line(&mut coords, line, closed, factor);
+            }
+            if closed { POLYGON } else { MULTILINESTRING }
+        }
+        Geometry::MultiPolygon(polygons) => {
+            if polygons.len() != 1 || polygons[0].len() != 1 {
+                lengths.push(polygons.len() as u64);
+                for polygon in polygons {
+                    lengths.push(polygon.len() as u64);
+                    lengths.extend(polygon.iter().map(|ring| ring.len().saturating_sub(1) as u64));
+                }
+            }
+            for ring in polygons.iter().flatten() {
+                push_line(&mut coords, ring, true, factor);
+            }
+            MULTIPOLYGON
+        }
+    };
+    field_key(&mut message, 1, VARINT);
+    varint(&mut message, kind);
+    packed(&mut message, 2, &lengths);
+    packed(&mut message, 3, &coords.into_iter().map(zigzag).collect::<Vec<_>>());
+    message
+}
+
+/// Appends a line's positions as deltas from the previous one, starting from
+/// zero, leaving out the closing position of a ring.
+fn push_line(coords: &mut Vec<i64>, line: &[Vec<f64>], closed: bool, factor: f64) {
+    let count = if closed { line.len().saturating_sub(1) } else { line.len() };
+    let mut previous = [0i64; 2];
+    for position in &line[..count] {
+        for (axis, last) in previous.iter_mut().enumerate() {
+            let value = (position.get(axis).copied().unwrap_or_default() * factor).round() as i64;
+            coords.push(value - *last);
+            *last = value;
+        }
+    }
+}
+
+fn encode_value(value: &PropertyValue) -> Vec<u8> {
+    let mut message = Vec::new();
+    match value {
+        PropertyValue::String(text) => length_delimited(&mut message, 1, text.as_bytes()),
+        PropertyValue::Float(number) => {
+            field_key(&mut message, 2, FIXED64);
+            message.extend_from_slice(&number.to_le_bytes());
+        }
+        PropertyValue::Int(number) if *number >= 0 => {
+            field_key(&mut message, 3, VARINT);
+            varint(&mut message, *number as u64);
+        }
+        PropertyValue::Int(number) => {
+            field_key(&mut message, 4, VARINT);
+            varint(&mut message, number.unsigned_abs());
+        }
+        PropertyValue::Null => length_delimited(&mut message, 6, b"null"),
+    }
+    message
+}
+
+fn field_key(out: &mut Vec<u8>, field: u32, wire_type: u8) {
+    varint(out, u64::from(field << 3 | u32::from(wire_type)));
+}
+
+fn varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn length_delimited(out: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    field_key(out, field, LENGTH_DELIMITED);
+    varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// A packed repeated varint field, left out when empty.
+fn packed(out: &mut Vec<u8>, field: u32, values: &[u64]) {
+    if values.is_empty() {
+        return;
+    }
+    let mut bytes = Vec::with_capacity(values.len() * 2);
+    for &value in values {
+        varint(&mut bytes, value);
+    }
+    length_delimited(out, field, &bytes);
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
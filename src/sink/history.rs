@@ -0,0 +1,156 @@
+use super::{Sink, write_json};
+use crate::geojson::GeoJsonFeature;
+use crate::history::{self, Change, Current, CurrentIndex, Entry};
+use crate::paths;
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+/// Appends the changes since the previous run to the store in
+/// `<output>/history/`; see [`crate::history`]. Features removed from a layer
+/// are only noticed in layers that got features this run, so a missing source
+/// file doesn't close every version of its layer.
+pub struct HistorySink {
+    dir: PathBuf,
+    run_time: String,
+    /// The open versions recorded by the previous run, read on first use.
+    current: Option<CurrentIndex>,
+    /// Hash of every feature written this run, by layer and ID.
+    seen: BTreeMap<String, BTreeMap<String, String>>,
+    /// Features that are new or differ from their open version.
+    changed: Vec<(String, String, GeoJsonFeature)>,
+}
+
+impl HistorySink {
+    pub fn new(output_dir: &str) -> Self {
+        HistorySink {
+            dir: PathBuf::from(output_dir).join("history"),
+            run_time: history::now(),
+            current: None,
+            seen: BTreeMap::new(),
+            changed: Vec::new(),
+        }
+    }
+
+    fn current(&mut self) -> Result<&mut CurrentIndex> {
+        if self.current.is_none() {
+            let path = self.dir.join(history::CURRENT_FILE);
+            let current = match fs::read_to_string(&path) {
+                Ok(text) => serde_json::from_str(&text)
+                    .with_context(|| format!("Invalid history index: {}", path.display()))?,
+                Err(_) => CurrentIndex::new(),
+            };
+            self.current = Some(current);
+        }
+        Ok(self.current.as_mut().expect("read above"))
+    }
+}
+
+impl Sink for HistorySink {
+    fn write(&mut self, layer: &str, id: &str, feature: &GeoJsonFeature) -> Result<()> {
+        let json = serde_json::to_vec(feature)?;
+        let hash: String = Sha256::digest(&json).iter().map(|byte| format!("{:02x}", byte)).collect();
+        let unchanged = self
+            .current()?
+            .get(layer)
+            .and_then(|layer| layer.get(id))
+            .is_some_and(|current| current.hash == hash);
+        if !unchanged {
+            self.changed.push((layer.to_string(), id.to_string(), feature.clone()));
+        }
+        self.seen.entry(layer.to_string()).or_default().insert(id.to_string(), hash);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let mut current = std::mem::take(self.current()?);
+        fs::create_dir_all(paths::long_path(&self.dir)).with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let log_path = self.dir.join(history::LOG_FILE);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(paths::long_path(&log_path))
+            .with_context(|| format!("Failed to open {}", log_path.display()))?;
+        let mut log = BufWriter::new(file);
+        let mut append = |entry: &Entry| -> Result<()> {
+            serde_json::to_writer(&mut log, entry)?;
+            writeln!(log)?;
+            Ok(())
+        };
+
+        let (mut added, mut modified, mut removed) = (0, 0, 0);
+        for (layer, id, feature) in self.changed.drain(..) {
+            let previous = current.get(&layer).and_then(|layer| layer.get(&id));
+            let change = match previous {
+                Some(previous) => {
+                    append(&Entry {
+                        layer: layer.clone(),
+                        id: id.clone(),
+                        change: Change::Modified,
+                        valid_from: previous.valid_from.clone(),
+                        valid_to: Some(self.run_time.clone()),
+                        feature: None,
+                    })?;
+                    modified += 1;
+                    Change::Modified
+                }
+                None => {
+                    added += 1;
+                    Change::Added
+                }
+            };
+            append(&Entry {
+                layer,
+                id,
+                change,
+                valid_from: self.run_time.clone(),
+                valid_to: None,
+                feature: Some(feature),
+            })?;
+        }
+        for (layer, seen) in &self.seen {
+            for (id, open) in current.get(layer).into_iter().flatten() {
+                if !seen.contains_key(id) {
+                    append(&Entry {
+                        layer: layer.clone(),
+                        id: id.clone(),
+                        change: Change::Removed,
+                        valid_from: open.valid_from.clone(),
+                        valid_to: Some(self.run_time.clone()),
+                        feature: None,
+                    })?;
+                    removed += 1;
+                }
+            }
+        }
+        log.flush()?;
+
+        for (layer, seen) in std::mem::take(&mut self.seen) {
+            let previous = current.remove(&layer).unwrap_or_default();
+            let versions = seen
+                .into_iter()
+                .map(|(id, hash)| {
+                    let valid_from = match previous.get(&id) {
+                        Some(open) if open.hash == hash => open.valid_from.clone(),
+                        _ => self.run_time.clone(),
+                    };
+                    (id, Current { hash, valid_from })
+                })
+                .collect();
+            current.insert(layer, versions);
+        }
+        let current_path = self.dir.join(history::CURRENT_FILE);
+        let temporary = self.dir.join(format!("{}.tmp", history::CURRENT_FILE));
+        write_json(&temporary.to_string_lossy(), &current)?;
+        fs::rename(&temporary, &current_path)
+            .with_context(|| format!("Failed to replace {}", current_path.display()))?;
+        eprintln!(
+            "History at {}: {} added, {} modified and {} removed features",
+            self.run_time, added, modified, removed
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use super::Sink;
+use crate::geojson::GeoJsonFeature;
+use anyhow::{Result, anyhow, bail};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Wait before the first retry; doubled on each further attempt.
+const FIRST_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How [`HttpSink`] batches and sends its requests.
+#[derive(Debug, Clone)]
+pub struct PostOptions {
+    pub url: String,
+    /// Features per request.
+    pub batch_size: usize,
+    /// Requests in flight at once.
+    pub concurrency: usize,
+    /// Further attempts after a request fails with a network error, 429 or 5xx.
+    pub retries: u32,
+}
+
+/// POSTs features to an HTTP endpoint in batches, one layer per request. Each
+/// body is a GeoJSON FeatureCollection with a `layer` member, whose features
+/// carry their ID as `id`. Requests are sent by a pool of threads; the first
+/// one to fail for good stops the conversion.
+pub struct HttpSink {
+    batch_size: usize,
+    batches: BTreeMap<String, Vec<Value>>,
+    sender: Option<SyncSender<Batch>>,
+    workers: Vec<JoinHandle<Result<()>>>,
+    posted: Arc<Mutex<usize>>,
+}
+
+/// A request waiting for a sender thread: its layer and features.
+type Batch = (String, Vec<Value>);
+
+impl HttpSink {
+    pub fn new(options: PostOptions) -> Self {
+        let concurrency = options.concurrency.max(1);
+        let (sender, receiver) = mpsc::sync_channel::<Batch>(concurrency);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let posted = Arc::new(Mutex::new(0));
+        let workers = (0..concurrency)
+            .map(|_| {
+                let (receiver, posted, options) = (Arc::clone(&receiver), Arc::clone(&posted), options.clone());
+                thread::spawn(move || post_batches(&receiver, &posted, &options))
+            })
+            .collect();
+        HttpSink {
+            batch_size: options.batch_size.max(1),
+            batches: BTreeMap::new(),
+            sender: Some(sender),
+            workers,
+            posted,
+        }
+    }
+
+    fn send(&mut self, layer: String, features: Vec<Value>) -> Result<()> {
+        let sent = match &self.sender {
+            Some(sender) => sender.send((layer, features)).is_ok(),
+            None => false,
+        };
+        if sent {
+            return Ok(());
+        }
+        // Every sender has stopped, so one of them failed
+        self.join()?;
+        Err(anyhow!("HTTP senders stopped unexpectedly"))
+    }
+
+    /// Waits for every queued request, returning the first error.
+    fn join(&mut self) -> Result<()> {
+        self.sender = None;
+        let mut result = Ok(());
+        for worker in self.workers.drain(..) {
+            let outcome = worker.join().unwrap_or_else(|_| Err(anyhow!("An HTTP sender panicked")));
+            if result.is_ok() {
+                result = outcome;
+            }
+        }
+        result
+    }
+}
+
+impl Sink for HttpSink {
+    fn write(&mut self, layer: &str, id: &str, feature: &GeoJsonFeature) -> Result<()> {
+        let mut value = serde_json::to_value(feature)?;
+        value["id"] = id.into();
+        let batch = self.batches.entry(layer.to_string()).or_default();
+        batch.push(value);
+        if batch.len() >= self.batch_size {
+            let features = std::mem::take(batch);
+            self.send(layer.to_string(), features)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        for (layer, features) in std::mem::take(&mut self.batches) {
+            if !features.is_empty() {
+                self.send(layer, features)?;
+            }
+        }
+        self.join()?;
+        let posted = *self.posted.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        eprintln!("Posted {} batches", posted);
+        Ok(())
+    }
+}
+
+fn post_batches(receiver: &Mutex<Receiver<Batch>>, posted: &Mutex<usize>, options: &PostOptions) -> Result<()> {
+    let agent = ureq::AgentBuilder::new().timeout(Duration::from_secs(60)).build();
+    loop {
+        let batch = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => return Err(anyhow!("An HTTP sender panicked")),
+        };
+        // The sink has finished and the queue is drained
+        let Ok((layer, features)) = batch else {
+            return Ok(());
+        };
+        let count = features.len();
+        let body = serde_json::to_vec(&json!({
+            "type": "FeatureCollection",
+            "layer": layer,
+            "features": features,
+        }))?;
+        post(&agent, options, &body).map_err(|e| anyhow!("Failed to post {} {} features to {}: {}", count, layer, options.url, e))?;
+        *posted.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) += 1;
+    }
+}
+
+/// Sends one request, retrying with exponential backoff when it may succeed later.
+fn post(agent: &ureq::Agent, options: &PostOptions, body: &[u8]) -> Result<()> {
+    let mut backoff = FIRST_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        let error = match agent.post(&options.url).set("Content-Type", "application/geo+json").send_bytes(body) {
+            Ok(_) => return Ok(()),
+            Err(ureq::Error::Status(status, _)) if status != 429 && status < 500 => bail!("HTTP {}", status),
+            Err(ureq::Error::Status(status, _)) => anyhow!("HTTP {}", status),
+            Err(ureq::Error::Transport(e)) => anyhow!(e),
+        };
+        if attempt >= options.retries {
+            return Err(error.context(format!("giving up after {} attempts", attempt + 1)));
+        }
+        eprintln!("Warning: {}; retrying in {:?}", error, backoff);
+        thread::sleep(backoff);
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+        attempt += 1;
+    }
+}
@@ -0,0 +1,91 @@
+use super::Sink;
+use crate::geojson::GeoJsonFeature;
+use anyhow::{Context, Result, bail};
+use rdkafka::ClientContext;
+use rdkafka::config::ClientConfig;
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::producer::{BaseRecord, DeliveryResult, Producer, ProducerContext, ThreadedProducer};
+use rdkafka::util::Timeout;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// How long [`Sink::finish`] waits for outstanding messages to be acknowledged.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Publishes every feature to the Kafka topic `<prefix><layer>`, keyed by its ID
+/// with the GeoJSON Feature as the value. Messages are sent in the background;
+/// [`Sink::finish`] waits for them and fails if any weren't delivered.
+pub struct KafkaSink {
+    producer: ThreadedProducer<DeliveryLog>,
+    topic_prefix: String,
+}
+
+/// Delivery failures reported by the producer.
+#[derive(Default)]
+struct DeliveryLog {
+    /// How many messages failed, and the first error.
+    failures: Mutex<(usize, Option<String>)>,
+}
+
+impl ClientContext for DeliveryLog {}
+
+impl ProducerContext for DeliveryLog {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, result: &DeliveryResult<'_>, _: ()) {
+        if let Err((e, _)) = result {
+            let mut failures = self.failures.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            failures.0 += 1;
+            failures.1.get_or_insert_with(|| e.to_string());
+        }
+    }
+}
+
+impl KafkaSink {
+    pub fn new(brokers: &str, topic_prefix: &str) -> Result<Self> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("enable.idempotence", "true")
+            .create_with_context(DeliveryLog::default())
+            .with_context(|| format!("Failed to create a Kafka producer for {}", brokers))?;
+        Ok(KafkaSink {
+            producer,
+            topic_prefix: topic_prefix.to_string(),
+        })
+    }
+}
+
+impl Sink for KafkaSink {
+    fn write(&mut self, layer: &str, id: &str, feature: &GeoJsonFeature) -> Result<()> {
+        let topic = format!("{}{}", self.topic_prefix, layer);
+        let payload = serde_json::to_vec(feature)?;
+        let mut record = BaseRecord::to(&topic).key(id).payload(&payload);
+        loop {
+            match self.producer.send(record) {
+                Ok(()) => return Ok(()),
+                // The local queue drains as the broker acknowledges messages
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), returned)) => {
+                    record = returned;
+                    thread::sleep(Duration::from_millis(100));
+                }
+                Err((e, _)) => return Err(e).with_context(|| format!("Failed to publish {} to {}", id, topic)),
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.producer
+            .flush(Timeout::After(FLUSH_TIMEOUT))
+            .context("Timed out waiting for Kafka to acknowledge every feature")?;
+        let failures = self.producer.context().failures.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if failures.0 > 0 {
+            bail!(
+                "{} features were not delivered to Kafka: {}",
+                failures.0,
+                failures.1.as_deref().unwrap_or_default()
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use super::{Sink, write_json};
+use crate::geojson::{GeoJsonFeature, PropertyValue};
+use anyhow::Result;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+
+const PALETTE: &[[u8; 3]] = &[[255, 153, 31], [18, 147, 154], [221, 178, 124], [136, 87, 44], [130, 154, 227]];
+
+/// Buffers every layer and writes a single kepler.gl map file (`keplergl.json`)
+/// that can be dropped onto kepler.gl: one dataset and one GeoJSON layer per
+/// output layer, centred on Hong Kong.
+pub struct KeplerGlSink {
+    path: String,
+    layers: BTreeMap<String, Layer>,
+}
+
+#[derive(Default)]
+struct Layer {
+    /// Property name to kepler field type.
+    fields: BTreeMap<String, &'static str>,
+    features: Vec<GeoJsonFeature>,
+}
+
+fn field_type(value: &PropertyValue) -> Option<&'static str> {
+    match value {
+        PropertyValue::String(_) => Some("string"),
+        PropertyValue::Int(_) => Some("integer"),
+        PropertyValue::Float(_) => Some("real"),
+        PropertyValue::Null => None,
+    }
+}
+
+impl KeplerGlSink {
+    pub fn new(output_dir: &str) -> Self {
+        KeplerGlSink {
+            path: format!("{}/keplergl.json", output_dir),
+            layers: BTreeMap::new(),
+        }
+    }
+
+    fn dataset(name: &str, layer: &Layer) -> Value {
+        let mut fields = vec![json!({
+            "name": "_geojson",
+            "type": "geojson",
+            "format": "",
+            "analyzerType": "GEOMETRY",
+        })];
+        fields.extend(layer.fields.iter().map(|(name, kind)| {
+            json!({ "name": name, "type": kind, "format": "" })
+        }));
+
+        let rows: Vec<Value> = layer
+            .features
+            .iter()
+            .map(|feature| {
+                let mut row = vec![json!(feature)];
+                row.extend(layer.fields.keys().map(|name| match feature.properties.get(name) {
+                    Some(value) => json!(value),
+                    None => Value::Null,
+                }));
+                Value::Array(row)
+            })
+            .collect();
+
+        json!({
+            "version": "v1",
+            "data": { "id": name, "label": name, "allData": rows, "fields": fields },
+        })
+    }
+
+    fn layer_config(name: &str, index: usize) -> Value {
+        json!({
+            "id": name,
+            "type": "geojson",
+            "config": {
+                "dataId": name,
+                "label": name,
+                "color": PALETTE[index % PALETTE.len()],
+                "columns": { "geojson": "_geojson" },
+                "isVisible": true,
+            },
+        })
+    }
+}
+
+impl Sink for KeplerGlSink {
+    fn write(&mut self, layer: &str, _id: &str, feature: &GeoJsonFeature) -> Result<()> {
+        let entry = self.layers.entry(layer.to_string()).or_default();
+        for (name, value) in &feature.properties {
+            if let Some(kind) = field_type(value) {
+                // Mixed types within one column are shown as strings.
+                let existing = entry.fields.entry(name.clone()).or_insert(kind);
+                if *existing != kind {
+                    *existing = "string";
+                }
+            }
+        }
+        entry.features.push(feature.clone());
+        Ok(())
+    }
+
+    fn reserve(&mut self, layer: &str, features: usize) {
+        self.layers.entry(layer.to_string()).or_default().features.reserve(features);
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let datasets: Vec<Value> = self.layers.iter().map(|(name, layer)| Self::dataset(name, layer)).collect();
+        let layers: Vec<Value> = self
+            .layers
+            .keys()
+            .enumerate()
+            .map(|(index, name)| Self::layer_config(name, index))
+            .collect();
+        let map = json!({
+            "info": { "app": "kepler.gl", "source": "road-network-json" },
+            "datasets": datasets,
+            "config": {
+                "version": "v1",
+                "config": {
+                    "visState": { "layers": layers },
+                    "mapState": { "latitude": 22.35, "longitude": 114.15, "zoom": 10.5 },
+                    "mapStyle": {},
+                },
+            },
+        });
+        write_json(&self.path, &map)
+    }
+}
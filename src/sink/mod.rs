@@ -0,0 +1,196 @@
+//! Output formats for the conversion run.
+//!
+//! The converter hands every feature to a [`Sink`] tagged with its layer: a
+//! dataset's `output_subdir` (e.g. `centerlines`) or a companion layer derived
+//! from it (e.g. `centerlines_points`).
+
+#[cfg(feature = "arrow")]
+mod arrow;
+mod cityjson;
+mod csv;
+mod duckdb;
+mod dxf;
+mod files;
+mod geobuf;
+mod history;
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod keplergl;
+mod pages;
+#[cfg(feature = "parquet")]
+mod parquet;
+mod schema;
+mod set;
+mod well_known;
+
+#[cfg(feature = "arrow")]
+pub use arrow::ArrowSink;
+pub use cityjson::CityJsonSink;
+pub use csv::CsvSink;
+pub use duckdb::DuckDbSink;
+pub use dxf::DxfSink;
+pub use files::{FileOptions, FileParts, FileSink};
+pub use geobuf::GeobufSink;
+pub use history::HistorySink;
+#[cfg(feature = "http")]
+pub use http::{HttpSink, PostOptions};
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaSink;
+pub use keplergl::KeplerGlSink;
+pub use pages::PagesSink;
+#[cfg(feature = "parquet")]
+pub use parquet::ParquetSink;
+pub use set::SinkSet;
+pub use well_known::{Encoding, WellKnownSink};
+
+use crate::geojson::GeoJsonFeature;
+use crate::paths;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+pub trait Sink {
+    fn write(&mut self, layer: &str, id: &str, feature: &GeoJsonFeature) -> Result<()>;
+
+    /// Writes several features that share one source ID, such as a segment's two
+    /// endpoints. Sinks with a file per ID override this to keep them together.
+    fn write_group(&mut self, layer: &str, id: &str, features: &[GeoJsonFeature]) -> Result<()> {
+        for feature in features {
+            self.write(layer, id, feature)?;
+        }
+        Ok(())
+    }
+
+    /// Told how many features `layer` is about to get, when a pre-scan has
+    /// counted them, so sinks that buffer features can allocate once.
+    fn reserve(&mut self, _layer: &str, _features: usize) {}
+
+    /// Flushes anything buffered once every dataset has been converted.
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+pub fn write_json<T: Serialize>(path: &str, value: &T) -> Result<()> {
+    let json = serde_json::to_string_pretty(value)?;
+    let mut file = File::create(paths::long_path(path)).with_context(|| format!("Failed to create {}", path))?;
+    file.write_all(json.as_bytes())?;
+    Ok(())
+}
+
+const COLLECTION_START: &[u8] = br#"{"type":"FeatureCollection","features":["#;
+const COLLECTION_END: &[u8] = b"]}";
+
+#[derive(Serialize)]
+struct Collection<'a, T> {
+    #[serde(rename = "type")]
+    collection_type: &'static str,
+    features: &'a [T],
+}
+
+/// One file of a FeatureCollection split by [`write_collection`].
+#[derive(Serialize, Deserialize)]
+struct Part {
+    file: String,
+    features: usize,
+    bytes: usize,
+}
+
+/// The `<name>.parts.json` listing the parts of a split FeatureCollection.
+#[derive(Serialize, Deserialize)]
+struct Parts {
+    features: usize,
+    parts: Vec<Part>,
+}
+
+/// Writes `features` as a FeatureCollection to `path`. With `max_bytes`, the
+/// collection is written compactly, and if it would still be larger it goes to
+/// numbered parts instead, e.g. `edges-0001.geojson`, each a FeatureCollection
+/// of at most `max_bytes`, listed in `edges.parts.json`. A feature larger than
+/// the budget gets a part of its own. Parts, or the whole file, left by an
+/// earlier run are removed so the two never mix.
+pub fn write_collection<T: Serialize>(path: &str, features: &[T], max_bytes: Option<u64>) -> Result<()> {
+    let file = Path::new(path);
+    let dir = file.parent().unwrap_or(Path::new(""));
+    let stem = file.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let manifest = dir.join(format!("{}.parts.json", stem));
+    if let Ok(text) = fs::read_to_string(&manifest)
+        && let Ok(previous) = serde_json::from_str::<Parts>(&text)
+    {
+        for part in previous.parts {
+            let _ = fs::remove_file(dir.join(part.file));
+        }
+        fs::remove_file(&manifest).with_context(|| format!("Failed to remove {}", manifest.display()))?;
+    }
+    let Some(max_bytes) = max_bytes else {
+        return write_json(
+            path,
+            &Collection {
+                collection_type: "FeatureCollection",
+                features,
+            },
+        );
+    };
+    let encoded: Vec<Vec<u8>> = features.iter().map(serde_json::to_vec).collect::<Result<_, _>>()?;
+    let overhead = COLLECTION_START.len() + COLLECTION_END.len();
+    let total = overhead + encoded.iter().map(|feature| feature.len() + 1).sum::<usize>();
+    if total as u64 <= max_bytes || encoded.is_empty() {
+        return write_part(path, &encoded);
+    }
+    if file.exists() {
+        fs::remove_file(file).with_context(|| format!("Failed to remove {}", path))?;
+    }
+
+    let extension = file.extension().map(|extension| extension.to_string_lossy().into_owned());
+    let mut parts = Vec::new();
+    let mut start = 0;
+    while start < encoded.len() {
+        let mut end = start;
+        let mut bytes = overhead;
+        while end < encoded.len() && (end == start || (bytes + encoded[end].len() + 1) as u64 <= max_bytes) {
+            bytes += encoded[end].len() + usize::from(end > start);
+            end += 1;
+        }
+        if end == start + 1 && bytes as u64 > max_bytes {
+            eprintln!("Warning: a feature alone is {} bytes, over --max-file-size", bytes - overhead);
+        }
+        let name = match &extension {
+            Some(extension) => format!("{}-{:04}.{}", stem, parts.len() + 1, extension),
+            None => format!("{}-{:04}", stem, parts.len() + 1),
+        };
+        write_part(&dir.join(&name).to_string_lossy(), &encoded[start..end])?;
+        parts.push(Part {
+            file: name,
+            features: end - start,
+            bytes,
+        });
+        start = end;
+    }
+    eprintln!("Split {} into {} parts, listed in {}", path, parts.len(), manifest.display());
+    write_json(
+        &manifest.to_string_lossy(),
+        &Parts {
+            features: features.len(),
+            parts,
+        },
+    )
+}
+
+fn write_part(path: &str, features: &[Vec<u8>]) -> Result<()> {
+    let file = File::create(paths::long_path(path)).with_context(|| format!("Failed to create {}", path))?;
+    let mut out = BufWriter::new(file);
+    out.write_all(COLLECTION_START)?;
+    for (i, feature) in features.iter().enumerate() {
+        if i > 0 {
+            out.write_all(b",")?;
+        }
+        out.write_all(feature)?;
+    }
+    out.write_all(COLLECTION_END)?;
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use super::{Sink, write_json, write_part};
+use crate::geojson::GeoJsonFeature;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+
+/// Every layer as numbered FeatureCollections of `chunk_size` features,
+/// `<output>/<layer>-0001.geojson` onwards, the way a paginated API serves them.
+/// Each feature carries its ID as the GeoJSON `id`. `pages.json` lists every
+/// layer's pages with their first and last IDs. Pages listed by an earlier run
+/// into the same directory are removed first.
+pub struct PagesSink {
+    output_dir: String,
+    chunk_size: usize,
+    layers: BTreeMap<String, Layer>,
+}
+
+/// The `pages.json` index.
+#[derive(Serialize, Deserialize)]
+struct Index {
+    chunk_size: usize,
+    layers: BTreeMap<String, Layer>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Layer {
+    features: usize,
+    pages: Vec<Page>,
+    /// Encoded features and IDs of the page being filled.
+    #[serde(skip)]
+    pending: Vec<(String, Vec<u8>)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Page {
+    file: String,
+    features: usize,
+    first_id: String,
+    last_id: String,
+}
+
+impl PagesSink {
+    pub fn new(output_dir: &str, chunk_size: usize) -> Self {
+        let index = format!("{}/pages.json", output_dir);
+        if let Ok(text) = fs::read_to_string(&index)
+            && let Ok(previous) = serde_json::from_str::<Index>(&text)
+        {
+            for page in previous.layers.into_values().flat_map(|layer| layer.pages) {
+                let _ = fs::remove_file(format!("{}/{}", output_dir, page.file));
+            }
+        }
+        PagesSink {
+            output_dir: output_dir.to_string(),
+            chunk_size,
+            layers: BTreeMap::new(),
+        }
+    }
+
+    fn flush(output_dir: &str, name: &str, layer: &mut Layer) -> Result<()> {
+        let (Some((first_id, _)), Some((last_id, _))) = (layer.pending.first(), layer.pending.last()) else {
+            return Ok(());
+        };
+        let page = Page {
+            file: format!("{}-{:04}.geojson", name, layer.pages.len() + 1),
+            features: layer.pending.len(),
+            first_id: first_id.clone(),
+            last_id: last_id.clone(),
+        };
+        let features: Vec<Vec<u8>> = layer.pending.drain(..).map(|(_, json)| json).collect();
+        write_part(&format!("{}/{}", output_dir, page.file), &features)?;
+        layer.pages.push(page);
+        Ok(())
+    }
+}
+
+impl Sink for PagesSink {
+    fn write(&mut self, layer: &str, id: &str, feature: &GeoJsonFeature) -> Result<()> {
+        let mut value = serde_json::to_value(feature)?;
+        value["id"] = id.into();
+        let entry = self.layers.entry(layer.to_string()).or_default();
+        entry.pending.push((id.to_string(), serde_json::to_vec(&value)?));
+        entry.features += 1;
+        if entry.pending.len() >= self.chunk_size {
+            Self::flush(&self.output_dir, layer, entry)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        for (name, layer) in &mut self.layers {
+            Self::flush(&self.output_dir, name, layer)?;
+        }
+        let pages: usize = self.layers.values().map(|layer| layer.pages.len()).sum();
+        eprintln!("Wrote {} pages, listed in {}/pages.json", pages, self.output_dir);
+        write_json(
+            &format!("{}/pages.json", self.output_dir),
+            &Index {
+                chunk_size: self.chunk_size,
+                layers: std::mem::take(&mut self.layers),
+            },
+        )
+    }
+}
@@ -0,0 +1,50 @@
+use super::Sink;
+use super::arrow::record_batch;
+use super::schema::Table;
+use crate::crs::Crs;
+use crate::geojson::GeoJsonFeature;
+use crate::paths;
+use anyhow::{Context, Result};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::collections::BTreeMap;
+use std::fs::File;
+
+/// Writes `<layer>.parquet` per layer with the same columns as the Arrow stream,
+/// minus `layer`. Geometry is left out entirely when `crs` is `None`.
+pub struct ParquetSink {
+    output_dir: String,
+    crs: Option<Crs>,
+    layers: BTreeMap<String, Table>,
+}
+
+impl ParquetSink {
+    pub fn new(output_dir: &str, crs: Option<Crs>) -> Self {
+        ParquetSink {
+            output_dir: output_dir.to_string(),
+            crs,
+            layers: BTreeMap::new(),
+        }
+    }
+}
+
+impl Sink for ParquetSink {
+    fn write(&mut self, layer: &str, id: &str, feature: &GeoJsonFeature) -> Result<()> {
+        self.layers.entry(layer.to_string()).or_default().push(id, feature);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+        for (name, table) in &self.layers {
+            let batch = record_batch(table, None, self.crs)?;
+            let path = format!("{}/{}.parquet", self.output_dir, name);
+            let file = File::create(paths::long_path(&path)).with_context(|| format!("Failed to create {}", path))?;
+            let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(properties.clone()))?;
+            writer.write(&batch)?;
+            writer.close()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,60 @@
+use crate::geojson::{GeoJsonFeature, PropertyValue};
+use std::collections::BTreeMap;
+
+/// Column type for a property, widened as features are seen: integers widen to
+/// floats, and anything mixed with strings becomes a string.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnType {
+    Int,
+    Float,
+    String,
+}
+
+impl ColumnType {
+    pub fn of(value: &PropertyValue) -> Option<ColumnType> {
+        match value {
+            PropertyValue::Int(_) => Some(ColumnType::Int),
+            PropertyValue::Float(_) => Some(ColumnType::Float),
+            PropertyValue::String(_) => Some(ColumnType::String),
+            PropertyValue::Null => None,
+        }
+    }
+
+    pub fn widen(self, other: ColumnType) -> ColumnType {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ColumnType::Int, ColumnType::Float) | (ColumnType::Float, ColumnType::Int) => ColumnType::Float,
+            _ => ColumnType::String,
+        }
+    }
+}
+
+pub struct Row {
+    pub id: String,
+    pub feature: GeoJsonFeature,
+}
+
+/// A layer's buffered features plus the column types inferred across all of
+/// them, for formats that need a fixed schema before the first row is written.
+#[derive(Default)]
+pub struct Table {
+    pub columns: BTreeMap<String, ColumnType>,
+    pub rows: Vec<Row>,
+}
+
+impl Table {
+    pub fn push(&mut self, id: &str, feature: &GeoJsonFeature) {
+        for (name, value) in &feature.properties {
+            if let Some(column) = ColumnType::of(value) {
+                self.columns
+                    .entry(name.clone())
+                    .and_modify(|existing| *existing = existing.widen(column))
+                    .or_insert(column);
+            }
+        }
+        self.rows.push(Row {
+            id: id.to_string(),
+            feature: feature.clone(),
+        });
+    }
+}
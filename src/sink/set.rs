@@ -0,0 +1,41 @@
+use super::Sink;
+use crate::paths;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
+use std::fs;
+
+/// One sink per output directory, created on first use. A plain conversion only
+/// ever uses the output directory itself; `--split-by` adds one per district.
+pub struct SinkSet<F> {
+    make: F,
+    sinks: BTreeMap<String, Box<dyn Sink>>,
+}
+
+impl<F: FnMut(&str) -> Box<dyn Sink>> SinkSet<F> {
+    /// `make` builds the sink for a directory.
+    pub fn new(make: F) -> Self {
+        SinkSet {
+            make,
+            sinks: BTreeMap::new(),
+        }
+    }
+
+    pub fn sink_for(&mut self, dir: &str) -> Result<&mut dyn Sink> {
+        let sink = match self.sinks.entry(dir.to_string()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                fs::create_dir_all(paths::long_path(dir)).with_context(|| format!("Failed to create {}", dir))?;
+                entry.insert((self.make)(dir))
+            }
+        };
+        Ok(sink.as_mut())
+    }
+
+    pub fn finish(&mut self) -> Result<()> {
+        for sink in self.sinks.values_mut() {
+            sink.finish()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use super::Sink;
+use crate::geojson::GeoJsonFeature;
+use crate::paths;
+use crate::well_known;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Encoding {
+    Wkt,
+    /// Hex-encoded WKB, which PostGIS accepts directly in `COPY`.
+    WkbHex,
+}
+
+/// Geometry-only output: one `<layer>.wkt` / `<layer>.wkb` file per layer with an
+/// `id<TAB>geometry` line per feature, ready for PostgreSQL `COPY ... FROM`.
+pub struct WellKnownSink {
+    output_dir: String,
+    encoding: Encoding,
+    files: HashMap<String, BufWriter<File>>,
+}
+
+/// Escapes a value for PostgreSQL's text `COPY` format.
+fn copy_escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+impl WellKnownSink {
+    pub fn new(output_dir: &str, encoding: Encoding) -> Self {
+        WellKnownSink {
+            output_dir: output_dir.to_string(),
+            encoding,
+            files: HashMap::new(),
+        }
+    }
+}
+
+impl Sink for WellKnownSink {
+    fn write(&mut self, layer: &str, id: &str, feature: &GeoJsonFeature) -> Result<()> {
+        if !self.files.contains_key(layer) {
+            let extension = match self.encoding {
+                Encoding::Wkt => "wkt",
+                Encoding::WkbHex => "wkb",
+            };
+            let path = format!("{}/{}.{}", self.output_dir, layer, extension);
+            let file = File::create(paths::long_path(&path)).with_context(|| format!("Failed to create {}", path))?;
+            self.files.insert(layer.to_string(), BufWriter::new(file));
+        }
+        let geometry = match self.encoding {
+            Encoding::Wkt => well_known::to_wkt(&feature.geometry),
+            Encoding::WkbHex => well_known::to_wkb_hex(&feature.geometry),
+        };
+        let out = self.files.get_mut(layer).expect("opened above");
+        writeln!(out, "{}\t{}", copy_escape(id), geometry)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        for out in self.files.values_mut() {
+            out.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+//! In-memory R-tree over feature geometries for nearest-feature queries.
+//!
+//! Geometries are indexed segment by segment in the local metric plane from
+//! [`geometry::to_local_m`], so distances come back in metres.
+
+use crate::geometry;
+use rstar::primitives::{GeomWithData, Line};
+use rstar::{PointDistance, RTree};
+
+type IndexedLine = GeomWithData<Line<[f64; 2]>, usize>;
+
+pub struct SpatialIndex {
+    tree: RTree<IndexedLine>,
+}
+
+impl SpatialIndex {
+    /// Indexes each `(item, coordinates)` pair; `item` is returned by queries.
+    pub fn build<'a>(items: impl IntoIterator<Item = (usize, &'a [Vec<f64>])>) -> Self {
+        let mut lines = Vec::new();
+        for (item, coords) in items {
+            let local: Vec<[f64; 2]> = coords.iter().map(|p| geometry::to_local_m(p)).collect();
+            if let [only] = local[..] {
+                lines.push(GeomWithData::new(Line::new(only, only), item));
+            }
+            for w in local.windows(2) {
+                lines.push(GeomWithData::new(Line::new(w[0], w[1]), item));
+            }
+        }
+        SpatialIndex {
+            tree: RTree::bulk_load(lines),
+        }
+    }
+
+    /// The closest item accepted by `filter`, with its distance in metres.
+    pub fn nearest_where(&self, point: &[f64], filter: impl Fn(usize) -> bool) -> Option<(usize, f64)> {
+        let query = geometry::to_local_m(point);
+        self.tree
+            .nearest_neighbor_iter(&query)
+            .find(|line| filter(line.data))
+            .map(|line| (line.data, line.distance_2(&query).sqrt()))
+    }
+}
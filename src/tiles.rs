@@ -0,0 +1,173 @@
+//! Which web map tiles converted features fall in, for `--tile-report`.
+//!
+//! Tiles are the usual z/x/y slippy-map tiles in Web Mercator. A feature counts
+//! towards every tile its geometry touches: lines through each tile a segment
+//! crosses, and zones also through each tile whose centre lies inside them, so
+//! a zone larger than a tile still covers the tiles in its middle. Segments are
+//! taken as straight in tile space, which is close enough at the lengths of
+//! road segments.
+
+use crate::crs::{self, Crs};
+use crate::geojson::Geometry;
+use crate::geometry;
+use crate::paths;
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The deepest zoom accepted; tiles are under 10 cm across by then.
+pub const MAX_ZOOM: u8 = 24;
+
+/// Web Mercator stops short of the poles at this latitude.
+const MAX_LATITUDE: f64 = 85.051_128_779_806_59;
+
+/// Feature counts per tile at one zoom level.
+pub struct TileReport {
+    zoom: u8,
+    crs: Crs,
+    /// Features per `(x, y)` tile.
+    counts: BTreeMap<(u32, u32), usize>,
+}
+
+impl TileReport {
+    /// An empty report at `zoom` for geometries in `crs`.
+    pub fn new(zoom: u8, crs: Crs) -> Self {
+        TileReport {
+            zoom,
+            crs,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    /// Counts a feature's geometry towards every tile it touches.
+    pub fn add(&mut self, geometry: &Geometry) {
+        let geometry = match self.crs {
+            Crs::Wgs84 => Cow::Borrowed(geometry),
+            Crs::Hk80 => match to_wgs84(geometry) {
+                Some(geometry) => Cow::Owned(geometry),
+                None => return,
+            },
+        };
+        let mut tiles = BTreeSet::new();
+        match geometry.as_ref() {
+            Geometry::Point(position) => {
+                let [x, y] = self.tile_position(position);
+                tiles.insert(self.clamp(x, y));
+            }
+            Geometry::LineString(coords) => self.cover_line(coords, &mut tiles),
+            Geometry::MultiLineString(lines) => {
+                for coords in lines {
+                    self.cover_line(coords, &mut tiles);
+                }
+            }
+            Geometry::Polygon(_) | Geometry::MultiPolygon(_) => {
+                for polygon in geometry.polygons() {
+                    self.cover_polygon(polygon, &mut tiles);
+                }
+            }
+        }
+        for tile in tiles {
+            *self.counts.entry(tile).or_default() += 1;
+        }
+    }
+
+    /// Writes `z,x,y,features` rows, one per tile with any feature.
+    pub fn write(&self, path: &str) -> Result<()> {
+        let mut writer = csv::Writer::from_path(paths::long_path(path)).with_context(|| format!("Failed to create {}", path))?;
+        writer.write_record(["z", "x", "y", "features"])?;
+        for (&(x, y), count) in &self.counts {
+            writer.write_record([self.zoom.to_string(), x.to_string(), y.to_string(), count.to_string()])?;
+        }
+        writer.flush()?;
+        eprintln!("Listed {} tile(s) at zoom {} in {}", self.counts.len(), self.zoom, path);
+        Ok(())
+    }
+
+    /// A WGS84 position in fractional tile units at this zoom.
+    fn tile_position(&self, position: &[f64]) -> [f64; 2] {
+        let scale = f64::from(1u32 << self.zoom);
+        let latitude = position[1].clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians();
+        let x = (position[0] + 180.0) / 360.0 * scale;
+        let y = (1.0 - latitude.tan().asinh() / std::f64::consts::PI) / 2.0 * scale;
+        [x, y]
+    }
+
+    /// The tile holding a fractional tile position, kept within the world.
+    fn clamp(&self, x: f64, y: f64) -> (u32, u32) {
+        let last = ((1u64 << self.zoom) - 1) as f64;
+        (x.floor().clamp(0.0, last) as u32, y.floor().clamp(0.0, last) as u32)
+    }
+
+    fn cover_line(&self, coords: &[Vec<f64>], tiles: &mut BTreeSet<(u32, u32)>) {
+        let points: Vec<[f64; 2]> = coords.iter().map(|position| self.tile_position(position)).collect();
+        self.cover_segments(&points, tiles);
+    }
+
+    /// Adds the tiles a line in tile units passes through.
+    fn cover_segments(&self, points: &[[f64; 2]], tiles: &mut BTreeSet<(u32, u32)>) {
+        for point in points {
+            tiles.insert(self.clamp(point[0], point[1]));
+        }
+        for w in points.windows(2) {
+            let (a, b) = (w[0], w[1]);
+            let min = self.clamp(a[0].min(b[0]), a[1].min(b[1]));
+            let max = self.clamp(a[0].max(b[0]), a[1].max(b[1]));
+            for x in min.0..=max.0 {
+                for y in min.1..=max.1 {
+                    let bbox = [f64::from(x), f64::from(y), f64::from(x + 1), f64::from(y + 1)];
+                    if geometry::clip_segment(&a, &b, &bbox).is_some() {
+                        tiles.insert((x, y));
+                    }
+                }
+            }
+        }
+    }
+
+    fn cover_polygon(&self, rings: &[Vec<Vec<f64>>], tiles: &mut BTreeSet<(u32, u32)>) {
+        let rings: Vec<Vec<[f64; 2]>> = rings
+            .iter()
+            .map(|ring| ring.iter().map(|position| self.tile_position(position)).collect())
+            .collect();
+        let Some(exterior) = rings.first().filter(|ring| !ring.is_empty()) else {
+            return;
+        };
+        for ring in &rings {
+            self.cover_segments(ring, tiles);
+        }
+        let (mut min, mut max) = ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]);
+        for point in exterior {
+            for axis in 0..2 {
+                min[axis] = min[axis].min(point[axis]);
+                max[axis] = max[axis].max(point[axis]);
+            }
+        }
+        let (min, max) = (self.clamp(min[0], min[1]), self.clamp(max[0], max[1]));
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                let centre = [f64::from(x) + 0.5, f64::from(y) + 0.5];
+                if geometry::point_in_ring(&centre, exterior)
+                    && !rings[1..].iter().any(|hole| geometry::point_in_ring(&centre, hole))
+                {
+                    tiles.insert((x, y));
+                }
+            }
+        }
+    }
+}
+
+/// The geometry with its HK80 Grid positions projected to WGS84, or `None` if
+/// any position fails to project.
+fn to_wgs84(geometry: &Geometry) -> Option<Geometry> {
+    let mut geometry = geometry.clone();
+    let mut positions = geometry.positions_mut();
+    let values: Vec<f64> = positions.iter().flat_map(|position| [position[0], position[1]]).collect();
+    let projected = crs::project(&values, 2, Crs::Hk80.epsg(), Crs::Wgs84);
+    if projected.len() != positions.len() {
+        return None;
+    }
+    for (position, projected) in positions.iter_mut().zip(projected) {
+        position[0] = projected[0];
+        position[1] = projected[1];
+    }
+    Some(geometry)
+}
@@ -0,0 +1,83 @@
+//! Per-feature transforms run before features are written, configured as a
+//! chain of built-in steps under `[[transforms]]`. Library users can add their
+//! own [`FeatureTransform`]s to a [`TransformChain`].
+
+use crate::config::{TransformConfig, TransformStep};
+use crate::geojson::{GeoJsonFeature, PropertyValue};
+use crate::geometry;
+use std::collections::HashSet;
+
+pub trait FeatureTransform {
+    /// Changes a feature of `layer` in place, returning false to drop it.
+    fn apply(&self, layer: &str, id: &str, feature: &mut GeoJsonFeature) -> bool;
+}
+
+/// Transforms applied in order, each to the layers it was added for.
+#[derive(Default)]
+pub struct TransformChain {
+    steps: Vec<(HashSet<String>, Box<dyn FeatureTransform>)>,
+}
+
+impl TransformChain {
+    pub fn new(config: &[TransformConfig]) -> Self {
+        let mut chain = TransformChain::default();
+        for transform in config {
+            chain.push(transform.layers.iter().cloned().collect(), Box::new(transform.step.clone()));
+        }
+        chain
+    }
+
+    /// Adds a step for the given layers, or for all of them when empty.
+    pub fn push(&mut self, layers: HashSet<String>, transform: Box<dyn FeatureTransform>) {
+        self.steps.push((layers, transform));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Runs every step for `layer`, stopping at the first that drops the feature.
+    pub fn apply(&self, layer: &str, id: &str, feature: &mut GeoJsonFeature) -> bool {
+        self.steps
+            .iter()
+            .filter(|(layers, _)| layers.is_empty() || layers.contains(layer))
+            .all(|(_, transform)| transform.apply(layer, id, feature))
+    }
+}
+
+impl FeatureTransform for TransformStep {
+    fn apply(&self, _layer: &str, _id: &str, feature: &mut GeoJsonFeature) -> bool {
+        let properties = &mut feature.properties;
+        match self {
+            TransformStep::Rename { from, to } => {
+                if let Some(value) = properties.remove(from) {
+                    properties.insert(to.clone(), value);
+                }
+            }
+            TransformStep::Drop { attributes } => {
+                for attribute in attributes {
+                    properties.remove(attribute);
+                }
+            }
+            TransformStep::Keep { attributes } => properties.retain(|name, _| attributes.contains(name)),
+            TransformStep::Set { attribute, value } => {
+                properties.insert(attribute.clone(), value.clone());
+            }
+            TransformStep::Filter { attribute, values } => {
+                let text = match properties.get(attribute) {
+                    None | Some(PropertyValue::Null) => return false,
+                    Some(value) => value.to_string(),
+                };
+                return values.contains(&text);
+            }
+            TransformStep::RoundCoordinates { decimals } => {
+                for position in feature.geometry.positions_mut() {
+                    for ordinate in position.iter_mut() {
+                        *ordinate = geometry::round_to(*ordinate, *decimals);
+                    }
+                }
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,372 @@
+//! The terminal UI of `--tui`: a row per dataset with its progress, warnings and
+//! throughput, above a live log pane.
+//!
+//! The conversion runs on a worker thread and reports through [`start_dataset`]
+//! and [`checkpoint`], which do nothing while no UI is running. Whatever it
+//! writes to stderr meanwhile goes to the log pane, and the kept lines are
+//! printed to stderr once the UI closes.
+
+use anyhow::{Context, Result};
+use ratatui::DefaultTerminal;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, Paragraph, Row, Table};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::os::fd::FromRawFd;
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Log lines kept for the log pane and printed when the UI closes.
+const LOG_LINES: usize = 1000;
+
+/// How often the UI redraws while no key is pressed.
+const REDRAW: Duration = Duration::from_millis(100);
+
+/// The error a dataset stops with when it is skipped from the UI.
+#[derive(Debug)]
+pub struct Skipped;
+
+impl fmt::Display for Skipped {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Skipped from the terminal UI")
+    }
+}
+
+impl std::error::Error for Skipped {}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Status {
+    Running,
+    Done,
+    Skipped,
+}
+
+struct Dataset {
+    source: String,
+    /// The feature count from `--prescan`, if known.
+    expected: Option<usize>,
+    features: usize,
+    warnings: usize,
+    started: Instant,
+    finished: Option<Instant>,
+    status: Status,
+}
+
+impl Dataset {
+    fn finish(&mut self, status: Status) {
+        if self.status == Status::Running {
+            self.status = status;
+            self.finished = Some(Instant::now());
+        }
+    }
+
+    fn features_per_second(&self) -> f64 {
+        let elapsed = self.finished.unwrap_or_else(Instant::now) - self.started;
+        self.features as f64 / elapsed.as_secs_f64().max(0.001)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    datasets: Vec<Dataset>,
+    log: VecDeque<String>,
+    paused: bool,
+    skip: bool,
+    stop: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    /// Signalled when the run is resumed or stopped.
+    resumed: Condvar,
+}
+
+static SHARED: OnceLock<Shared> = OnceLock::new();
+
+fn lock(shared: &Shared) -> MutexGuard<'_, State> {
+    shared.state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Shows `source` as the dataset being converted, `expected` being its feature
+/// count when known.
+pub fn start_dataset(source: &str, expected: Option<usize>) {
+    let Some(shared) = SHARED.get() else { return };
+    let mut state = lock(shared);
+    if let Some(previous) = state.datasets.last_mut() {
+        previous.finish(Status::Done);
+    }
+    state.skip = false;
+    state.datasets.push(Dataset {
+        source: source.to_string(),
+        expected,
+        features: 0,
+        warnings: 0,
+        started: Instant::now(),
+        finished: None,
+        status: Status::Running,
+    });
+}
+
+/// Counts a feature of the current dataset, first waiting while the run is
+/// paused. Fails with [`Skipped`] when the dataset was skipped, or with an
+/// error when the whole run was stopped.
+pub fn checkpoint() -> Result<()> {
+    let Some(shared) = SHARED.get() else { return Ok(()) };
+    let mut state = lock(shared);
+    while state.paused && !state.stop {
+        state = shared.resumed.wait(state).unwrap_or_else(PoisonError::into_inner);
+    }
+    if state.stop {
+        anyhow::bail!("Stopped from the terminal UI");
+    }
+    let skip = std::mem::take(&mut state.skip);
+    let Some(dataset) = state.datasets.last_mut() else { return Ok(()) };
+    if skip {
+        dataset.finish(Status::Skipped);
+        return Err(Skipped.into());
+    }
+    dataset.features += 1;
+    Ok(())
+}
+
+/// Runs `work` on a worker thread behind the terminal UI and returns its result
+/// once the UI is closed with `q`.
+pub fn run<T: Send>(work: impl FnOnce() -> Result<T> + Send) -> Result<T> {
+    if !io::stdout().is_terminal() || !io::stdin().is_terminal() {
+        anyhow::bail!("--tui needs a terminal");
+    }
+    let shared = SHARED.get_or_init(Shared::default);
+    let (capture, log) = StderrCapture::start().context("Failed to capture stderr for the log pane")?;
+    let reader = thread::spawn(move || {
+        for line in BufReader::new(log).lines().map_while(Result::ok) {
+            add_log_line(shared, line);
+        }
+    });
+
+    let mut terminal = match ratatui::try_init() {
+        Ok(terminal) => terminal,
+        Err(error) => {
+            capture.restore();
+            let _ = reader.join();
+            return Err(error).context("Failed to start the terminal UI");
+        }
+    };
+    let result = thread::scope(|scope| {
+        let worker = scope.spawn(work);
+        let outcome = interact(&mut terminal, shared, || worker.is_finished());
+        if outcome.is_err() {
+            lock(shared).stop = true;
+            shared.resumed.notify_all();
+        }
+        let result = worker.join().unwrap_or_else(|_| Err(anyhow::anyhow!("The conversion panicked")));
+        let closed = match (outcome, &result) {
+            (Err(error), _) => Err(error),
+            (Ok(true), _) => Ok(()),
+            (Ok(false), Ok(_)) => show_until_closed(&mut terminal, shared, "Finished"),
+            (Ok(false), Err(error)) => show_until_closed(&mut terminal, shared, &format!("Failed: {:#}", error)),
+        };
+        closed.and(result)
+    });
+    ratatui::restore();
+    capture.restore();
+    let _ = reader.join();
+
+    let mut stderr = io::stderr().lock();
+    for line in &lock(shared).log {
+        let _ = writeln!(stderr, "{}", line);
+    }
+    result
+}
+
+fn add_log_line(shared: &Shared, line: String) {
+    // The panel already shows progress, so its log lines would only crowd out the rest
+    let trimmed = line.trim();
+    let percent = trimmed.split_once("% (").is_some_and(|(percent, _)| percent.parse::<usize>().is_ok());
+    if percent || (trimmed.starts_with("Processed ") && trimmed.ends_with(" features...")) {
+        return;
+    }
+    let mut state = lock(shared);
+    if (trimmed.starts_with("Warning") || trimmed.starts_with("Error"))
+        && let Some(dataset) = state.datasets.last_mut()
+    {
+        dataset.warnings += 1;
+    }
+    if state.log.len() == LOG_LINES {
+        state.log.pop_front();
+    }
+    state.log.push_back(line);
+}
+
+/// Draws the UI and handles keys until the work is done. Returns whether the
+/// user already asked to close the UI.
+fn interact(terminal: &mut DefaultTerminal, shared: &Shared, finished: impl Fn() -> bool) -> Result<bool> {
+    let mut closing = false;
+    while !finished() {
+        terminal.draw(|frame| draw(frame, shared, None))?;
+        let Some(key) = next_key()? else { continue };
+        let mut state = lock(shared);
+        match key {
+            Key::Pause => state.paused = !state.paused,
+            Key::Skip => state.skip = state.datasets.last().is_some_and(|d| d.status == Status::Running),
+            Key::Quit => {
+                state.stop = true;
+                closing = true;
+            }
+        }
+        shared.resumed.notify_all();
+    }
+    Ok(closing)
+}
+
+/// Keeps showing the final state, headed by `status`, until the user closes it.
+fn show_until_closed(terminal: &mut DefaultTerminal, shared: &Shared, status: &str) -> Result<()> {
+    if let Some(dataset) = lock(shared).datasets.last_mut() {
+        dataset.finish(Status::Done);
+    }
+    loop {
+        terminal.draw(|frame| draw(frame, shared, Some(status)))?;
+        if next_key()? == Some(Key::Quit) {
+            return Ok(());
+        }
+    }
+}
+
+#[derive(PartialEq)]
+enum Key {
+    Pause,
+    Skip,
+    Quit,
+}
+
+/// The next key pressed within one redraw interval, if any.
+fn next_key() -> Result<Option<Key>> {
+    if !event::poll(REDRAW)? {
+        return Ok(None);
+    }
+    let Event::Key(key) = event::read()? else { return Ok(None) };
+    if key.kind != KeyEventKind::Press {
+        return Ok(None);
+    }
+    Ok(match key.code {
+        KeyCode::Char('p') | KeyCode::Char(' ') => Some(Key::Pause),
+        KeyCode::Char('s') => Some(Key::Skip),
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Some(Key::Quit),
+        KeyCode::Char('q') | KeyCode::Esc => Some(Key::Quit),
+        _ => None,
+    })
+}
+
+fn draw(frame: &mut ratatui::Frame, shared: &Shared, finished: Option<&str>) {
+    let state = lock(shared);
+    let [header, datasets, log] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(state.datasets.len() as u16 + 3),
+        Constraint::Min(3),
+    ])
+    .areas(frame.area());
+
+    let features: usize = state.datasets.iter().map(|d| d.features).sum();
+    let status = match finished {
+        Some(status) => format!("{} with {} features. q: close", status, features),
+        None if state.stop => format!("Stopping after {} features...", features),
+        None if state.paused => format!("Paused at {} features. p: resume  s: skip dataset  q: stop", features),
+        None => format!("Converting, {} features so far. p: pause  s: skip dataset  q: stop", features),
+    };
+    frame.render_widget(Paragraph::new(status).bold(), header);
+
+    let rows = state.datasets.iter().map(|dataset| {
+        let progress = match (dataset.expected, dataset.status) {
+            (_, Status::Done) => "100%".to_string(),
+            (Some(expected), _) if expected > 0 => format!("{}%", (dataset.features * 100 / expected).min(100)),
+            _ => "-".to_string(),
+        };
+        let (status, color) = match dataset.status {
+            Status::Running if state.paused => ("paused", Color::Cyan),
+            Status::Running => ("running", Color::Cyan),
+            Status::Done => ("done", Color::Green),
+            Status::Skipped => ("skipped", Color::Yellow),
+        };
+        Row::new(vec![
+            dataset.source.clone(),
+            status.to_string(),
+            progress,
+            dataset.features.to_string(),
+            format!("{:.0}", dataset.features_per_second()),
+            dataset.warnings.to_string(),
+        ])
+        .style(Style::default().fg(color))
+    });
+    let widths = [
+        Constraint::Fill(1),
+        Constraint::Length(8),
+        Constraint::Length(8),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(8),
+    ];
+    let table = Table::new(rows, widths)
+        .header(Row::new(["Dataset", "Status", "Progress", "Features", "Per sec", "Warnings"]).bold())
+        .block(Block::bordered().title("Datasets"));
+    frame.render_widget(table, datasets);
+
+    let height = log.height.saturating_sub(2) as usize;
+    let lines = state.log.iter().skip(state.log.len().saturating_sub(height)).map(|line| {
+        let trimmed = line.trim_start();
+        let color = if trimmed.starts_with("Error") {
+            Color::Red
+        } else if trimmed.starts_with("Warning") {
+            Color::Yellow
+        } else {
+            Color::Reset
+        };
+        ListItem::new(Line::from(line.as_str()).fg(color))
+    });
+    frame.render_widget(List::new(lines).block(Block::bordered().title("Log")), log);
+}
+
+/// Stderr redirected into a pipe.
+struct StderrCapture {
+    /// A duplicate of the original stderr, put back by [`restore`](Self::restore).
+    saved: libc::c_int,
+}
+
+impl StderrCapture {
+    /// Redirects stderr, returning the pipe's read end with the capture.
+    fn start() -> io::Result<(StderrCapture, File)> {
+        let mut fds = [0; 2];
+        // SAFETY: `pipe`, `dup` and `dup2` only touch the descriptors named, and
+        // the read end is owned by the `File` alone from here on
+        unsafe {
+            if libc::pipe(fds.as_mut_ptr()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let saved = libc::dup(libc::STDERR_FILENO);
+            if saved < 0 || libc::dup2(fds[1], libc::STDERR_FILENO) < 0 {
+                let error = io::Error::last_os_error();
+                libc::close(fds[0]);
+                libc::close(fds[1]);
+                return Err(error);
+            }
+            libc::close(fds[1]);
+            Ok((StderrCapture { saved }, File::from_raw_fd(fds[0])))
+        }
+    }
+
+    /// Points stderr back at the original. The pipe's reader sees its end once
+    /// it has read everything written before.
+    fn restore(self) {
+        let _ = io::stderr().flush();
+        // SAFETY: `saved` is a descriptor this capture owns
+        unsafe {
+            libc::dup2(self.saved, libc::STDERR_FILENO);
+            libc::close(self.saved);
+        }
+    }
+}
@@ -0,0 +1,23 @@
+//! Browser bindings (`wasm` feature). Converts a GML extract held in memory, with
+//! no filesystem access, e.g. after `wasm-pack build --target web --features wasm`:
+//!
+//! ```js
+//! import init, { convert_gml_to_geojson } from "./pkg/road_network_json.js";
+//! await init();
+//! const geojson = convert_gml_to_geojson(new Uint8Array(await file.arrayBuffer()));
+//! ```
+
+use crate::geojson::GeoJsonFeatureCollection;
+use crate::gml;
+use wasm_bindgen::prelude::*;
+
+/// Converts a `CENTERLINE.gml` or `PEDESTRIAN_ZONE.gml` document to a GeoJSON
+/// FeatureCollection in WGS84, with zone rings closed and wound as RFC 7946
+/// requires. Throws with the same message as the command line on malformed XML.
+#[wasm_bindgen]
+pub fn convert_gml_to_geojson(bytes: &[u8]) -> Result<String, JsError> {
+    let content = std::str::from_utf8(bytes).map_err(|e| JsError::new(&format!("GML is not UTF-8: {}", e)))?;
+    let features = gml::convert_document(content).map_err(|e| JsError::new(&format!("{:#}", e)))?;
+    let collection = GeoJsonFeatureCollection::new(features.into_iter().map(|(_, feature)| feature).collect());
+    serde_json::to_string(&collection).map_err(|e| JsError::new(&e.to_string()))
+}
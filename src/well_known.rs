@@ -0,0 +1,138 @@
+//! Well-known text (WKT) and well-known binary (WKB) encodings of geometries.
+
+use crate::geojson::Geometry;
+use std::fmt::Write as _;
+
+fn wkt_position(out: &mut String, p: &[f64]) {
+    let _ = write!(out, "{} {}", p[0], p[1]);
+}
+
+fn wkt_positions(out: &mut String, coords: &[Vec<f64>]) {
+    out.push('(');
+    for (i, p) in coords.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        wkt_position(out, p);
+    }
+    out.push(')');
+}
+
+pub fn to_wkt(geometry: &Geometry) -> String {
+    let mut out = String::new();
+    match geometry {
+        Geometry::Point(p) if p.is_empty() => out.push_str("POINT EMPTY"),
+        Geometry::Point(p) => {
+            out.push_str("POINT (");
+            wkt_position(&mut out, p);
+            out.push(')');
+        }
+        Geometry::LineString(coords) if coords.is_empty() => out.push_str("LINESTRING EMPTY"),
+        Geometry::LineString(coords) => {
+            out.push_str("LINESTRING ");
+            wkt_positions(&mut out, coords);
+        }
+        Geometry::MultiLineString(lines) if lines.is_empty() => out.push_str("MULTILINESTRING EMPTY"),
+        Geometry::MultiLineString(lines) => {
+            out.push_str("MULTILINESTRING (");
+            for (i, coords) in lines.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                wkt_positions(&mut out, coords);
+            }
+            out.push(')');
+        }
+        Geometry::Polygon(rings) if rings.is_empty() => out.push_str("POLYGON EMPTY"),
+        Geometry::Polygon(rings) => {
+            out.push_str("POLYGON ");
+            wkt_rings(&mut out, rings);
+        }
+        Geometry::MultiPolygon(polygons) if polygons.is_empty() => out.push_str("MULTIPOLYGON EMPTY"),
+        Geometry::MultiPolygon(polygons) => {
+            out.push_str("MULTIPOLYGON (");
+            for (i, rings) in polygons.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                wkt_rings(&mut out, rings);
+            }
+            out.push(')');
+        }
+    }
+    out
+}
+
+fn wkt_rings(out: &mut String, rings: &[Vec<Vec<f64>>]) {
+    out.push('(');
+    for (i, coords) in rings.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        wkt_positions(out, coords);
+    }
+    out.push(')');
+}
+
+/// Little-endian ISO WKB.
+pub fn to_wkb(geometry: &Geometry) -> Vec<u8> {
+    let mut out = vec![1u8];
+    match geometry {
+        Geometry::Point(p) => {
+            out.extend(1u32.to_le_bytes());
+            // An empty point is encoded as NaN coordinates.
+            let (x, y) = p.first().zip(p.get(1)).map_or((f64::NAN, f64::NAN), |(x, y)| (*x, *y));
+            out.extend(x.to_le_bytes());
+            out.extend(y.to_le_bytes());
+        }
+        Geometry::LineString(coords) => wkb_line_string(&mut out, coords),
+        Geometry::MultiLineString(lines) => {
+            out.extend(5u32.to_le_bytes());
+            out.extend((lines.len() as u32).to_le_bytes());
+            for coords in lines {
+                out.push(1);
+                wkb_line_string(&mut out, coords);
+            }
+        }
+        Geometry::Polygon(rings) => wkb_polygon(&mut out, rings),
+        Geometry::MultiPolygon(polygons) => {
+            out.extend(6u32.to_le_bytes());
+            out.extend((polygons.len() as u32).to_le_bytes());
+            for rings in polygons {
+                out.push(1);
+                wkb_polygon(&mut out, rings);
+            }
+        }
+    }
+    out
+}
+
+/// Appends a polygon's type code and body (everything after the byte order).
+fn wkb_polygon(out: &mut Vec<u8>, rings: &[Vec<Vec<f64>>]) {
+    out.extend(3u32.to_le_bytes());
+    out.extend((rings.len() as u32).to_le_bytes());
+    for coords in rings {
+        out.extend((coords.len() as u32).to_le_bytes());
+        for p in coords {
+            out.extend(p[0].to_le_bytes());
+            out.extend(p[1].to_le_bytes());
+        }
+    }
+}
+
+/// Appends a line string's type code and body (everything after the byte order).
+fn wkb_line_string(out: &mut Vec<u8>, coords: &[Vec<f64>]) {
+    out.extend(2u32.to_le_bytes());
+    out.extend((coords.len() as u32).to_le_bytes());
+    for p in coords {
+        out.extend(p[0].to_le_bytes());
+        out.extend(p[1].to_le_bytes());
+    }
+}
+
+pub fn to_wkb_hex(geometry: &Geometry) -> String {
+    to_wkb(geometry).iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02X}", byte);
+        hex
+    })
+}
@@ -0,0 +1,117 @@
+//! Reading numbers written as `[input.numbers]` describes: thousands
+//! separators, trailing units and decimal commas, and values kept as written
+//! when they still aren't numbers.
+
+use road_network_json::attributes::{AttributeType, NumberFormat};
+use road_network_json::geojson::PropertyValue;
+use road_network_json::gml::{self, ParseOptions};
+
+fn int(text: &str, numbers: &NumberFormat) -> Option<i64> {
+    match AttributeType::Int.parse_with(text, numbers)? {
+        PropertyValue::Int(value) => Some(value),
+        other => panic!("{:?} read as {:?}", text, other),
+    }
+}
+
+fn float(text: &str, numbers: &NumberFormat) -> Option<f64> {
+    match AttributeType::Float.parse_with(text, numbers)? {
+        PropertyValue::Float(value) => Some(value),
+        other => panic!("{:?} read as {:?}", text, other),
+    }
+}
+
+fn decimal_comma() -> NumberFormat {
+    NumberFormat {
+        thousands_separator: ".".to_string(),
+        decimal_separator: ",".to_string(),
+        strip_units: true,
+    }
+}
+
+#[test]
+fn thousands_separators_are_dropped() {
+    let numbers = NumberFormat::default();
+    assert_eq!(int("1,234", &numbers), Some(1234));
+    assert_eq!(int("1,234,567", &numbers), Some(1_234_567));
+    assert_eq!(float("1,234.5", &numbers), Some(1234.5));
+    assert_eq!(int("-12,000", &numbers), Some(-12_000));
+}
+
+#[test]
+fn trailing_units_are_dropped() {
+    let numbers = NumberFormat::default();
+    assert_eq!(float("123.4 m", &numbers), Some(123.4));
+    assert_eq!(int("50km/h", &numbers), Some(50));
+    assert_eq!(int("12%", &numbers), Some(12));
+    assert_eq!(float(" 2,500.75 sq ft ", &numbers), Some(2500.75));
+    assert_eq!(int("n/a", &numbers), None);
+}
+
+#[test]
+fn units_are_refused_unless_stripped() {
+    let numbers = NumberFormat {
+        strip_units: false,
+        ..NumberFormat::default()
+    };
+    assert_eq!(float("123.4 m", &numbers), None);
+    assert_eq!(int("12%", &numbers), None);
+    assert_eq!(float("123.4", &numbers), Some(123.4));
+    assert_eq!(int("1,234", &numbers), Some(1234));
+}
+
+#[test]
+fn decimal_commas_are_read() {
+    let numbers = decimal_comma();
+    assert_eq!(float("1.234,5", &numbers), Some(1234.5));
+    assert_eq!(float("12,5 m", &numbers), Some(12.5));
+    assert_eq!(int("1.234", &numbers), Some(1234));
+    assert_eq!(int("3,0", &numbers), Some(3));
+}
+
+#[test]
+fn strict_numbers_are_plain_literals() {
+    let numbers = NumberFormat::strict();
+    assert_eq!(int("1234", &numbers), Some(1234));
+    assert_eq!(int("1,234", &numbers), None);
+    assert_eq!(float("123.4 m", &numbers), None);
+    // Whole numbers written as doubles are still ints
+    assert_eq!(int("3.0", &numbers), Some(3));
+    assert_eq!(int("3.5", &numbers), None);
+}
+
+const DOCUMENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<core:CityModel xmlns:core="http://www.opengis.net/citygml/2.0" xmlns:gen="http://www.opengis.net/citygml/generics/2.0" xmlns:gml="http://www.opengis.net/gml">
+<core:cityObjectMember>
+<gen:GenericCityObject gml:id="CL_1">
+<gen:intAttribute name="ROUTE_ID"><gen:value>1,001</gen:value></gen:intAttribute>
+<gen:doubleAttribute name="SHAPE_LENGTH"><gen:value>141.42 m</gen:value></gen:doubleAttribute>
+<gen:doubleAttribute name="WIDTH"><gen:value>about ten</gen:value></gen:doubleAttribute>
+<gen:intAttribute name="LANES"><gen:value>two</gen:value></gen:intAttribute>
+<gen:stringAttribute name="SPEED_LIMIT"><gen:value>fifty</gen:value></gen:stringAttribute>
+<gen:lod0Network><gml:MultiCurve srsName="EPSG:2326" srsDimension="2"><gml:curveMember><gml:LineString><gml:posList>836600 819000 836700 819100</gml:posList></gml:LineString></gml:curveMember></gml:MultiCurve></gen:lod0Network>
+</gen:GenericCityObject>
+</core:cityObjectMember>
+</core:CityModel>
+"#;
+
+#[test]
+fn unparsed_numbers_keep_the_text_they_were_written_as() {
+    let mut options = ParseOptions::default();
+    options.attributes.insert("SPEED_LIMIT".to_string(), AttributeType::Int);
+    let mut properties = None;
+    let summary = gml::parse_gml("CENTERLINE.gml", DOCUMENT, "", &options, |_, feature| {
+        properties = Some(feature.properties);
+        Ok(())
+    })
+    .unwrap();
+    let properties = properties.expect("no feature was read");
+
+    assert!(matches!(properties["ROUTE_ID"], PropertyValue::Int(1001)));
+    assert!(matches!(properties["SHAPE_LENGTH"], PropertyValue::Float(value) if value == 141.42));
+    assert!(matches!(&properties["WIDTH"], PropertyValue::String(text) if text == "about ten"));
+    assert!(matches!(&properties["LANES"], PropertyValue::String(text) if text == "two"));
+    assert_eq!(summary.unparsed_numbers, 2);
+    // A configured type makes the value null instead
+    assert!(matches!(properties["SPEED_LIMIT"], PropertyValue::Null));
+    assert_eq!(summary.type_mismatches, 1);
+}
@@ -0,0 +1,55 @@
+//! Property tests of `crs::project`: positions anywhere in Hong Kong must
+//! survive a round trip between the HK80 grid and WGS84, so that a change of
+//! projection code or proj backend can't silently lose precision.
+
+use proptest::prelude::*;
+use road_network_json::crs::{self, Crs};
+
+/// HK80 grid extent of the territory, with some sea around it.
+const EASTING: std::ops::Range<f64> = 800_000.0..870_000.0;
+const NORTHING: std::ops::Range<f64> = 800_000.0..850_000.0;
+/// The same area in WGS84 degrees.
+const LONGITUDE: std::ops::Range<f64> = 113.8..114.55;
+const LATITUDE: std::ops::Range<f64> = 22.1..22.6;
+
+/// One millimetre.
+const GRID_TOLERANCE: f64 = 1e-3;
+/// About one millimetre on the ground.
+const DEGREE_TOLERANCE: f64 = 1e-8;
+
+fn project_one(position: &[f64], source: Crs, target: Crs) -> Vec<f64> {
+    let projected = crs::project(position, position.len(), source.epsg(), target);
+    assert_eq!(projected.len(), 1, "{:?} failed to project", position);
+    projected.into_iter().next().unwrap()
+}
+
+proptest! {
+    #[test]
+    fn hk80_round_trips_through_wgs84(easting in EASTING, northing in NORTHING) {
+        let wgs84 = project_one(&[easting, northing], Crs::Hk80, Crs::Wgs84);
+        prop_assert!(LONGITUDE.contains(&wgs84[0]) && LATITUDE.contains(&wgs84[1]), "{:?} is outside Hong Kong", wgs84);
+        let back = project_one(&wgs84, Crs::Wgs84, Crs::Hk80);
+        prop_assert!((back[0] - easting).abs() < GRID_TOLERANCE, "easting {} came back as {}", easting, back[0]);
+        prop_assert!((back[1] - northing).abs() < GRID_TOLERANCE, "northing {} came back as {}", northing, back[1]);
+    }
+
+    #[test]
+    fn wgs84_round_trips_through_hk80(longitude in LONGITUDE, latitude in LATITUDE) {
+        let hk80 = project_one(&[longitude, latitude], Crs::Wgs84, Crs::Hk80);
+        let back = project_one(&hk80, Crs::Hk80, Crs::Wgs84);
+        prop_assert!((back[0] - longitude).abs() < DEGREE_TOLERANCE, "longitude {} came back as {}", longitude, back[0]);
+        prop_assert!((back[1] - latitude).abs() < DEGREE_TOLERANCE, "latitude {} came back as {}", latitude, back[1]);
+    }
+
+    #[test]
+    fn heights_are_carried_over(easting in EASTING, northing in NORTHING, height in -50.0..1000.0f64) {
+        let wgs84 = project_one(&[easting, northing, height], Crs::Hk80, Crs::Wgs84);
+        prop_assert_eq!(wgs84.len(), 3);
+        prop_assert_eq!(wgs84[2], height);
+    }
+
+    #[test]
+    fn identical_crs_copies_positions(easting in EASTING, northing in NORTHING) {
+        prop_assert_eq!(project_one(&[easting, northing], Crs::Hk80, Crs::Hk80), vec![easting, northing]);
+    }
+}
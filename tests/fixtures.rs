@@ -0,0 +1,25 @@
+//! Runs the golden-output checks of `road_network_json::fixtures` on the
+//! bundled fixtures, as `road-network-json verify-fixtures` does.
+
+use std::path::Path;
+
+#[test]
+fn fixtures_match_golden_outputs() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+    let mismatches = road_network_json::fixtures::verify(&dir).unwrap();
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
+
+#[test]
+fn escaped_names_match_golden_outputs() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/escaping");
+    let mismatches = road_network_json::fixtures::verify(&dir).unwrap();
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
+
+#[test]
+fn lifespan_dates_match_golden_outputs() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/lifespan");
+    let mismatches = road_network_json::fixtures::verify(&dir).unwrap();
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
@@ -0,0 +1,351 @@
+//! Reads `--format geobuf` output back against the Geobuf v3 schema
+//! (https://github.com/mapbox/geobuf/blob/master/geobuf.proto), decoding the
+//! protobuf by hand as `geobuf.decode` does.
+
+use road_network_json::crs::Crs;
+use road_network_json::geojson::{GeoJsonFeature, Geometry, PropertyValue};
+use road_network_json::sink::{GeobufSink, Sink};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+enum Field<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+}
+
+/// The fields of one protobuf message, in order.
+fn fields(mut bytes: &[u8]) -> Vec<(u32, Field<'_>)> {
+    fn varint(bytes: &mut &[u8]) -> u64 {
+        let (mut value, mut shift) = (0u64, 0);
+        loop {
+            let (&byte, rest) = bytes.split_first().expect("message ends in a varint");
+            *bytes = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte < 0x80 {
+                return value;
+            }
+        }
+    }
+    let mut fields = Vec::new();
+    while !bytes.is_empty() {
+        let key = varint(&mut bytes);
+        let field = match key & 7 {
+            0 => Field::Varint(varint(&mut bytes)),
+            1 => {
+                let (value, rest) = bytes.split_at(8);
+                bytes = rest;
+                Field::Fixed64(u64::from_le_bytes(value.try_into().unwrap()))
+            }
+            2 => {
+                let length = varint(&mut bytes) as usize;
+                let (value, rest) = bytes.split_at(length);
+                bytes = rest;
+                Field::Bytes(value)
+            }
+            wire_type => panic!("unexpected wire type {}", wire_type),
+        };
+        fields.push(((key >> 3) as u32, field));
+    }
+    fields
+}
+
+fn packed(bytes: &[u8]) -> Vec<u64> {
+    let mut values = Vec::new();
+    let (mut value, mut shift) = (0u64, 0);
+    for &byte in bytes {
+        value |= u64::from(byte & 0x7f) << shift;
+        shift += 7;
+        if byte < 0x80 {
+            values.push(value);
+            (value, shift) = (0, 0);
+        }
+    }
+    values
+}
+
+fn text(bytes: &[u8]) -> String {
+    String::from_utf8(bytes.to_vec()).unwrap()
+}
+
+/// A decoded `Data.Geometry`: its type, `lengths` and de-zigzagged `coords`.
+struct RawGeometry {
+    kind: u64,
+    lengths: Vec<usize>,
+    coords: Vec<i64>,
+}
+
+/// A decoded `Data`, with the raw geometry of each feature beside it as GeoJSON.
+struct Data {
+    precision: u32,
+    dimensions: usize,
+    features: Vec<(RawGeometry, Value)>,
+}
+
+fn decode(bytes: &[u8]) -> Data {
+    let (mut keys, mut precision, mut dimensions, mut collection) = (Vec::new(), 6, 2, None);
+    for (number, field) in fields(bytes) {
+        match (number, field) {
+            (1, Field::Bytes(key)) => keys.push(text(key)),
+            (2, Field::Varint(value)) => dimensions = value as usize,
+            (3, Field::Varint(value)) => precision = value as u32,
+            (4, Field::Bytes(message)) => collection = Some(message),
+            (number, _) => panic!("unexpected Data field {}", number),
+        }
+    }
+    let collection = collection.expect("Data holds no FeatureCollection");
+    let features = fields(collection)
+        .into_iter()
+        .map(|(number, field)| match (number, field) {
+            (1, Field::Bytes(feature)) => decode_feature(feature, &keys, precision, dimensions),
+            (number, _) => panic!("unexpected FeatureCollection field {}", number),
+        })
+        .collect();
+    Data { precision, dimensions, features }
+}
+
+fn decode_feature(bytes: &[u8], keys: &[String], precision: u32, dimensions: usize) -> (RawGeometry, Value) {
+    let (mut geometry, mut id, mut values, mut properties) = (None, None, Vec::new(), Vec::new());
+    for (number, field) in fields(bytes) {
+        match (number, field) {
+            (1, Field::Bytes(message)) => geometry = Some(decode_geometry(message)),
+            (11, Field::Bytes(value)) => id = Some(text(value)),
+            (13, Field::Bytes(message)) => values.push(decode_value(message)),
+            (14, Field::Bytes(indexes)) => properties = packed(indexes),
+            (number, _) => panic!("unexpected Feature field {}", number),
+        }
+    }
+    let properties: serde_json::Map<String, Value> = properties
+        .chunks(2)
+        .map(|pair| (keys[pair[0] as usize].clone(), values[pair[1] as usize].clone()))
+        .collect();
+    let geometry = geometry.expect("Feature has no geometry");
+    let geojson = json!({
+        "id": id,
+        "geometry": to_geojson(&geometry, precision, dimensions),
+        "properties": properties,
+    });
+    (geometry, geojson)
+}
+
+fn decode_geometry(bytes: &[u8]) -> RawGeometry {
+    let mut geometry = RawGeometry { kind: 0, lengths: Vec::new(), coords: Vec::new() };
+    for (number, field) in fields(bytes) {
+        match (number, field) {
+            (1, Field::Varint(kind)) => geometry.kind = kind,
+            (2, Field::Bytes(lengths)) => geometry.lengths = packed(lengths).into_iter().map(|length| length as usize).collect(),
+            (3, Field::Bytes(coords)) => {
+                geometry.coords = packed(coords).into_iter().map(|value| (value >> 1) as i64 ^ -((value & 1) as i64)).collect();
+            }
+            (number, _) => panic!("unexpected Geometry field {}", number),
+        }
+    }
+    geometry
+}
+
+fn decode_value(bytes: &[u8]) -> Value {
+    let fields = fields(bytes);
+    assert_eq!(fields.len(), 1, "a Value holds one of its fields");
+    match &fields[0] {
+        (1, Field::Bytes(value)) => Value::String(text(value)),
+        (2, Field::Fixed64(bits)) => json!(f64::from_bits(*bits)),
+        (3, Field::Varint(value)) => json!(value),
+        (4, Field::Varint(value)) => json!(-(*value as i64)),
+        (6, Field::Bytes(value)) => serde_json::from_slice(value).unwrap(),
+        (number, _) => panic!("unexpected Value field {}", number),
+    }
+}
+
+/// Rebuilds GeoJSON coordinates as `geobuf.decode` does: deltas restart at
+/// every line, and rings get their closing position back.
+fn to_geojson(geometry: &RawGeometry, precision: u32, dimensions: usize) -> Value {
+    let factor = 10f64.powi(precision as i32);
+    let mut offset = 0;
+    let mut line = |count: usize, closed: bool| -> Vec<Vec<f64>> {
+        let mut sum = vec![0i64; dimensions];
+        let mut positions: Vec<Vec<f64>> = (0..count)
+            .map(|_| {
+                sum.iter_mut()
+                    .map(|sum| {
+                        *sum += geometry.coords[offset];
+                        offset += 1;
+                        *sum as f64 / factor
+                    })
+                    .collect()
+            })
+            .collect();
+        if closed {
+            positions.push(positions[0].clone());
+        }
+        positions
+    };
+    let all = geometry.coords.len() / dimensions;
+    let (kind, coordinates) = match geometry.kind {
+        0 => ("Point", json!(line(1, false)[0])),
+        2 => ("LineString", json!(line(all, false))),
+        3 | 4 => {
+            let closed = geometry.kind == 4;
+            let lengths = if geometry.lengths.is_empty() { vec![all] } else { geometry.lengths.clone() };
+            let lines: Vec<_> = lengths.iter().map(|&count| line(count, closed)).collect();
+            (if closed { "Polygon" } else { "MultiLineString" }, json!(lines))
+        }
+        5 => {
+            let lengths = if geometry.lengths.is_empty() { vec![1, 1, all] } else { geometry.lengths.clone() };
+            let mut next = lengths[1..].iter();
+            let polygons: Vec<Vec<_>> = (0..lengths[0])
+                .map(|_| {
+                    let rings = *next.next().unwrap();
+                    (0..rings).map(|_| line(*next.next().unwrap(), true)).collect()
+                })
+                .collect();
+            assert!(next.next().is_none(), "lengths {:?} has entries left over", lengths);
+            ("MultiPolygon", json!(polygons))
+        }
+        kind => panic!("unexpected geometry type {}", kind),
+    };
+    assert_eq!(offset, geometry.coords.len(), "coords left over");
+    json!({ "type": kind, "coordinates": coordinates })
+}
+
+/// A closed ring of `count` positions around `centre`, counter-clockwise.
+fn ring(centre: [f64; 2], radius: f64, count: usize) -> Vec<Vec<f64>> {
+    let mut ring: Vec<Vec<f64>> = (0..count)
+        .map(|step| {
+            let angle = std::f64::consts::TAU * step as f64 / count as f64;
+            vec![centre[0] + radius * angle.cos(), centre[1] + radius * angle.sin()]
+        })
+        .collect();
+    ring.push(ring[0].clone());
+    ring
+}
+
+/// One feature of every geometry type around `origin`, `step` apart.
+fn features(origin: [f64; 2], step: f64) -> Vec<(String, GeoJsonFeature)> {
+    let at = |x: f64, y: f64| [origin[0] + x * step, origin[1] + y * step];
+    let line = |from: [f64; 2], to: [f64; 2]| vec![from.to_vec(), [from[0], to[1]].to_vec(), to.to_vec()];
+    let geometries = [
+        Geometry::Point(at(0.1234567, 0.7654321).to_vec()),
+        Geometry::LineString(line(at(0.0, 0.0), at(-3.0, 2.0))),
+        Geometry::MultiLineString(vec![line(at(0.0, 0.0), at(1.0, 1.0)), line(at(5.0, 5.0), at(4.0, 7.0))]),
+        Geometry::Polygon(vec![ring(at(0.0, 0.0), 2.0 * step, 6), ring(at(0.0, 0.0), step, 4)]),
+        Geometry::Polygon(vec![ring(at(10.0, 0.0), step, 5)]),
+        // Two polygons, the first with a hole
+        Geometry::MultiPolygon(vec![
+            vec![ring(at(0.0, 0.0), 3.0 * step, 7), ring(at(0.0, 0.0), step, 3)],
+            vec![ring(at(20.0, -20.0), step, 5)],
+        ]),
+        Geometry::MultiPolygon(vec![vec![ring(at(-20.0, 20.0), step, 8)]]),
+    ];
+    geometries
+        .into_iter()
+        .enumerate()
+        .map(|(index, geometry)| {
+            let properties = HashMap::from([
+                ("NAME".to_string(), PropertyValue::String(format!("Feature {}", index))),
+                ("RANK".to_string(), PropertyValue::Int(index as i64)),
+                ("OFFSET".to_string(), PropertyValue::Int(-1000 * index as i64 - 1)),
+                ("WIDTH".to_string(), PropertyValue::Float(7.25 - index as f64)),
+                ("REMARKS".to_string(), PropertyValue::Null),
+            ]);
+            (format!("F{}", index), GeoJsonFeature::new(geometry, properties))
+        })
+        .collect()
+}
+
+/// The feature as Geobuf should give it back: coordinates rounded to
+/// `precision` decimal places, without heights.
+fn expected(id: &str, feature: &GeoJsonFeature, precision: u32) -> Value {
+    let factor = 10f64.powi(precision as i32);
+    let mut geometry = feature.geometry.clone();
+    for position in geometry.positions_mut() {
+        position.truncate(2);
+        for ordinate in position.iter_mut() {
+            *ordinate = (*ordinate * factor).round() / factor;
+        }
+    }
+    json!({ "id": id, "geometry": geometry, "properties": feature.properties })
+}
+
+/// Writes `features` as the one layer of a Geobuf file and decodes it.
+fn round_trip(name: &str, crs: Crs, features: &[(String, GeoJsonFeature)]) -> Data {
+    let dir: PathBuf = std::env::temp_dir().join(format!("rnj-{}-geobuf-{}", std::process::id(), name));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut sink = GeobufSink::new(dir.to_str().unwrap(), crs);
+    for (id, feature) in features {
+        sink.write("layer", id, feature).unwrap();
+    }
+    sink.finish().unwrap();
+    let bytes = std::fs::read(dir.join("layer.pbf")).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    decode(&bytes)
+}
+
+fn assert_features(data: &Data, features: &[(String, GeoJsonFeature)]) {
+    assert_eq!(data.dimensions, 2);
+    assert_eq!(data.features.len(), features.len());
+    for ((_, decoded), (id, feature)) in data.features.iter().zip(features) {
+        assert_eq!(decoded, &expected(id, feature, data.precision), "{}", id);
+    }
+}
+
+#[test]
+fn wgs84_features_decode_at_the_default_precision() {
+    let features = features([114.17, 22.30], 0.0001);
+    let data = round_trip("wgs84", Crs::Wgs84, &features);
+    // Six decimal places is Geobuf's default, so the field is left out
+    assert_eq!(data.precision, 6);
+    assert_features(&data, &features);
+}
+
+#[test]
+fn hk80_features_carry_their_precision() {
+    let features = features([835_000.0, 819_000.0], 10.0);
+    let data = round_trip("hk80", Crs::Hk80, &features);
+    assert_eq!(data.precision, 3);
+    assert_features(&data, &features);
+}
+
+#[test]
+fn multipolygon_lengths_follow_the_schema() {
+    let features = features([114.17, 22.30], 0.0001);
+    let data = round_trip("lengths", Crs::Wgs84, &features);
+    let lengths = |index: usize| data.features[index].0.lengths.clone();
+
+    // Polygon: the positions in each ring, less the closing one
+    assert_eq!(data.features[3].0.kind, 4);
+    assert_eq!(lengths(3), [6, 4]);
+    // A single ring needs no lengths
+    assert!(lengths(4).is_empty());
+    // MultiPolygon: polygon count, then each polygon's ring count and ring sizes
+    assert_eq!(data.features[5].0.kind, 5);
+    assert_eq!(lengths(5), [2, 2, 7, 3, 1, 5]);
+    assert!(lengths(6).is_empty());
+    assert_eq!(lengths(2), [3, 3]);
+}
+
+#[test]
+fn rings_leave_out_their_closing_position() {
+    let features = features([114.17, 22.30], 0.0001);
+    let data = round_trip("closure", Crs::Wgs84, &features);
+    for ((raw, _), (id, feature)) in data.features.iter().zip(&features) {
+        let stored = match &feature.geometry {
+            Geometry::Polygon(rings) => rings.iter().map(|ring| ring.len() - 1).sum(),
+            Geometry::MultiPolygon(polygons) => polygons.iter().flatten().map(|ring| ring.len() - 1).sum(),
+            geometry => geometry.positions().len(),
+        };
+        assert_eq!(raw.coords.len(), 2 * stored, "{}", id);
+    }
+}
+
+#[test]
+fn negative_integers_use_neg_int_value() {
+    let features = features([114.17, 22.30], 0.0001);
+    let data = round_trip("negative", Crs::Wgs84, &features);
+    for (_, decoded) in &data.features {
+        let offset = decoded["properties"]["OFFSET"].as_i64().unwrap();
+        assert!(offset < 0);
+    }
+    assert_eq!(data.features[3].1["properties"]["OFFSET"], -3001);
+}
@@ -0,0 +1,128 @@
+//! ALT routing on a small hand-built grid: landmark tables survive a write and
+//! read, are refused for another graph, and never change a route's length.
+
+use road_network_json::config::DirectionConfig;
+use road_network_json::geojson::{GeoJsonFeature, Geometry, PropertyValue};
+use road_network_json::graph::{Graph, RestrictionRow};
+use road_network_json::landmarks::Landmarks;
+use road_network_json::routing;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+const COLUMNS: usize = 5;
+const ROWS: usize = 4;
+
+fn position(x: usize, y: usize) -> Vec<f64> {
+    vec![114.17 + 0.001 * x as f64, 22.30 + 0.0008 * y as f64]
+}
+
+fn segment(id: String, from: (usize, usize), to: (usize, usize), direction: &str) -> (String, GeoJsonFeature) {
+    let properties = HashMap::from([("TRAVEL_DIRECTION".to_string(), PropertyValue::String(direction.to_string()))]);
+    (id, GeoJsonFeature::new(Geometry::LineString(vec![position(from.0, from.1), position(to.0, to.1)]), properties))
+}
+
+/// A grid of streets, with a one-way street along row 1 and a one-way column 3,
+/// leaving out the segments `skip` names.
+fn grid(skip: &[&str]) -> Graph {
+    let mut features = Vec::new();
+    for y in 0..ROWS {
+        for x in 0..COLUMNS - 1 {
+            features.push(segment(format!("h{}{}", x, y), (x, y), (x + 1, y), if y == 1 { "2" } else { "1" }));
+        }
+    }
+    for x in 0..COLUMNS {
+        for y in 0..ROWS - 1 {
+            features.push(segment(format!("v{}{}", x, y), (x, y), (x, y + 1), if x == 3 { "2" } else { "1" }));
+        }
+    }
+    let mut graph = Graph::default();
+    for (id, feature) in features.iter().filter(|(id, _)| !skip.contains(&id.as_str())) {
+        graph.add_segment(id, feature, &DirectionConfig::default());
+    }
+    graph
+}
+
+fn restrict(graph: &mut Graph) {
+    let rows = [("h11", "v21"), ("v10", "h11"), ("h12", "h22"), ("v31", "h32")].map(|(from, to)| RestrictionRow {
+        from_edge: from.to_string(),
+        via_node: None,
+        to_edge: to.to_string(),
+    });
+    assert_eq!(graph.apply_restrictions(&rows), 0);
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("rnj-{}-{}", std::process::id(), name))
+}
+
+/// Every pair of nodes routes to the same length with and without landmarks.
+fn assert_same_lengths(graph: &Graph, landmarks: &Landmarks) {
+    for from in 0..graph.nodes.len() {
+        for to in 0..graph.nodes.len() {
+            let plain = routing::shortest_path(graph, from, to).map(|route| route.length_m);
+            let alt = routing::shortest_path_with(graph, landmarks, from, to).map(|route| route.length_m);
+            match (plain, alt) {
+                (Some(plain), Some(alt)) => assert!((plain - alt).abs() < 1e-6, "{} -> {}: {} vs {}", from, to, plain, alt),
+                (plain, alt) => assert_eq!(plain.is_some(), alt.is_some(), "{} -> {}", from, to),
+            }
+        }
+    }
+}
+
+#[test]
+fn tables_survive_a_round_trip() {
+    let graph = grid(&[]);
+    let landmarks = Landmarks::build(&graph, 4);
+    assert_eq!(landmarks.nodes.len(), 4);
+
+    let path = temp_path("round-trip.bin");
+    landmarks.write(path.to_str().unwrap()).unwrap();
+    let read = Landmarks::read(path.to_str().unwrap(), &graph);
+    std::fs::remove_file(&path).unwrap();
+    let read = read.unwrap();
+
+    assert_eq!(read.nodes, landmarks.nodes);
+    for node in 0..graph.nodes.len() {
+        for target in 0..graph.nodes.len() {
+            assert_eq!(read.lower_bound(node, target).to_bits(), landmarks.lower_bound(node, target).to_bits());
+        }
+    }
+}
+
+#[test]
+fn tables_for_another_graph_are_refused() {
+    let graph = grid(&[]);
+    let path = temp_path("other-graph.bin");
+    Landmarks::build(&graph, 3).write(path.to_str().unwrap()).unwrap();
+    let read = Landmarks::read(path.to_str().unwrap(), &grid(&["v22"]));
+    std::fs::remove_file(&path).unwrap();
+
+    let error = read.err().expect("tables for another graph were accepted").to_string();
+    assert!(error.contains("different graph"), "{}", error);
+}
+
+#[test]
+fn landmarks_keep_shortest_path_lengths() {
+    let graph = grid(&[]);
+    assert_same_lengths(&graph, &Landmarks::build(&graph, 4));
+}
+
+#[test]
+fn landmarks_keep_shortest_path_lengths_under_turn_restrictions() {
+    let mut graph = grid(&[]);
+    let landmarks = Landmarks::build(&graph, 4);
+    let unrestricted: Vec<Option<f64>> = (0..graph.nodes.len())
+        .flat_map(|from| (0..graph.nodes.len()).map(move |to| (from, to)))
+        .map(|(from, to)| routing::shortest_path(&graph, from, to).map(|route| route.length_m))
+        .collect();
+
+    // Tables built before the restrictions still bound the restricted routes
+    restrict(&mut graph);
+    assert_same_lengths(&graph, &landmarks);
+
+    let restricted: Vec<Option<f64>> = (0..graph.nodes.len())
+        .flat_map(|from| (0..graph.nodes.len()).map(move |to| (from, to)))
+        .map(|(from, to)| routing::shortest_path(&graph, from, to).map(|route| route.length_m))
+        .collect();
+    assert_ne!(restricted, unrestricted, "the restrictions lengthen no route");
+}
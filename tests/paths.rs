@@ -0,0 +1,45 @@
+//! `paths::long_path` on Windows, where it lifts the 260-character limit. On
+//! other platforms it returns paths unchanged and there is nothing to check.
+#![cfg(windows)]
+
+use road_network_json::paths::long_path;
+use std::path::PathBuf;
+
+/// A path component long enough to take any path past the limit.
+fn long_name() -> String {
+    "d".repeat(250)
+}
+
+#[test]
+fn short_paths_are_unchanged() {
+    assert_eq!(long_path(r"output\centerlines"), PathBuf::from(r"output\centerlines"));
+    assert_eq!(long_path(r"C:\output\centerlines"), PathBuf::from(r"C:\output\centerlines"));
+}
+
+#[test]
+fn long_drive_paths_get_the_verbatim_prefix() {
+    let path = format!(r"C:\output\{}\1001.json", long_name());
+    assert_eq!(long_path(&path), PathBuf::from(format!(r"\\?\{}", path)));
+}
+
+#[test]
+fn long_relative_paths_are_made_absolute() {
+    let path = format!(r"output\{}", long_name());
+    let long = long_path(&path).into_os_string().into_string().unwrap();
+    assert!(long.starts_with(r"\\?\"), "{}", long);
+    assert!(long.ends_with(&path), "{}", long);
+}
+
+#[test]
+fn long_unc_paths_get_the_unc_prefix() {
+    let path = format!(r"\\server\share\output\{}", long_name());
+    let expected = format!(r"\\?\UNC\server\share\output\{}", long_name());
+    assert_eq!(long_path(&path), PathBuf::from(expected));
+}
+
+#[test]
+fn prefixed_paths_are_unchanged() {
+    for path in [format!(r"\\?\C:\output\{}", long_name()), format!(r"\\?\UNC\server\share\{}", long_name())] {
+        assert_eq!(long_path(&path), PathBuf::from(&path));
+    }
+}
@@ -0,0 +1,111 @@
+//! Google encoded polylines as written by `--polyline`: the reference example
+//! from Google's documentation, round trips at both precisions, and reading a
+//! converted dataset back with `decode_geometry`.
+
+use proptest::prelude::*;
+use road_network_json::geojson::Geometry;
+use road_network_json::polyline;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The example of Google's polyline algorithm documentation.
+const REFERENCE: &str = "_p~iF~ps|U_ulLnnqC_mqNvxq`@";
+
+fn reference_positions() -> Vec<Vec<f64>> {
+    vec![vec![-120.2, 38.5], vec![-120.95, 40.7], vec![-126.453, 43.252]]
+}
+
+#[test]
+fn reference_polyline_encodes() {
+    assert_eq!(polyline::encode(&reference_positions(), 5), REFERENCE);
+}
+
+#[test]
+fn reference_polyline_decodes() {
+    let decoded = polyline::decode(REFERENCE, 5).unwrap();
+    assert_eq!(decoded.len(), 3);
+    for (decoded, expected) in decoded.iter().zip(reference_positions()) {
+        assert!((decoded[0] - expected[0]).abs() < 1e-9 && (decoded[1] - expected[1]).abs() < 1e-9, "{:?} is not {:?}", decoded, expected);
+    }
+}
+
+fn position() -> impl Strategy<Value = Vec<f64>> {
+    (-180.0..180.0, -90.0..90.0).prop_map(|(lon, lat): (f64, f64)| vec![lon, lat])
+}
+
+fn assert_round_trip(positions: &[Vec<f64>], precision: u32) -> Result<(), TestCaseError> {
+    let encoded = polyline::encode(positions, precision);
+    let decoded = polyline::decode(&encoded, precision).unwrap();
+    prop_assert_eq!(decoded.len(), positions.len());
+    // Half a unit in the last place kept, plus float noise
+    let tolerance = 0.5 * 10f64.powi(-(precision as i32)) + 1e-12;
+    for (decoded, position) in decoded.iter().zip(positions) {
+        for (decoded, ordinate) in decoded.iter().zip(position) {
+            prop_assert!((decoded - ordinate).abs() <= tolerance, "{} came back as {}", ordinate, decoded);
+        }
+    }
+    prop_assert_eq!(polyline::encode(&decoded, precision), encoded);
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn precision_5_round_trips(positions in prop::collection::vec(position(), 0..20)) {
+        assert_round_trip(&positions, 5)?;
+    }
+
+    #[test]
+    fn precision_6_round_trips(positions in prop::collection::vec(position(), 0..20)) {
+        assert_round_trip(&positions, 6)?;
+    }
+}
+
+/// Converts the bundled fixtures into `output`, with any extra options.
+fn convert(output: &Path, options: &[&str]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_road-network-json"))
+        .arg("--input-dir")
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures"))
+        .arg("--output-dir")
+        .arg(output)
+        .args(options)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+fn read_geometry(path: &Path) -> Value {
+    let feature: Value = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+    feature["geometry"].clone()
+}
+
+#[test]
+fn polyline_output_decodes_to_the_plain_output() {
+    let root: PathBuf = std::env::temp_dir().join(format!("rnj-{}-polyline", std::process::id()));
+    let (plain, encoded) = (root.join("plain"), root.join("encoded"));
+    convert(&plain, &[]);
+    convert(&encoded, &["--polyline", "6"]);
+
+    let mut checked = 0;
+    for layer in ["centerlines", "pedestrian_zones"] {
+        for entry in std::fs::read_dir(plain.join(layer)).unwrap() {
+            let path = entry.unwrap().path();
+            let expected: Geometry = serde_json::from_value(read_geometry(&path)).unwrap();
+            let geometry = read_geometry(&encoded.join(layer).join(path.file_name().unwrap()));
+            assert_eq!(geometry["encoding"], "polyline6", "{}", path.display());
+            let decoded = polyline::decode_geometry(&geometry).unwrap();
+
+            assert_eq!(std::mem::discriminant(&decoded), std::mem::discriminant(&expected), "{}", path.display());
+            let (decoded, expected) = (decoded.positions(), expected.positions());
+            assert_eq!(decoded.len(), expected.len(), "{}", path.display());
+            for (decoded, expected) in decoded.iter().zip(&expected) {
+                for (decoded, ordinate) in decoded.iter().zip(expected.iter()) {
+                    assert!((decoded - ordinate).abs() <= 0.5e-6 + 1e-12, "{}: {} came back as {}", path.display(), ordinate, decoded);
+                }
+            }
+            checked += 1;
+        }
+    }
+    std::fs::remove_dir_all(&root).unwrap();
+    assert!(checked > 0, "no features were converted");
+}